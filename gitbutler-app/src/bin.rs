@@ -187,6 +187,8 @@ fn main() {
                     users::commands::delete_user,
                     users::commands::get_user,
                     projects::commands::add_project,
+                    projects::commands::clone_project,
+                    projects::commands::cancel_clone_project,
                     projects::commands::get_project,
                     projects::commands::update_project,
                     projects::commands::delete_project,
@@ -216,7 +218,18 @@ fn main() {
                     virtual_branches::commands::list_remote_branches,
                     virtual_branches::commands::get_remote_branch_data,
                     virtual_branches::commands::squash_branch_commit,
+                    virtual_branches::commands::rewrite_branch,
+                    virtual_branches::commands::continue_rewrite_branch,
+                    virtual_branches::commands::move_commit,
+                    virtual_branches::commands::list_operations,
+                    virtual_branches::commands::restore_operation,
+                    virtual_branches::commands::list_conflicts,
+                    virtual_branches::commands::resolve_conflict,
+                    virtual_branches::commands::finalize_merge,
+                    virtual_branches::commands::create_pull_request,
+                    virtual_branches::commands::refresh_pull_request,
                     virtual_branches::commands::fetch_from_target,
+                    virtual_branches::commands::cancel_fetch_from_target,
                     menu::menu_item_set_enabled,
                     keys::commands::get_public_key,
                     github::commands::init_device_oauth,
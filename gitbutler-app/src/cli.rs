@@ -0,0 +1,315 @@
+//! A headless command line interface to the virtual branches of a project.
+//!
+//! It operates on the same data directory as the desktop app, so projects added
+//! in the app can be scripted from a terminal, over ssh or in CI. Every command
+//! prints its result as JSON, using the same representation the app receives.
+
+use std::{env, path};
+
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand};
+use serde::Serialize;
+
+use gblib::{
+    git, keys, projects, users,
+    virtual_branches::{
+        branch::{self, BranchId, FileOwnership, Ownership},
+        BaseBranch, Controller, VirtualBranch,
+    },
+};
+
+/// Base tauri config of the app, the one `tauri dev` uses.
+const BASE_CONFIG: &str = include_str!("../tauri.conf.json");
+
+/// Config `tauri build --config` merged into the base config, if the cli was built that
+/// way, e.g. `tauri.conf.nightly.json` for nightly builds.
+const MERGED_CONFIG: Option<&str> = option_env!("TAURI_CONFIG");
+
+/// Manage the virtual branches of a GitButler project.
+#[derive(Parser)]
+#[command(name = "gitbutler", version)]
+struct Args {
+    /// Directory the app stores its data in. Defaults to the data directory of the app
+    /// channel (release, nightly or dev) the cli was built for; pass it to operate on
+    /// projects of another one.
+    #[arg(long, env = "GITBUTLER_DATA_DIR")]
+    data_dir: Option<path::PathBuf>,
+
+    /// Project to operate on, either its id or a path inside of it.
+    /// Defaults to the current directory.
+    #[arg(long, short)]
+    project: Option<String>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List virtual branches of the project.
+    List,
+    /// Set the remote branch virtual branches are based on.
+    SetBase {
+        /// Remote branch name, e.g. `refs/remotes/origin/master`.
+        branch: git::RemoteRefname,
+    },
+    /// Create a new virtual branch.
+    New {
+        /// Name of the branch. Generated if not set.
+        name: Option<String>,
+        /// Name or id of the branch to stack the new branch on.
+        #[arg(long, short)]
+        parent: Option<String>,
+    },
+    /// Commit changes owned by a virtual branch.
+    Commit {
+        /// Name or id of the branch to commit to.
+        #[arg(long, short)]
+        branch: String,
+        /// Commit message.
+        #[arg(long, short)]
+        message: String,
+        /// Commit only the given hunks, e.g. `file.txt:1-5,7-10`.
+        #[arg(long, short)]
+        ownership: Option<Ownership>,
+        /// Run git hooks before committing.
+        #[arg(long)]
+        run_hooks: bool,
+    },
+    /// Apply a virtual branch to the working directory.
+    Apply {
+        /// Name or id of the branch.
+        branch: String,
+    },
+    /// Remove a virtual branch from the working directory.
+    Unapply {
+        /// Name or id of the branch.
+        branch: String,
+    },
+    /// Push a virtual branch to its upstream.
+    Push {
+        /// Name or id of the branch.
+        branch: String,
+        /// Allow rewriting the upstream history.
+        #[arg(long, short)]
+        force: bool,
+    },
+    /// Move hunks to a virtual branch.
+    MoveHunk {
+        /// Hunks to move, e.g. `file.txt:1-5,7-10`.
+        ownership: FileOwnership,
+        /// Name or id of the destination branch.
+        #[arg(long, short)]
+        branch: String,
+    },
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let data_dir = match args.data_dir {
+        Some(data_dir) => data_dir,
+        None => dirs::data_dir()
+            .context("failed to find data directory")?
+            .join(app_identifier()?),
+    };
+
+    let projects = projects::Controller::try_from(&data_dir)?;
+    let project = find_project(&projects, args.project.as_deref())?;
+    let controller = Controller::new(
+        data_dir.clone(),
+        projects,
+        users::Controller::try_from(&data_dir)?,
+        keys::Controller::try_from(&data_dir)?,
+        git::credentials::Helper::try_from(&data_dir)?,
+    );
+
+    let cli = Cli {
+        controller,
+        project_id: project.id,
+    };
+
+    match args.command {
+        Command::List => print(&cli.list().await?),
+        Command::SetBase { branch } => print(&cli.set_base(&branch).await?),
+        Command::New { name, parent } => print(&cli.new_branch(name, parent.as_deref()).await?),
+        Command::Commit {
+            branch,
+            message,
+            ownership,
+            run_hooks,
+        } => print(
+            &cli.commit(&branch, &message, ownership.as_ref(), run_hooks)
+                .await?,
+        ),
+        Command::Apply { branch } => print(&cli.apply(&branch).await?),
+        Command::Unapply { branch } => print(&cli.unapply(&branch).await?),
+        Command::Push { branch, force } => print(&cli.push(&branch, force).await?),
+        Command::MoveHunk { ownership, branch } => {
+            print(&cli.move_hunk(&branch, &ownership).await?)
+        }
+    }
+}
+
+/// Identifier of the app, which its data directory is named after. Resolved the same way
+/// the app build resolves it: from the merged config if it sets one, or the base config.
+fn app_identifier() -> Result<String> {
+    let identifier = |config: &str| -> Result<Option<String>> {
+        let config: serde_json::Value =
+            serde_json::from_str(config).context("failed to parse tauri config")?;
+        Ok(config
+            .pointer("/tauri/bundle/identifier")
+            .and_then(serde_json::Value::as_str)
+            .map(ToString::to_string))
+    };
+
+    if let Some(identifier) = MERGED_CONFIG.map(identifier).transpose()?.flatten() {
+        return Ok(identifier);
+    }
+    identifier(BASE_CONFIG)?.context("tauri config has no identifier")
+}
+
+fn print<T: Serialize>(value: &T) -> Result<()> {
+    let json = serde_json::to_string_pretty(value).context("failed to serialize output")?;
+    println!("{json}");
+    Ok(())
+}
+
+/// Finds the project by its id, or by a path inside of its working directory.
+fn find_project(
+    projects: &projects::Controller,
+    project: Option<&str>,
+) -> Result<projects::Project> {
+    let all_projects = projects.list().context("failed to list projects")?;
+
+    if let Some(project_id) = project.and_then(|project| project.parse().ok()) {
+        if let Some(project) = all_projects.iter().find(|p| p.id == project_id) {
+            return Ok(project.clone());
+        }
+    }
+
+    let path = match project {
+        Some(path) => path::PathBuf::from(path),
+        None => env::current_dir().context("failed to get current directory")?,
+    };
+    let path = path
+        .canonicalize()
+        .with_context(|| format!("{} does not exist", path.display()))?;
+
+    all_projects
+        .into_iter()
+        .filter(|project| {
+            project
+                .path
+                .canonicalize()
+                .is_ok_and(|project_path| path.starts_with(project_path))
+        })
+        // prefer the innermost project if they are nested
+        .max_by_key(|project| project.path.components().count())
+        .with_context(|| format!("no project found at {}", path.display()))
+}
+
+struct Cli {
+    controller: Controller,
+    project_id: projects::ProjectId,
+}
+
+impl Cli {
+    async fn list(&self) -> Result<Vec<VirtualBranch>> {
+        Ok(self
+            .controller
+            .list_virtual_branches(&self.project_id)
+            .await?)
+    }
+
+    /// Resolves a branch by its id or by its name.
+    async fn find_branch(&self, branch: &str) -> Result<VirtualBranch> {
+        let branch_id = branch.parse::<BranchId>().ok();
+        self.list()
+            .await?
+            .into_iter()
+            .find(|b| Some(b.id) == branch_id || b.name == branch)
+            .with_context(|| format!("branch {branch} not found"))
+    }
+
+    async fn set_base(&self, branch: &git::RemoteRefname) -> Result<BaseBranch> {
+        Ok(self
+            .controller
+            .set_base_branch(&self.project_id, branch)
+            .await?)
+    }
+
+    async fn new_branch(&self, name: Option<String>, parent: Option<&str>) -> Result<BranchId> {
+        let parent = match parent {
+            Some(parent) => Some(self.find_branch(parent).await?.id),
+            None => None,
+        };
+        Ok(self
+            .controller
+            .create_virtual_branch(
+                &self.project_id,
+                &branch::BranchCreateRequest {
+                    name,
+                    parent,
+                    ..Default::default()
+                },
+            )
+            .await?)
+    }
+
+    async fn commit(
+        &self,
+        branch: &str,
+        message: &str,
+        ownership: Option<&Ownership>,
+        run_hooks: bool,
+    ) -> Result<git::Oid> {
+        let branch = self.find_branch(branch).await?;
+        Ok(self
+            .controller
+            .create_commit(&self.project_id, &branch.id, message, ownership, run_hooks)
+            .await?)
+    }
+
+    async fn apply(&self, branch: &str) -> Result<()> {
+        let branch = self.find_branch(branch).await?;
+        Ok(self
+            .controller
+            .apply_virtual_branch(&self.project_id, &branch.id)
+            .await?)
+    }
+
+    async fn unapply(&self, branch: &str) -> Result<()> {
+        let branch = self.find_branch(branch).await?;
+        Ok(self
+            .controller
+            .unapply_virtual_branch(&self.project_id, &branch.id)
+            .await?)
+    }
+
+    async fn push(&self, branch: &str, with_force: bool) -> Result<()> {
+        let branch = self.find_branch(branch).await?;
+        Ok(self
+            .controller
+            .push_virtual_branch(&self.project_id, &branch.id, with_force)
+            .await?)
+    }
+
+    async fn move_hunk(&self, branch: &str, ownership: &FileOwnership) -> Result<()> {
+        let branch = self.find_branch(branch).await?;
+        let mut branch_ownership = branch.ownership;
+        branch_ownership.put(ownership);
+        Ok(self
+            .controller
+            .update_virtual_branch(
+                &self.project_id,
+                branch::BranchUpdateRequest {
+                    id: branch.id,
+                    ownership: Some(branch_ownership),
+                    ..Default::default()
+                },
+            )
+            .await?)
+    }
+}
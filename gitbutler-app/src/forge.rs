@@ -0,0 +1,589 @@
+// pull requests on the forge the project is hosted on. only github is supported for now,
+// but the api url is configurable, so that github enterprise (and tests) work too.
+use std::{fmt, str};
+
+use anyhow::Context;
+use bstr::ByteSlice;
+use serde::{Deserialize, Serialize};
+
+use crate::git;
+
+pub const DEFAULT_API_URL: &str = "https://api.github.com";
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("can not find forge repository for remote {0}")]
+    UnsupportedRemote(String),
+    #[error("forge responded with {status}: {message}")]
+    Response { status: u16, message: String },
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Status {
+    Open,
+    Draft,
+    Closed,
+    Merged,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Status::Open => write!(f, "open"),
+            Status::Draft => write!(f, "draft"),
+            Status::Closed => write!(f, "closed"),
+            Status::Merged => write!(f, "merged"),
+        }
+    }
+}
+
+impl str::FromStr for Status {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "open" => Ok(Status::Open),
+            "draft" => Ok(Status::Draft),
+            "closed" => Ok(Status::Closed),
+            "merged" => Ok(Status::Merged),
+            _ => Err(anyhow::anyhow!("invalid pull request status: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PullRequest {
+    pub number: u64,
+    pub url: String,
+    pub status: Status,
+}
+
+// mapping of the github pull request json, only the fields we care about
+#[derive(Debug, Deserialize)]
+struct PullRequestResponse {
+    number: u64,
+    html_url: String,
+    state: String,
+    #[serde(default)]
+    draft: bool,
+    merged_at: Option<String>,
+}
+
+impl From<PullRequestResponse> for PullRequest {
+    fn from(value: PullRequestResponse) -> Self {
+        let status = if value.merged_at.is_some() {
+            Status::Merged
+        } else if value.state == "closed" {
+            Status::Closed
+        } else if value.draft {
+            Status::Draft
+        } else {
+            Status::Open
+        };
+        Self {
+            number: value.number,
+            url: value.html_url,
+            status,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repository {
+    pub owner: String,
+    pub name: String,
+}
+
+impl Repository {
+    /// finds the forge repository from the remote url, i.e. `gitbutlerapp/gitbutler` for
+    /// `git@github.com:gitbutlerapp/gitbutler.git`
+    pub fn from_remote_url(remote_url: &str) -> Result<Self, Error> {
+        let url = remote_url
+            .parse::<git::Url>()
+            .map_err(|_| Error::UnsupportedRemote(remote_url.to_string()))?;
+        let path = url.path.to_str_lossy();
+        let path = path.trim_end_matches('/');
+        let path = path.strip_suffix(".git").unwrap_or(path);
+        let mut parts = path.rsplit('/').filter(|part| !part.is_empty());
+        match (parts.next(), parts.next()) {
+            (Some(name), Some(owner)) => Ok(Self {
+                owner: owner.to_string(),
+                name: name.to_string(),
+            }),
+            _ => Err(Error::UnsupportedRemote(remote_url.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PullRequestRequest<'a> {
+    pub title: &'a str,
+    pub body: &'a str,
+    // branch name the changes are pushed to
+    pub head: &'a str,
+    // branch name the changes should be merged into
+    pub base: &'a str,
+}
+
+pub struct Client {
+    api_url: String,
+    token: String,
+    http: reqwest::Client,
+}
+
+impl Client {
+    pub fn new(token: &str) -> Self {
+        Self {
+            api_url: DEFAULT_API_URL.to_string(),
+            token: token.to_string(),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    pub fn with_api_url(mut self, api_url: &str) -> Self {
+        self.api_url = api_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// updates the pull request with the given number, or the one that is already open for
+    /// the head branch. if there is none, a new pull request is created.
+    ///
+    /// closed and merged pull requests are never updated, a new one is opened instead.
+    pub async fn create_or_update(
+        &self,
+        repository: &Repository,
+        number: Option<u64>,
+        request: &PullRequestRequest<'_>,
+    ) -> Result<PullRequest, Error> {
+        // the pull request might have been closed or merged since we've last seen it
+        let number = match number {
+            Some(number) => match self.get(repository, number).await?.status {
+                Status::Open | Status::Draft => Some(number),
+                Status::Closed | Status::Merged => None,
+            },
+            None => None,
+        };
+        let number = match number {
+            Some(number) => Some(number),
+            None => self
+                .find_open(repository, request.head)
+                .await?
+                .map(|pr| pr.number),
+        };
+        match number {
+            Some(number) => self.update(repository, number, request).await,
+            None => self.create(repository, request).await,
+        }
+    }
+
+    pub async fn get(&self, repository: &Repository, number: u64) -> Result<PullRequest, Error> {
+        let url = format!(
+            "{}/repos/{}/{}/pulls/{}",
+            self.api_url, repository.owner, repository.name, number
+        );
+        let response = self.request(reqwest::Method::GET, &url).send().await;
+        parse_response::<PullRequestResponse>(response)
+            .await
+            .map(Into::into)
+    }
+
+    async fn find_open(
+        &self,
+        repository: &Repository,
+        head: &str,
+    ) -> Result<Option<PullRequest>, Error> {
+        let url = format!(
+            "{}/repos/{}/{}/pulls",
+            self.api_url, repository.owner, repository.name
+        );
+        let head = format!("{}:{}", repository.owner, head);
+        let response = self
+            .request(reqwest::Method::GET, &url)
+            .query(&[("state", "open"), ("head", head.as_str())])
+            .send()
+            .await;
+        let pull_requests = parse_response::<Vec<PullRequestResponse>>(response).await?;
+        Ok(pull_requests.into_iter().next().map(Into::into))
+    }
+
+    async fn create(
+        &self,
+        repository: &Repository,
+        request: &PullRequestRequest<'_>,
+    ) -> Result<PullRequest, Error> {
+        let url = format!(
+            "{}/repos/{}/{}/pulls",
+            self.api_url, repository.owner, repository.name
+        );
+        let response = self
+            .request(reqwest::Method::POST, &url)
+            .json(request)
+            .send()
+            .await;
+        parse_response::<PullRequestResponse>(response)
+            .await
+            .map(Into::into)
+    }
+
+    async fn update(
+        &self,
+        repository: &Repository,
+        number: u64,
+        request: &PullRequestRequest<'_>,
+    ) -> Result<PullRequest, Error> {
+        #[derive(Serialize)]
+        struct UpdateRequest<'a> {
+            title: &'a str,
+            body: &'a str,
+            base: &'a str,
+        }
+
+        let url = format!(
+            "{}/repos/{}/{}/pulls/{}",
+            self.api_url, repository.owner, repository.name, number
+        );
+        let response = self
+            .request(reqwest::Method::PATCH, &url)
+            .json(&UpdateRequest {
+                title: request.title,
+                body: request.body,
+                base: request.base,
+            })
+            .send()
+            .await;
+        parse_response::<PullRequestResponse>(response)
+            .await
+            .map(Into::into)
+    }
+
+    fn request(&self, method: reqwest::Method, url: &str) -> reqwest::RequestBuilder {
+        self.http
+            .request(method, url)
+            .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+            .header(reqwest::header::USER_AGENT, "gitbutler")
+            .bearer_auth(&self.token)
+    }
+}
+
+async fn parse_response<T: for<'de> Deserialize<'de>>(
+    response: Result<reqwest::Response, reqwest::Error>,
+) -> Result<T, Error> {
+    let response = response.context("failed to send request")?;
+    let status = response.status();
+    let body = response
+        .text()
+        .await
+        .context("failed to get response body")?;
+    if !status.is_success() {
+        return Err(Error::Response {
+            status: status.as_u16(),
+            message: body,
+        });
+    }
+    serde_json::from_str(&body)
+        .context("failed to parse response body")
+        .map_err(Into::into)
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct Request {
+        method: String,
+        path: String,
+        body: String,
+    }
+
+    // serves canned responses one connection at a time, and returns the requests
+    // it has received
+    async fn serve(
+        responses: Vec<(u16, &'static str)>,
+    ) -> (String, tokio::task::JoinHandle<Vec<Request>>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let mut requests = vec![];
+            for (status, body) in responses {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut stream = tokio::io::BufReader::new(stream);
+
+                let mut request_line = String::new();
+                stream.read_line(&mut request_line).await.unwrap();
+                let mut parts = request_line.split_whitespace();
+                let method = parts.next().unwrap().to_string();
+                let path = parts.next().unwrap().to_string();
+
+                let mut content_length = 0;
+                loop {
+                    let mut header = String::new();
+                    stream.read_line(&mut header).await.unwrap();
+                    let header = header.trim_end();
+                    if header.is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = header.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut request_body = vec![0; content_length];
+                stream.read_exact(&mut request_body).await.unwrap();
+
+                requests.push(Request {
+                    method,
+                    path,
+                    body: String::from_utf8(request_body).unwrap(),
+                });
+
+                let response = format!(
+                    "HTTP/1.1 {} OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+                stream.shutdown().await.unwrap();
+            }
+            requests
+        });
+        (url, handle)
+    }
+
+    fn repository() -> Repository {
+        Repository {
+            owner: "gitbutlerapp".to_string(),
+            name: "gitbutler".to_string(),
+        }
+    }
+
+    fn request() -> PullRequestRequest<'static> {
+        PullRequestRequest {
+            title: "my branch",
+            body: "notes",
+            head: "my-branch",
+            base: "master",
+        }
+    }
+
+    #[test]
+    fn test_repository_from_remote_url() {
+        for remote_url in [
+            "git@github.com:gitbutlerapp/gitbutler.git",
+            "https://github.com/gitbutlerapp/gitbutler.git",
+            "https://github.com/gitbutlerapp/gitbutler",
+            "ssh://git@github.com/gitbutlerapp/gitbutler.git",
+        ] {
+            assert_eq!(
+                Repository::from_remote_url(remote_url).unwrap(),
+                repository(),
+                "{}",
+                remote_url
+            );
+        }
+    }
+
+    #[test]
+    fn test_status_from_response() {
+        let response = |state: &str, draft: bool, merged_at: Option<&str>| PullRequestResponse {
+            number: 1,
+            html_url: String::new(),
+            state: state.to_string(),
+            draft,
+            merged_at: merged_at.map(ToString::to_string),
+        };
+        assert_eq!(
+            PullRequest::from(response("open", false, None)).status,
+            Status::Open
+        );
+        assert_eq!(
+            PullRequest::from(response("open", true, None)).status,
+            Status::Draft
+        );
+        assert_eq!(
+            PullRequest::from(response("closed", false, None)).status,
+            Status::Closed
+        );
+        assert_eq!(
+            PullRequest::from(response("closed", false, Some("2024-01-01T00:00:00Z"))).status,
+            Status::Merged
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create() {
+        let (url, server) = serve(vec![
+            (200, "[]"),
+            (
+                201,
+                r#"{"number":12,"html_url":"https://github.com/gitbutlerapp/gitbutler/pull/12","state":"open","draft":false,"merged_at":null}"#,
+            ),
+        ])
+        .await;
+
+        let pr = Client::new("token")
+            .with_api_url(&url)
+            .create_or_update(&repository(), None, &request())
+            .await
+            .unwrap();
+        assert_eq!(
+            pr,
+            PullRequest {
+                number: 12,
+                url: "https://github.com/gitbutlerapp/gitbutler/pull/12".to_string(),
+                status: Status::Open,
+            }
+        );
+
+        let requests = server.await.unwrap();
+        assert_eq!(requests[0].method, "GET");
+        assert_eq!(
+            requests[0].path,
+            "/repos/gitbutlerapp/gitbutler/pulls?state=open&head=gitbutlerapp%3Amy-branch"
+        );
+        assert_eq!(requests[1].method, "POST");
+        assert_eq!(requests[1].path, "/repos/gitbutlerapp/gitbutler/pulls");
+        let body: serde_json::Value = serde_json::from_str(&requests[1].body).unwrap();
+        assert_eq!(body["title"], "my branch");
+        assert_eq!(body["body"], "notes");
+        assert_eq!(body["head"], "my-branch");
+        assert_eq!(body["base"], "master");
+    }
+
+    #[tokio::test]
+    async fn test_update_existing() {
+        let (url, server) = serve(vec![
+            (
+                200,
+                r#"{"number":12,"html_url":"https://github.com/gitbutlerapp/gitbutler/pull/12","state":"open","draft":true,"merged_at":null}"#,
+            ),
+            (
+                200,
+                r#"{"number":12,"html_url":"https://github.com/gitbutlerapp/gitbutler/pull/12","state":"open","draft":true,"merged_at":null}"#,
+            ),
+        ])
+        .await;
+
+        let pr = Client::new("token")
+            .with_api_url(&url)
+            .create_or_update(&repository(), Some(12), &request())
+            .await
+            .unwrap();
+        assert_eq!(pr.number, 12);
+        assert_eq!(pr.status, Status::Draft);
+
+        let requests = server.await.unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].method, "GET");
+        assert_eq!(requests[0].path, "/repos/gitbutlerapp/gitbutler/pulls/12");
+        assert_eq!(requests[1].method, "PATCH");
+        assert_eq!(requests[1].path, "/repos/gitbutlerapp/gitbutler/pulls/12");
+    }
+
+    #[tokio::test]
+    async fn test_existing_merged() {
+        let (url, server) = serve(vec![
+            (
+                200,
+                r#"{"number":12,"html_url":"https://github.com/gitbutlerapp/gitbutler/pull/12","state":"closed","draft":false,"merged_at":"2024-01-01T00:00:00Z"}"#,
+            ),
+            (200, "[]"),
+            (
+                201,
+                r#"{"number":13,"html_url":"https://github.com/gitbutlerapp/gitbutler/pull/13","state":"open","draft":false,"merged_at":null}"#,
+            ),
+        ])
+        .await;
+
+        let pr = Client::new("token")
+            .with_api_url(&url)
+            .create_or_update(&repository(), Some(12), &request())
+            .await
+            .unwrap();
+        assert_eq!(pr.number, 13);
+        assert_eq!(pr.status, Status::Open);
+
+        let requests = server.await.unwrap();
+        assert_eq!(requests[0].method, "GET");
+        assert_eq!(requests[0].path, "/repos/gitbutlerapp/gitbutler/pulls/12");
+        assert_eq!(requests[1].method, "GET");
+        assert_eq!(requests[2].method, "POST");
+        assert_eq!(requests[2].path, "/repos/gitbutlerapp/gitbutler/pulls");
+    }
+
+    #[tokio::test]
+    async fn test_existing_closed_found_by_head() {
+        let (url, server) = serve(vec![
+            (
+                200,
+                r#"{"number":12,"html_url":"https://github.com/gitbutlerapp/gitbutler/pull/12","state":"closed","draft":false,"merged_at":null}"#,
+            ),
+            (
+                200,
+                r#"[{"number":7,"html_url":"https://github.com/gitbutlerapp/gitbutler/pull/7","state":"open","draft":false,"merged_at":null}]"#,
+            ),
+            (
+                200,
+                r#"{"number":7,"html_url":"https://github.com/gitbutlerapp/gitbutler/pull/7","state":"open","draft":false,"merged_at":null}"#,
+            ),
+        ])
+        .await;
+
+        let pr = Client::new("token")
+            .with_api_url(&url)
+            .create_or_update(&repository(), Some(12), &request())
+            .await
+            .unwrap();
+        assert_eq!(pr.number, 7);
+
+        let requests = server.await.unwrap();
+        assert_eq!(requests[2].method, "PATCH");
+        assert_eq!(requests[2].path, "/repos/gitbutlerapp/gitbutler/pulls/7");
+    }
+
+    #[tokio::test]
+    async fn test_update_found_by_head() {
+        let (url, server) = serve(vec![
+            (
+                200,
+                r#"[{"number":7,"html_url":"https://github.com/gitbutlerapp/gitbutler/pull/7","state":"open","draft":false,"merged_at":null}]"#,
+            ),
+            (
+                200,
+                r#"{"number":7,"html_url":"https://github.com/gitbutlerapp/gitbutler/pull/7","state":"open","draft":false,"merged_at":null}"#,
+            ),
+        ])
+        .await;
+
+        let pr = Client::new("token")
+            .with_api_url(&url)
+            .create_or_update(&repository(), None, &request())
+            .await
+            .unwrap();
+        assert_eq!(pr.number, 7);
+
+        let requests = server.await.unwrap();
+        assert_eq!(requests[1].method, "PATCH");
+        assert_eq!(requests[1].path, "/repos/gitbutlerapp/gitbutler/pulls/7");
+    }
+
+    #[tokio::test]
+    async fn test_error_response() {
+        let (url, _server) = serve(vec![(422, r#"{"message":"Validation Failed"}"#)]).await;
+
+        let error = Client::new("token")
+            .with_api_url(&url)
+            .get(&repository(), 1)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, Error::Response { status: 422, .. }));
+    }
+}
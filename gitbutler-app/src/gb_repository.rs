@@ -3,4 +3,5 @@ mod repository;
 #[cfg(test)]
 mod repository_tests;
 
+pub(crate) use repository::build_branches_tree;
 pub use repository::{Error, RemoteError, Repository};
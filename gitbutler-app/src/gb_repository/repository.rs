@@ -51,7 +51,11 @@ impl Repository {
         user: Option<&users::User>,
     ) -> Result<Self, Error> {
         let project = project_repository.project();
-        let project_objects_path = project.path.join(".git/objects");
+        let project_objects_path = project_repository
+            .git_repository
+            .common_dir()
+            .context("failed to find project git directory")?
+            .join("objects");
         if !project_objects_path.exists() {
             return Err(Error::ProjectPathNotFound(project_objects_path));
         }
@@ -350,7 +354,7 @@ impl Repository {
             "created new session"
         );
 
-        self.flush_gitbutler_file(&session.id)?;
+        self.flush_gitbutler_file(project_repository, &session.id)?;
 
         Ok(session)
     }
@@ -539,7 +543,11 @@ impl Repository {
         }
     }
 
-    fn flush_gitbutler_file(&self, session_id: &SessionId) -> Result<()> {
+    fn flush_gitbutler_file(
+        &self,
+        project_repository: &project_repository::Repository,
+        session_id: &SessionId,
+    ) -> Result<()> {
         let gb_path = self.git_repository.path();
         let project_id = self.project.id.to_string();
         let gb_file_content = serde_json::json!({
@@ -549,7 +557,11 @@ impl Repository {
             "api": self.project.api,
         });
 
-        let gb_file_path = self.project.path.join(".git/gitbutler.json");
+        // per worktree, in case more than one worktree of a repository is a project
+        let gb_file_path = project_repository
+            .git_repository
+            .path()
+            .join("gitbutler.json");
         std::fs::write(&gb_file_path, gb_file_content.to_string())?;
 
         tracing::debug!("gitbutler file updated: {:?}", gb_file_path);
@@ -644,6 +656,22 @@ fn build_wd_tree_from_repo(
 
     let mut added: HashMap<String, bool> = HashMap::new();
 
+    // files outside of a sparse checkout are not in the working directory, so we skip them
+    // as if they were ignored.
+    let sparse_checkout = project_repository
+        .git_repository
+        .sparse_checkout()
+        .context("failed to read sparse checkout")?;
+    let is_excluded = |file_path: &path::Path| {
+        project_repository
+            .git_repository
+            .is_path_ignored(file_path)
+            .unwrap_or(true)
+            || sparse_checkout
+                .as_ref()
+                .is_some_and(|sparse_checkout| !sparse_checkout.is_included(file_path))
+    };
+
     // first, add session/wd files. session/wd are written at the same time as deltas, so it's important to add them first
     // to make sure they are in sync with the deltas
     for file_path in fs::list_files(gb_repository.session_wd_path(), &[]).with_context(|| {
@@ -652,11 +680,7 @@ fn build_wd_tree_from_repo(
             gb_repository.session_wd_path().display()
         )
     })? {
-        if project_repository
-            .git_repository
-            .is_path_ignored(&file_path)
-            .unwrap_or(true)
-        {
+        if is_excluded(&file_path) {
             continue;
         }
 
@@ -688,11 +712,7 @@ fn build_wd_tree_from_repo(
             continue;
         }
 
-        if project_repository
-            .git_repository
-            .is_path_ignored(&file_path)
-            .unwrap_or(true)
-        {
+        if is_excluded(&file_path) {
             continue;
         }
 
@@ -822,7 +842,7 @@ fn sha256_digest(path: &std::path::Path) -> Result<String> {
     Ok(format!("{:X}", digest))
 }
 
-fn build_branches_tree(gb_repository: &Repository) -> Result<git::Oid> {
+pub(crate) fn build_branches_tree(gb_repository: &Repository) -> Result<git::Oid> {
     let mut index = git::Index::new()?;
 
     let branches_dir = gb_repository.root().join("branches");
@@ -29,6 +29,8 @@ pub use remote::*;
 mod index;
 pub use index::*;
 
+mod merge;
+
 mod oid;
 pub use oid::*;
 
@@ -40,3 +42,6 @@ pub use config::*;
 
 mod url;
 pub use self::url::*;
+
+mod sparse_checkout;
+pub use sparse_checkout::*;
@@ -17,6 +17,10 @@ pub enum ChangeType {
     Deleted,
     /// Entry content changed between old and new
     Modified,
+    /// Entry was moved from another path, possibly with changes
+    Renamed,
+    /// Entry was copied from another path, possibly with changes
+    Copied,
 }
 impl From<git2::Delta> for ChangeType {
     fn from(v: git2::Delta) -> Self {
@@ -24,12 +28,9 @@ impl From<git2::Delta> for ChangeType {
         use ChangeType as C;
         match v {
             D::Untracked | D::Added => C::Added,
-            D::Modified
-            | D::Unmodified
-            | D::Renamed
-            | D::Copied
-            | D::Typechange
-            | D::Conflicted => C::Modified,
+            D::Renamed => C::Renamed,
+            D::Copied => C::Copied,
+            D::Modified | D::Unmodified | D::Typechange | D::Conflicted => C::Modified,
             D::Ignored | D::Unreadable | D::Deleted => C::Deleted,
         }
     }
@@ -44,6 +45,8 @@ pub struct Hunk {
     pub diff: String,
     pub binary: bool,
     pub change_type: ChangeType,
+    // path the file was renamed or copied from
+    pub old_path: Option<path::PathBuf>,
 }
 
 pub struct Options {
@@ -74,7 +77,8 @@ pub fn workdir(
         .ignore_submodules(true)
         .context_lines(0);
 
-    let diff = repository.diff_tree_to_workdir(Some(&tree), Some(&mut diff_opts))?;
+    let mut diff = repository.diff_tree_to_workdir(Some(&tree), Some(&mut diff_opts))?;
+    find_renames(repository, &mut diff)?;
 
     hunks_by_filepath(repository, &diff)
 }
@@ -93,18 +97,70 @@ pub fn trees(
         .context_lines(0)
         .show_untracked_content(true);
 
-    let diff =
+    let mut diff =
         repository.diff_tree_to_tree(Some(old_tree), Some(new_tree), Some(&mut diff_opts))?;
+    find_renames(repository, &mut diff)?;
 
     hunks_by_filepath(repository, &diff)
 }
 
+// similarity (in percent) of files to be considered renames or copies of each other.
+// can be changed with `gitbutler.renameThreshold` config, 0 turns the detection off.
+const DEFAULT_RENAME_THRESHOLD: u16 = 50;
+
+fn rename_threshold(repository: &Repository) -> Result<u16> {
+    let threshold = repository
+        .config()?
+        .get_string("gitbutler.renameThreshold")?
+        .map(|threshold| threshold.parse::<u16>())
+        .transpose()
+        .context("failed to parse gitbutler.renameThreshold")?
+        .unwrap_or(DEFAULT_RENAME_THRESHOLD);
+    Ok(threshold.min(100))
+}
+
+// pairs up deleted and added files that are similar enough into renames and copies.
+// this runs on every status update, so it is skipped unless there are both added and
+// deleted files to pair up.
+fn find_renames(repository: &Repository, diff: &mut git2::Diff) -> Result<()> {
+    let (mut added, mut deleted) = (false, false);
+    for delta in diff.deltas() {
+        match delta.status() {
+            git2::Delta::Added | git2::Delta::Untracked => added = true,
+            git2::Delta::Deleted => deleted = true,
+            _ => {}
+        }
+    }
+    if !added || !deleted {
+        return Ok(());
+    }
+
+    let threshold = rename_threshold(repository)?;
+    if threshold == 0 {
+        return Ok(());
+    }
+
+    let mut find_opts = git2::DiffFindOptions::new();
+    find_opts
+        .renames(true)
+        .copies(true)
+        .for_untracked(true)
+        .rename_threshold(threshold)
+        .copy_threshold(threshold);
+    diff.find_similar(Some(&mut find_opts))
+        .context("failed to find renames")?;
+
+    Ok(())
+}
+
 fn hunks_by_filepath(
     repository: &Repository,
     diff: &git2::Diff,
 ) -> Result<HashMap<path::PathBuf, Vec<Hunk>>> {
     // find all the hunks
     let mut hunks_by_filepath: HashMap<path::PathBuf, Vec<Hunk>> = HashMap::new();
+    // renamed and copied files, with their original paths
+    let mut old_paths: HashMap<path::PathBuf, (ChangeType, path::PathBuf)> = HashMap::new();
 
     diff.print(
         git2::DiffFormat::Patch,
@@ -116,6 +172,15 @@ fn hunks_by_filepath(
                     .path()
                     .expect("failed to get file name from diff")
             });
+            let old_path = match change_type {
+                ChangeType::Renamed | ChangeType::Copied => {
+                    delta.old_file().path().map(path::Path::to_path_buf)
+                }
+                _ => None,
+            };
+            if let Some(old_path) = &old_path {
+                old_paths.insert(file_path.to_path_buf(), (change_type, old_path.clone()));
+            }
 
             hunks_by_filepath
                 .entry(file_path.to_path_buf())
@@ -196,6 +261,7 @@ fn hunks_by_filepath(
                             diff: line,
                             binary: is_binary,
                             change_type,
+                            old_path,
                         });
                     }
                 } else {
@@ -207,6 +273,7 @@ fn hunks_by_filepath(
                         diff: line,
                         binary: is_binary,
                         change_type,
+                        old_path,
                     });
                 }
             }
@@ -233,11 +300,28 @@ fn hunks_by_filepath(
                             diff: binary_hunk.diff.clone(),
                             binary: true,
                             change_type: binary_hunk.change_type,
+                            old_path: binary_hunk.old_path.clone(),
                         }],
                     )
                 } else {
                     (k, v)
                 }
+            } else if let (true, Some((change_type, old_path))) = (v.is_empty(), old_paths.get(&k))
+            {
+                // this is a file that was moved without changes
+                (
+                    k,
+                    vec![Hunk {
+                        old_start: 0,
+                        old_lines: 0,
+                        new_start: 0,
+                        new_lines: 0,
+                        diff: String::new(),
+                        binary: false,
+                        change_type: *change_type,
+                        old_path: Some(old_path.clone()),
+                    }],
+                )
             } else if v.is_empty() {
                 // this is a new file
                 (
@@ -250,6 +334,7 @@ fn hunks_by_filepath(
                         diff: String::new(),
                         binary: false,
                         change_type: ChangeType::Modified,
+                        old_path: None,
                     }],
                 )
             } else {
@@ -323,6 +408,7 @@ pub fn reverse_hunk(hunk: &Hunk) -> Option<Hunk> {
             diff,
             binary: hunk.binary,
             change_type: hunk.change_type,
+            old_path: hunk.old_path.clone(),
         })
     }
 }
@@ -352,6 +438,7 @@ mod tests {
                 diff: "@@ -0,0 +1 @@\n+hello\n\\ No newline at end of file\n".to_string(),
                 binary: false,
                 change_type: ChangeType::Added,
+                old_path: None,
             }]
         );
     }
@@ -375,6 +462,7 @@ mod tests {
                 diff: String::new(),
                 binary: false,
                 change_type: ChangeType::Modified,
+                old_path: None,
             }]
         );
     }
@@ -399,6 +487,7 @@ mod tests {
                 diff: String::new(),
                 binary: false,
                 change_type: ChangeType::Modified,
+                old_path: None,
             }]
         );
         assert_eq!(
@@ -411,6 +500,7 @@ mod tests {
                 diff: String::new(),
                 binary: false,
                 change_type: ChangeType::Modified,
+                old_path: None,
             }]
         );
     }
@@ -442,6 +532,7 @@ mod tests {
                 diff: "71ae6e216f38164b6633e25d35abb043c3785af6".to_string(),
                 binary: true,
                 change_type: ChangeType::Added,
+                old_path: None,
             }]
         );
     }
@@ -485,7 +576,99 @@ mod tests {
                 diff: "3fc41b9ae6836a94f41c78b4ce69d78b6e7080f1".to_string(),
                 binary: true,
                 change_type: ChangeType::Added,
+                old_path: None,
             }]
         );
     }
+
+    #[test]
+    fn diff_renamed_file() {
+        let repository = test_utils::test_repository();
+        let content: String = (0..10).map(|i| format!("line {}\n", i)).collect();
+        std::fs::write(repository.workdir().unwrap().join("file"), &content).unwrap();
+        test_utils::commit_all(&repository);
+
+        std::fs::remove_file(repository.workdir().unwrap().join("file")).unwrap();
+        std::fs::write(repository.workdir().unwrap().join("renamed"), &content).unwrap();
+
+        let head_commit_id = repository.head().unwrap().peel_to_commit().unwrap().id();
+
+        let diff = workdir(&repository, &head_commit_id).unwrap();
+        assert_eq!(diff.len(), 1);
+        assert_eq!(
+            diff[&path::PathBuf::from("renamed")],
+            vec![Hunk {
+                old_start: 0,
+                old_lines: 0,
+                new_start: 0,
+                new_lines: 0,
+                diff: String::new(),
+                binary: false,
+                change_type: ChangeType::Renamed,
+                old_path: Some(path::PathBuf::from("file")),
+            }]
+        );
+    }
+
+    #[test]
+    fn diff_renamed_file_with_changes() {
+        let repository = test_utils::test_repository();
+        let content: String = (0..10).map(|i| format!("line {}\n", i)).collect();
+        std::fs::write(repository.workdir().unwrap().join("file"), &content).unwrap();
+        test_utils::commit_all(&repository);
+
+        std::fs::remove_file(repository.workdir().unwrap().join("file")).unwrap();
+        std::fs::write(
+            repository.workdir().unwrap().join("renamed"),
+            content.replace("line 5\n", "changed\n"),
+        )
+        .unwrap();
+
+        let head_commit_id = repository.head().unwrap().peel_to_commit().unwrap().id();
+
+        let diff = workdir(&repository, &head_commit_id).unwrap();
+        assert_eq!(diff.len(), 1);
+        assert_eq!(
+            diff[&path::PathBuf::from("renamed")],
+            vec![Hunk {
+                old_start: 6,
+                old_lines: 1,
+                new_start: 6,
+                new_lines: 1,
+                diff: "@@ -6 +6 @@ line 4\n-line 5\n+changed\n".to_string(),
+                binary: false,
+                change_type: ChangeType::Renamed,
+                old_path: Some(path::PathBuf::from("file")),
+            }]
+        );
+    }
+
+    #[test]
+    fn diff_rename_detection_disabled() {
+        let repository = test_utils::test_repository();
+        repository
+            .config()
+            .unwrap()
+            .set_str("gitbutler.renameThreshold", "0")
+            .unwrap();
+        let content: String = (0..10).map(|i| format!("line {}\n", i)).collect();
+        std::fs::write(repository.workdir().unwrap().join("file"), &content).unwrap();
+        test_utils::commit_all(&repository);
+
+        std::fs::remove_file(repository.workdir().unwrap().join("file")).unwrap();
+        std::fs::write(repository.workdir().unwrap().join("renamed"), &content).unwrap();
+
+        let head_commit_id = repository.head().unwrap().peel_to_commit().unwrap().id();
+
+        let diff = workdir(&repository, &head_commit_id).unwrap();
+        assert_eq!(diff.len(), 2);
+        assert_eq!(
+            diff[&path::PathBuf::from("file")][0].change_type,
+            ChangeType::Deleted
+        );
+        assert_eq!(
+            diff[&path::PathBuf::from("renamed")][0].change_type,
+            ChangeType::Added
+        );
+    }
 }
@@ -0,0 +1,424 @@
+// merge drivers, as configured with the `merge` attribute in .gitattributes.
+//
+// libgit2 knows builtin `text`, `union` and `binary` drivers, and falls back to `text`
+// for everything else. the result is then corrected with the driver of the path, same
+// as git would do it:
+//
+// - `ours` is builtin, and keeps our side of every path changed on both sides
+// - `merge.<name>.driver` command from git config resolves conflicts, if configured
+// - anything else leaves the conflict as is
+//
+// driver commands are run with `sh`, like git does. on windows that is the one that comes
+// with git for windows. if it can't be run, the conflict is left as the builtin merge left it.
+use std::{io::Write, path, process, str};
+
+use super::Result;
+
+// size of conflict markers, passed to merge driver commands as %L
+const MARKER_SIZE: &str = "7";
+
+// stage bits of the index entry flags
+const STAGE_MASK: u16 = 0x3000;
+
+enum Driver {
+    Builtin,
+    Ours,
+    Command(String),
+}
+
+fn driver_for_path(repo: &git2::Repository, path: &str) -> Result<Driver> {
+    let value = repo.get_attr_bytes(
+        path::Path::new(path),
+        "merge",
+        git2::AttrCheckFlags::FILE_THEN_INDEX,
+    )?;
+    let name = match git2::AttrValue::from_bytes(value) {
+        git2::AttrValue::Unspecified | git2::AttrValue::True | git2::AttrValue::False => {
+            return Ok(Driver::Builtin)
+        }
+        git2::AttrValue::String(name) => name.to_string(),
+        git2::AttrValue::Bytes(name) => String::from_utf8_lossy(name).to_string(),
+    };
+
+    if name == "ours" {
+        return Ok(Driver::Ours);
+    }
+
+    let config = repo.config()?;
+    match config.get_string(&format!("merge.{name}.driver")) {
+        Ok(command) => Ok(Driver::Command(command)),
+        Err(error) if error.code() == git2::ErrorCode::NotFound => Ok(Driver::Builtin),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// corrects the merge of `theirs` into `ours` in `index` with the merge drivers of the
+/// merged paths. driver commands from git config are only run if `run_commands` is set.
+/// conflicts that can not be resolved are left in the index.
+pub fn apply_drivers(
+    repo: &git2::Repository,
+    ancestor: &git2::Tree,
+    ours: &git2::Tree,
+    theirs: &git2::Tree,
+    index: &mut git2::Index,
+    run_commands: bool,
+) -> Result<()> {
+    // libgit2 merges `ours` paths as text, so changes from their side that merged
+    // cleanly have to be reverted as well as the conflicting ones
+    let their_changes = repo.diff_tree_to_tree(Some(ancestor), Some(theirs), None)?;
+    for delta in their_changes.deltas() {
+        if delta.status() != git2::Delta::Modified {
+            continue;
+        }
+        let Some(path) = delta.new_file().path().and_then(path::Path::to_str) else {
+            continue;
+        };
+        let Ok(our_entry) = ours.get_path(path::Path::new(path)) else {
+            // deleted on our side, which is a conflict for the user to resolve
+            continue;
+        };
+        if our_entry.id() == delta.old_file().id() || our_entry.id() == delta.new_file().id() {
+            // changed on their side only, or the same way on both
+            continue;
+        }
+        if !matches!(driver_for_path(repo, path)?, Driver::Ours) {
+            continue;
+        }
+        let Some(mut entry) = index
+            .get_path(path::Path::new(path), 0)
+            .or_else(|| index.get_path(path::Path::new(path), 2))
+        else {
+            continue;
+        };
+        entry.mode = our_entry.filemode() as u32;
+        resolve(index, path, entry, our_entry.id())?;
+    }
+
+    let conflicts = index
+        .conflicts()?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    for conflict in conflicts {
+        // drivers only merge content, so modify/delete conflicts are left to the user
+        let (Some(ours), Some(theirs)) = (conflict.our, conflict.their) else {
+            continue;
+        };
+        let path = str::from_utf8(&ours.path)?.to_string();
+
+        let resolved = match driver_for_path(repo, &path)? {
+            Driver::Builtin => None,
+            Driver::Ours => Some(ours.id),
+            Driver::Command(_) if !run_commands => None,
+            Driver::Command(command) => run_driver(
+                repo,
+                &command,
+                &path,
+                conflict.ancestor.as_ref(),
+                &ours,
+                &theirs,
+            )?,
+        };
+
+        if let Some(id) = resolved {
+            resolve(index, &path, ours, id)?;
+        }
+    }
+
+    Ok(())
+}
+
+// replaces all stages of the path with a single merged entry
+fn resolve(
+    index: &mut git2::Index,
+    path: &str,
+    mut entry: git2::IndexEntry,
+    id: git2::Oid,
+) -> Result<()> {
+    for stage in 0..=3 {
+        match index.remove(path::Path::new(path), stage) {
+            Ok(()) => {}
+            Err(error) if error.code() == git2::ErrorCode::NotFound => {}
+            Err(error) => return Err(error.into()),
+        }
+    }
+    entry.id = id;
+    entry.flags &= !STAGE_MASK;
+    index.add(&entry)?;
+    Ok(())
+}
+
+// runs the driver command, just like git does. the command is expected to leave the
+// merged content in %A file, and exit with non zero code if it can not be merged.
+fn run_driver(
+    repo: &git2::Repository,
+    command: &str,
+    path: &str,
+    ancestor: Option<&git2::IndexEntry>,
+    ours: &git2::IndexEntry,
+    theirs: &git2::IndexEntry,
+) -> Result<Option<git2::Oid>> {
+    // temporary files are removed when dropped
+    let write_file = |entry: Option<&git2::IndexEntry>| -> Result<tempfile::TempPath> {
+        let mut file = tempfile::Builder::new()
+            .prefix(".merge_file_")
+            .tempfile_in(repo.path())?;
+        if let Some(entry) = entry {
+            file.write_all(repo.find_blob(entry.id)?.content())?;
+        }
+        Ok(file.into_temp_path())
+    };
+    let ancestor_path = write_file(ancestor)?;
+    let ours_path = write_file(Some(ours))?;
+    let theirs_path = write_file(Some(theirs))?;
+
+    let command = command
+        .replace("%O", &quote(&ancestor_path.to_string_lossy()))
+        .replace("%A", &quote(&ours_path.to_string_lossy()))
+        .replace("%B", &quote(&theirs_path.to_string_lossy()))
+        .replace("%L", MARKER_SIZE)
+        .replace("%P", &quote(path));
+
+    let status = match process::Command::new("sh")
+        .arg("-c")
+        .arg(&command)
+        .current_dir(repo.workdir().unwrap_or(repo.path()))
+        .status()
+    {
+        Ok(status) => status,
+        Err(error) => {
+            tracing::warn!(?error, command, "failed to run merge driver");
+            return Ok(None);
+        }
+    };
+
+    if status.success() {
+        let content = std::fs::read(&ours_path)?;
+        Ok(Some(repo.blob(&content)?))
+    } else {
+        Ok(None)
+    }
+}
+
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path;
+
+    use crate::{git, test_utils};
+
+    fn tree(repository: &git::Repository, files: &[(&str, &str)]) -> git::Oid {
+        let mut builder = repository.treebuilder(None);
+        for (path, content) in files {
+            let blob = repository.blob(content.as_bytes()).unwrap();
+            builder.upsert(path, blob, git::FileMode::Blob);
+        }
+        builder.write().unwrap()
+    }
+
+    fn merge(
+        repository: &git::Repository,
+        ancestor: &str,
+        ours: &str,
+        theirs: &str,
+    ) -> Option<String> {
+        merge_with(repository, ancestor, ours, theirs, true)
+    }
+
+    fn merge_with(
+        repository: &git::Repository,
+        ancestor: &str,
+        ours: &str,
+        theirs: &str,
+        run_commands: bool,
+    ) -> Option<String> {
+        let ancestor = repository
+            .find_tree(tree(repository, &[("file.lock", ancestor)]))
+            .unwrap();
+        let ours = repository
+            .find_tree(tree(repository, &[("file.lock", ours)]))
+            .unwrap();
+        let theirs = repository
+            .find_tree(tree(repository, &[("file.lock", theirs)]))
+            .unwrap();
+        let index = if run_commands {
+            repository.merge_trees_with_commands(&ancestor, &ours, &theirs)
+        } else {
+            repository.merge_trees(&ancestor, &ours, &theirs)
+        }
+        .unwrap();
+        if index.has_conflicts() {
+            return None;
+        }
+        let entry = index.get_path(path::Path::new("file.lock"), 0).unwrap();
+        let blob = repository.find_blob(entry.id).unwrap();
+        Some(String::from_utf8(blob.content().to_vec()).unwrap())
+    }
+
+    fn set_attributes(repository: &git::Repository, attributes: &str) {
+        std::fs::write(
+            repository.workdir().unwrap().join(".gitattributes"),
+            attributes,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_text_conflicts() {
+        let repository = test_utils::test_repository();
+        assert_eq!(merge(&repository, "a\n", "b\n", "c\n"), None);
+    }
+
+    #[test]
+    fn test_union() {
+        let repository = test_utils::test_repository();
+        set_attributes(&repository, "*.lock merge=union\n");
+        assert_eq!(
+            merge(&repository, "a\n", "b\n", "c\n"),
+            Some("b\nc\n".to_string())
+        );
+    }
+
+    #[test]
+    fn test_ours() {
+        let repository = test_utils::test_repository();
+        set_attributes(&repository, "*.lock merge=ours\n");
+        assert_eq!(
+            merge(&repository, "a\n", "b\n", "c\n"),
+            Some("b\n".to_string())
+        );
+    }
+
+    #[test]
+    fn test_ours_without_conflicts() {
+        let repository = test_utils::test_repository();
+        set_attributes(&repository, "*.lock merge=ours\n");
+        assert_eq!(
+            merge(&repository, "a\nb\nc\n", "A\nb\nc\n", "a\nb\nC\n"),
+            Some("A\nb\nc\n".to_string())
+        );
+        // changes from their side only are taken
+        assert_eq!(
+            merge(&repository, "a\n", "a\n", "c\n"),
+            Some("c\n".to_string())
+        );
+    }
+
+    #[test]
+    fn test_ours_is_builtin() {
+        let repository = test_utils::test_repository();
+        set_attributes(&repository, "*.lock merge=ours\n");
+        repository
+            .config()
+            .unwrap()
+            .set_str("merge.ours.driver", "false")
+            .unwrap();
+        assert_eq!(
+            merge(&repository, "a\n", "b\n", "c\n"),
+            Some("b\n".to_string())
+        );
+    }
+
+    #[test]
+    fn test_binary() {
+        let repository = test_utils::test_repository();
+        set_attributes(&repository, "*.lock merge=binary\n");
+        assert_eq!(merge(&repository, "a\n", "b\n", "c\n"), None);
+    }
+
+    #[test]
+    fn test_unknown_driver_is_text() {
+        let repository = test_utils::test_repository();
+        set_attributes(&repository, "*.lock merge=unknown\n");
+        assert_eq!(merge(&repository, "a\n", "b\n", "c\n"), None);
+    }
+
+    #[test]
+    fn test_driver_command() {
+        let repository = test_utils::test_repository();
+        set_attributes(&repository, "*.lock merge=theirs\n");
+        repository
+            .config()
+            .unwrap()
+            .set_str("merge.theirs.driver", "cp %B %A")
+            .unwrap();
+        assert_eq!(
+            merge(&repository, "a\n", "b\n", "c\n"),
+            Some("c\n".to_string())
+        );
+    }
+
+    #[test]
+    fn test_failing_driver_command() {
+        let repository = test_utils::test_repository();
+        set_attributes(&repository, "*.lock merge=fail\n");
+        repository
+            .config()
+            .unwrap()
+            .set_str("merge.fail.driver", "false")
+            .unwrap();
+        assert_eq!(merge(&repository, "a\n", "b\n", "c\n"), None);
+    }
+
+    #[test]
+    fn test_missing_driver_command() {
+        let repository = test_utils::test_repository();
+        set_attributes(&repository, "*.lock merge=missing\n");
+        repository
+            .config()
+            .unwrap()
+            .set_str(
+                "merge.missing.driver",
+                "this-merge-driver-does-not-exist %A",
+            )
+            .unwrap();
+        assert_eq!(merge(&repository, "a\n", "b\n", "c\n"), None);
+    }
+
+    #[test]
+    fn test_driver_command_not_run_without_commands() {
+        let repository = test_utils::test_repository();
+        set_attributes(&repository, "*.lock merge=touch\n");
+        let marker = repository.path().join("driver-ran");
+        repository
+            .config()
+            .unwrap()
+            .set_str(
+                "merge.touch.driver",
+                &format!("touch '{}' && cp %B %A", marker.display()),
+            )
+            .unwrap();
+        assert_eq!(merge_with(&repository, "a\n", "b\n", "c\n", false), None);
+        assert!(!marker.exists());
+        assert_eq!(
+            merge_with(&repository, "a\n", "b\n", "c\n", true),
+            Some("c\n".to_string())
+        );
+        assert!(marker.exists());
+    }
+
+    #[test]
+    fn test_temporary_files_are_removed() {
+        let repository = test_utils::test_repository();
+        set_attributes(&repository, "*.lock merge=theirs\n");
+        repository
+            .config()
+            .unwrap()
+            .set_str("merge.theirs.driver", "cp %B %A")
+            .unwrap();
+        merge(&repository, "a\n", "b\n", "c\n");
+        let leftovers = std::fs::read_dir(repository.path())
+            .unwrap()
+            .filter_map(Result::ok)
+            .filter(|entry| {
+                entry
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with(".merge_file_")
+            })
+            .count();
+        assert_eq!(leftovers, 0);
+    }
+}
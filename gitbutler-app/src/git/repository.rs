@@ -6,8 +6,8 @@ use git2_hooks::HookResult;
 use crate::keys;
 
 use super::{
-    Blob, Branch, Commit, Config, Index, Oid, Reference, Refname, Remote, Result, Signature, Tree,
-    TreeBuilder, Url,
+    Blob, Branch, Commit, Config, Index, Oid, Reference, Refname, Remote, Result, Signature,
+    SparseCheckout, Tree, TreeBuilder, Url,
 };
 
 // wrapper around git2::Repository to get control over how it's used.
@@ -114,21 +114,52 @@ impl Repository {
             .map_err(Into::into)
     }
 
+    /// merges the trees, honoring the builtin merge drivers from gitattributes. driver
+    /// commands from git config are never run: this is called on almost every operation,
+    /// conflicts a command would have resolved are reported as such.
     pub fn merge_trees(
         &self,
         ancestor_tree: &Tree<'_>,
         our_tree: &Tree<'_>,
         their_tree: &Tree<'_>,
     ) -> Result<Index> {
-        self.0
-            .merge_trees(
-                ancestor_tree.into(),
-                our_tree.into(),
-                their_tree.into(),
-                None,
-            )
-            .map(Index::from)
-            .map_err(Into::into)
+        self.merge_trees_with_drivers(ancestor_tree, our_tree, their_tree, false)
+    }
+
+    /// same as `merge_trees`, but also runs merge driver commands from git config.
+    /// only use it for merges the user asked for, like updating the base branch,
+    /// merging upstream changes or applying a branch.
+    pub fn merge_trees_with_commands(
+        &self,
+        ancestor_tree: &Tree<'_>,
+        our_tree: &Tree<'_>,
+        their_tree: &Tree<'_>,
+    ) -> Result<Index> {
+        self.merge_trees_with_drivers(ancestor_tree, our_tree, their_tree, true)
+    }
+
+    fn merge_trees_with_drivers(
+        &self,
+        ancestor_tree: &Tree<'_>,
+        our_tree: &Tree<'_>,
+        their_tree: &Tree<'_>,
+        run_commands: bool,
+    ) -> Result<Index> {
+        let mut index = self.0.merge_trees(
+            ancestor_tree.into(),
+            our_tree.into(),
+            their_tree.into(),
+            None,
+        )?;
+        super::merge::apply_drivers(
+            &self.0,
+            ancestor_tree.into(),
+            our_tree.into(),
+            their_tree.into(),
+            &mut index,
+            run_commands,
+        )?;
+        Ok(Index::from(index))
     }
 
     pub fn diff_tree_to_tree(
@@ -310,6 +341,38 @@ impl Repository {
         self.0.workdir()
     }
 
+    pub fn is_worktree(&self) -> bool {
+        self.0.is_worktree()
+    }
+
+    // the directory shared by all worktrees of a repository, holding objects, refs and config.
+    // for linked worktrees `path()` points to `.git/worktrees/<name>` instead.
+    //
+    // the path is canonical, so that worktrees of the same repository have the same one.
+    pub fn common_dir(&self) -> Result<path::PathBuf> {
+        let common_dir = if self.0.is_worktree() {
+            let common_dir = std::fs::read_to_string(self.0.path().join("commondir"))?;
+            self.0.path().join(common_dir.trim_end())
+        } else {
+            self.0.path().to_path_buf()
+        };
+        Ok(common_dir.canonicalize()?)
+    }
+
+    // sparse checkout patterns of the worktree, if it is a sparse checkout.
+    pub fn sparse_checkout(&self) -> Result<Option<SparseCheckout>> {
+        let config = self.config()?;
+        if !config.get_bool("core.sparseCheckout")?.unwrap_or(false) {
+            return Ok(None);
+        }
+        let cone = config.get_bool("core.sparseCheckoutCone")?.unwrap_or(false);
+        match std::fs::read_to_string(self.0.path().join("info/sparse-checkout")) {
+            Ok(contents) => Ok(Some(SparseCheckout::parse(&contents, cone))),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
     pub fn branch_upstream_name(&self, branch_name: &str) -> Result<String> {
         self.0
             .branch_upstream_name(branch_name)
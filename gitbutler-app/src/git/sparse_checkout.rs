@@ -0,0 +1,228 @@
+use std::path;
+
+// patterns of a sparse checkout, as found in $GIT_DIR/info/sparse-checkout.
+// libgit2 doesn't know about sparse checkouts, so we match paths against them ourselves.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SparseCheckout {
+    // cone mode: files at the root, files directly inside of `parents`,
+    // and everything inside of `recursive` are checked out.
+    Cone {
+        parents: Vec<String>,
+        recursive: Vec<String>,
+    },
+    // non-cone mode: gitignore-style patterns, the last matching one wins.
+    Patterns(Vec<Pattern>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    glob: String,
+    negated: bool,
+    anchored: bool,
+    dir_only: bool,
+}
+
+impl SparseCheckout {
+    pub fn parse(contents: &str, cone: bool) -> Self {
+        let lines = contents
+            .lines()
+            .map(str::trim_end)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+
+        if cone {
+            let mut parents = vec![];
+            let mut recursive = vec![];
+            for line in lines {
+                if let Some(dir) = line
+                    .strip_prefix("!/")
+                    .and_then(|line| line.strip_suffix("/*/"))
+                {
+                    parents.push(unescape(dir));
+                } else if let Some(dir) = line
+                    .strip_prefix('/')
+                    .and_then(|line| line.strip_suffix('/'))
+                {
+                    recursive.push(unescape(dir));
+                }
+            }
+            // a directory that is listed as a parent only has its direct children checked out
+            recursive.retain(|dir| !parents.contains(dir));
+            Self::Cone { parents, recursive }
+        } else {
+            Self::Patterns(lines.map(Pattern::parse).collect())
+        }
+    }
+
+    // returns true if the given path, relative to the working directory, is checked out.
+    pub fn is_included<P: AsRef<path::Path>>(&self, path: P) -> bool {
+        let path = path.as_ref().to_string_lossy().replace('\\', "/");
+        let path = path.trim_matches('/');
+
+        match self {
+            Self::Cone { parents, recursive } => {
+                let parent = path.rsplit_once('/').map_or("", |(parent, _)| parent);
+                parent.is_empty()
+                    || parents.iter().any(|dir| dir == parent)
+                    || recursive.iter().any(|dir| {
+                        path.strip_prefix(dir.as_str())
+                            .is_some_and(|rest| rest.starts_with('/'))
+                    })
+            }
+            Self::Patterns(patterns) => patterns
+                .iter()
+                .rev()
+                .find(|pattern| pattern.matches(path))
+                .is_some_and(|pattern| !pattern.negated),
+        }
+    }
+}
+
+impl Pattern {
+    fn parse(line: &str) -> Self {
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(line) => (true, line),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(line) => (true, line),
+            None => (false, line),
+        };
+        let anchored = line.contains('/');
+        Self {
+            glob: line.trim_start_matches('/').to_string(),
+            negated,
+            anchored,
+            dir_only,
+        }
+    }
+
+    // a pattern matches a file if it matches the file itself, or any of its parent directories.
+    fn matches(&self, path: &str) -> bool {
+        let mut candidates = path
+            .match_indices('/')
+            .map(|(i, _)| &path[..i])
+            .collect::<Vec<_>>();
+        if !self.dir_only {
+            candidates.push(path);
+        }
+
+        candidates.into_iter().any(|candidate| {
+            if self.anchored {
+                wildmatch(self.glob.as_bytes(), candidate.as_bytes())
+            } else {
+                let name = candidate.rsplit('/').next().unwrap_or(candidate);
+                wildmatch(self.glob.as_bytes(), name.as_bytes())
+            }
+        })
+    }
+}
+
+fn unescape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => result.extend(chars.next()),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+// matches text against a glob, where `*` and `?` don't match `/`, but `**` does.
+fn wildmatch(glob: &[u8], text: &[u8]) -> bool {
+    match glob {
+        [] => text.is_empty(),
+        [b'*', b'*', b'/', rest @ ..] => {
+            // `**/` matches zero or more directories
+            wildmatch(rest, text)
+                || text
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, c)| **c == b'/')
+                    .any(|(i, _)| wildmatch(rest, &text[i + 1..]))
+        }
+        [b'*', b'*', rest @ ..] => (0..=text.len()).any(|i| wildmatch(rest, &text[i..])),
+        [b'*', rest @ ..] => (0..=text.len())
+            .take_while(|i| *i == 0 || text[i - 1] != b'/')
+            .any(|i| wildmatch(rest, &text[i..])),
+        [b'?', rest @ ..] => match text {
+            [c, text @ ..] if *c != b'/' => wildmatch(rest, text),
+            _ => false,
+        },
+        [b'[', rest @ ..] => match (rest.iter().position(|c| *c == b']'), text) {
+            (Some(end), [c, text @ ..]) => {
+                let (class, negated) = match &rest[..end] {
+                    [b'!' | b'^', class @ ..] => (class, true),
+                    class => (class, false),
+                };
+                let mut matched = false;
+                let mut i = 0;
+                while i < class.len() {
+                    if i + 2 < class.len() && class[i + 1] == b'-' {
+                        matched |= (class[i]..=class[i + 2]).contains(c);
+                        i += 3;
+                    } else {
+                        matched |= class[i] == *c;
+                        i += 1;
+                    }
+                }
+                matched != negated && *c != b'/' && wildmatch(&rest[end + 1..], text)
+            }
+            _ => false,
+        },
+        [b'\\', c, rest @ ..] | [c, rest @ ..] => match text {
+            [t, text @ ..] if t == c => wildmatch(rest, text),
+            _ => false,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cone() {
+        let sparse = SparseCheckout::parse("/*\n!/*/\n/a/\n!/a/*/\n/a/b/\n/c\\ d/\n", true);
+        assert!(sparse.is_included("README.md"));
+        assert!(sparse.is_included("a/file.txt"));
+        assert!(!sparse.is_included("a/other/file.txt"));
+        assert!(sparse.is_included("a/b/file.txt"));
+        assert!(sparse.is_included("a/b/c/file.txt"));
+        assert!(sparse.is_included("c d/file.txt"));
+        assert!(!sparse.is_included("ab/file.txt"));
+        assert!(!sparse.is_included("d/file.txt"));
+    }
+
+    #[test]
+    fn test_patterns() {
+        let sparse = SparseCheckout::parse("/*\n!/*/\ndocs/\n*.md\n!/docs/private/\n", false);
+        assert!(sparse.is_included("Cargo.toml"));
+        assert!(!sparse.is_included("src/lib.rs"));
+        assert!(sparse.is_included("src/README.md"));
+        assert!(sparse.is_included("docs/index.html"));
+        assert!(sparse.is_included("nested/docs/index.html"));
+        assert!(!sparse.is_included("docs/private/secret.txt"));
+    }
+
+    #[test]
+    fn test_patterns_empty() {
+        let sparse = SparseCheckout::parse("# nothing\n", false);
+        assert!(!sparse.is_included("file.txt"));
+    }
+
+    #[test]
+    fn test_wildmatch() {
+        assert!(wildmatch(b"*.rs", b"lib.rs"));
+        assert!(!wildmatch(b"*.rs", b"src/lib.rs"));
+        assert!(wildmatch(b"src/**/*.rs", b"src/lib.rs"));
+        assert!(wildmatch(b"src/**/*.rs", b"src/a/b/lib.rs"));
+        assert!(wildmatch(b"src/**", b"src/a/b/lib.rs"));
+        assert!(wildmatch(b"file?.txt", b"file1.txt"));
+        assert!(wildmatch(b"file[0-9].txt", b"file1.txt"));
+        assert!(!wildmatch(b"file[!0-9].txt", b"file1.txt"));
+        assert!(wildmatch(b"\\*.txt", b"*.txt"));
+        assert!(!wildmatch(b"\\*.txt", b"a.txt"));
+    }
+}
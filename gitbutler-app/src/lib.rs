@@ -10,6 +10,7 @@ pub mod dedup;
 pub mod deltas;
 pub mod error;
 pub mod events;
+pub mod forge;
 pub mod fs;
 pub mod gb_repository;
 pub mod git;
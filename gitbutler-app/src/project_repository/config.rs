@@ -33,6 +33,15 @@ impl Config<'_> {
         Ok(gb_comitter == "0")
     }
 
+    pub fn forge_api_url(&self) -> Result<String, git::Error> {
+        let forge_api_url = self
+            .git_repository
+            .config()?
+            .get_string("gitbutler.forgeApiUrl")?
+            .unwrap_or_else(|| crate::forge::DEFAULT_API_URL.to_string());
+        Ok(forge_api_url)
+    }
+
     pub fn user_name(&self) -> Result<Option<String>, git::Error> {
         self.git_repository.config()?.get_string("user.name")
     }
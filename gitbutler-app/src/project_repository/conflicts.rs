@@ -2,12 +2,18 @@
 // this is the dumbest possible way to do this, but it is a placeholder
 // conflicts are stored one path per line in .git/conflicts
 // merge parent is stored in .git/base_merge_parent
+// base, ours and theirs blobs of every conflict are stored in .git/conflicts_sides
+// the rest of a branch rewrite that stopped at a conflict is stored in .git/rewrite_todo
 // conflicts are removed as they are resolved, the conflicts file is removed when there are no more conflicts
 // the merge parent file is removed when the merge is complete
 
-use std::io::{BufRead, Write};
+use std::{
+    collections::HashMap,
+    io::{BufRead, Write},
+};
 
-use anyhow::Result;
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
 
 use crate::git;
 
@@ -32,6 +38,49 @@ pub fn mark(repository: &Repository, paths: &[String], parent: Option<git::Oid>)
     Ok(())
 }
 
+// marks all conflicts of the merge index, remembering each side of them
+pub fn mark_index(
+    repository: &Repository,
+    index: &git::Index,
+    parent: Option<git::Oid>,
+) -> Result<()> {
+    let mut paths = Vec::new();
+    let mut sides = Vec::new();
+    for conflict in index
+        .conflicts()
+        .context("failed to get conflicts")?
+        .flatten()
+    {
+        if let Some(ours) = &conflict.our {
+            let path = std::str::from_utf8(&ours.path)
+                .context("failed to convert path to utf8")?
+                .to_string();
+            let side_id = |entry: &Option<git2::IndexEntry>| {
+                entry
+                    .as_ref()
+                    .map_or("-".to_string(), |entry| entry.id.to_string())
+            };
+            sides.push(format!(
+                "{} {} {} {}",
+                side_id(&conflict.ancestor),
+                side_id(&conflict.our),
+                side_id(&conflict.their),
+                path
+            ));
+            paths.push(path);
+        }
+    }
+
+    let sides_path = repository.git_repository.path().join("conflicts_sides");
+    let mut file = std::fs::File::create(sides_path)?;
+    for line in sides {
+        file.write_all(line.as_bytes())?;
+        file.write_all(b"\n")?;
+    }
+
+    mark(repository, &paths, parent)
+}
+
 pub fn merge_parent(repository: &Repository) -> Result<Option<git::Oid>> {
     let merge_path = repository.git_repository.path().join("base_merge_parent");
     if !merge_path.exists() {
@@ -122,5 +171,247 @@ pub fn clear(repository: &Repository) -> Result<()> {
         resolve(repository, &file)?;
     }
 
+    let sides_path = repository.git_repository.path().join("conflicts_sides");
+    if sides_path.exists() {
+        std::fs::remove_file(sides_path)?;
+    }
+
+    Ok(())
+}
+
+// remembers the rest of a branch rewrite, so that it can be continued once the
+// conflicts are resolved
+pub fn mark_rewrite(repository: &Repository, todo: &str) -> Result<()> {
+    let todo_path = repository.git_repository.path().join("rewrite_todo");
+    std::fs::write(todo_path, todo)?;
+    Ok(())
+}
+
+pub fn rewrite_todo(repository: &Repository) -> Result<Option<String>> {
+    let todo_path = repository.git_repository.path().join("rewrite_todo");
+    if !todo_path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(std::fs::read_to_string(todo_path)?))
+}
+
+pub fn clear_rewrite(repository: &Repository) -> Result<()> {
+    let todo_path = repository.git_repository.path().join("rewrite_todo");
+    if todo_path.exists() {
+        std::fs::remove_file(todo_path)?;
+    }
     Ok(())
 }
+
+// three-way content of a conflicting file. a side is None if the file does not exist
+// on it.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Conflict {
+    pub path: String,
+    pub base: Option<String>,
+    pub ours: Option<String>,
+    pub theirs: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Side {
+    Ours,
+    Theirs,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Resolution {
+    // take the whole file from one of the sides
+    Side(Side),
+    // pick a side for every conflicting hunk of the file, in order of appearance
+    Hunks(Vec<Side>),
+    // resolved content of the file
+    Content(String),
+}
+
+type Sides = (Option<git::Oid>, Option<git::Oid>, Option<git::Oid>);
+
+fn read_sides(repository: &Repository) -> Result<HashMap<String, Sides>> {
+    let sides_path = repository.git_repository.path().join("conflicts_sides");
+    if !sides_path.exists() {
+        return Ok(HashMap::new());
+    }
+
+    let parse_side = |side: &str| -> Result<Option<git::Oid>> {
+        if side == "-" {
+            Ok(None)
+        } else {
+            Ok(Some(side.parse()?))
+        }
+    };
+
+    let file = std::fs::File::open(sides_path)?;
+    let reader = std::io::BufReader::new(file);
+    let mut sides = HashMap::new();
+    for line in reader.lines().map_while(Result::ok) {
+        let mut parts = line.splitn(4, ' ');
+        let (Some(base), Some(ours), Some(theirs), Some(path)) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(anyhow!("malformed conflict sides: {}", line));
+        };
+        sides.insert(
+            path.to_string(),
+            (parse_side(base)?, parse_side(ours)?, parse_side(theirs)?),
+        );
+    }
+    Ok(sides)
+}
+
+fn read_blob(repository: &Repository, oid: Option<git::Oid>) -> Result<Option<String>> {
+    oid.map(|oid| {
+        let blob = repository
+            .git_repository
+            .find_blob(oid)
+            .context(format!("failed to find blob {}", oid))?;
+        Ok(String::from_utf8_lossy(blob.content()).to_string())
+    })
+    .transpose()
+}
+
+// returns three-way content of every file that is still conflicting
+pub fn list(repository: &Repository) -> Result<Vec<Conflict>> {
+    let sides = read_sides(repository)?;
+    conflicting_files(repository)?
+        .into_iter()
+        .map(|path| {
+            let (base, ours, theirs) = sides.get(&path).copied().unwrap_or_default();
+            Ok(Conflict {
+                base: read_blob(repository, base)?,
+                ours: read_blob(repository, ours)?,
+                theirs: read_blob(repository, theirs)?,
+                path,
+            })
+        })
+        .collect()
+}
+
+// writes resolved content of the conflicting file to the working directory, and marks
+// it as resolved.
+pub fn resolve_with(repository: &Repository, path: &str, resolution: &Resolution) -> Result<()> {
+    let file_path = repository.path().join(path);
+    let content = match resolution {
+        Resolution::Side(side) => {
+            let (_, ours, theirs) = read_sides(repository)?
+                .get(path)
+                .copied()
+                .context(format!("sides of {} are unknown", path))?;
+            let oid = match side {
+                Side::Ours => ours,
+                Side::Theirs => theirs,
+            };
+            read_blob(repository, oid)?
+        }
+        Resolution::Hunks(sides) => {
+            let content =
+                std::fs::read_to_string(&file_path).context(format!("failed to read {}", path))?;
+            Some(resolve_hunks(&content, sides)?)
+        }
+        Resolution::Content(content) => Some(content.clone()),
+    };
+
+    match content {
+        Some(content) => std::fs::write(&file_path, content)?,
+        None if file_path.exists() => std::fs::remove_file(&file_path)?,
+        None => {}
+    }
+
+    resolve(repository, path)
+}
+
+// replaces every conflict markers section of the content with the chosen side
+fn resolve_hunks(content: &str, sides: &[Side]) -> Result<String> {
+    enum State {
+        Outside,
+        Ours,
+        Base,
+        Theirs,
+    }
+
+    let mut state = State::Outside;
+    let mut resolved = String::new();
+    let mut ours = String::new();
+    let mut theirs = String::new();
+    let mut hunk = 0;
+    for line in content.split_inclusive('\n') {
+        match state {
+            State::Outside if line.starts_with("<<<<<<<") => state = State::Ours,
+            State::Outside => resolved.push_str(line),
+            State::Ours | State::Base if line.starts_with("=======") => state = State::Theirs,
+            State::Ours if line.starts_with("|||||||") => state = State::Base,
+            State::Ours => ours.push_str(line),
+            State::Base => {}
+            State::Theirs if line.starts_with(">>>>>>>") => {
+                let side = sides
+                    .get(hunk)
+                    .context(format!("no side chosen for conflict {}", hunk + 1))?;
+                resolved.push_str(match side {
+                    Side::Ours => &ours,
+                    Side::Theirs => &theirs,
+                });
+                ours.clear();
+                theirs.clear();
+                hunk += 1;
+                state = State::Outside;
+            }
+            State::Theirs => theirs.push_str(line),
+        }
+    }
+
+    if !matches!(state, State::Outside) {
+        return Err(anyhow!("unterminated conflict {}", hunk + 1));
+    }
+    if hunk != sides.len() {
+        return Err(anyhow!(
+            "{} sides chosen for {} conflicts",
+            sides.len(),
+            hunk
+        ));
+    }
+
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_hunks_picks_sides() {
+        let content = "a\n<<<<<<< ours\nb\n=======\nc\n>>>>>>> theirs\nd\n<<<<<<< ours\ne\n=======\nf\n>>>>>>> theirs\n";
+        assert_eq!(
+            resolve_hunks(content, &[Side::Ours, Side::Theirs]).unwrap(),
+            "a\nb\nd\nf\n"
+        );
+        assert_eq!(
+            resolve_hunks(content, &[Side::Theirs, Side::Ours]).unwrap(),
+            "a\nc\nd\ne\n"
+        );
+    }
+
+    #[test]
+    fn resolve_hunks_skips_base() {
+        let content = "<<<<<<< ours\nb\n||||||| base\nx\n=======\nc\n>>>>>>> theirs\n";
+        assert_eq!(resolve_hunks(content, &[Side::Theirs]).unwrap(), "c\n");
+    }
+
+    #[test]
+    fn resolve_hunks_wrong_number_of_sides() {
+        let content = "<<<<<<< ours\nb\n=======\nc\n>>>>>>> theirs\n";
+        resolve_hunks(content, &[]).unwrap_err();
+        resolve_hunks(content, &[Side::Ours, Side::Ours]).unwrap_err();
+    }
+
+    #[test]
+    fn resolve_hunks_unterminated() {
+        resolve_hunks("<<<<<<< ours\nb\n=======\n", &[Side::Ours]).unwrap_err();
+    }
+}
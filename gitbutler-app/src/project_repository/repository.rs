@@ -98,7 +98,8 @@ impl Repository {
     }
 
     pub fn root(&self) -> &std::path::Path {
-        self.git_repository.path().parent().unwrap()
+        // not the parent of `.git`, which is elsewhere for linked worktrees
+        self.git_repository.workdir().unwrap()
     }
 
     pub fn git_remote_branches(&self) -> Result<Vec<git::RemoteRefname>> {
@@ -114,29 +115,38 @@ impl Repository {
     }
 
     pub fn add_branch_reference(&self, branch: &Branch) -> Result<()> {
-        let (should_write, with_force) =
-            match self.git_repository.find_reference(&branch.refname().into()) {
-                Ok(reference) => match reference.target() {
-                    Some(head_oid) => Ok((head_oid != branch.head, true)),
-                    None => Ok((true, true)),
-                },
-                Err(git::Error::NotFound(_)) => Ok((true, false)),
-                Err(error) => Err(error),
-            }
-            .context("failed to lookup reference")?;
-
-        if should_write {
-            self.git_repository
-                .reference(
-                    &branch.refname().into(),
-                    branch.head,
-                    with_force,
-                    "new vbranch",
-                )
-                .context("failed to create branch reference")?;
-        }
-
-        Ok(())
+        use gitbutler_git::Repository;
+
+        // the reference is only written if it changed, and only if no one else moved it
+        // in the meantime
+        let expected = match self.git_repository.find_reference(&branch.refname().into()) {
+            Ok(reference) => match reference.target() {
+                Some(head_oid) if head_oid == branch.head => return Ok(()),
+                Some(head_oid) => gitbutler_git::RefExpectation::Target(head_oid.to_string()),
+                None => gitbutler_git::RefExpectation::Any,
+            },
+            Err(git::Error::NotFound(_)) => gitbutler_git::RefExpectation::Absent,
+            Err(error) => return Err(error).context("failed to lookup reference"),
+        };
+        let transaction = gitbutler_git::RefTransaction::default()
+            .with_update(
+                branch.refname().to_string(),
+                branch.head.to_string(),
+                expected,
+            )
+            .with_message("new vbranch");
+
+        futures::executor::block_on(async {
+            let repository = gitbutler_git::git2::Repository::<
+                gitbutler_git::git2::tokio::TokioThreadedResource,
+            >::open(self.git_repository.path())
+            .await
+            .context("failed to open repository")?;
+            repository
+                .update_refs(&transaction)
+                .await
+                .context("failed to create branch reference")
+        })
     }
 
     pub fn delete_branch_reference(&self, branch: &Branch) -> Result<()> {
@@ -395,11 +405,21 @@ impl Repository {
         Err(RemoteError::Auth)
     }
 
+    /// fetches all branches of the remote, reporting progress to `options`. the fetch
+    /// is aborted with `RemoteError::Cancelled` once its cancellation token is cancelled.
     pub fn fetch(
         &self,
         remote_name: &str,
         credentials: &git::credentials::Helper,
+        options: &gitbutler_git::FetchOptions,
     ) -> Result<(), RemoteError> {
+        let is_cancelled = || {
+            options
+                .cancellation
+                .as_ref()
+                .map_or(false, gitbutler_git::CancellationToken::is_cancelled)
+        };
+
         let refspec = &format!("+refs/heads/*:refs/remotes/{}/*", remote_name);
         let auth_flows = credentials.help(self, remote_name)?;
         for (mut remote, callbacks) in auth_flows {
@@ -409,11 +429,23 @@ impl Repository {
                 }
             }
             for callback in callbacks {
+                if is_cancelled() {
+                    return Err(RemoteError::Cancelled);
+                }
+
                 let mut fetch_opts = git2::FetchOptions::new();
                 let mut cbs: git2::RemoteCallbacks = callback.into();
                 if self.project.omit_certificate_check.unwrap_or(false) {
                     cbs.certificate_check(|_, _| Ok(git2::CertificateCheckStatus::CertificateOk));
                 }
+                // returning false from the progress callbacks aborts the fetch
+                cbs.sideband_progress(|_| !is_cancelled());
+                cbs.transfer_progress(|progress| {
+                    if let Some(on_progress) = &options.progress {
+                        on_progress(gitbutler_git::Progress::from(&progress));
+                    }
+                    !is_cancelled()
+                });
                 fetch_opts.remote_callbacks(cbs);
                 fetch_opts.prune(git2::FetchPrune::On);
 
@@ -422,6 +454,7 @@ impl Repository {
                         tracing::info!(project_id = %self.project.id, %refspec, "git fetched");
                         return Ok(());
                     }
+                    Err(_) if is_cancelled() => return Err(RemoteError::Cancelled),
                     Err(git::Error::Auth(error) | git::Error::Http(error)) => {
                         tracing::warn!(project_id = %self.project.id, ?error, "fetch failed");
                         continue;
@@ -447,6 +480,8 @@ pub enum RemoteError {
     Network,
     #[error("authentication failed")]
     Auth,
+    #[error("cancelled")]
+    Cancelled,
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
@@ -463,6 +498,10 @@ impl From<RemoteError> for crate::error::Error {
                 code: crate::error::Code::ProjectGitAuth,
                 message: "Project remote authentication error".to_string(),
             },
+            RemoteError::Cancelled => crate::error::Error::UserError {
+                code: crate::error::Code::ProjectGitRemote,
+                message: "Fetch cancelled".to_string(),
+            },
             RemoteError::Other(error) => {
                 tracing::error!(?error);
                 crate::error::Error::Unknown
@@ -61,6 +61,13 @@ impl From<controller::AddError> for Error {
                 code: Code::Projects,
                 message: "Project already exists".to_string(),
             },
+            controller::AddError::SameRepository(path) => Error::UserError {
+                code: Code::Projects,
+                message: format!(
+                    "Another worktree of this repository is already a project: {}",
+                    path.display()
+                ),
+            },
             controller::AddError::OpenProjectRepository(error) => error.into(),
             controller::AddError::NotADirectory => Error::UserError {
                 code: Code::Projects,
@@ -88,6 +95,64 @@ pub async fn add_project(
     handle.state::<Controller>().add(path).map_err(Into::into)
 }
 
+impl From<controller::CloneError> for Error {
+    fn from(value: controller::CloneError) -> Self {
+        match value {
+            controller::CloneError::PathNotEmpty => Error::UserError {
+                code: Code::Projects,
+                message: "Directory is not empty".to_string(),
+            },
+            controller::CloneError::AuthorizationFailed => Error::UserError {
+                code: Code::Projects,
+                message: "Authorization failed".to_string(),
+            },
+            controller::CloneError::Cancelled => Error::UserError {
+                code: Code::Projects,
+                message: "Clone cancelled".to_string(),
+            },
+            controller::CloneError::Add(error) => error.into(),
+            controller::CloneError::Other(error) => {
+                tracing::error!(?error, "failed to clone project");
+                Error::Unknown
+            }
+        }
+    }
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn clone_project(
+    handle: tauri::AppHandle,
+    url: &str,
+    path: &path::Path,
+    preferred_key: Option<projects::AuthKey>,
+) -> Result<projects::Project, Error> {
+    let progress_handle = handle.clone();
+    handle
+        .state::<Controller>()
+        .clone(
+            url,
+            path,
+            &preferred_key.unwrap_or_default(),
+            gitbutler_git::FetchOptions::default().with_progress(move |progress| {
+                if let Err(error) = progress_handle.emit_all("project://clone/progress", progress) {
+                    tracing::error!(?error, "failed to emit clone progress");
+                }
+            }),
+        )
+        .await
+        .map_err(Into::into)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn cancel_clone_project(
+    handle: tauri::AppHandle,
+    path: &path::Path,
+) -> Result<bool, Error> {
+    Ok(handle.state::<Controller>().cancel_clone(path))
+}
+
 impl From<controller::GetError> for Error {
     fn from(value: controller::GetError) -> Self {
         match value {
@@ -1,11 +1,15 @@
-use std::path;
+use std::{
+    collections::HashMap,
+    path,
+    sync::{Arc, Mutex},
+};
 
 use anyhow::Context;
 use tauri::{AppHandle, Manager};
 
-use crate::{gb_repository, project_repository, users, watcher};
+use crate::{gb_repository, git, keys, project_repository, users, watcher};
 
-use super::{storage, storage::UpdateRequest, Project, ProjectId};
+use super::{storage, storage::UpdateRequest, AuthKey, Project, ProjectId};
 
 #[derive(Clone)]
 pub struct Controller {
@@ -13,6 +17,8 @@ pub struct Controller {
     projects_storage: storage::Storage,
     users: users::Controller,
     watchers: Option<watcher::Watchers>,
+    // running clones, by the path they clone into
+    clones: Arc<Mutex<HashMap<path::PathBuf, gitbutler_git::CancellationToken>>>,
 }
 
 impl TryFrom<&AppHandle> for Controller {
@@ -27,6 +33,7 @@ impl TryFrom<&AppHandle> for Controller {
                 projects_storage: storage::Storage::try_from(value)?,
                 users: users::Controller::try_from(value)?,
                 watchers: Some(watcher::Watchers::try_from(value)?),
+                clones: Arc::default(),
             })
         } else {
             Err(anyhow::anyhow!("failed to get app data dir"))
@@ -43,6 +50,7 @@ impl TryFrom<&std::path::PathBuf> for Controller {
             projects_storage: storage::Storage::try_from(value)?,
             users: users::Controller::try_from(value)?,
             watchers: None,
+            clones: Arc::default(),
         })
     }
 }
@@ -82,9 +90,24 @@ impl Controller {
             ..Default::default()
         };
 
+        let project_repository = project_repository::Repository::open(&project)?;
+
+        // gitbutler refs, like the integration branch, are shared by all worktrees
+        // of a repository, so only one of them can be a project.
+        let common_dir = project_repository
+            .git_repository
+            .common_dir()
+            .context("failed to find git directory")?;
+        if let Some(existing) = all_projects.iter().find(|existing| {
+            git::Repository::open(&existing.path)
+                .and_then(|repository| repository.common_dir())
+                .is_ok_and(|existing_common_dir| existing_common_dir == common_dir)
+        }) {
+            return Err(AddError::SameRepository(existing.path.clone()));
+        }
+
         // create all required directories to avoid racing later
         let user = self.users.get_user()?;
-        let project_repository = project_repository::Repository::open(&project)?;
         gb_repository::Repository::open(&self.local_data_dir, &project_repository, user.as_ref())
             .context("failed to open repository")?;
 
@@ -99,6 +122,110 @@ impl Controller {
         Ok(project)
     }
 
+    /// clones the repository into `path` and adds it as a project, which then uses
+    /// `preferred_key` to authenticate with its remotes.
+    ///
+    /// progress is reported to `options`, and the clone can be aborted with its
+    /// cancellation token, or with `cancel_clone`.
+    pub async fn clone(
+        &self,
+        url: &str,
+        path: &path::Path,
+        preferred_key: &AuthKey,
+        options: gitbutler_git::FetchOptions,
+    ) -> Result<Project, CloneError> {
+        if path.exists()
+            && path
+                .read_dir()
+                .context("failed to read directory")?
+                .next()
+                .is_some()
+        {
+            return Err(CloneError::PathNotEmpty);
+        }
+
+        let authorization = self.authorization(preferred_key)?;
+
+        let cancellation = options.cancellation.clone().unwrap_or_default();
+        let options = options.with_cancellation(cancellation.clone());
+        self.clones
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), cancellation);
+
+        let result = gitbutler_git::git2::Repository::<
+            gitbutler_git::git2::tokio::TokioThreadedResource,
+        >::clone(url, path, &authorization, &options)
+        .await;
+
+        self.clones.lock().unwrap().remove(path);
+
+        result.map_err(|error| match error {
+            gitbutler_git::Error::AuthorizationFailed(_) => CloneError::AuthorizationFailed,
+            gitbutler_git::Error::Cancelled => CloneError::Cancelled,
+            error => CloneError::Other(anyhow::Error::from(error).context("failed to clone")),
+        })?;
+
+        let project = self.add(path)?;
+        if matches!(preferred_key, AuthKey::Default) {
+            return Ok(project);
+        }
+        self.projects_storage
+            .update(&UpdateRequest {
+                id: project.id,
+                preferred_key: Some(preferred_key.clone()),
+                ..Default::default()
+            })
+            .context("failed to update project")
+            .map_err(Into::into)
+    }
+
+    /// cancels the clone into `path`, returns false if there is none running
+    pub fn cancel_clone(&self, path: &path::Path) -> bool {
+        match self.clones.lock().unwrap().get(path) {
+            Some(cancellation) => {
+                cancellation.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn authorization(&self, key: &AuthKey) -> anyhow::Result<gitbutler_git::Authorization> {
+        match key {
+            AuthKey::Default | AuthKey::GitCredentialsHelper => {
+                Ok(gitbutler_git::Authorization::Auto)
+            }
+            AuthKey::Local {
+                private_key_path,
+                passphrase,
+            } => {
+                use resolve_path::PathResolveExt;
+                Ok(gitbutler_git::Authorization::Ssh {
+                    private_key: Some(private_key_path.resolve().display().to_string()),
+                    passphrase: passphrase.clone(),
+                    host_key_policy: gitbutler_git::HostKeyPolicy::default(),
+                })
+            }
+            AuthKey::Generated => {
+                // the key is kept in the app data dir, generated on first use
+                keys::Controller::try_from(&self.local_data_dir)?
+                    .get_or_create()
+                    .context("failed to get or create generated key")?;
+                Ok(gitbutler_git::Authorization::Ssh {
+                    private_key: Some(
+                        self.local_data_dir
+                            .join("keys/ed25519")
+                            .display()
+                            .to_string(),
+                    ),
+                    passphrase: None,
+                    host_key_policy: gitbutler_git::HostKeyPolicy::default(),
+                })
+            }
+        }
+    }
+
     pub async fn update(&self, project: &UpdateRequest) -> Result<Project, UpdateError> {
         if let Some(super::AuthKey::Local {
             private_key_path, ..
@@ -243,6 +370,20 @@ pub enum UpdateValidationError {
     KeyNotFile(path::PathBuf),
 }
 
+#[derive(Debug, thiserror::Error)]
+pub enum CloneError {
+    #[error("path is not empty")]
+    PathNotEmpty,
+    #[error("authorization failed")]
+    AuthorizationFailed,
+    #[error("cancelled")]
+    Cancelled,
+    #[error(transparent)]
+    Add(#[from] AddError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum AddError {
     #[error("not a directory")]
@@ -253,6 +394,8 @@ pub enum AddError {
     PathNotFound,
     #[error("project already exists")]
     AlreadyExists,
+    #[error("another worktree of the repository is already a project: {0}")]
+    SameRepository(path::PathBuf),
     #[error(transparent)]
     User(#[from] users::GetError),
     #[error(transparent)]
@@ -33,3 +33,7 @@ pub use r#virtual::*;
 
 mod remote;
 pub use remote::*;
+
+mod stack;
+
+pub mod oplog;
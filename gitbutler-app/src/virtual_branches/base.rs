@@ -1,4 +1,4 @@
-use std::time;
+use std::{collections::HashMap, time};
 
 use anyhow::{Context, Result};
 use serde::Serialize;
@@ -201,6 +201,9 @@ pub fn set_base_branch(
                 ownership,
                 order: 0,
                 selected_for_changes: None,
+                parent: None,
+                pr_number: None,
+                pr_status: None,
             };
 
             let branch_writer =
@@ -308,193 +311,278 @@ pub fn update_base_branch(
     let branch_writer =
         branch::Writer::new(gb_repository).context("failed to create branch writer")?;
 
-    // try to update every branch
-    let updated_vbranches = super::get_status_by_branch(gb_repository, project_repository)?
-        .into_iter()
-        .map(|(branch, _)| branch)
-        .map(
-            |mut branch: branch::Branch| -> Result<Option<branch::Branch>> {
-                let branch_tree = repo.find_tree(branch.tree)?;
-
-                let branch_head_commit = repo.find_commit(branch.head).context(format!(
-                    "failed to find commit {} for branch {}",
-                    branch.head, branch.id
-                ))?;
-                let branch_head_tree = branch_head_commit.tree().context(format!(
-                    "failed to find tree for commit {} for branch {}",
-                    branch.head, branch.id
-                ))?;
+    let update_branch = |mut branch: branch::Branch| -> Result<Option<branch::Branch>> {
+        let branch_tree = repo.find_tree(branch.tree)?;
 
-                let result_integrated_detected =
-                    |mut branch: branch::Branch| -> Result<Option<branch::Branch>> {
-                        // branch head tree is the same as the new target tree.
-                        // meaning we can safely use the new target commit as the branch head.
-
-                        branch.head = new_target_commit.id();
-
-                        // it also means that the branch is fully integrated into the target.
-                        // disconnect it from the upstream
-                        branch.upstream = None;
-                        branch.upstream_head = None;
-
-                        let non_commited_files = diff::trees(
-                            &project_repository.git_repository,
-                            &branch_head_tree,
-                            &branch_tree,
-                        )?;
-                        if non_commited_files.is_empty() {
-                            // if there are no commited files, then the branch is fully merged
-                            // and we can delete it.
-                            branch_writer.delete(&branch)?;
-                            project_repository.delete_branch_reference(&branch)?;
-                            Ok(None)
-                        } else {
-                            branch_writer.write(&mut branch)?;
-                            Ok(Some(branch))
-                        }
-                    };
+        let branch_head_commit = repo.find_commit(branch.head).context(format!(
+            "failed to find commit {} for branch {}",
+            branch.head, branch.id
+        ))?;
+        let branch_head_tree = branch_head_commit.tree().context(format!(
+            "failed to find tree for commit {} for branch {}",
+            branch.head, branch.id
+        ))?;
 
-                if branch_head_tree.id() == new_target_tree.id() {
-                    return result_integrated_detected(branch);
+        let result_integrated_detected =
+            |mut branch: branch::Branch| -> Result<Option<branch::Branch>> {
+                // branch head tree is the same as the new target tree.
+                // meaning we can safely use the new target commit as the branch head.
+
+                branch.head = new_target_commit.id();
+
+                // it also means that the branch is fully integrated into the target.
+                // disconnect it from the upstream
+                branch.upstream = None;
+                branch.upstream_head = None;
+
+                let non_commited_files = diff::trees(
+                    &project_repository.git_repository,
+                    &branch_head_tree,
+                    &branch_tree,
+                )?;
+                if non_commited_files.is_empty() {
+                    // if there are no commited files, then the branch is fully merged
+                    // and we can delete it.
+                    branch_writer.delete(&branch)?;
+                    project_repository.delete_branch_reference(&branch)?;
+                    Ok(None)
+                } else {
+                    branch_writer.write(&mut branch)?;
+                    Ok(Some(branch))
                 }
+            };
 
-                // try to merge branch head with new target
-                let mut branch_tree_merge_index = repo
-                    .merge_trees(&old_target_tree, &branch_tree, &new_target_tree)
-                    .context(format!("failed to merge trees for branch {}", branch.id))?;
+        if branch_head_tree.id() == new_target_tree.id() {
+            return result_integrated_detected(branch);
+        }
 
-                if branch_tree_merge_index.has_conflicts() {
-                    // branch tree conflicts with new target, unapply branch for now. we'll handle it later, when user applies it back.
-                    branch.applied = false;
-                    branch_writer.write(&mut branch)?;
-                    return Ok(Some(branch));
-                }
+        // try to merge branch head with new target
+        let mut branch_tree_merge_index = repo
+            .merge_trees_with_commands(&old_target_tree, &branch_tree, &new_target_tree)
+            .context(format!("failed to merge trees for branch {}", branch.id))?;
 
-                let branch_merge_index_tree_oid = branch_tree_merge_index.write_tree_to(repo)?;
+        if branch_tree_merge_index.has_conflicts() {
+            // branch tree conflicts with new target, unapply branch for now. we'll handle it later, when user applies it back.
+            branch.applied = false;
+            branch_writer.write(&mut branch)?;
+            return Ok(Some(branch));
+        }
 
-                if branch_merge_index_tree_oid == new_target_tree.id() {
-                    return result_integrated_detected(branch);
-                }
+        let branch_merge_index_tree_oid = branch_tree_merge_index.write_tree_to(repo)?;
 
-                if branch.head == target.sha {
-                    // there are no commits on the branch, so we can just update the head to the new target and calculate the new tree
-                    branch.head = new_target_commit.id();
-                    branch.tree = branch_merge_index_tree_oid;
-                    branch_writer.write(&mut branch)?;
-                    return Ok(Some(branch));
-                }
+        if branch_merge_index_tree_oid == new_target_tree.id() {
+            return result_integrated_detected(branch);
+        }
 
-                let mut branch_head_merge_index = repo
-                    .merge_trees(&old_target_tree, &branch_head_tree, &new_target_tree)
-                    .context(format!(
-                        "failed to merge head tree for branch {}",
-                        branch.id
-                    ))?;
-
-                if branch_head_merge_index.has_conflicts() {
-                    // branch commits conflict with new target, make sure the branch is
-                    // unapplied. conflicts witll be dealt with when applying it back.
-                    branch.applied = false;
-                    branch_writer.write(&mut branch)?;
-                    return Ok(Some(branch));
-                }
+        if branch.head == target.sha {
+            // there are no commits on the branch, so we can just update the head to the new target and calculate the new tree
+            branch.head = new_target_commit.id();
+            branch.tree = branch_merge_index_tree_oid;
+            branch_writer.write(&mut branch)?;
+            return Ok(Some(branch));
+        }
 
-                // branch commits do not conflict with new target, so lets merge them
-                let branch_head_merge_tree_oid = branch_head_merge_index
-                    .write_tree_to(repo)
-                    .context(format!(
-                        "failed to write head merge index for {}",
-                        branch.id
-                    ))?;
-
-                let ok_with_force_push = project_repository.project().ok_with_force_push;
-
-                let result_merge = |mut branch: branch::Branch| -> Result<Option<branch::Branch>> {
-                    // branch was pushed to upstream, and user doesn't like force pushing.
-                    // create a merge commit to avoid the need of force pushing then.
-                    let branch_head_merge_tree = repo
-                        .find_tree(branch_head_merge_tree_oid)
-                        .context("failed to find tree")?;
-
-                    let new_target_head = project_repository
-                        .commit(
-                            user,
-                            format!(
-                                "Merged {}/{} into {}",
-                                target.branch.remote(),
-                                target.branch.branch(),
-                                branch.name
-                            )
-                            .as_str(),
-                            &branch_head_merge_tree,
-                            &[&branch_head_commit, &new_target_commit],
-                            signing_key,
-                        )
-                        .context("failed to commit merge")?;
-
-                    branch.head = new_target_head;
-                    branch.tree = branch_merge_index_tree_oid;
-                    branch_writer.write(&mut branch)?;
-                    Ok(Some(branch))
-                };
+        let mut branch_head_merge_index = repo
+            .merge_trees_with_commands(&old_target_tree, &branch_head_tree, &new_target_tree)
+            .context(format!(
+                "failed to merge head tree for branch {}",
+                branch.id
+            ))?;
+
+        if branch_head_merge_index.has_conflicts() {
+            // branch commits conflict with new target, make sure the branch is
+            // unapplied. conflicts witll be dealt with when applying it back.
+            branch.applied = false;
+            branch_writer.write(&mut branch)?;
+            return Ok(Some(branch));
+        }
 
-                if branch.upstream.is_some() && !ok_with_force_push {
-                    return result_merge(branch);
-                }
+        // branch commits do not conflict with new target, so lets merge them
+        let branch_head_merge_tree_oid =
+            branch_head_merge_index
+                .write_tree_to(repo)
+                .context(format!(
+                    "failed to write head merge index for {}",
+                    branch.id
+                ))?;
 
-                // branch was not pushed to upstream yet. attempt a rebase,
-                let (_, committer) = project_repository.git_signatures(user)?;
-                let mut rebase_options = git2::RebaseOptions::new();
-                rebase_options.quiet(true);
-                rebase_options.inmemory(true);
-                let mut rebase = repo
-                    .rebase(
-                        Some(branch.head),
-                        Some(new_target_commit.id()),
-                        None,
-                        Some(&mut rebase_options),
+        let ok_with_force_push = project_repository.project().ok_with_force_push;
+
+        let result_merge = |mut branch: branch::Branch| -> Result<Option<branch::Branch>> {
+            // branch was pushed to upstream, and user doesn't like force pushing.
+            // create a merge commit to avoid the need of force pushing then.
+            let branch_head_merge_tree = repo
+                .find_tree(branch_head_merge_tree_oid)
+                .context("failed to find tree")?;
+
+            let new_target_head = project_repository
+                .commit(
+                    user,
+                    format!(
+                        "Merged {}/{} into {}",
+                        target.branch.remote(),
+                        target.branch.branch(),
+                        branch.name
                     )
-                    .context("failed to rebase")?;
-
-                let mut rebase_success = true;
-                // check to see if these commits have already been pushed
-                let mut last_rebase_head = branch.head;
-                while rebase.next().is_some() {
-                    let index = rebase
-                        .inmemory_index()
-                        .context("failed to get inmemory index")?;
-                    if index.has_conflicts() {
-                        rebase_success = false;
-                        break;
-                    }
+                    .as_str(),
+                    &branch_head_merge_tree,
+                    &[&branch_head_commit, &new_target_commit],
+                    signing_key,
+                )
+                .context("failed to commit merge")?;
+
+            branch.head = new_target_head;
+            branch.tree = branch_merge_index_tree_oid;
+            branch_writer.write(&mut branch)?;
+            Ok(Some(branch))
+        };
 
-                    if let Ok(commit_id) = rebase.commit(None, &committer.clone().into(), None) {
-                        last_rebase_head = commit_id.into();
-                    } else {
-                        rebase_success = false;
-                        break;
-                    }
-                }
+        if branch.upstream.is_some() && !ok_with_force_push {
+            return result_merge(branch);
+        }
 
-                if rebase_success {
-                    // rebase worked out, rewrite the branch head
-                    rebase.finish(None).context("failed to finish rebase")?;
-                    branch.head = last_rebase_head;
-                    branch.tree = branch_merge_index_tree_oid;
-                    branch_writer.write(&mut branch)?;
-                    return Ok(Some(branch));
-                }
+        // branch was not pushed to upstream yet. attempt a rebase,
+        let (_, committer) = project_repository.git_signatures(user)?;
+        let mut rebase_options = git2::RebaseOptions::new();
+        rebase_options.quiet(true);
+        rebase_options.inmemory(true);
+        let mut rebase = repo
+            .rebase(
+                Some(branch.head),
+                Some(new_target_commit.id()),
+                None,
+                Some(&mut rebase_options),
+            )
+            .context("failed to rebase")?;
+
+        let mut rebase_success = true;
+        // check to see if these commits have already been pushed
+        let mut last_rebase_head = branch.head;
+        while rebase.next().is_some() {
+            let index = rebase
+                .inmemory_index()
+                .context("failed to get inmemory index")?;
+            if index.has_conflicts() {
+                rebase_success = false;
+                break;
+            }
+
+            if let Ok(commit_id) = rebase.commit(None, &committer.clone().into(), None) {
+                last_rebase_head = commit_id.into();
+            } else {
+                rebase_success = false;
+                break;
+            }
+        }
 
-                // rebase failed, do a merge commit
-                rebase.abort().context("failed to abort rebase")?;
+        if rebase_success {
+            // rebase worked out, rewrite the branch head
+            rebase.finish(None).context("failed to finish rebase")?;
+            branch.head = last_rebase_head;
+            branch.tree = branch_merge_index_tree_oid;
+            branch_writer.write(&mut branch)?;
+            return Ok(Some(branch));
+        }
 
-                result_merge(branch)
-            },
-        )
-        .collect::<Result<Vec<_>>>()?
+        // rebase failed, do a merge commit
+        rebase.abort().context("failed to abort rebase")?;
+
+        result_merge(branch)
+    };
+
+    // stacked branches are moved from the old head of the branch they are stacked on to
+    // the updated one, or to the new target if they are not stacked anymore
+    let update_stacked_branch = |mut branch: branch::Branch,
+                                 parent: Option<&branch::Branch>,
+                                 old_base: git::Oid|
+     -> Result<branch::Branch> {
+        let restacked = match parent {
+            // parent conflicts with the new target, and so does the rest of the stack
+            Some(parent) if !parent.applied => false,
+            Some(parent) => super::stack::restack_branch(
+                project_repository,
+                &mut branch,
+                old_base,
+                parent.head,
+                &parent.name,
+                user,
+                signing_key,
+            )?,
+            None => super::stack::restack_branch(
+                project_repository,
+                &mut branch,
+                old_base,
+                new_target_commit.id(),
+                &format!("{}/{}", target.branch.remote(), target.branch.branch()),
+                user,
+                signing_key,
+            )?,
+        };
+        if !restacked {
+            // branch conflicts with its new base, unapply it for now.
+            // conflicts will be dealt with when applying it back.
+            branch.applied = false;
+        }
+        branch_writer.write(&mut branch)?;
+        Ok(branch)
+    };
+
+    // try to update every branch, parents of the stacks go first
+    let mut vbranches = super::get_status_by_branch(gb_repository, project_repository)?
         .into_iter()
-        .flatten()
+        .map(|(branch, _)| branch)
         .collect::<Vec<_>>();
+    let mut updated_vbranches: Vec<branch::Branch> = vec![];
+    let mut old_heads = HashMap::new();
+    // branches integrated into the target and deleted, with the branch each was stacked on
+    let mut deleted_parents: HashMap<BranchId, Option<BranchId>> = HashMap::new();
+    while !vbranches.is_empty() {
+        let position = vbranches
+            .iter()
+            .position(|branch| {
+                branch.parent.map_or(true, |parent_id| {
+                    !vbranches.iter().any(|other| other.id == parent_id)
+                })
+            })
+            .context("branches are stacked in a loop")?;
+        let mut branch = vbranches.remove(position);
+        old_heads.insert(branch.id, branch.head);
+
+        let Some(parent_id) = branch.parent else {
+            let branch_id = branch.id;
+            match update_branch(branch)? {
+                Some(updated_branch) => updated_vbranches.push(updated_branch),
+                None => {
+                    deleted_parents.insert(branch_id, None);
+                }
+            }
+            continue;
+        };
+
+        let old_base = *old_heads.get(&parent_id).context(format!(
+            "branch {} is stacked on {}, which is not applied",
+            branch.id, parent_id
+        ))?;
+
+        // the parent was integrated into the target and deleted. the branch is stacked on
+        // whatever the parent was stacked on instead.
+        let mut new_parent_id = Some(parent_id);
+        while let Some(deleted_parent) = new_parent_id.and_then(|id| deleted_parents.get(&id)) {
+            new_parent_id = *deleted_parent;
+        }
+        branch.parent = new_parent_id;
+
+        let parent = new_parent_id
+            .map(|parent_id| {
+                updated_vbranches
+                    .iter()
+                    .find(|updated| updated.id == parent_id)
+                    .context(format!("parent branch {} not found", parent_id))
+            })
+            .transpose()?;
+        let updated_branch = update_stacked_branch(branch, parent, old_base)?;
+        updated_vbranches.push(updated_branch);
+    }
 
     // ok, now all the problematic branches have been unapplied
     // now we calculate and checkout new tree for the working directory
@@ -505,7 +593,8 @@ pub fn update_base_branch(
         .fold(new_target_commit.tree(), |final_tree, branch| {
             let final_tree = final_tree?;
             let branch_tree = repo.find_tree(branch.tree)?;
-            let mut merge_result = repo.merge_trees(&new_target_tree, &final_tree, &branch_tree)?;
+            let mut merge_result =
+                repo.merge_trees_with_commands(&new_target_tree, &final_tree, &branch_tree)?;
             let final_tree_oid = merge_result.write_tree_to(repo)?;
             repo.find_tree(final_tree_oid)
         })
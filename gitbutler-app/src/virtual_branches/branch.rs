@@ -5,7 +5,7 @@ mod reader;
 mod writer;
 
 pub use file_ownership::FileOwnership;
-pub use hunk::Hunk;
+pub use hunk::{select_lines, Hunk, Line};
 pub use ownership::Ownership;
 pub use reader::BranchReader as Reader;
 pub use writer::BranchWriter as Writer;
@@ -43,6 +43,13 @@ pub struct Branch {
     // is Some(timestamp), the branch is considered a default destination for new changes.
     // if more than one branch is selected, the branch with the highest timestamp wins.
     pub selected_for_changes: Option<i64>,
+    // is Some(id), the branch is stacked on top of the parent branch, and is based on it's
+    // head instead of the default target.
+    pub parent: Option<BranchId>,
+    // number of the pull request opened for the upstream branch, if any
+    pub pr_number: Option<u64>,
+    // status of the pull request, as of the last time it was created, updated or refreshed
+    pub pr_status: Option<crate::forge::Status>,
 }
 
 impl Branch {
@@ -68,6 +75,7 @@ pub struct BranchCreateRequest {
     pub ownership: Option<Ownership>,
     pub order: Option<usize>,
     pub selected_for_changes: Option<bool>,
+    pub parent: Option<BranchId>,
 }
 
 impl TryFrom<&crate::reader::Reader<'_>> for Branch {
@@ -88,6 +96,9 @@ impl TryFrom<&crate::reader::Reader<'_>> for Branch {
             "meta/updated_timestamp_ms",
             "meta/ownership",
             "meta/selected_for_changes",
+            "meta/parent",
+            "meta/pr_number",
+            "meta/pr_status",
         ])?;
 
         let id: String = results[0].clone()?.try_into()?;
@@ -185,6 +196,55 @@ impl TryFrom<&crate::reader::Reader<'_>> for Branch {
             Err(e) => Err(e),
         }?;
 
+        let parent = match results[13].clone() {
+            Ok(crate::reader::Content::UTF8(parent)) => {
+                parent.parse::<BranchId>().map(Some).map_err(|e| {
+                    crate::reader::Error::Io(
+                        std::io::Error::new(
+                            std::io::ErrorKind::Other,
+                            format!("meta/parent: {}", e),
+                        )
+                        .into(),
+                    )
+                })
+            }
+            Ok(_) | Err(crate::reader::Error::NotFound) => Ok(None),
+            Err(e) => Err(e),
+        }?;
+
+        let pr_number = match results[14].clone() {
+            Ok(pr_number) => {
+                let pr_number = pr_number.try_into().map_err(|e| {
+                    crate::reader::Error::Io(
+                        std::io::Error::new(
+                            std::io::ErrorKind::Other,
+                            format!("meta/pr_number: {}", e),
+                        )
+                        .into(),
+                    )
+                })?;
+                Ok(Some(pr_number))
+            }
+            Err(crate::reader::Error::NotFound) => Ok(None),
+            Err(e) => Err(e),
+        }?;
+
+        let pr_status = match results[15].clone() {
+            Ok(crate::reader::Content::UTF8(pr_status)) => {
+                pr_status.parse().map(Some).map_err(|e| {
+                    crate::reader::Error::Io(
+                        std::io::Error::new(
+                            std::io::ErrorKind::Other,
+                            format!("meta/pr_status: {}", e),
+                        )
+                        .into(),
+                    )
+                })
+            }
+            Ok(_) | Err(crate::reader::Error::NotFound) => Ok(None),
+            Err(e) => Err(e),
+        }?;
+
         Ok(Self {
             id,
             name,
@@ -209,6 +269,9 @@ impl TryFrom<&crate::reader::Reader<'_>> for Branch {
             ownership,
             order,
             selected_for_changes,
+            parent,
+            pr_number,
+            pr_status,
         })
     }
 }
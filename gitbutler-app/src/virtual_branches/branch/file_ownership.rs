@@ -63,7 +63,14 @@ impl FileOwnership {
             return false;
         }
 
-        another.hunks.iter().all(|hunk| self.hunks.contains(hunk))
+        another.hunks.iter().all(|hunk| {
+            self.hunks.iter().any(|owned| {
+                owned.same_hunk(hunk)
+                    && (!owned.is_partial()
+                        || (hunk.is_partial()
+                            && hunk.lines.iter().all(|line| owned.lines.contains(line))))
+            })
+        })
     }
 
     // return a copy of self, with another ranges added
@@ -85,12 +92,27 @@ impl FileOwnership {
         let mut hunks = self
             .hunks
             .iter()
-            .filter(|hunk| !another.hunks.contains(hunk))
+            .filter(|hunk| !another.hunks.iter().any(|h| h.same_hunk(hunk)))
             .cloned()
             .collect::<Vec<Hunk>>();
 
         another.hunks.iter().for_each(|hunk| {
-            hunks.insert(0, hunk.clone());
+            // lines of the same hunk are merged together, owning any hunk entirely
+            // means owning all of its lines.
+            let lines = self
+                .hunks
+                .iter()
+                .filter(|owned| owned.same_hunk(hunk))
+                .try_fold(hunk.lines.clone(), |mut lines, owned| {
+                    if owned.is_partial() && !lines.is_empty() {
+                        lines.extend(owned.lines.iter());
+                        Some(lines)
+                    } else {
+                        None
+                    }
+                })
+                .unwrap_or_default();
+            hunks.insert(0, hunk.with_lines(lines));
         });
 
         FileOwnership {
@@ -124,11 +146,26 @@ impl FileOwnership {
             left = left
                 .iter()
                 .flat_map(|r: &Hunk| -> Vec<Hunk> {
-                    if r.eq(range) {
+                    if !r.same_hunk(range) {
+                        vec![r.clone()]
+                    } else if !range.is_partial() {
                         taken.push(r.clone());
                         vec![]
-                    } else {
+                    } else if !r.is_partial() {
+                        // whole hunk - some lines = whole hunk, for the same reason as
+                        // full ownership - partial ownership.
                         vec![r.clone()]
+                    } else {
+                        let (taken_lines, left_lines): (Vec<_>, Vec<_>) =
+                            r.lines.iter().partition(|line| range.lines.contains(line));
+                        if !taken_lines.is_empty() {
+                            taken.push(r.with_lines(taken_lines));
+                        }
+                        if left_lines.is_empty() {
+                            vec![]
+                        } else {
+                            vec![r.with_lines(left_lines)]
+                        }
                     }
                 })
                 .collect();
@@ -233,6 +270,18 @@ mod tests {
             ),
             ("file.txt:1-10", "file.txt:1-10", "file.txt:1-10"),
             ("file.txt:1-10,3-15", "file.txt:1-10", "file.txt:1-10,3-15"),
+            (
+                "file.txt:1-10[+2]",
+                "file.txt:1-10[+3]",
+                "file.txt:1-10[+2;+3]",
+            ),
+            ("file.txt:1-10[+2]", "file.txt:1-10", "file.txt:1-10"),
+            ("file.txt:1-10", "file.txt:1-10[+2]", "file.txt:1-10"),
+            (
+                "file.txt:1-10[+2],11-15",
+                "file.txt:1-10[-4]",
+                "file.txt:1-10[+2;-4],11-15",
+            ),
         ]
         .into_iter()
         .map(|(a, b, expected)| {
@@ -285,6 +334,26 @@ mod tests {
                 "file.txt:1-10,15-17",
                 (Some("file.txt:1-10,15-17"), Some("file.txt:11-15")),
             ),
+            (
+                "file.txt:1-10[+2;+3]",
+                "file.txt:1-10[+3]",
+                (Some("file.txt:1-10[+3]"), Some("file.txt:1-10[+2]")),
+            ),
+            (
+                "file.txt:1-10[+2]",
+                "file.txt:1-10",
+                (Some("file.txt:1-10[+2]"), None),
+            ),
+            (
+                "file.txt:1-10",
+                "file.txt:1-10[+2]",
+                (None, Some("file.txt:1-10")),
+            ),
+            (
+                "file.txt:1-10[+2]",
+                "file.txt:1-10[+3]",
+                (None, Some("file.txt:1-10[+2]")),
+            ),
         ]
         .into_iter()
         .map(|(a, b, expected)| {
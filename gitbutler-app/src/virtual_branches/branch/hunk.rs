@@ -10,6 +10,48 @@ pub struct Hunk {
     pub timestamp_ms: Option<u128>,
     pub start: u32,
     pub end: u32,
+    // changed lines of the hunk that are owned. empty means all of them.
+    pub lines: Vec<Line>,
+}
+
+// a single changed line of a hunk.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
+pub enum Line {
+    // line added to the file, identified by its number in the new file
+    Added(u32),
+    // line removed from the file, identified by its number in the old file
+    Removed(u32),
+}
+
+impl FromStr for Line {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        if let Some(raw_line) = s.strip_prefix('+') {
+            Ok(Line::Added(
+                raw_line
+                    .parse()
+                    .context(format!("failed to parse line: {}", s))?,
+            ))
+        } else if let Some(raw_line) = s.strip_prefix('-') {
+            Ok(Line::Removed(
+                raw_line
+                    .parse()
+                    .context(format!("failed to parse line: {}", s))?,
+            ))
+        } else {
+            Err(anyhow!("invalid line: {}", s))
+        }
+    }
+}
+
+impl Display for Line {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Line::Added(line) => write!(f, "+{}", line),
+            Line::Removed(line) => write!(f, "-{}", line),
+        }
+    }
 }
 
 impl From<&diff::Hunk> for Hunk {
@@ -19,17 +61,14 @@ impl From<&diff::Hunk> for Hunk {
             end: hunk.new_start + hunk.new_lines,
             hash: None,
             timestamp_ms: None,
+            lines: vec![],
         }
     }
 }
 
 impl PartialEq for Hunk {
     fn eq(&self, other: &Self) -> bool {
-        if self.hash.is_some() && other.hash.is_some() {
-            self.hash == other.hash && self.start == other.start && self.end == other.end
-        } else {
-            self.start == other.start && self.end == other.end
-        }
+        self.same_hunk(other) && self.lines == other.lines
     }
 }
 
@@ -40,6 +79,7 @@ impl From<RangeInclusive<u32>> for Hunk {
             end: *range.end(),
             hash: None,
             timestamp_ms: None,
+            lines: vec![],
         }
     }
 }
@@ -48,6 +88,20 @@ impl FromStr for Hunk {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        // line selection goes right after the range, like 1-5[+2;-3]
+        let (s, lines) = match (s.find('['), s.find(']')) {
+            (Some(open), Some(close)) if open < close => (
+                format!("{}{}", &s[..open], &s[close + 1..]),
+                s[open + 1..close]
+                    .split(';')
+                    .map(str::parse)
+                    .collect::<Result<Vec<Line>>>()?,
+            ),
+            (None, None) => (s.to_string(), vec![]),
+            _ => return Err(anyhow!("invalid line selection: {}", s)),
+        };
+        let s = s.as_str();
+
         let mut range = s.split('-');
         let start = if let Some(raw_start) = range.next() {
             raw_start
@@ -85,13 +139,24 @@ impl FromStr for Hunk {
             None
         };
 
-        Hunk::new(start, end, hash, timestamp_ms)
+        Ok(Hunk::new(start, end, hash, timestamp_ms)?.with_lines(lines))
     }
 }
 
 impl Display for Hunk {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}-{}", self.start, self.end)?;
+        if !self.lines.is_empty() {
+            write!(
+                f,
+                "[{}]",
+                self.lines
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(";")
+            )?;
+        }
         match (self.hash.as_ref(), self.timestamp_ms.as_ref()) {
             (Some(hash), Some(timestamp_ms)) => write!(f, "-{}-{}", hash, timestamp_ms),
             (Some(hash), None) => write!(f, "-{}", hash),
@@ -116,6 +181,7 @@ impl Hunk {
                 end,
                 hash,
                 timestamp_ms,
+                lines: vec![],
             })
         }
     }
@@ -126,6 +192,7 @@ impl Hunk {
             end: self.end,
             hash: Some(hash.to_string()),
             timestamp_ms: self.timestamp_ms,
+            lines: self.lines.clone(),
         }
     }
 
@@ -135,6 +202,19 @@ impl Hunk {
             end: self.end,
             hash: self.hash.clone(),
             timestamp_ms: Some(timestamp_ms),
+            lines: self.lines.clone(),
+        }
+    }
+
+    pub fn with_lines(&self, mut lines: Vec<Line>) -> Self {
+        lines.sort();
+        lines.dedup();
+        Hunk {
+            start: self.start,
+            end: self.end,
+            hash: self.hash.clone(),
+            timestamp_ms: self.timestamp_ms,
+            lines,
         }
     }
 
@@ -142,6 +222,20 @@ impl Hunk {
         self.timestamp_ms
     }
 
+    // true if only some of the lines of the hunk are owned
+    pub fn is_partial(&self) -> bool {
+        !self.lines.is_empty()
+    }
+
+    // true if both hunks are the same, regardless of the owned lines
+    pub fn same_hunk(&self, another: &Hunk) -> bool {
+        if self.hash.is_some() && another.hash.is_some() {
+            self.hash == another.hash && self.start == another.start && self.end == another.end
+        } else {
+            self.start == another.start && self.end == another.end
+        }
+    }
+
     pub fn contains(&self, line: &u32) -> bool {
         self.start <= *line && self.end >= *line
     }
@@ -152,6 +246,166 @@ impl Hunk {
             || another.contains(&self.start)
             || another.contains(&self.end)
     }
+
+    // returns the owned hunk moved to where the given diff hunk with the given hash is now,
+    // if it is the same change. hunks with a hash are matched by it alone, so that owned lines survive edits
+    // above the hunk: added lines move with it, removed lines are numbered in the old file,
+    // which doesn't change. `None` if any owned line is not a changed line of the hunk.
+    pub fn relocate(&self, hunk: &diff::Hunk, hash: &str) -> Option<Hunk> {
+        let current = Hunk::from(hunk).with_hash(hash);
+        match &self.hash {
+            Some(owned_hash) if owned_hash != hash => return None,
+            None if !self.same_hunk(&current) => return None,
+            _ => {}
+        }
+
+        let delta = i64::from(current.start) - i64::from(self.start);
+        let lines = self
+            .lines
+            .iter()
+            .map(|line| match line {
+                Line::Added(line) => u32::try_from(i64::from(*line) + delta)
+                    .ok()
+                    .map(Line::Added),
+                Line::Removed(line) => Some(Line::Removed(*line)),
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        let changed = changed_lines(hunk);
+        if !lines.iter().all(|line| changed.contains(line)) {
+            return None;
+        }
+
+        Some(Hunk {
+            timestamp_ms: self.timestamp_ms,
+            lines,
+            ..current
+        })
+    }
+
+    // returns the part of the diff hunk that changes the owned lines only
+    pub fn select(&self, hunk: &diff::Hunk) -> Option<diff::Hunk> {
+        if self.is_partial() {
+            select_lines(hunk, |line| self.lines.contains(line))
+        } else {
+            Some(hunk.clone())
+        }
+    }
+}
+
+/// returns a copy of the diff hunk that changes only lines matching the predicate,
+/// or `None` if no changed lines match.
+///
+/// removed lines that are not selected are kept as context, added lines that are not
+/// selected are left out. the returned hunk starts where the original one does, its line
+/// counts are those of the rewritten patch.
+pub fn select_lines(hunk: &diff::Hunk, is_selected: impl Fn(&Line) -> bool) -> Option<diff::Hunk> {
+    if hunk.binary {
+        return None;
+    }
+
+    let mut lines = hunk.diff.split_inclusive('\n');
+    if !lines.next()?.starts_with("@@") {
+        return None;
+    }
+
+    let mut old_line = if hunk.old_lines == 0 {
+        hunk.old_start + 1
+    } else {
+        hunk.old_start
+    };
+    let mut new_line = hunk.new_start;
+
+    let mut body = String::new();
+    let mut old_lines = 0;
+    let mut new_lines = 0;
+    let mut has_changes = false;
+    let mut previous_kept = true;
+    for line in lines {
+        match line.chars().next() {
+            Some('+') => {
+                previous_kept = is_selected(&Line::Added(new_line));
+                if previous_kept {
+                    body.push_str(line);
+                    new_lines += 1;
+                    has_changes = true;
+                }
+                new_line += 1;
+            }
+            Some('-') => {
+                if is_selected(&Line::Removed(old_line)) {
+                    body.push_str(line);
+                    has_changes = true;
+                } else {
+                    // the line stays in the file
+                    body.push(' ');
+                    body.push_str(&line[1..]);
+                    new_lines += 1;
+                }
+                old_lines += 1;
+                old_line += 1;
+                previous_kept = true;
+            }
+            Some(' ') => {
+                body.push_str(line);
+                old_lines += 1;
+                new_lines += 1;
+                old_line += 1;
+                new_line += 1;
+                previous_kept = true;
+            }
+            _ => {
+                // "no newline at end of file" marker, belongs to the previous line
+                if previous_kept {
+                    body.push_str(line);
+                }
+            }
+        }
+    }
+
+    if !has_changes {
+        return None;
+    }
+
+    Some(diff::Hunk {
+        diff: format!(
+            "@@ -{},{} +{},{} @@\n{}",
+            hunk.old_start, old_lines, hunk.new_start, new_lines, body
+        ),
+        old_lines,
+        new_lines,
+        ..hunk.clone()
+    })
+}
+
+// returns the changed lines of the diff hunk, in the order they appear in the patch
+fn changed_lines(hunk: &diff::Hunk) -> Vec<Line> {
+    let mut old_line = if hunk.old_lines == 0 {
+        hunk.old_start + 1
+    } else {
+        hunk.old_start
+    };
+    let mut new_line = hunk.new_start;
+
+    let mut lines = vec![];
+    for line in hunk.diff.lines().skip(1) {
+        match line.chars().next() {
+            Some('+') => {
+                lines.push(Line::Added(new_line));
+                new_line += 1;
+            }
+            Some('-') => {
+                lines.push(Line::Removed(old_line));
+                old_line += 1;
+            }
+            Some(' ') => {
+                old_line += 1;
+                new_line += 1;
+            }
+            _ => {}
+        }
+    }
+    lines
 }
 
 #[cfg(test)]
@@ -245,4 +499,114 @@ mod tests {
             assert_eq!(a == b, expected, "comapring {} and {}", a, b);
         }
     }
+
+    #[test]
+    fn to_from_string_with_lines() {
+        let hunk = "1-5[+2;-3]-abc-123".parse::<Hunk>().unwrap();
+        assert_eq!(hunk.lines, vec![Line::Added(2), Line::Removed(3)]);
+        assert_eq!(hunk.hash, Some("abc".to_string()));
+        assert_eq!(hunk.timestamp_ms, Some(123));
+        assert_eq!("1-5[+2;-3]-abc-123", hunk.to_string());
+    }
+
+    #[test]
+    fn parse_invalid_lines() {
+        "1-5[2]".parse::<Hunk>().unwrap_err();
+        "1-5[+2".parse::<Hunk>().unwrap_err();
+    }
+
+    #[test]
+    fn eq_with_lines() {
+        assert_ne!(
+            "1-5[+2]".parse::<Hunk>().unwrap(),
+            "1-5".parse::<Hunk>().unwrap()
+        );
+        assert_eq!(
+            "1-5[+2;-3]".parse::<Hunk>().unwrap(),
+            "1-5[-3;+2]".parse::<Hunk>().unwrap()
+        );
+    }
+
+    fn diff_hunk() -> diff::Hunk {
+        diff::Hunk {
+            old_start: 2,
+            old_lines: 2,
+            new_start: 2,
+            new_lines: 3,
+            diff: "@@ -2,2 +2,3 @@\n-two\n-three\n+TWO\n+THREE\n+FOUR\n".to_string(),
+            binary: false,
+            change_type: diff::ChangeType::Modified,
+            old_path: None,
+        }
+    }
+
+    #[test]
+    fn select_added_lines() {
+        let hunk = select_lines(&diff_hunk(), |line| matches!(line, Line::Added(3 | 4))).unwrap();
+        assert_eq!(hunk.diff, "@@ -2,2 +2,4 @@\n two\n three\n+THREE\n+FOUR\n");
+        assert_eq!(hunk.old_start, 2);
+        assert_eq!(hunk.old_lines, 2);
+        assert_eq!(hunk.new_start, 2);
+        assert_eq!(hunk.new_lines, 4);
+    }
+
+    #[test]
+    fn select_removed_lines() {
+        let hunk = select_lines(&diff_hunk(), |line| *line == Line::Removed(2)).unwrap();
+        assert_eq!(hunk.diff, "@@ -2,2 +2,1 @@\n-two\n three\n");
+        assert_eq!(hunk.old_lines, 2);
+        assert_eq!(hunk.new_lines, 1);
+    }
+
+    #[test]
+    fn relocate_shifted_hunk() {
+        let owned = "2-5[+3;-2]".parse::<Hunk>().unwrap().with_hash("abc");
+        let shifted = diff::Hunk {
+            new_start: 4,
+            diff: "@@ -2,2 +4,3 @@\n-two\n-three\n+TWO\n+THREE\n+FOUR\n".to_string(),
+            ..diff_hunk()
+        };
+
+        let relocated = owned.relocate(&shifted, "abc").unwrap();
+        assert_eq!(relocated.start, 4);
+        assert_eq!(relocated.end, 7);
+        assert_eq!(relocated.lines, vec![Line::Added(5), Line::Removed(2)]);
+        assert_eq!(relocated.hash.as_deref(), Some("abc"));
+
+        // a different change is not the same hunk
+        assert!(owned.relocate(&shifted, "def").is_none());
+    }
+
+    #[test]
+    fn relocate_without_hash() {
+        // the hunk is matched by its range, and takes the hash of the change it matched
+        let owned = "2-5[+3]".parse::<Hunk>().unwrap();
+        let relocated = owned.relocate(&diff_hunk(), "abc").unwrap();
+        assert_eq!(relocated.lines, owned.lines);
+        assert_eq!(relocated.hash.as_deref(), Some("abc"));
+
+        let shifted = diff::Hunk {
+            new_start: 4,
+            ..diff_hunk()
+        };
+        assert!(owned.relocate(&shifted, "abc").is_none());
+    }
+
+    #[test]
+    fn relocate_unknown_lines() {
+        // line 6 is not one of the changed lines of the hunk
+        let owned = "2-5[+6]".parse::<Hunk>().unwrap().with_hash("abc");
+        assert!(owned.relocate(&diff_hunk(), "abc").is_none());
+    }
+
+    #[test]
+    fn select_nothing() {
+        assert!(select_lines(&diff_hunk(), |_| false).is_none());
+    }
+
+    #[test]
+    fn select_whole_hunk() {
+        let hunk = "2-5".parse::<Hunk>().unwrap();
+        assert_eq!(hunk.select(&diff_hunk()), Some(diff_hunk()));
+    }
 }
@@ -1,4 +1,4 @@
-use std::{fmt, str::FromStr};
+use std::{fmt, path, str::FromStr};
 
 use serde::{Deserialize, Serialize, Serializer};
 
@@ -118,6 +118,17 @@ impl Ownership {
 
         taken
     }
+
+    // moves ownership of the original file of a rename over to the renamed file, so
+    // that both of them stay together. returns true if the original file was owned.
+    pub fn rename(&mut self, from: &path::Path, to: &FileOwnership) -> bool {
+        if !self.files.iter().any(|o| o.file_path == from) {
+            return false;
+        }
+        self.files.retain(|o| o.file_path != from);
+        self.put(to);
+        true
+    }
 }
 
 #[cfg(test)]
@@ -126,6 +137,33 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_rename() {
+        let mut ownership = "src/old.rs:1-5\nsrc/main.rs:0-100"
+            .parse::<Ownership>()
+            .unwrap();
+        assert!(ownership.rename(
+            path::Path::new("src/old.rs"),
+            &"src/new.rs:1-6".parse::<FileOwnership>().unwrap()
+        ));
+        assert_eq!(
+            ownership,
+            "src/new.rs:1-6\nsrc/main.rs:0-100"
+                .parse::<Ownership>()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_rename_not_owned() {
+        let mut ownership = "src/main.rs:0-100".parse::<Ownership>().unwrap();
+        assert!(!ownership.rename(
+            path::Path::new("src/old.rs"),
+            &"src/new.rs:1-6".parse::<FileOwnership>().unwrap()
+        ));
+        assert_eq!(ownership, "src/main.rs:0-100".parse::<Ownership>().unwrap());
+    }
+
     #[test]
     fn test_ownership() {
         let ownership = "src/main.rs:0-100\nsrc/main2.rs:200-300".parse::<Ownership>();
@@ -80,6 +80,9 @@ mod tests {
                     .unwrap()],
             },
             selected_for_changes: Some(1),
+            parent: None,
+            pr_number: None,
+            pr_status: None,
         }
     }
 
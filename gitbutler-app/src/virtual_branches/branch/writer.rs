@@ -140,6 +140,42 @@ impl<'writer> BranchWriter<'writer> {
             )));
         }
 
+        if let Some(parent) = &branch.parent {
+            batch.push(writer::BatchTask::Write(
+                format!("branches/{}/meta/parent", branch.id),
+                parent.to_string(),
+            ));
+        } else {
+            batch.push(writer::BatchTask::Remove(format!(
+                "branches/{}/meta/parent",
+                branch.id
+            )));
+        }
+
+        if let Some(pr_number) = branch.pr_number {
+            batch.push(writer::BatchTask::Write(
+                format!("branches/{}/meta/pr_number", branch.id),
+                pr_number.to_string(),
+            ));
+        } else {
+            batch.push(writer::BatchTask::Remove(format!(
+                "branches/{}/meta/pr_number",
+                branch.id
+            )));
+        }
+
+        if let Some(pr_status) = branch.pr_status {
+            batch.push(writer::BatchTask::Write(
+                format!("branches/{}/meta/pr_status", branch.id),
+                pr_status.to_string(),
+            ));
+        } else {
+            batch.push(writer::BatchTask::Remove(format!(
+                "branches/{}/meta/pr_status",
+                branch.id
+            )));
+        }
+
         self.writer.batch(&batch)?;
 
         Ok(())
@@ -206,6 +242,9 @@ mod tests {
             },
             order: TEST_INDEX.load(Ordering::Relaxed),
             selected_for_changes: Some(1),
+            parent: None,
+            pr_number: None,
+            pr_status: None,
         }
     }
 
@@ -8,7 +8,9 @@ use tracing::instrument;
 use crate::{
     assets,
     error::{Code, Error},
-    git, projects,
+    forge, git,
+    project_repository::conflicts,
+    projects,
 };
 
 use super::{
@@ -571,6 +573,232 @@ pub async fn squash_branch_commit(
     Ok(())
 }
 
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn rewrite_branch(
+    handle: tauri::AppHandle,
+    project_id: &str,
+    branch_id: &str,
+    steps: Vec<super::RewriteStep>,
+) -> Result<Option<git::Oid>, Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".into(),
+    })?;
+    let branch_id = branch_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed branch id".into(),
+    })?;
+    let head = handle
+        .state::<Controller>()
+        .rewrite_branch(&project_id, &branch_id, &steps)
+        .await?;
+    emit_vbranches(&handle, &project_id).await;
+    Ok(head)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn continue_rewrite_branch(
+    handle: tauri::AppHandle,
+    project_id: &str,
+    branch_id: &str,
+) -> Result<Option<git::Oid>, Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".into(),
+    })?;
+    let branch_id = branch_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed branch id".into(),
+    })?;
+    let head = handle
+        .state::<Controller>()
+        .continue_rewrite(&project_id, &branch_id)
+        .await?;
+    emit_vbranches(&handle, &project_id).await;
+    Ok(head)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn move_commit(
+    handle: tauri::AppHandle,
+    project_id: &str,
+    commit_oid: &str,
+    from_branch_id: &str,
+    to_branch_id: &str,
+) -> Result<git::Oid, Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".into(),
+    })?;
+    let commit_oid = commit_oid.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed commit oid".into(),
+    })?;
+    let from_branch_id = from_branch_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed branch id".into(),
+    })?;
+    let to_branch_id = to_branch_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed branch id".into(),
+    })?;
+    let new_commit_oid = handle
+        .state::<Controller>()
+        .move_commit(&project_id, commit_oid, &from_branch_id, &to_branch_id)
+        .await?;
+    emit_vbranches(&handle, &project_id).await;
+    Ok(new_commit_oid)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn list_operations(
+    handle: tauri::AppHandle,
+    project_id: &str,
+) -> Result<Vec<super::oplog::Operation>, Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".into(),
+    })?;
+    handle
+        .state::<Controller>()
+        .list_operations(&project_id)
+        .await
+        .map_err(Into::into)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn restore_operation(
+    handle: tauri::AppHandle,
+    project_id: &str,
+    operation_id: &str,
+) -> Result<(), Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".into(),
+    })?;
+    let operation_id = operation_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed operation id".into(),
+    })?;
+    handle
+        .state::<Controller>()
+        .restore_operation(&project_id, operation_id)
+        .await?;
+    emit_vbranches(&handle, &project_id).await;
+    Ok(())
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn list_conflicts(
+    handle: tauri::AppHandle,
+    project_id: &str,
+) -> Result<Vec<conflicts::Conflict>, Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".into(),
+    })?;
+    handle
+        .state::<Controller>()
+        .list_conflicts(&project_id)
+        .map_err(Into::into)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn resolve_conflict(
+    handle: tauri::AppHandle,
+    project_id: &str,
+    path: &str,
+    resolution: conflicts::Resolution,
+) -> Result<(), Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".into(),
+    })?;
+    handle
+        .state::<Controller>()
+        .resolve_conflict(&project_id, path, &resolution)
+        .await?;
+    emit_vbranches(&handle, &project_id).await;
+    Ok(())
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn finalize_merge(
+    handle: tauri::AppHandle,
+    project_id: &str,
+    branch_id: &str,
+    message: &str,
+) -> Result<git::Oid, Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".into(),
+    })?;
+    let branch_id = branch_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed branch id".into(),
+    })?;
+    let oid = handle
+        .state::<Controller>()
+        .finalize_merge(&project_id, &branch_id, message)
+        .await?;
+    emit_vbranches(&handle, &project_id).await;
+    Ok(oid)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn create_pull_request(
+    handle: tauri::AppHandle,
+    project_id: &str,
+    branch_id: &str,
+) -> Result<forge::PullRequest, Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".into(),
+    })?;
+    let branch_id = branch_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed branch id".into(),
+    })?;
+    let pull_request = handle
+        .state::<Controller>()
+        .create_pull_request(&project_id, &branch_id)
+        .await?;
+    emit_vbranches(&handle, &project_id).await;
+    Ok(pull_request)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn refresh_pull_request(
+    handle: tauri::AppHandle,
+    project_id: &str,
+    branch_id: &str,
+) -> Result<Option<forge::PullRequest>, Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".into(),
+    })?;
+    let branch_id = branch_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed branch id".into(),
+    })?;
+    let pull_request = handle
+        .state::<Controller>()
+        .refresh_pull_request(&project_id, &branch_id)
+        .await?;
+    emit_vbranches(&handle, &project_id).await;
+    Ok(pull_request)
+}
+
 #[tauri::command(async)]
 #[instrument(skip(handle))]
 pub async fn fetch_from_target(
@@ -581,14 +809,38 @@ pub async fn fetch_from_target(
         code: Code::Validation,
         message: "Malformed project id".into(),
     })?;
+    let progress_handle = handle.clone();
     let base_branch = handle
         .state::<Controller>()
-        .fetch_from_target(&project_id)
+        .fetch_from_target(
+            &project_id,
+            gitbutler_git::FetchOptions::default().with_progress(move |progress| {
+                if let Err(error) = progress_handle.emit_all(
+                    &format!("project://{}/fetch/progress", project_id),
+                    progress,
+                ) {
+                    tracing::error!(?error, "failed to emit fetch progress");
+                }
+            }),
+        )
         .await?;
     emit_vbranches(&handle, &project_id).await;
     Ok(base_branch)
 }
 
+#[tauri::command(async)]
+#[instrument(skip(handle))]
+pub async fn cancel_fetch_from_target(
+    handle: tauri::AppHandle,
+    project_id: &str,
+) -> Result<bool, Error> {
+    let project_id = project_id.parse().map_err(|_| Error::UserError {
+        code: Code::Validation,
+        message: "Malformed project id".into(),
+    })?;
+    Ok(handle.state::<Controller>().cancel_fetch(&project_id))
+}
+
 pub async fn update_commit_message(
     handle: tauri::AppHandle,
     project_id: &str,
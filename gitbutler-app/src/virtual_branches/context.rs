@@ -24,6 +24,7 @@ pub fn hunk_with_context(
             new_lines: 0,
             binary: is_binary,
             change_type,
+            old_path: None,
         });
     }
 
@@ -131,6 +132,7 @@ pub fn hunk_with_context(
         new_lines: line_count_after as u32,
         binary: is_binary,
         change_type,
+        old_path: None,
     };
     Ok(hunk)
 }
@@ -6,7 +6,8 @@ use tokio::sync::Semaphore;
 
 use crate::{
     error::Error,
-    gb_repository, git, keys, project_repository,
+    forge, gb_repository, git, keys,
+    project_repository::{self, conflicts},
     projects::{self, ProjectId},
     users,
 };
@@ -29,6 +30,8 @@ pub struct Controller {
     helper: git::credentials::Helper,
 
     by_project_id: Arc<tokio::sync::Mutex<HashMap<ProjectId, ControllerInner>>>,
+    // running fetches, by the project they fetch for
+    fetches: Arc<std::sync::Mutex<HashMap<ProjectId, gitbutler_git::CancellationToken>>>,
 }
 
 impl TryFrom<&AppHandle> for Controller {
@@ -61,6 +64,7 @@ impl Controller {
     ) -> Self {
         Self {
             by_project_id: Arc::new(tokio::sync::Mutex::new(HashMap::new())),
+            fetches: Arc::default(),
 
             local_data_dir,
             projects,
@@ -350,15 +354,158 @@ impl Controller {
             .await
     }
 
-    pub async fn fetch_from_target(
+    pub async fn rewrite_branch(
         &self,
         project_id: &ProjectId,
-    ) -> Result<BaseBranch, ControllerError<errors::FetchFromTargetError>> {
+        branch_id: &BranchId,
+        steps: &[super::RewriteStep],
+    ) -> Result<Option<git::Oid>, ControllerError<errors::RewriteBranchError>> {
+        self.inner(project_id)
+            .await
+            .rewrite_branch(project_id, branch_id, steps)
+            .await
+    }
+
+    pub async fn continue_rewrite(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+    ) -> Result<Option<git::Oid>, ControllerError<errors::RewriteBranchError>> {
+        self.inner(project_id)
+            .await
+            .continue_rewrite(project_id, branch_id)
+            .await
+    }
+
+    pub async fn move_commit(
+        &self,
+        project_id: &ProjectId,
+        commit_oid: git::Oid,
+        from_branch_id: &BranchId,
+        to_branch_id: &BranchId,
+    ) -> Result<git::Oid, ControllerError<errors::MoveCommitError>> {
         self.inner(project_id)
             .await
-            .fetch_from_target(project_id)
+            .move_commit(project_id, commit_oid, from_branch_id, to_branch_id)
             .await
     }
+
+    pub async fn list_operations(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<Vec<super::oplog::Operation>, ControllerError<errors::ListOperationsError>> {
+        self.inner(project_id)
+            .await
+            .list_operations(project_id)
+            .await
+    }
+
+    pub async fn restore_operation(
+        &self,
+        project_id: &ProjectId,
+        operation_id: git::Oid,
+    ) -> Result<(), ControllerError<errors::RestoreOperationError>> {
+        self.inner(project_id)
+            .await
+            .restore_operation(project_id, operation_id)
+            .await
+    }
+
+    pub fn list_conflicts(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<Vec<conflicts::Conflict>, ControllerError<errors::ListConflictsError>> {
+        let project = self.projects.get(project_id).map_err(Error::from)?;
+        let project_repository =
+            project_repository::Repository::open(&project).map_err(Error::from)?;
+        super::list_conflicts(&project_repository).map_err(ControllerError::Action)
+    }
+
+    pub async fn resolve_conflict(
+        &self,
+        project_id: &ProjectId,
+        path: &str,
+        resolution: &conflicts::Resolution,
+    ) -> Result<(), ControllerError<errors::ResolveConflictError>> {
+        self.inner(project_id)
+            .await
+            .resolve_conflict(project_id, path, resolution)
+            .await
+    }
+
+    pub async fn finalize_merge(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        message: &str,
+    ) -> Result<git::Oid, ControllerError<errors::FinalizeMergeError>> {
+        self.inner(project_id)
+            .await
+            .finalize_merge(project_id, branch_id, message)
+            .await
+    }
+
+    pub async fn create_pull_request(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+    ) -> Result<forge::PullRequest, ControllerError<errors::CreatePullRequestError>> {
+        self.inner(project_id)
+            .await
+            .create_pull_request(project_id, branch_id)
+            .await
+    }
+
+    /// reads the current status of the pull request of the branch from the forge, as it
+    /// might have been merged or closed there. returns `None` if the branch has none.
+    pub async fn refresh_pull_request(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+    ) -> Result<Option<forge::PullRequest>, ControllerError<errors::CreatePullRequestError>> {
+        self.inner(project_id)
+            .await
+            .refresh_pull_request(project_id, branch_id)
+            .await
+    }
+
+    /// fetches the remote of the default target, reporting progress to `options`.
+    ///
+    /// the fetch can be aborted with the cancellation token of `options`, or with
+    /// `cancel_fetch`, e.g. when the project is closed.
+    pub async fn fetch_from_target(
+        &self,
+        project_id: &ProjectId,
+        options: gitbutler_git::FetchOptions,
+    ) -> Result<BaseBranch, ControllerError<errors::FetchFromTargetError>> {
+        let cancellation = options.cancellation.clone().unwrap_or_default();
+        let options = options.with_cancellation(cancellation.clone());
+        self.fetches
+            .lock()
+            .unwrap()
+            .insert(*project_id, cancellation);
+
+        let result = self
+            .inner(project_id)
+            .await
+            .fetch_from_target(project_id, &options)
+            .await;
+
+        self.fetches.lock().unwrap().remove(project_id);
+
+        result
+    }
+
+    /// cancels the fetch of the project, returns false if there is none running
+    pub fn cancel_fetch(&self, project_id: &ProjectId) -> bool {
+        match self.fetches.lock().unwrap().get(project_id) {
+            Some(cancellation) => {
+                cancellation.cancel();
+                true
+            }
+            None => false,
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -618,26 +765,30 @@ impl ControllerInner {
     ) -> Result<(), ControllerError<errors::UpdateBaseBranchError>> {
         let _permit = self.semaphore.acquire().await;
 
-        self.with_verify_branch(project_id, |gb_repository, project_repository, user| {
-            let signing_key = project_repository
-                .config()
-                .sign_commits()
-                .context("failed to get sign commits option")?
-                .then(|| {
-                    self.keys
-                        .get_or_create()
-                        .context("failed to get private key")
-                })
-                .transpose()?;
-
-            super::update_base_branch(
-                gb_repository,
-                project_repository,
-                user,
-                signing_key.as_ref(),
-            )
-            .map_err(Into::into)
-        })
+        self.with_snapshot(
+            project_id,
+            "update base branch",
+            |gb_repository, project_repository, user| {
+                let signing_key = project_repository
+                    .config()
+                    .sign_commits()
+                    .context("failed to get sign commits option")?
+                    .then(|| {
+                        self.keys
+                            .get_or_create()
+                            .context("failed to get private key")
+                    })
+                    .transpose()?;
+
+                super::update_base_branch(
+                    gb_repository,
+                    project_repository,
+                    user,
+                    signing_key.as_ref(),
+                )
+                .map_err(Into::into)
+            },
+        )
     }
 
     pub async fn update_virtual_branch(
@@ -660,10 +811,14 @@ impl ControllerInner {
     ) -> Result<(), ControllerError<errors::DeleteBranchError>> {
         let _permit = self.semaphore.acquire().await;
 
-        self.with_verify_branch(project_id, |gb_repository, project_repository, _| {
-            super::delete_branch(gb_repository, project_repository, branch_id)?;
-            Ok(())
-        })
+        self.with_snapshot(
+            project_id,
+            "delete branch",
+            |gb_repository, project_repository, _| {
+                super::delete_branch(gb_repository, project_repository, branch_id)?;
+                Ok(())
+            },
+        )
     }
 
     pub async fn apply_virtual_branch(
@@ -703,10 +858,14 @@ impl ControllerInner {
     ) -> Result<(), ControllerError<errors::UnapplyOwnershipError>> {
         let _permit = self.semaphore.acquire().await;
 
-        self.with_verify_branch(project_id, |gb_repository, project_repository, _| {
-            super::unapply_ownership(gb_repository, project_repository, ownership)
-                .map_err(Into::into)
-        })
+        self.with_snapshot(
+            project_id,
+            "unapply changes",
+            |gb_repository, project_repository, _| {
+                super::unapply_ownership(gb_repository, project_repository, ownership)
+                    .map_err(Into::into)
+            },
+        )
     }
 
     pub async fn amend(
@@ -717,10 +876,32 @@ impl ControllerInner {
     ) -> Result<git::Oid, ControllerError<errors::AmendError>> {
         let _permit = self.semaphore.acquire().await;
 
-        self.with_verify_branch(project_id, |gb_repository, project_repository, _| {
-            super::amend(gb_repository, project_repository, branch_id, ownership)
+        self.with_snapshot(
+            project_id,
+            "amend",
+            |gb_repository, project_repository, user| {
+                let signing_key = project_repository
+                    .config()
+                    .sign_commits()
+                    .context("failed to get sign commits option")?
+                    .then(|| {
+                        self.keys
+                            .get_or_create()
+                            .context("failed to get private key")
+                    })
+                    .transpose()?;
+
+                super::amend(
+                    gb_repository,
+                    project_repository,
+                    branch_id,
+                    ownership,
+                    user,
+                    signing_key.as_ref(),
+                )
                 .map_err(Into::into)
-        })
+            },
+        )
     }
 
     pub async fn reset_virtual_branch(
@@ -731,15 +912,32 @@ impl ControllerInner {
     ) -> Result<(), ControllerError<errors::ResetBranchError>> {
         let _permit = self.semaphore.acquire().await;
 
-        self.with_verify_branch(project_id, |gb_repository, project_repository, _| {
-            super::reset_branch(
-                gb_repository,
-                project_repository,
-                branch_id,
-                target_commit_oid,
-            )
-            .map_err(Into::into)
-        })
+        self.with_snapshot(
+            project_id,
+            "reset branch",
+            |gb_repository, project_repository, user| {
+                let signing_key = project_repository
+                    .config()
+                    .sign_commits()
+                    .context("failed to get sign commits option")?
+                    .then(|| {
+                        self.keys
+                            .get_or_create()
+                            .context("failed to get private key")
+                    })
+                    .transpose()?;
+
+                super::reset_branch(
+                    gb_repository,
+                    project_repository,
+                    branch_id,
+                    target_commit_oid,
+                    user,
+                    signing_key.as_ref(),
+                )
+                .map_err(Into::into)
+            },
+        )
     }
 
     pub async fn unapply_virtual_branch(
@@ -749,11 +947,15 @@ impl ControllerInner {
     ) -> Result<(), ControllerError<errors::UnapplyBranchError>> {
         let _permit = self.semaphore.acquire().await;
 
-        self.with_verify_branch(project_id, |gb_repository, project_repository, _| {
-            super::unapply_branch(gb_repository, project_repository, branch_id)
-                .map(|_| ())
-                .map_err(Into::into)
-        })
+        self.with_snapshot(
+            project_id,
+            "unapply branch",
+            |gb_repository, project_repository, _| {
+                super::unapply_branch(gb_repository, project_repository, branch_id)
+                    .map(|_| ())
+                    .map_err(Into::into)
+            },
+        )
     }
 
     pub async fn push_virtual_branch(
@@ -784,10 +986,32 @@ impl ControllerInner {
     ) -> Result<Option<git::Oid>, ControllerError<errors::CherryPickError>> {
         let _permit = self.semaphore.acquire().await;
 
-        self.with_verify_branch(project_id, |gb_repository, project_repository, _| {
-            super::cherry_pick(gb_repository, project_repository, branch_id, commit_oid)
+        self.with_snapshot(
+            project_id,
+            "cherry pick",
+            |gb_repository, project_repository, user| {
+                let signing_key = project_repository
+                    .config()
+                    .sign_commits()
+                    .context("failed to get sign commits option")?
+                    .then(|| {
+                        self.keys
+                            .get_or_create()
+                            .context("failed to get private key")
+                    })
+                    .transpose()?;
+
+                super::cherry_pick(
+                    gb_repository,
+                    project_repository,
+                    branch_id,
+                    commit_oid,
+                    user,
+                    signing_key.as_ref(),
+                )
                 .map_err(Into::into)
-        })
+            },
+        )
     }
 
     pub fn list_remote_branches(
@@ -835,10 +1059,32 @@ impl ControllerInner {
     ) -> Result<(), ControllerError<errors::SquashError>> {
         let _permit = self.semaphore.acquire().await;
 
-        self.with_verify_branch(project_id, |gb_repository, project_repository, _| {
-            super::squash(gb_repository, project_repository, branch_id, commit_oid)
+        self.with_snapshot(
+            project_id,
+            "squash",
+            |gb_repository, project_repository, user| {
+                let signing_key = project_repository
+                    .config()
+                    .sign_commits()
+                    .context("failed to get sign commits option")?
+                    .then(|| {
+                        self.keys
+                            .get_or_create()
+                            .context("failed to get private key")
+                    })
+                    .transpose()?;
+
+                super::squash(
+                    gb_repository,
+                    project_repository,
+                    branch_id,
+                    commit_oid,
+                    user,
+                    signing_key.as_ref(),
+                )
                 .map_err(Into::into)
-        })
+            },
+        )
     }
 
     pub async fn update_commit_message(
@@ -849,21 +1095,288 @@ impl ControllerInner {
         message: &str,
     ) -> Result<(), ControllerError<errors::UpdateCommitMessageError>> {
         let _permit = self.semaphore.acquire().await;
+
+        self.with_snapshot(
+            project_id,
+            "update commit message",
+            |gb_repository, project_repository, user| {
+                let signing_key = project_repository
+                    .config()
+                    .sign_commits()
+                    .context("failed to get sign commits option")?
+                    .then(|| {
+                        self.keys
+                            .get_or_create()
+                            .context("failed to get private key")
+                    })
+                    .transpose()?;
+
+                super::update_commit_message(
+                    gb_repository,
+                    project_repository,
+                    branch_id,
+                    commit_oid,
+                    message,
+                    user,
+                    signing_key.as_ref(),
+                )
+                .map_err(Into::into)
+            },
+        )
+    }
+
+    pub async fn rewrite_branch(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        steps: &[super::RewriteStep],
+    ) -> Result<Option<git::Oid>, ControllerError<errors::RewriteBranchError>> {
+        let _permit = self.semaphore.acquire().await;
+
+        self.with_snapshot(
+            project_id,
+            "rewrite branch",
+            |gb_repository, project_repository, user| {
+                let signing_key = project_repository
+                    .config()
+                    .sign_commits()
+                    .context("failed to get sign commits option")?
+                    .then(|| {
+                        self.keys
+                            .get_or_create()
+                            .context("failed to get private key")
+                    })
+                    .transpose()?;
+
+                super::rewrite_branch(
+                    gb_repository,
+                    project_repository,
+                    branch_id,
+                    steps,
+                    user,
+                    signing_key.as_ref(),
+                )
+                .map_err(Into::into)
+            },
+        )
+    }
+
+    pub async fn continue_rewrite(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+    ) -> Result<Option<git::Oid>, ControllerError<errors::RewriteBranchError>> {
+        let _permit = self.semaphore.acquire().await;
+
+        self.with_verify_branch(project_id, |gb_repository, project_repository, user| {
+            let signing_key = project_repository
+                .config()
+                .sign_commits()
+                .context("failed to get sign commits option")?
+                .then(|| {
+                    self.keys
+                        .get_or_create()
+                        .context("failed to get private key")
+                })
+                .transpose()?;
+
+            super::continue_rewrite(
+                gb_repository,
+                project_repository,
+                branch_id,
+                user,
+                signing_key.as_ref(),
+            )
+            .map_err(Into::into)
+        })
+    }
+
+    pub async fn move_commit(
+        &self,
+        project_id: &ProjectId,
+        commit_oid: git::Oid,
+        from_branch_id: &BranchId,
+        to_branch_id: &BranchId,
+    ) -> Result<git::Oid, ControllerError<errors::MoveCommitError>> {
+        let _permit = self.semaphore.acquire().await;
+
+        self.with_snapshot(
+            project_id,
+            "move commit",
+            |gb_repository, project_repository, user| {
+                let signing_key = project_repository
+                    .config()
+                    .sign_commits()
+                    .context("failed to get sign commits option")?
+                    .then(|| {
+                        self.keys
+                            .get_or_create()
+                            .context("failed to get private key")
+                    })
+                    .transpose()?;
+
+                super::move_commit(
+                    gb_repository,
+                    project_repository,
+                    commit_oid,
+                    from_branch_id,
+                    to_branch_id,
+                    user,
+                    signing_key.as_ref(),
+                )
+                .map_err(Into::into)
+            },
+        )
+    }
+
+    pub async fn list_operations(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<Vec<super::oplog::Operation>, ControllerError<errors::ListOperationsError>> {
+        let _permit = self.semaphore.acquire().await;
+
+        let project = self.projects.get(project_id).map_err(Error::from)?;
+        let project_repository =
+            project_repository::Repository::open(&project).map_err(Error::from)?;
+        let user = self.users.get_user().map_err(Error::from)?;
+        let gb_repository = gb_repository::Repository::open(
+            &self.local_data_dir,
+            &project_repository,
+            user.as_ref(),
+        )
+        .context("failed to open gitbutler repository")?;
+        super::oplog::list(&gb_repository)
+            .map_err(errors::ListOperationsError::Other)
+            .map_err(ControllerError::Action)
+    }
+
+    pub async fn restore_operation(
+        &self,
+        project_id: &ProjectId,
+        operation_id: git::Oid,
+    ) -> Result<(), ControllerError<errors::RestoreOperationError>> {
+        let _permit = self.semaphore.acquire().await;
+
         self.with_verify_branch(project_id, |gb_repository, project_repository, _| {
-            super::update_commit_message(
+            super::oplog::restore(gb_repository, project_repository, operation_id)
+        })
+    }
+
+    pub async fn resolve_conflict(
+        &self,
+        project_id: &ProjectId,
+        path: &str,
+        resolution: &conflicts::Resolution,
+    ) -> Result<(), ControllerError<errors::ResolveConflictError>> {
+        let _permit = self.semaphore.acquire().await;
+
+        self.with_verify_branch(project_id, |_, project_repository, _| {
+            super::resolve_conflict(project_repository, path, resolution)
+        })
+    }
+
+    pub async fn finalize_merge(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        message: &str,
+    ) -> Result<git::Oid, ControllerError<errors::FinalizeMergeError>> {
+        let _permit = self.semaphore.acquire().await;
+
+        self.with_verify_branch(project_id, |gb_repository, project_repository, user| {
+            let signing_key = project_repository
+                .config()
+                .sign_commits()
+                .context("failed to get sign commits option")?
+                .then(|| {
+                    self.keys
+                        .get_or_create()
+                        .context("failed to get private key")
+                })
+                .transpose()?;
+
+            super::finalize_merge(
                 gb_repository,
                 project_repository,
                 branch_id,
-                commit_oid,
                 message,
+                signing_key.as_ref(),
+                user,
             )
-            .map_err(Into::into)
         })
     }
 
+    pub async fn create_pull_request(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+    ) -> Result<forge::PullRequest, ControllerError<errors::CreatePullRequestError>> {
+        let _permit = self.semaphore.acquire().await;
+
+        let params =
+            self.with_verify_branch(project_id, |gb_repository, project_repository, user| {
+                super::pull_request_params(gb_repository, project_repository, branch_id, user)
+            })?;
+
+        let pull_request = forge::Client::new(&params.token)
+            .with_api_url(&params.api_url)
+            .create_or_update(
+                &params.repository,
+                params.number,
+                &forge::PullRequestRequest {
+                    title: &params.title,
+                    body: &params.body,
+                    head: &params.head,
+                    base: &params.base,
+                },
+            )
+            .await
+            .map_err(|error| {
+                ControllerError::Action(errors::CreatePullRequestError::from(error))
+            })?;
+
+        self.with_verify_branch(project_id, |gb_repository, _, _| {
+            super::set_pull_request(gb_repository, branch_id, &pull_request)
+        })?;
+
+        Ok(pull_request)
+    }
+
+    pub async fn refresh_pull_request(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+    ) -> Result<Option<forge::PullRequest>, ControllerError<errors::CreatePullRequestError>> {
+        let _permit = self.semaphore.acquire().await;
+
+        let params =
+            self.with_verify_branch(project_id, |gb_repository, project_repository, user| {
+                super::pull_request_params(gb_repository, project_repository, branch_id, user)
+            })?;
+        let Some(number) = params.number else {
+            // no pull request was created for the branch
+            return Ok(None);
+        };
+
+        let pull_request = forge::Client::new(&params.token)
+            .with_api_url(&params.api_url)
+            .get(&params.repository, number)
+            .await
+            .map_err(|error| {
+                ControllerError::Action(errors::CreatePullRequestError::from(error))
+            })?;
+
+        self.with_verify_branch(project_id, |gb_repository, _, _| {
+            super::set_pull_request(gb_repository, branch_id, &pull_request)
+        })?;
+
+        Ok(Some(pull_request))
+    }
+
     pub async fn fetch_from_target(
         &self,
         project_id: &ProjectId,
+        options: &gitbutler_git::FetchOptions,
     ) -> Result<BaseBranch, ControllerError<errors::FetchFromTargetError>> {
         let project = self.projects.get(project_id).map_err(Error::from)?;
         let mut project_repository =
@@ -887,12 +1400,20 @@ impl ControllerInner {
             .map_err(ControllerError::Action)?;
 
         let project_data_last_fetched = match project_repository
-            .fetch(default_target.branch.remote(), &self.helper)
+            .fetch(default_target.branch.remote(), &self.helper, options)
             .map_err(errors::FetchFromTargetError::Remote)
         {
             Ok(()) => projects::FetchResult::Fetched {
                 timestamp: std::time::SystemTime::now(),
             },
+            // a cancelled fetch didn't fail, so it isn't recorded
+            Err(
+                error @ errors::FetchFromTargetError::Remote(
+                    project_repository::RemoteError::Cancelled,
+                ),
+            ) => {
+                return Err(ControllerError::Action(error));
+            }
             Err(error) => projects::FetchResult::Error {
                 timestamp: std::time::SystemTime::now(),
                 error: error.to_string(),
@@ -941,4 +1462,40 @@ impl ControllerInner {
         super::integration::verify_branch(&gb_repository, &project_repository)?;
         action(&gb_repository, &project_repository, user.as_ref()).map_err(ControllerError::Action)
     }
+
+    // like `with_verify_branch`, but records the state of the project before
+    // performing `operation` once it succeeded, so that it can be restored later
+    fn with_snapshot<T, E: Into<Error>>(
+        &self,
+        project_id: &ProjectId,
+        operation: &str,
+        action: impl FnOnce(
+            &gb_repository::Repository,
+            &project_repository::Repository,
+            Option<&users::User>,
+        ) -> Result<T, E>,
+    ) -> Result<T, ControllerError<E>> {
+        let project = self.projects.get(project_id).map_err(Error::from)?;
+        let project_repository =
+            project_repository::Repository::open(&project).map_err(Error::from)?;
+        let user = self.users.get_user().map_err(Error::from)?;
+        let gb_repository = gb_repository::Repository::open(
+            &self.local_data_dir,
+            &project_repository,
+            user.as_ref(),
+        )
+        .context("failed to open gitbutler repository")?;
+        super::integration::verify_branch(&gb_repository, &project_repository)?;
+        let snapshot = super::oplog::prepare(&gb_repository, &project_repository)
+            .context("failed to snapshot virtual branches")?;
+        let result = action(&gb_repository, &project_repository, user.as_ref())
+            .map_err(ControllerError::Action)?;
+        if let Err(error) =
+            super::oplog::record(&gb_repository, &project_repository, snapshot, operation)
+        {
+            // the operation itself succeeded, it just can't be undone
+            tracing::warn!(?error, operation, "failed to record operation");
+        }
+        Ok(result)
+    }
 }
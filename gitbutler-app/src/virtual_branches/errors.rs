@@ -1,6 +1,6 @@
 use crate::{
     error::Error,
-    git,
+    forge, git,
     project_repository::{self, RemoteError},
     projects::ProjectId,
 };
@@ -66,6 +66,8 @@ pub enum ResetBranchError {
     #[error("default target not set")]
     DefaultTargetNotSet(DefaultTargetNotSetError),
     #[error(transparent)]
+    Restack(#[from] RestackError),
+    #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
 
@@ -79,6 +81,8 @@ pub enum ApplyBranchError {
     BranchConflicts(BranchId),
     #[error("default target not set")]
     DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error("branch is stacked on a branch that is not applied")]
+    ParentNotApplied(BranchId),
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
@@ -101,6 +105,8 @@ pub enum UnapplyBranchError {
     Conflict(ProjectConflictError),
     #[error("branch not found")]
     BranchNotFound(BranchNotFoundError),
+    #[error("other branches are stacked on the branch")]
+    HasStackedBranches(BranchId),
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
@@ -125,6 +131,10 @@ pub enum ListVirtualBranchesError {
 pub enum CreateVirtualBranchError {
     #[error("project")]
     DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error("parent branch not found")]
+    ParentNotFound(BranchNotFoundError),
+    #[error("parent branch is not applied")]
+    ParentNotApplied,
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
@@ -154,6 +164,16 @@ pub enum CommitError {
     #[error("commit msg hook rejected")]
     CommitMsgHookRejected(String),
     #[error(transparent)]
+    Restack(#[from] RestackError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RestackError {
+    #[error("stacked branch {0} conflicts with the branch it is stacked on")]
+    Conflict(BranchId),
+    #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
 
@@ -218,6 +238,8 @@ pub enum AmendError {
     #[error("project is in conflict state")]
     Conflict(ProjectConflictError),
     #[error(transparent)]
+    Restack(#[from] RestackError),
+    #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
 #[derive(Debug, thiserror::Error)]
@@ -229,6 +251,8 @@ pub enum CherryPickError {
     #[error("project is in conflict state")]
     Conflict(ProjectConflictError),
     #[error(transparent)]
+    Restack(#[from] RestackError),
+    #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
 
@@ -247,6 +271,8 @@ pub enum SquashError {
     #[error("can not squash root commit")]
     CantSquashRootCommit,
     #[error(transparent)]
+    Restack(#[from] RestackError),
+    #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
 
@@ -288,6 +314,8 @@ pub enum UpdateCommitMessageError {
     #[error("project is in conflict state")]
     Conflict(ProjectConflictError),
     #[error(transparent)]
+    Restack(#[from] RestackError),
+    #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
 
@@ -306,6 +334,7 @@ impl From<UpdateCommitMessageError> for Error {
             },
             UpdateCommitMessageError::BranchNotFound(error) => error.into(),
             UpdateCommitMessageError::Conflict(error) => error.into(),
+            UpdateCommitMessageError::Restack(error) => error.into(),
             UpdateCommitMessageError::Other(error) => {
                 tracing::error!(?error, "update commit message error");
                 Error::Unknown
@@ -314,6 +343,316 @@ impl From<UpdateCommitMessageError> for Error {
     }
 }
 
+#[derive(Debug, thiserror::Error)]
+pub enum RewriteBranchError {
+    #[error("force push not allowed")]
+    ForcePushNotAllowed(ForcePushNotAllowedError),
+    #[error("empty message")]
+    EmptyMessage,
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error("commit {0} not in the branch")]
+    CommitNotFound(git::Oid),
+    #[error("branch not found")]
+    BranchNotFound(BranchNotFoundError),
+    #[error("can not rewrite not applied branch")]
+    NotApplied,
+    #[error("project is in conflict state")]
+    Conflict(ProjectConflictError),
+    #[error("every commit must be listed exactly once")]
+    IncompleteTodo,
+    #[error("can not squash root commit")]
+    CantSquashRootCommit,
+    #[error("commit {0} conflicts with the rewritten history and uncommitted changes")]
+    RebaseConflict(git::Oid),
+    #[error("dropped changes conflict with the working directory")]
+    WorkdirConflict,
+    #[error("splitting commit {0} leaves an empty commit")]
+    EmptySplit(git::Oid),
+    #[error("branch is not being rewritten")]
+    NotRewriting,
+    #[error(transparent)]
+    Restack(#[from] RestackError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl From<RewriteBranchError> for Error {
+    fn from(value: RewriteBranchError) -> Self {
+        match value {
+            RewriteBranchError::ForcePushNotAllowed(error) => error.into(),
+            RewriteBranchError::EmptyMessage => Error::UserError {
+                message: "Commit message can not be empty".to_string(),
+                code: crate::error::Code::Branches,
+            },
+            RewriteBranchError::DefaultTargetNotSet(error) => error.into(),
+            RewriteBranchError::CommitNotFound(oid) => Error::UserError {
+                message: format!("Commit {} not found", oid),
+                code: crate::error::Code::Branches,
+            },
+            RewriteBranchError::BranchNotFound(error) => error.into(),
+            RewriteBranchError::NotApplied => Error::UserError {
+                message: "Can not rewrite non applied branch".to_string(),
+                code: crate::error::Code::Branches,
+            },
+            RewriteBranchError::Conflict(error) => error.into(),
+            RewriteBranchError::IncompleteTodo => Error::UserError {
+                message: "Every commit of the branch must be listed exactly once".to_string(),
+                code: crate::error::Code::Validation,
+            },
+            RewriteBranchError::CantSquashRootCommit => Error::UserError {
+                message: "Can not squash root branch commit".to_string(),
+                code: crate::error::Code::Branches,
+            },
+            RewriteBranchError::RebaseConflict(oid) => Error::UserError {
+                message: format!(
+                    "Commit {} conflicts, commit or stash uncommitted changes before resolving it",
+                    oid
+                ),
+                code: crate::error::Code::Branches,
+            },
+            RewriteBranchError::WorkdirConflict => Error::UserError {
+                message: "Dropped changes conflict with uncommitted changes".to_string(),
+                code: crate::error::Code::Branches,
+            },
+            RewriteBranchError::EmptySplit(oid) => Error::UserError {
+                message: format!("Splitting commit {} leaves an empty commit", oid),
+                code: crate::error::Code::Validation,
+            },
+            RewriteBranchError::NotRewriting => Error::UserError {
+                message: "Branch is not being rewritten".to_string(),
+                code: crate::error::Code::Branches,
+            },
+            RewriteBranchError::Restack(error) => error.into(),
+            RewriteBranchError::Other(error) => {
+                tracing::error!(?error, "rewrite branch error");
+                Error::Unknown
+            }
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum MoveCommitError {
+    #[error("force push not allowed")]
+    ForcePushNotAllowed(ForcePushNotAllowedError),
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error("commit {0} not in the branch")]
+    CommitNotFound(git::Oid),
+    #[error("branch not found")]
+    BranchNotFound(BranchNotFoundError),
+    #[error("can not move commits between not applied branches")]
+    NotApplied,
+    #[error("project is in conflict state")]
+    Conflict(ProjectConflictError),
+    #[error("commit {0} conflicts after removing the moved commit")]
+    SourceBranchConflict(git::Oid),
+    #[error("commit {0} conflicts with the destination branch")]
+    DestinationBranchConflict(git::Oid),
+    #[error(transparent)]
+    Restack(#[from] RestackError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl From<MoveCommitError> for Error {
+    fn from(value: MoveCommitError) -> Self {
+        match value {
+            MoveCommitError::ForcePushNotAllowed(error) => error.into(),
+            MoveCommitError::DefaultTargetNotSet(error) => error.into(),
+            MoveCommitError::CommitNotFound(oid) => Error::UserError {
+                message: format!("Commit {} not found", oid),
+                code: crate::error::Code::Branches,
+            },
+            MoveCommitError::BranchNotFound(error) => error.into(),
+            MoveCommitError::NotApplied => Error::UserError {
+                message: "Can not move commits between non applied branches".to_string(),
+                code: crate::error::Code::Branches,
+            },
+            MoveCommitError::Conflict(error) => error.into(),
+            MoveCommitError::SourceBranchConflict(oid) => Error::UserError {
+                message: format!(
+                    "Commit {} depends on the moved commit and can not be replayed without it",
+                    oid
+                ),
+                code: crate::error::Code::Branches,
+            },
+            MoveCommitError::DestinationBranchConflict(oid) => Error::UserError {
+                message: format!(
+                    "Commit {} can not be applied to the destination branch without conflicts",
+                    oid
+                ),
+                code: crate::error::Code::Branches,
+            },
+            MoveCommitError::Restack(error) => error.into(),
+            MoveCommitError::Other(error) => {
+                tracing::error!(?error, "move commit error");
+                Error::Unknown
+            }
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ListOperationsError {
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl From<ListOperationsError> for Error {
+    fn from(value: ListOperationsError) -> Self {
+        match value {
+            ListOperationsError::Other(error) => {
+                tracing::error!(?error, "list operations error");
+                Error::Unknown
+            }
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RestoreOperationError {
+    #[error("operation {0} not found")]
+    OperationNotFound(git::Oid),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl From<RestoreOperationError> for Error {
+    fn from(value: RestoreOperationError) -> Self {
+        match value {
+            RestoreOperationError::OperationNotFound(oid) => Error::UserError {
+                message: format!("Operation {} not found", oid),
+                code: crate::error::Code::Branches,
+            },
+            RestoreOperationError::Other(error) => {
+                tracing::error!(?error, "restore operation error");
+                Error::Unknown
+            }
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ListConflictsError {
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl From<ListConflictsError> for Error {
+    fn from(value: ListConflictsError) -> Self {
+        match value {
+            ListConflictsError::Other(error) => {
+                tracing::error!(?error, "list conflicts error");
+                Error::Unknown
+            }
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ResolveConflictError {
+    #[error("file {0} is not conflicting")]
+    NotConflicting(String),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl From<ResolveConflictError> for Error {
+    fn from(value: ResolveConflictError) -> Self {
+        match value {
+            ResolveConflictError::NotConflicting(path) => Error::UserError {
+                message: format!("File {} is not conflicting", path),
+                code: crate::error::Code::Branches,
+            },
+            ResolveConflictError::Other(error) => {
+                tracing::error!(?error, "resolve conflict error");
+                Error::Unknown
+            }
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum FinalizeMergeError {
+    #[error("project is not being merged")]
+    NotResolving,
+    #[error("project is in conflict state")]
+    Conflict(ProjectConflictError),
+    #[error(transparent)]
+    Commit(#[from] CommitError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl From<FinalizeMergeError> for Error {
+    fn from(value: FinalizeMergeError) -> Self {
+        match value {
+            FinalizeMergeError::NotResolving => Error::UserError {
+                message: "Project is not being merged".to_string(),
+                code: crate::error::Code::Branches,
+            },
+            FinalizeMergeError::Conflict(error) => error.into(),
+            FinalizeMergeError::Commit(error) => error.into(),
+            FinalizeMergeError::Other(error) => {
+                tracing::error!(?error, "finalize merge error");
+                Error::Unknown
+            }
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CreatePullRequestError {
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSetError),
+    #[error("branch not found")]
+    BranchNotFound(BranchNotFoundError),
+    #[error("branch is not pushed")]
+    NotPushed,
+    #[error("not authenticated with github")]
+    NotAuthenticated,
+    #[error(transparent)]
+    Forge(#[from] forge::Error),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl From<CreatePullRequestError> for Error {
+    fn from(value: CreatePullRequestError) -> Self {
+        match value {
+            CreatePullRequestError::DefaultTargetNotSet(error) => error.into(),
+            CreatePullRequestError::BranchNotFound(error) => error.into(),
+            CreatePullRequestError::NotPushed => Error::UserError {
+                message: "Branch must be pushed before creating a pull request".to_string(),
+                code: crate::error::Code::Branches,
+            },
+            CreatePullRequestError::NotAuthenticated => Error::UserError {
+                message: "Log in with GitHub to create pull requests".to_string(),
+                code: crate::error::Code::Branches,
+            },
+            CreatePullRequestError::Forge(forge::Error::UnsupportedRemote(remote_url)) => {
+                Error::UserError {
+                    message: format!("Can not create pull requests for {}", remote_url),
+                    code: crate::error::Code::Branches,
+                }
+            }
+            CreatePullRequestError::Forge(forge::Error::Response { status, message }) => {
+                Error::UserError {
+                    message: format!("Failed to create pull request ({}): {}", status, message),
+                    code: crate::error::Code::Branches,
+                }
+            }
+            CreatePullRequestError::Forge(forge::Error::Other(error))
+            | CreatePullRequestError::Other(error) => {
+                tracing::error!(?error, "create pull request error");
+                Error::Unknown
+            }
+        }
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum GetBaseBranchDataError {
     #[error(transparent)]
@@ -463,6 +802,7 @@ impl From<CommitError> for Error {
                 code: crate::error::Code::CommitMsgHook,
                 message: error,
             },
+            CommitError::Restack(error) => error.into(),
             CommitError::Other(error) => {
                 tracing::error!(?error, "commit error");
                 Error::Unknown
@@ -471,6 +811,24 @@ impl From<CommitError> for Error {
     }
 }
 
+impl From<RestackError> for Error {
+    fn from(value: RestackError) -> Self {
+        match value {
+            RestackError::Conflict(id) => Error::UserError {
+                message: format!(
+                    "Branch {} is stacked on this branch and conflicts with the change",
+                    id
+                ),
+                code: crate::error::Code::Branches,
+            },
+            RestackError::Other(error) => {
+                tracing::error!(?error, "restack error");
+                Error::Unknown
+            }
+        }
+    }
+}
+
 impl From<IsRemoteBranchMergableError> for Error {
     fn from(value: IsRemoteBranchMergableError) -> Self {
         match value {
@@ -509,6 +867,10 @@ impl From<ApplyBranchError> for Error {
                 message: format!("Branch {} is in a conflicing state", id),
                 code: crate::error::Code::Branches,
             },
+            ApplyBranchError::ParentNotApplied(id) => Error::UserError {
+                message: format!("Branch is stacked on branch {}, apply it first", id),
+                code: crate::error::Code::Branches,
+            },
             ApplyBranchError::Other(error) => {
                 tracing::error!(?error, "apply branch error");
                 Error::Unknown
@@ -546,6 +908,11 @@ impl From<CreateVirtualBranchError> for Error {
     fn from(value: CreateVirtualBranchError) -> Self {
         match value {
             CreateVirtualBranchError::DefaultTargetNotSet(error) => error.into(),
+            CreateVirtualBranchError::ParentNotFound(error) => error.into(),
+            CreateVirtualBranchError::ParentNotApplied => Error::UserError {
+                message: "Can not stack a branch on a non applied branch".to_string(),
+                code: crate::error::Code::Branches,
+            },
             CreateVirtualBranchError::Other(error) => {
                 tracing::error!(?error, "create virtual branch error");
                 Error::Unknown
@@ -650,6 +1017,7 @@ impl From<AmendError> for Error {
                 message: "target ownership not found".to_string(),
                 code: crate::error::Code::Branches,
             },
+            AmendError::Restack(error) => error.into(),
             AmendError::Other(error) => {
                 tracing::error!(?error, "amend error");
                 Error::Unknown
@@ -667,6 +1035,7 @@ impl From<ResetBranchError> for Error {
                 code: crate::error::Code::Branches,
                 message: format!("commit {} not found", oid),
             },
+            ResetBranchError::Restack(error) => error.into(),
             ResetBranchError::Other(error) => {
                 tracing::error!(?error, "reset branch error");
                 Error::Unknown
@@ -681,6 +1050,10 @@ impl From<UnapplyBranchError> for Error {
             UnapplyBranchError::Conflict(error) => error.into(),
             UnapplyBranchError::DefaultTargetNotSet(error) => error.into(),
             UnapplyBranchError::BranchNotFound(error) => error.into(),
+            UnapplyBranchError::HasStackedBranches(id) => Error::UserError {
+                message: format!("Branch {} is stacked on this branch, unapply it first", id),
+                code: crate::error::Code::Branches,
+            },
             UnapplyBranchError::Other(error) => {
                 tracing::error!(?error, "unapply branch error");
                 Error::Unknown
@@ -727,6 +1100,7 @@ impl From<CherryPickError> for Error {
                 message: format!("commit {oid} not found"),
                 code: crate::error::Code::Branches,
             },
+            CherryPickError::Restack(error) => error.into(),
             CherryPickError::Other(error) => {
                 tracing::error!(?error, "cherry pick error");
                 Error::Unknown
@@ -798,6 +1172,7 @@ impl From<SquashError> for Error {
                 message: format!("commit {oid} not found"),
                 code: crate::error::Code::Branches,
             },
+            SquashError::Restack(error) => error.into(),
             SquashError::Other(error) => {
                 tracing::error!(?error, "squash error");
                 Error::Unknown
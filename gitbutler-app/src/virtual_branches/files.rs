@@ -12,6 +12,8 @@ use crate::virtual_branches::context;
 #[serde(rename_all = "camelCase")]
 pub struct RemoteBranchFile {
     pub path: path::PathBuf,
+    // path the file was renamed or copied from
+    pub old_path: Option<path::PathBuf>,
     pub hunks: Vec<diff::Hunk>,
     pub binary: bool,
 }
@@ -41,6 +43,7 @@ pub fn list_remote_commit_files(
         .into_iter()
         .map(|(file_path, hunks)| RemoteBranchFile {
             path: file_path.clone(),
+            old_path: hunks.iter().find_map(|h| h.old_path.clone()),
             hunks: hunks.clone(),
             binary: hunks.iter().any(|h| h.binary),
         })
@@ -62,9 +65,12 @@ fn files_with_hunk_context(
             continue;
         }
         // Get file content as it looked before the diffs
-        let file_content_before =
-            show::show_file_at_tree(repository, file.path.clone(), parent_tree)
-                .context("failed to get file contents at HEAD")?;
+        let file_content_before = show::show_file_at_tree(
+            repository,
+            file.old_path.clone().unwrap_or_else(|| file.path.clone()),
+            parent_tree,
+        )
+        .context("failed to get file contents at HEAD")?;
         let file_lines_before = file_content_before.split('\n').collect::<Vec<_>>();
 
         file.hunks = file
@@ -84,6 +90,10 @@ fn files_with_hunk_context(
                         &file_lines_before,
                         hunk.change_type,
                     )
+                    .map(|hunk_with_context| diff::Hunk {
+                        old_path: hunk.old_path.clone(),
+                        ..hunk_with_context
+                    })
                 }
             })
             .collect::<Result<Vec<diff::Hunk>>>()
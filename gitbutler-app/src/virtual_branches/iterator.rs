@@ -107,6 +107,9 @@ mod tests {
             ownership: branch::Ownership::default(),
             order: TEST_INDEX.load(Ordering::Relaxed),
             selected_for_changes: Some(1),
+            parent: None,
+            pr_number: None,
+            pr_status: None,
         }
     }
 
@@ -0,0 +1,359 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::{gb_repository, git, project_repository, writer};
+
+use super::errors;
+
+// operations are recorded as a chain of commits in the gitbutler repository, next to
+// the sessions history. every commit holds the state right before the operation:
+//
+// - `branches` is the virtual branches metadata, same as in the session commits
+// - `wd` is the working directory of the project
+const OPERATIONS_REFERENCE: &str = "refs/heads/operations";
+
+// working directory trees are written to the project repository, where nothing else
+// references them. this reference keeps them reachable, so that `git gc` in the
+// project doesn't prune them while their operations can still be restored.
+const WD_TREES_REFERENCE: &str = "refs/gitbutler-operations";
+
+// only the most recent operations are kept
+const MAX_OPERATIONS: usize = 100;
+
+// an operation is identified by the commit it was recorded as. commits that are
+// rewritten when the oldest operations are pruned keep that id in a trailer of their
+// message.
+const ID_TRAILER: &str = "\n\nOperation-Id: ";
+
+// this struct is a mapping to the view `Operation` type in Typescript
+#[derive(Debug, PartialEq, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Operation {
+    pub id: git::Oid,
+    // name of the action that was performed after the snapshot was taken
+    pub name: String,
+    pub created_at: u128,
+}
+
+/// the state of the project, written but not yet recorded as an operation.
+pub struct Snapshot {
+    tree: git::Oid,
+}
+
+/// writes the current state of the virtual branches and the working directory. it
+/// is recorded with [`record`] once the operation it precedes has succeeded.
+pub fn prepare(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+) -> Result<Snapshot> {
+    let repo = gb_repository.git_repository();
+
+    // wd tree is written to the project repository. it is readable from the
+    // gitbutler repository too, since project objects are its alternates.
+    let wd_tree = project_repository
+        .get_wd_tree()
+        .context("failed to get working directory tree")?;
+
+    let _lock = gb_repository.lock();
+
+    let mut tree_builder = repo.treebuilder(None);
+    tree_builder.upsert(
+        "branches",
+        gb_repository::build_branches_tree(gb_repository)
+            .context("failed to build branches tree")?,
+        git::FileMode::Tree,
+    );
+    tree_builder.upsert("wd", wd_tree.id(), git::FileMode::Tree);
+    let tree = tree_builder.write().context("failed to write tree")?;
+
+    Ok(Snapshot { tree })
+}
+
+/// records the snapshot as the state right before performing `name` operation,
+/// forgetting the oldest operations if there are too many.
+pub fn record(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    snapshot: Snapshot,
+    name: &str,
+) -> Result<git::Oid> {
+    let repo = gb_repository.git_repository();
+
+    let _lock = gb_repository.lock();
+
+    let tree = repo
+        .find_tree(snapshot.tree)
+        .context("failed to find tree")?;
+
+    let signature = git::Signature::now("gitbutler", "gitbutler@localhost")
+        .context("failed to create signature")?;
+
+    let refname: git::Refname = OPERATIONS_REFERENCE.parse().unwrap();
+    let parent = match repo.find_reference(&refname) {
+        Ok(reference) => Some(
+            reference
+                .peel_to_commit()
+                .context("failed to find last operation")?,
+        ),
+        Err(git::Error::NotFound(_)) => None,
+        Err(error) => return Err(error.into()),
+    };
+
+    let operation_oid = repo
+        .commit(
+            Some(&refname),
+            &signature,
+            &signature,
+            name,
+            &tree,
+            &parent.iter().collect::<Vec<_>>(),
+        )
+        .context("failed to write operation commit")?;
+
+    let operations = operation_commits(repo)?;
+    if operations.len() > MAX_OPERATIONS {
+        prune(repo, &refname, &operations[..MAX_OPERATIONS])?;
+    }
+
+    anchor_wd_trees(gb_repository, project_repository)?;
+
+    Ok(operation_oid)
+}
+
+/// records the current state of the virtual branches and the working directory
+/// before performing `name` operation.
+pub fn snapshot(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    name: &str,
+) -> Result<git::Oid> {
+    let snapshot = prepare(gb_repository, project_repository)?;
+    record(gb_repository, project_repository, snapshot, name)
+}
+
+// rewrites the kept operations, oldest first, onto a new root commit. the dropped
+// ones are no longer reachable after that.
+fn prune(
+    repo: &git::Repository,
+    refname: &git::Refname,
+    kept: &[(Operation, git::Oid)],
+) -> Result<()> {
+    let mut head: Option<git::Commit> = None;
+    for (operation, commit_oid) in kept.iter().rev() {
+        let commit = repo
+            .find_commit(*commit_oid)
+            .context("failed to find operation commit")?;
+        let commit_oid = repo
+            .commit(
+                None,
+                &commit.author(),
+                &commit.committer(),
+                &format!("{}{}{}", operation.name, ID_TRAILER, operation.id),
+                &commit.tree().context("failed to find operation tree")?,
+                &head.iter().collect::<Vec<_>>(),
+            )
+            .context("failed to write operation commit")?;
+        head = Some(
+            repo.find_commit(commit_oid)
+                .context("failed to find operation commit")?,
+        );
+    }
+
+    let head_oid = head.context("no operations to keep")?.id();
+    repo.reference(refname, head_oid, true, "prune operations")
+        .context("failed to update operations reference")?;
+    Ok(())
+}
+
+// points the project reference at a commit with the working directory trees of all
+// recorded operations
+fn anchor_wd_trees(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+) -> Result<()> {
+    let repo = gb_repository.git_repository();
+    let project_repo = &project_repository.git_repository;
+
+    let mut tree_builder = project_repo.treebuilder(None);
+    for (operation, commit_oid) in operation_commits(repo)? {
+        let operation_tree = repo
+            .find_commit(commit_oid)
+            .context("failed to find operation commit")?
+            .tree()
+            .context("failed to find operation tree")?;
+        let wd_tree = operation_tree
+            .get_name("wd")
+            .context("operation has no working directory tree")?;
+        tree_builder.upsert(operation.id.to_string(), wd_tree.id(), git::FileMode::Tree);
+    }
+    let tree_oid = tree_builder.write().context("failed to write tree")?;
+    let tree = project_repo
+        .find_tree(tree_oid)
+        .context("failed to find tree")?;
+
+    let signature = git::Signature::now("gitbutler", "gitbutler@localhost")
+        .context("failed to create signature")?;
+    let commit_oid = project_repo
+        .commit(
+            None,
+            &signature,
+            &signature,
+            "gitbutler operations",
+            &tree,
+            &[],
+        )
+        .context("failed to write operations commit")?;
+    project_repo
+        .reference(
+            &WD_TREES_REFERENCE.parse().unwrap(),
+            commit_oid,
+            true,
+            "record operation",
+        )
+        .context("failed to update operations reference")?;
+
+    Ok(())
+}
+
+/// lists recorded operations, most recent first.
+pub fn list(gb_repository: &gb_repository::Repository) -> Result<Vec<Operation>> {
+    Ok(operation_commits(gb_repository.git_repository())?
+        .into_iter()
+        .map(|(operation, _)| operation)
+        .collect())
+}
+
+// recorded operations, most recent first, along with the commits they are stored in
+fn operation_commits(repo: &git::Repository) -> Result<Vec<(Operation, git::Oid)>> {
+    let mut commit = match repo.find_reference(&OPERATIONS_REFERENCE.parse().unwrap()) {
+        Ok(reference) => reference
+            .peel_to_commit()
+            .context("failed to find last operation")?,
+        Err(git::Error::NotFound(_)) => return Ok(vec![]),
+        Err(error) => return Err(error.into()),
+    };
+
+    let mut operations = vec![];
+    loop {
+        let message = commit.message().unwrap_or_default();
+        let (name, id) = match message.rsplit_once(ID_TRAILER) {
+            Some((name, id)) => (
+                name,
+                id.parse::<git::Oid>()
+                    .context("failed to parse operation id")?,
+            ),
+            None => (message, commit.id()),
+        };
+        operations.push((
+            Operation {
+                id,
+                name: name.to_string(),
+                created_at: u128::try_from(commit.time().seconds())? * 1000,
+            },
+            commit.id(),
+        ));
+        if commit.parent_count() == 0 {
+            break;
+        }
+        commit = commit
+            .parent(0)
+            .context("failed to find previous operation")?;
+    }
+
+    Ok(operations)
+}
+
+/// restores virtual branches and the working directory to the state they were
+/// in right before the operation was performed.
+///
+/// the state that is being replaced is recorded as an operation as well, so that
+/// restoring can be undone in the same way.
+pub fn restore(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    operation_id: git::Oid,
+) -> Result<(), errors::RestoreOperationError> {
+    let repo = gb_repository.git_repository();
+
+    let Some((_, commit_oid)) = operation_commits(repo)?
+        .into_iter()
+        .find(|(operation, _)| operation.id == operation_id)
+    else {
+        return Err(errors::RestoreOperationError::OperationNotFound(
+            operation_id,
+        ));
+    };
+
+    let operation_tree = repo
+        .find_commit(commit_oid)
+        .context("failed to find operation commit")?
+        .tree()
+        .context("failed to find operation tree")?;
+    let branches_tree = repo
+        .find_tree(
+            operation_tree
+                .get_name("branches")
+                .context("operation has no branches tree")?
+                .id(),
+        )
+        .context("failed to find branches tree")?;
+    let wd_tree = project_repository
+        .git_repository
+        .find_tree(
+            operation_tree
+                .get_name("wd")
+                .context("operation has no working directory tree")?
+                .id(),
+        )
+        .context("failed to find working directory tree")?;
+
+    snapshot(
+        gb_repository,
+        project_repository,
+        &format!("restore {}", operation_id),
+    )
+    .context("failed to snapshot current state")?;
+
+    let mut branch_files = vec![];
+    branches_tree
+        .walk(|root, entry| {
+            if entry.kind() == Some(git2::ObjectType::Blob) {
+                if let Some(name) = entry.name() {
+                    branch_files.push((format!("branches/{}{}", root, name), entry.id()));
+                }
+            }
+            git::TreeWalkResult::Continue
+        })
+        .context("failed to walk branches tree")?;
+
+    let mut batch = vec![writer::BatchTask::Remove("branches".to_string())];
+    for (file_path, blob_oid) in branch_files {
+        let blob = repo.find_blob(blob_oid).context("failed to find blob")?;
+        batch.push(writer::BatchTask::Write(file_path, blob.content().to_vec()));
+    }
+
+    // make sure the session is created before the branches are replaced, otherwise
+    // a new session would copy over the branches of the previous one.
+    gb_repository
+        .mark_active_session()
+        .context("failed to mark session as active")?;
+    {
+        let _lock = gb_repository.lock();
+        writer::DirWriter::open(gb_repository.root())
+            .context("failed to open writer")?
+            .batch(&batch)
+            .context("failed to write branches")?;
+    }
+
+    project_repository
+        .git_repository
+        .checkout_tree(&wd_tree)
+        .force()
+        .remove_untracked()
+        .checkout()
+        .context("failed to checkout working directory tree")?;
+
+    super::integration::update_gitbutler_integration(gb_repository, project_repository)?;
+
+    Ok(())
+}
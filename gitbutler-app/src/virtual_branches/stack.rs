@@ -0,0 +1,202 @@
+use anyhow::{Context, Result};
+
+use crate::{gb_repository, git, keys, project_repository, reader, sessions, users};
+
+use super::{branch, errors, Iterator};
+
+// moves a stacked branch from the old head of the branch it is stacked on to the new one.
+//
+// commits of the branch are rebased onto the new base. if the branch was pushed and force
+// pushing is not allowed, the new base is merged into the branch instead, same as for the
+// branches based on the default target.
+//
+// returns false, leaving the branch as is, if its commits or uncommitted changes conflict
+// with the new base.
+pub fn restack_branch(
+    project_repository: &project_repository::Repository,
+    branch: &mut branch::Branch,
+    old_base: git::Oid,
+    new_base: git::Oid,
+    base_name: &str,
+    user: Option<&users::User>,
+    signing_key: Option<&keys::PrivateKey>,
+) -> Result<bool> {
+    if old_base == new_base {
+        return Ok(true);
+    }
+
+    let repo = &project_repository.git_repository;
+    let old_base_tree = repo
+        .find_commit(old_base)
+        .and_then(|commit| commit.tree())
+        .context(format!("failed to find old base tree {}", old_base))?;
+    let new_base_commit = repo
+        .find_commit(new_base)
+        .context(format!("failed to find new base commit {}", new_base))?;
+    let new_base_tree = new_base_commit
+        .tree()
+        .context("failed to find new base tree")?;
+
+    // uncommitted changes of the branch are moved over to the new base
+    let branch_tree = repo.find_tree(branch.tree)?;
+    let mut branch_tree_merge_index = repo
+        .merge_trees(&old_base_tree, &branch_tree, &new_base_tree)
+        .context(format!("failed to merge trees for branch {}", branch.id))?;
+    if branch_tree_merge_index.has_conflicts() {
+        return Ok(false);
+    }
+    let branch_tree_oid = branch_tree_merge_index.write_tree_to(repo)?;
+
+    let head = if branch.head == old_base {
+        // there are no commits on the branch, it just follows its base
+        new_base
+    } else if branch.upstream.is_some() && !project_repository.project().ok_with_force_push {
+        let branch_head_commit = repo
+            .find_commit(branch.head)
+            .context(format!("failed to find commit {}", branch.head))?;
+        let branch_head_tree = branch_head_commit
+            .tree()
+            .context("failed to find branch head tree")?;
+        let mut branch_head_merge_index = repo
+            .merge_trees(&old_base_tree, &branch_head_tree, &new_base_tree)
+            .context(format!(
+                "failed to merge head tree for branch {}",
+                branch.id
+            ))?;
+        if branch_head_merge_index.has_conflicts() {
+            return Ok(false);
+        }
+        let branch_head_merge_tree = repo
+            .find_tree(branch_head_merge_index.write_tree_to(repo)?)
+            .context("failed to find tree")?;
+        project_repository
+            .commit(
+                user,
+                format!("Merged {} into {}", base_name, branch.name).as_str(),
+                &branch_head_merge_tree,
+                &[&branch_head_commit, &new_base_commit],
+                signing_key,
+            )
+            .context("failed to commit merge")?
+    } else {
+        let (_, committer) = project_repository.git_signatures(user)?;
+        let mut rebase_options = git2::RebaseOptions::new();
+        rebase_options.quiet(true);
+        rebase_options.inmemory(true);
+        let mut rebase = repo
+            .rebase(
+                Some(branch.head),
+                Some(old_base),
+                Some(new_base),
+                Some(&mut rebase_options),
+            )
+            .context("failed to rebase")?;
+
+        let mut last_rebase_head = new_base;
+        while rebase.next().is_some() {
+            let index = rebase
+                .inmemory_index()
+                .context("failed to get inmemory index")?;
+            if index.has_conflicts() {
+                rebase.abort().context("failed to abort rebase")?;
+                return Ok(false);
+            }
+
+            match rebase.commit(None, &committer.clone().into(), None) {
+                Ok(commit_id) => last_rebase_head = commit_id.into(),
+                Err(_) => {
+                    rebase.abort().context("failed to abort rebase")?;
+                    return Ok(false);
+                }
+            }
+        }
+        rebase.finish(None).context("failed to finish rebase")?;
+        last_rebase_head
+    };
+
+    branch.head = head;
+    branch.tree = branch_tree_oid;
+    Ok(true)
+}
+
+// restacks the branches stacked on `parent`, and the ones stacked on those in turn, after
+// the head of the parent moved from `old_parent_head`. nothing is written, the restacked
+// branches are returned for the caller to write together with the parent.
+pub fn restack_children(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    parent: &branch::Branch,
+    old_parent_head: git::Oid,
+    user: Option<&users::User>,
+    signing_key: Option<&keys::PrivateKey>,
+) -> Result<Vec<branch::Branch>, errors::RestackError> {
+    if parent.head == old_parent_head {
+        return Ok(vec![]);
+    }
+
+    let current_session = gb_repository
+        .get_or_create_current_session()
+        .context("failed to get or create current session")?;
+    let current_session_reader = sessions::Reader::open(gb_repository, &current_session)
+        .context("failed to open current session")?;
+    let all_branches = Iterator::new(&current_session_reader)
+        .context("failed to create branch iterator")?
+        .collect::<Result<Vec<branch::Branch>, reader::Error>>()
+        .context("failed to read virtual branches")?;
+
+    restack_children_in(
+        project_repository,
+        &all_branches,
+        parent,
+        old_parent_head,
+        user,
+        signing_key,
+    )
+}
+
+// like `restack_children`, but finds the stacked branches among `all_branches` rather than
+// the written ones, for callers that move several branches before writing any of them.
+pub fn restack_children_in(
+    project_repository: &project_repository::Repository,
+    all_branches: &[branch::Branch],
+    parent: &branch::Branch,
+    old_parent_head: git::Oid,
+    user: Option<&users::User>,
+    signing_key: Option<&keys::PrivateKey>,
+) -> Result<Vec<branch::Branch>, errors::RestackError> {
+    if parent.head == old_parent_head {
+        return Ok(vec![]);
+    }
+
+    let mut restacked: Vec<branch::Branch> = vec![];
+    let mut moved = vec![(parent.clone(), old_parent_head)];
+    while let Some((moved_branch, old_head)) = moved.pop() {
+        for child in all_branches
+            .iter()
+            .filter(|branch| branch.parent == Some(moved_branch.id))
+        {
+            if child.id == parent.id || restacked.iter().any(|branch| branch.id == child.id) {
+                // branches are stacked in a loop
+                continue;
+            }
+
+            let mut child = child.clone();
+            let old_child_head = child.head;
+            if !restack_branch(
+                project_repository,
+                &mut child,
+                old_head,
+                moved_branch.head,
+                &moved_branch.name,
+                user,
+                signing_key,
+            )? {
+                return Err(errors::RestackError::Conflict(child.id));
+            }
+            moved.push((child.clone(), old_child_head));
+            restacked.push(child);
+        }
+    }
+
+    Ok(restacked)
+}
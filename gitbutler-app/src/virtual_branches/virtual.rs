@@ -1,4 +1,7 @@
-use std::{collections::HashMap, path, time, vec};
+use std::{
+    collections::{HashMap, HashSet},
+    path, time, vec,
+};
 
 #[cfg(target_family = "unix")]
 use std::os::unix::prelude::*;
@@ -7,11 +10,11 @@ use anyhow::{bail, Context, Result};
 use diffy::{apply_bytes, Patch};
 use git2_hooks::HookResult;
 use regex::Regex;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     dedup::{dedup, dedup_fmt},
-    gb_repository,
+    forge, gb_repository,
     git::{self, diff, show, Commit, Refname, RemoteRefname},
     keys,
     project_repository::{self, conflicts, LogUntil},
@@ -58,6 +61,10 @@ pub struct VirtualBranch {
     pub updated_at: u128,
     pub selected_for_changes: bool,
     pub head: git::Oid,
+    pub parent: Option<BranchId>, // the branch this vbranch is stacked on, if any
+    // the pull request opened for the upstream branch and its status, if any
+    pub pr_number: Option<u64>,
+    pub pr_status: Option<forge::Status>,
 }
 
 // this is the struct that maps to the view `Commit` type in Typescript
@@ -95,6 +102,8 @@ pub struct VirtualBranchCommit {
 pub struct VirtualBranchFile {
     pub id: String,
     pub path: path::PathBuf,
+    // path the file was renamed or copied from
+    pub old_path: Option<path::PathBuf>,
     pub hunks: Vec<VirtualBranchHunk>,
     pub modified_at: u128,
     pub conflicted: bool,
@@ -124,6 +133,7 @@ pub struct VirtualBranchHunk {
     pub locked: bool,
     pub locked_to: Option<git::Oid>,
     pub change_type: diff::ChangeType,
+    pub old_path: Option<path::PathBuf>,
 }
 
 #[derive(Debug, Serialize, Hash, Clone, PartialEq, Eq)]
@@ -217,6 +227,15 @@ pub fn apply_branch(
         return Ok(());
     }
 
+    if let Some(parent_id) = branch.parent {
+        let parent = branch::Reader::new(&current_session_reader)
+            .read(&parent_id)
+            .context("failed to read parent branch")?;
+        if !parent.applied {
+            return Err(errors::ApplyBranchError::ParentNotApplied(parent_id));
+        }
+    }
+
     let target_commit = repo
         .find_commit(default_target.sha)
         .context("failed to find target commit")?;
@@ -244,7 +263,7 @@ pub fn apply_branch(
             .context("failed to find branch tree")?;
 
         let mut merge_index = repo
-            .merge_trees(&merge_base_tree, &branch_tree, &target_tree)
+            .merge_trees_with_commands(&merge_base_tree, &branch_tree, &target_tree)
             .context("failed to merge trees")?;
 
         if merge_index.has_conflicts() {
@@ -271,23 +290,7 @@ pub fn apply_branch(
                 .context("failed to checkout index")?;
 
             // mark conflicts
-            let conflicts = merge_index
-                .conflicts()
-                .context("failed to get merge index conflicts")?;
-            let mut merge_conflicts = Vec::new();
-            for path in conflicts.flatten() {
-                if let Some(ours) = path.our {
-                    let path = std::str::from_utf8(&ours.path)
-                        .context("failed to convert path to utf8")?
-                        .to_string();
-                    merge_conflicts.push(path);
-                }
-            }
-            conflicts::mark(
-                project_repository,
-                &merge_conflicts,
-                Some(default_target.sha),
-            )?;
+            conflicts::mark_index(project_repository, &merge_index, Some(default_target.sha))?;
 
             return Ok(());
         }
@@ -407,7 +410,7 @@ pub fn apply_branch(
 
     // check index for conflicts
     let mut merge_index = repo
-        .merge_trees(&target_tree, &wd_tree, &branch_tree)
+        .merge_trees_with_commands(&target_tree, &wd_tree, &branch_tree)
         .context("failed to merge trees")?;
 
     if merge_index.has_conflicts() {
@@ -488,7 +491,11 @@ pub fn unapply_ownership(
                 let branch_files = calculate_non_commited_diffs(
                     project_repository,
                     branch,
-                    &default_target,
+                    get_branch_base(
+                        branch,
+                        applied_statuses.iter().map(|(branch, _)| branch),
+                        &default_target,
+                    )?,
                     branch_files,
                 )?;
 
@@ -589,6 +596,17 @@ pub fn unapply_branch(
         return Ok(Some(target_branch));
     }
 
+    // stacked branches need the branch they are stacked on
+    if let Some(child) = Iterator::new(&current_session_reader)
+        .context("failed to create branch iterator")?
+        .collect::<Result<Vec<branch::Branch>, reader::Error>>()
+        .context("failed to read virtual branches")?
+        .into_iter()
+        .find(|b| b.applied && b.parent == Some(target_branch.id))
+    {
+        return Err(errors::UnapplyBranchError::HasStackedBranches(child.id));
+    }
+
     let default_target = get_default_target(&current_session_reader)
         .context("failed to get default target")?
         .ok_or_else(|| {
@@ -745,10 +763,15 @@ pub fn list_virtual_branches(
         .max()
         .unwrap_or(-1);
     for (branch, files) in &statuses {
+        let base_oid = get_branch_base(
+            branch,
+            statuses.iter().map(|(branch, _)| branch),
+            &default_target,
+        )?;
+
         // check if head tree does not match target tree
         // if so, we diff the head tree and the new write_tree output to see what is new and filter the hunks to just those
-        let files =
-            calculate_non_commited_diffs(project_repository, branch, &default_target, files)?;
+        let files = calculate_non_commited_diffs(project_repository, branch, base_oid, files)?;
 
         let repo = &project_repository.git_repository;
 
@@ -794,9 +817,9 @@ pub fn list_virtual_branches(
         let mut is_integrated = false;
         let mut is_remote = false;
 
-        // find all commits on head that are not on target.sha
+        // find all commits on head that are not on target.sha, or on the parent branch
         let commits = project_repository
-            .log(branch.head, LogUntil::Commit(default_target.sha))
+            .log(branch.head, LogUntil::Commit(base_oid))
             .context(format!("failed to get log for branch {}", branch.name))?
             .iter()
             .map(|commit| {
@@ -879,6 +902,9 @@ pub fn list_virtual_branches(
             updated_at: branch.updated_timestamp_ms,
             selected_for_changes: branch.selected_for_changes == Some(max_selected_for_changes),
             head: branch.head,
+            parent: branch.parent,
+            pr_number: branch.pr_number,
+            pr_status: branch.pr_status,
         };
         branches.push(branch);
     }
@@ -965,9 +991,12 @@ fn files_with_hunk_context(
         // Get file content as it looked before the diffs
         let branch_head_commit = repository.find_commit(branch_head)?;
         let head_tree = branch_head_commit.tree()?;
-        let file_content_before =
-            show::show_file_at_tree(repository, file.path.clone(), &head_tree)
-                .context("failed to get file contents at base")?;
+        let file_content_before = show::show_file_at_tree(
+            repository,
+            file.old_path.clone().unwrap_or_else(|| file.path.clone()),
+            &head_tree,
+        )
+        .context("failed to get file contents at base")?;
         let file_lines_before = file_content_before.split('\n').collect::<Vec<_>>();
 
         // Update each hunk with contex lines before & after
@@ -1040,20 +1069,39 @@ fn is_requires_force(
     Ok(merge_base != upstream_commit.id())
 }
 
+// stacked branches are based on the head of their parent branch, all the others
+// on the default target. the parent of an applied branch is always applied.
+fn get_branch_base<'a>(
+    branch: &branch::Branch,
+    mut branches: impl Iterator<Item = &'a branch::Branch>,
+    default_target: &target::Target,
+) -> Result<git::Oid> {
+    let Some(parent_id) = branch.parent else {
+        return Ok(default_target.sha);
+    };
+    branches
+        .find(|b| b.id == parent_id)
+        .map(|parent| parent.head)
+        .context(format!(
+            "branch {} is stacked on {}, which is not applied",
+            branch.id, parent_id
+        ))
+}
+
 // given a virtual branch and it's files that are calculated off of a default target,
 // return files adjusted to the branch's head commit
 pub fn calculate_non_commited_diffs(
     project_repository: &project_repository::Repository,
     branch: &branch::Branch,
-    default_target: &target::Target,
+    base_oid: git::Oid,
     files: &HashMap<path::PathBuf, Vec<diff::Hunk>>,
 ) -> Result<HashMap<path::PathBuf, Vec<diff::Hunk>>> {
-    if default_target.sha == branch.head && !branch.applied {
+    if base_oid == branch.head && !branch.applied {
         return Ok(files.clone());
     };
 
     let branch_tree = if branch.applied {
-        let target_plus_wd_oid = write_tree(project_repository, default_target, files)?;
+        let target_plus_wd_oid = write_tree_onto_commit(project_repository, base_oid, files)?;
         project_repository
             .git_repository
             .find_tree(target_plus_wd_oid)
@@ -1176,15 +1224,6 @@ pub fn create_virtual_branch(
             )
         })?;
 
-    let commit = project_repository
-        .git_repository
-        .find_commit(default_target.sha)
-        .context("failed to find default target commit")?;
-
-    let tree = commit
-        .tree()
-        .context("failed to find defaut target commit tree")?;
-
     let mut all_virtual_branches = Iterator::new(&current_session_reader)
         .context("failed to create branch iterator")?
         .collect::<Result<Vec<branch::Branch>, reader::Error>>()
@@ -1193,6 +1232,32 @@ pub fn create_virtual_branch(
         .collect::<Vec<branch::Branch>>();
     all_virtual_branches.sort_by_key(|branch| branch.order);
 
+    // stacked branches start from the head of the parent branch
+    let base_oid = if let Some(parent_id) = create.parent {
+        let parent = all_virtual_branches
+            .iter()
+            .find(|branch| branch.id == parent_id)
+            .ok_or_else(|| {
+                errors::CreateVirtualBranchError::ParentNotFound(errors::BranchNotFoundError {
+                    project_id: project_repository.project().id,
+                    branch_id: parent_id,
+                })
+            })?;
+        if !parent.applied {
+            return Err(errors::CreateVirtualBranchError::ParentNotApplied);
+        }
+        parent.head
+    } else {
+        default_target.sha
+    };
+
+    let commit = project_repository
+        .git_repository
+        .find_commit(base_oid)
+        .context("failed to find base commit")?;
+
+    let tree = commit.tree().context("failed to find base commit tree")?;
+
     let order = create
         .order
         .unwrap_or(all_virtual_branches.len())
@@ -1257,12 +1322,15 @@ pub fn create_virtual_branch(
         upstream: None,
         upstream_head: None,
         tree: tree.id(),
-        head: default_target.sha,
+        head: base_oid,
         created_timestamp_ms: now,
         updated_timestamp_ms: now,
         ownership: Ownership::default(),
         order,
         selected_for_changes,
+        parent: create.parent,
+        pr_number: None,
+        pr_status: None,
     };
 
     if let Some(ownership) = &create.ownership {
@@ -1381,7 +1449,7 @@ pub fn merge_virtual_branch_upstream(
 
     // try to merge our wd tree with the upstream tree
     let mut merge_index = repo
-        .merge_trees(&merge_tree, &wd_tree, &remote_tree)
+        .merge_trees_with_commands(&merge_tree, &wd_tree, &remote_tree)
         .context("failed to merge trees")?;
 
     if merge_index.has_conflicts() {
@@ -1394,21 +1462,7 @@ pub fn merge_virtual_branch_upstream(
             .context("failed to checkout index")?;
 
         // mark conflicts
-        let conflicts = merge_index.conflicts().context("failed to get conflicts")?;
-        let mut merge_conflicts = Vec::new();
-        for path in conflicts.flatten() {
-            if let Some(ours) = path.our {
-                let path = std::str::from_utf8(&ours.path)
-                    .context("failed to convert path to utf8")?
-                    .to_string();
-                merge_conflicts.push(path);
-            }
-        }
-        conflicts::mark(
-            project_repository,
-            &merge_conflicts,
-            Some(upstream_commit.id()),
-        )?;
+        conflicts::mark_index(project_repository, &merge_index, Some(upstream_commit.id()))?;
     } else {
         // get the merge tree oid from writing the index out
         let merge_tree_oid = merge_index
@@ -1577,6 +1631,21 @@ pub fn delete_branch(
         return Ok(());
     }
 
+    // branches stacked on the deleted one are stacked on what it was stacked on instead,
+    // keeping its commits
+    for mut child in Iterator::new(&current_session_reader)
+        .context("failed to create branch iterator")?
+        .collect::<Result<Vec<branch::Branch>, reader::Error>>()
+        .context("failed to read virtual branches")?
+        .into_iter()
+        .filter(|b| b.parent == Some(branch.id))
+    {
+        child.parent = branch.parent;
+        branch_writer
+            .write(&mut child)
+            .context("failed to write stacked branch")?;
+    }
+
     branch_writer
         .delete(&branch)
         .context("Failed to remove branch")?;
@@ -1713,6 +1782,7 @@ pub fn virtual_hunks_by_filepath(
                     locked: false,
                     locked_to: None,
                     change_type: hunk.change_type,
+                    old_path: hunk.old_path.clone(),
                 })
                 .collect::<Vec<_>>();
             (file_path.clone(), hunks)
@@ -1843,6 +1913,39 @@ fn get_applied_status(
         .context("failed to create default branch")?];
     }
 
+    // renamed files go to the branch that owns the original file
+    for (file_path, hunks) in &diff {
+        if !hunks
+            .iter()
+            .any(|hunk| hunk.change_type == diff::ChangeType::Renamed)
+        {
+            continue;
+        }
+        let Some(old_path) = hunks.iter().find_map(|hunk| hunk.old_path.as_ref()) else {
+            continue;
+        };
+        let renamed = FileOwnership {
+            file_path: file_path.clone(),
+            hunks: hunks.iter().map(Hunk::from).collect(),
+        };
+        let Some(owner) = virtual_branches
+            .iter_mut()
+            .position(|branch| branch.ownership.rename(old_path, &renamed))
+        else {
+            continue;
+        };
+        // the renamed file might have been picked up by another branch before the
+        // original was removed, it belongs to the owner of the original only
+        for (index, branch) in virtual_branches.iter_mut().enumerate() {
+            if index != owner {
+                branch
+                    .ownership
+                    .files
+                    .retain(|ownership| &ownership.file_path != file_path);
+            }
+        }
+    }
+
     // align branch ownership to the real hunks:
     // - update shifted hunks
     // - remove non existent hunks
@@ -1855,6 +1958,42 @@ fn get_applied_status(
 
     let mut mtimes = HashMap::new();
 
+    // lines of hunks that are owned partially. those are split off from the hunk,
+    // the rest of it goes to the branch that owns the hunk as a whole.
+    let mut claimed_lines: HashMap<path::PathBuf, Vec<Hunk>> = HashMap::new();
+    for branch in &virtual_branches {
+        for file_ownership in &branch.ownership.files {
+            claimed_lines
+                .entry(file_ownership.file_path.clone())
+                .or_default()
+                .extend(
+                    file_ownership
+                        .hunks
+                        .iter()
+                        .filter(|h| h.is_partial())
+                        .cloned(),
+                );
+        }
+    }
+    let unclaimed = |file_path: &path::PathBuf, hunk: &diff::Hunk| -> Option<diff::Hunk> {
+        let hash = diff_hash(&hunk.diff);
+        let claimed = claimed_lines
+            .get(file_path)
+            .map(|hunks| {
+                hunks
+                    .iter()
+                    .filter_map(|h| h.relocate(hunk, &hash))
+                    .flat_map(|h| h.lines)
+                    .collect::<HashSet<_>>()
+            })
+            .unwrap_or_default();
+        if claimed.is_empty() {
+            Some(hunk.clone())
+        } else {
+            branch::select_lines(hunk, |line| !claimed.contains(line))
+        }
+    };
+
     for branch in &mut virtual_branches {
         if !branch.applied {
             bail!("branch {} is not applied", branch.name);
@@ -1881,6 +2020,24 @@ fn get_applied_status(
                         .hunks
                         .iter()
                         .filter_map(|owned_hunk| {
+                            if owned_hunk.is_partial() {
+                                // owned lines are kept as long as the hunk is the same change,
+                                // following it if it moved. the hunk itself stays available
+                                // for the other branches
+                                let (owned_hunk, ch) = relocate_partial(owned_hunk, current_hunks)?;
+                                let selected = owned_hunk.select(ch)?;
+
+                                hunks_by_branch_id
+                                    .entry(branch.id)
+                                    .or_default()
+                                    .entry(file_owership.file_path.clone())
+                                    .or_default()
+                                    .push(selected);
+
+                                let timestamp = owned_hunk.timestam_ms().unwrap_or(mtime);
+                                return Some(owned_hunk.with_timestamp(timestamp));
+                            }
+
                             // if any of the current hunks intersects with the owned hunk, we want to keep it
                             for (i, ch) in current_hunks.iter().enumerate() {
                                 let current_hunk = Hunk::from(ch);
@@ -1889,12 +2046,14 @@ fn get_applied_status(
                                     let timestamp = owned_hunk.timestam_ms().unwrap_or(mtime);
 
                                     // push hunk to the end of the list, preserving the order
-                                    hunks_by_branch_id
-                                        .entry(branch.id)
-                                        .or_default()
-                                        .entry(file_owership.file_path.clone())
-                                        .or_default()
-                                        .push(ch.clone());
+                                    if let Some(ch) = unclaimed(&file_owership.file_path, ch) {
+                                        hunks_by_branch_id
+                                            .entry(branch.id)
+                                            .or_default()
+                                            .entry(file_owership.file_path.clone())
+                                            .or_default()
+                                            .push(ch);
+                                    }
 
                                     // remove the hunk from the current hunks because each hunk can
                                     // only be owned once
@@ -1904,12 +2063,14 @@ fn get_applied_status(
                                 } else if owned_hunk.intersects(&current_hunk) {
                                     // if it's an intersection, push the hunk to the beginning,
                                     // indicating the the hunk has been updated
-                                    hunks_by_branch_id
-                                        .entry(branch.id)
-                                        .or_default()
-                                        .entry(file_owership.file_path.clone())
-                                        .or_default()
-                                        .insert(0, ch.clone());
+                                    if let Some(ch) = unclaimed(&file_owership.file_path, ch) {
+                                        hunks_by_branch_id
+                                            .entry(branch.id)
+                                            .or_default()
+                                            .entry(file_owership.file_path.clone())
+                                            .or_default()
+                                            .insert(0, ch);
+                                    }
 
                                     // track updated hunks to bubble them up later
                                     updated.push(FileOwnership {
@@ -1961,6 +2122,10 @@ fn get_applied_status(
     // put the remaining hunks into the default (first) branch
     for (filepath, hunks) in diff {
         for hunk in hunks {
+            let Some(unclaimed_hunk) = unclaimed(&filepath, &hunk) else {
+                // all of the changed lines are owned already
+                continue;
+            };
             virtual_branches[default_vbranch_pos]
                 .ownership
                 .put(&FileOwnership {
@@ -1974,7 +2139,7 @@ fn get_applied_status(
                 .or_default()
                 .entry(filepath.clone())
                 .or_default()
-                .push(hunk.clone());
+                .push(unclaimed_hunk);
         }
     }
 
@@ -1997,7 +2162,8 @@ fn get_applied_status(
         let branch_writer =
             branch::Writer::new(gb_repository).context("failed to create writer")?;
         for (vbranch, files) in &mut hunks_by_branch {
-            vbranch.tree = write_tree(project_repository, default_target, files)?;
+            let base_oid = get_branch_base(vbranch, virtual_branches.iter(), default_target)?;
+            vbranch.tree = write_tree_onto_commit(project_repository, base_oid, files)?;
             branch_writer
                 .write(vbranch)
                 .context(format!("failed to write virtual branch {}", vbranch.name))?;
@@ -2007,6 +2173,24 @@ fn get_applied_status(
     Ok(hunks_by_branch)
 }
 
+// finds where a partially owned hunk is in the current diff, preferring a hunk that
+// didn't move over another one with the same changes
+fn relocate_partial<'h>(
+    owned_hunk: &Hunk,
+    hunks: &'h [diff::Hunk],
+) -> Option<(Hunk, &'h diff::Hunk)> {
+    let relocate = |hunk: &'h diff::Hunk| {
+        owned_hunk
+            .relocate(hunk, &diff_hash(&hunk.diff))
+            .map(|owned_hunk| (owned_hunk, hunk))
+    };
+    hunks
+        .iter()
+        .filter(|hunk| hunk.new_start == owned_hunk.start)
+        .find_map(relocate)
+        .or_else(|| hunks.iter().find_map(relocate))
+}
+
 fn virtual_hunks_to_virtual_files(
     project_repository: &project_repository::Repository,
     hunks: &[VirtualBranchHunk],
@@ -2023,6 +2207,7 @@ fn virtual_hunks_to_virtual_files(
         .map(|(file_path, hunks)| VirtualBranchFile {
             id: file_path.display().to_string(),
             path: file_path.clone(),
+            old_path: hunks.iter().find_map(|h| h.old_path.clone()),
             hunks: hunks.clone(),
             binary: hunks.iter().any(|h| h.binary),
             modified_at: hunks.iter().map(|h| h.modified_at).max().unwrap_or(0),
@@ -2041,6 +2226,8 @@ pub fn reset_branch(
     project_repository: &project_repository::Repository,
     branch_id: &BranchId,
     target_commit_oid: git::Oid,
+    user: Option<&users::User>,
+    signing_key: Option<&keys::PrivateKey>,
 ) -> Result<(), errors::ResetBranchError> {
     let current_session = gb_repository.get_or_create_current_session()?;
     let current_session_reader = sessions::Reader::open(gb_repository, &current_session)?;
@@ -2070,9 +2257,20 @@ pub fn reset_branch(
         return Ok(());
     }
 
-    if default_target.sha != target_commit_oid
+    // stacked branches can only be reset down to the head of their parent
+    let base_oid = match branch.parent {
+        Some(parent_id) => {
+            branch_reader
+                .read(&parent_id)
+                .context("failed to read parent branch")?
+                .head
+        }
+        None => default_target.sha,
+    };
+
+    if base_oid != target_commit_oid
         && !project_repository
-            .l(branch.head, LogUntil::Commit(default_target.sha))?
+            .l(branch.head, LogUntil::Commit(base_oid))?
             .contains(&target_commit_oid)
     {
         return Err(errors::ResetBranchError::CommitNotFoundInBranch(
@@ -2081,10 +2279,24 @@ pub fn reset_branch(
     }
 
     let branch_writer = branch::Writer::new(gb_repository).context("failed to create writer")?;
+    let old_head = branch.head;
     branch.head = target_commit_oid;
+    let restacked = super::stack::restack_children(
+        gb_repository,
+        project_repository,
+        &branch,
+        old_head,
+        user,
+        signing_key,
+    )?;
     branch_writer
         .write(&mut branch)
         .context("failed to write branch")?;
+    for mut child in restacked {
+        branch_writer
+            .write(&mut child)
+            .context("failed to write stacked branch")?;
+    }
 
     super::integration::update_gitbutler_integration(gb_repository, project_repository)
         .context("failed to update gitbutler integration")?;
@@ -2144,6 +2356,8 @@ pub fn write_tree_onto_tree(
         // convert this string to a Path
         let rel_path = std::path::Path::new(&filepath);
         let full_path = project_repository.path().join(rel_path);
+        // renamed and copied files are changes on top of the original file
+        let old_path = hunks.iter().find_map(|hunk| hunk.old_path.as_deref());
 
         let is_submodule =
             full_path.is_dir() && hunks.len() == 1 && hunks[0].diff.contains("Subproject commit");
@@ -2190,7 +2404,7 @@ pub fn write_tree_onto_tree(
                         .as_bytes(),
                 )?;
                 builder.upsert(rel_path, blob_oid, filemode);
-            } else if let Ok(tree_entry) = base_tree.get_path(rel_path) {
+            } else if let Ok(tree_entry) = base_tree.get_path(old_path.unwrap_or(rel_path)) {
                 if hunks.len() == 1 && hunks[0].binary {
                     let new_blob_oid = &hunks[0].diff;
                     // convert string to Oid
@@ -2208,6 +2422,7 @@ pub fn write_tree_onto_tree(
                     let mut blob_contents = blob.content().to_vec();
 
                     let mut hunks = hunks.clone();
+                    hunks.retain(|hunk| !hunk.diff.is_empty());
                     hunks.sort_by_key(|hunk| hunk.new_start);
                     for hunk in hunks {
                         let patch = format!("--- original\n+++ modified\n{}", hunk.diff);
@@ -2253,6 +2468,16 @@ pub fn write_tree_onto_tree(
             // file not in index or base tree, do nothing
             // this is the
         }
+
+        // the original file of a rename goes away together with it
+        if hunks
+            .iter()
+            .any(|hunk| hunk.change_type == diff::ChangeType::Renamed)
+        {
+            if let Some(old_path) = old_path {
+                builder.remove(old_path);
+            }
+        }
     }
 
     // now write out the tree
@@ -2330,9 +2555,9 @@ pub fn commit(
     let mut statuses = get_status_by_branch(gb_repository, project_repository)
         .context("failed to get status by branch")?;
 
-    let (ref mut branch, files) = statuses
-        .iter_mut()
-        .find(|(branch, _)| branch.id == *branch_id)
+    let branch_index = statuses
+        .iter()
+        .position(|(branch, _)| branch.id == *branch_id)
         .ok_or_else(|| {
             errors::CommitError::BranchNotFound(errors::BranchNotFoundError {
                 project_id: project_repository.project().id,
@@ -2340,7 +2565,14 @@ pub fn commit(
             })
         })?;
 
-    let files = calculate_non_commited_diffs(project_repository, branch, &default_target, files)?;
+    let base_oid = get_branch_base(
+        &statuses[branch_index].0,
+        statuses.iter().map(|(branch, _)| branch),
+        &default_target,
+    )?;
+    let (ref mut branch, ref files) = statuses[branch_index];
+
+    let files = calculate_non_commited_diffs(project_repository, branch, base_oid, files)?;
     if conflicts::is_conflicting(project_repository, None)? {
         return Err(errors::CommitError::Conflicted(
             errors::ProjectConflictError {
@@ -2353,21 +2585,45 @@ pub fn commit(
         let files = files
             .iter()
             .filter_map(|(filepath, hunks)| {
+                let owned_hunks = ownership
+                    .files
+                    .iter()
+                    .filter(|f| f.file_path.eq(filepath))
+                    .flat_map(|f| f.hunks.iter())
+                    .collect::<Vec<_>>();
+                // each owned hunk selects at most one hunk, and each hunk is selected once
+                let mut used = HashSet::new();
                 let hunks = hunks
                     .iter()
-                    .filter(|hunk| {
-                        ownership
-                            .files
+                    .filter_map(|hunk| {
+                        let hash = diff_hash(&hunk.diff);
+                        let current_hunk = Hunk::from(hunk).with_hash(&hash);
+                        let selections = owned_hunks
                             .iter()
-                            .find(|f| f.file_path.eq(filepath))
-                            .map_or(false, |f| {
-                                f.hunks.iter().any(|h| {
-                                    h.start == hunk.new_start
-                                        && h.end == hunk.new_start + hunk.new_lines
-                                })
+                            .enumerate()
+                            .filter(|(i, h)| {
+                                !used.contains(i)
+                                    && match &h.hash {
+                                        Some(owned_hash) => *owned_hash == hash,
+                                        None => h.same_hunk(&current_hunk),
+                                    }
                             })
+                            .collect::<Vec<_>>();
+                        used.extend(selections.iter().map(|(i, _)| *i));
+                        if selections.is_empty() {
+                            None
+                        } else if selections.iter().any(|(_, h)| !h.is_partial()) {
+                            Some(hunk.clone())
+                        } else {
+                            // only the selected lines are committed
+                            let lines = selections
+                                .iter()
+                                .filter_map(|(_, h)| h.relocate(hunk, &hash))
+                                .flat_map(|h| h.lines)
+                                .collect::<HashSet<_>>();
+                            branch::select_lines(hunk, |line| lines.contains(line))
+                        }
                     })
-                    .cloned()
                     .collect::<Vec<_>>();
                 if hunks.is_empty() {
                     None
@@ -2418,11 +2674,25 @@ pub fn commit(
             .context("failed to run hook")?;
     }
 
-    // update the virtual branch head
+    // update the virtual branch head, and the branches stacked on it
     let writer = branch::Writer::new(gb_repository).context("failed to create writer")?;
+    let old_head = branch.head;
     branch.tree = tree_oid;
     branch.head = commit_oid;
+    let restacked = super::stack::restack_children(
+        gb_repository,
+        project_repository,
+        branch,
+        old_head,
+        user,
+        signing_key,
+    )?;
     writer.write(branch).context("failed to write branch")?;
+    for mut child in restacked {
+        writer
+            .write(&mut child)
+            .context("failed to write stacked branch")?;
+    }
 
     super::integration::update_gitbutler_integration(gb_repository, project_repository)
         .context("failed to update gitbutler integration")?;
@@ -2448,7 +2718,7 @@ pub fn push(
     let branch_reader = branch::Reader::new(&current_session_reader);
     let branch_writer = branch::Writer::new(gb_repository).context("failed to create writer")?;
 
-    let mut vbranch = branch_reader.read(branch_id).map_err(|error| match error {
+    let vbranch = branch_reader.read(branch_id).map_err(|error| match error {
         reader::Error::NotFound => errors::PushError::BranchNotFound(errors::BranchNotFoundError {
             project_id: project_repository.project().id,
             branch_id: *branch_id,
@@ -2456,51 +2726,71 @@ pub fn push(
         error => errors::PushError::Other(error.into()),
     })?;
 
-    let remote_branch = if let Some(upstream_branch) = vbranch.upstream.as_ref() {
-        upstream_branch.clone()
-    } else {
-        let default_target = get_default_target(&current_session_reader)
-            .context("failed to get default target")?
-            .ok_or_else(|| {
-                errors::PushError::DefaultTargetNotSet(errors::DefaultTargetNotSetError {
-                    project_id: project_repository.project().id,
-                })
-            })?;
+    // stacked branches are pushed together with the branches they are based on,
+    // starting from the bottom of the stack
+    let mut stack = vec![vbranch];
+    while let Some(parent_id) = stack.last().and_then(|branch| branch.parent) {
+        if stack.iter().any(|branch| branch.id == parent_id) {
+            break;
+        }
+        match branch_reader.read(&parent_id) {
+            Ok(parent) => stack.push(parent),
+            Err(reader::Error::NotFound) => break,
+            Err(error) => return Err(errors::PushError::Other(error.into())),
+        }
+    }
 
-        let remote_branch = format!(
-            "refs/remotes/{}/{}",
-            default_target.branch.remote(),
-            normalize_branch_name(&vbranch.name)
-        )
-        .parse::<git::RemoteRefname>()
-        .context("failed to parse remote branch name")?;
+    for mut vbranch in stack.into_iter().rev() {
+        let remote_branch = if let Some(upstream_branch) = vbranch.upstream.as_ref() {
+            upstream_branch.clone()
+        } else {
+            let default_target = get_default_target(&current_session_reader)
+                .context("failed to get default target")?
+                .ok_or_else(|| {
+                    errors::PushError::DefaultTargetNotSet(errors::DefaultTargetNotSetError {
+                        project_id: project_repository.project().id,
+                    })
+                })?;
 
-        let remote_branches = project_repository.git_remote_branches()?;
-        let existing_branches = remote_branches
-            .iter()
-            .map(RemoteRefname::branch)
-            .map(str::to_lowercase) // git is weird about case sensitivity here, assume not case sensitive
-            .collect::<Vec<_>>();
+            let remote_branch = format!(
+                "refs/remotes/{}/{}",
+                default_target.branch.remote(),
+                normalize_branch_name(&vbranch.name)
+            )
+            .parse::<git::RemoteRefname>()
+            .context("failed to parse remote branch name")?;
 
-        remote_branch.with_branch(&dedup_fmt(
-            &existing_branches
+            let remote_branches = project_repository.git_remote_branches()?;
+            let existing_branches = remote_branches
                 .iter()
-                .map(String::as_str)
-                .collect::<Vec<_>>(),
-            remote_branch.branch(),
-            "-",
-        ))
-    };
+                .map(RemoteRefname::branch)
+                .map(str::to_lowercase) // git is weird about case sensitivity here, assume not case sensitive
+                .collect::<Vec<_>>();
+
+            remote_branch.with_branch(&dedup_fmt(
+                &existing_branches
+                    .iter()
+                    .map(String::as_str)
+                    .collect::<Vec<_>>(),
+                remote_branch.branch(),
+                "-",
+            ))
+        };
 
-    project_repository.push(&vbranch.head, &remote_branch, with_force, credentials)?;
+        project_repository.push(&vbranch.head, &remote_branch, with_force, credentials)?;
 
-    vbranch.upstream = Some(remote_branch.clone());
-    vbranch.upstream_head = Some(vbranch.head);
-    branch_writer
-        .write(&mut vbranch)
-        .context("failed to write target branch after push")?;
+        vbranch.upstream = Some(remote_branch.clone());
+        vbranch.upstream_head = Some(vbranch.head);
+        branch_writer
+            .write(&mut vbranch)
+            .context("failed to write target branch after push")?;
 
-    project_repository.fetch(remote_branch.remote(), credentials)?;
+        project_repository.fetch(
+            remote_branch.remote(),
+            credentials,
+            &gitbutler_git::FetchOptions::default(),
+        )?;
+    }
 
     Ok(())
 }
@@ -2744,6 +3034,8 @@ pub fn amend(
     project_repository: &project_repository::Repository,
     branch_id: &BranchId,
     target_ownership: &Ownership,
+    user: Option<&users::User>,
+    signing_key: Option<&keys::PrivateKey>,
 ) -> Result<git::Oid, errors::AmendError> {
     if conflicts::is_conflicting(project_repository, None)? {
         return Err(errors::AmendError::Conflict(errors::ProjectConflictError {
@@ -2802,9 +3094,9 @@ pub fn amend(
         applied_branches,
     )?;
 
-    let (ref mut target_branch, target_status) = applied_statuses
-        .iter_mut()
-        .find(|(b, _)| b.id == *branch_id)
+    let branch_index = applied_statuses
+        .iter()
+        .position(|(b, _)| b.id == *branch_id)
         .ok_or_else(|| {
             errors::AmendError::BranchNotFound(errors::BranchNotFoundError {
                 project_id: project_repository.project().id,
@@ -2812,6 +3104,13 @@ pub fn amend(
             })
         })?;
 
+    let base_oid = get_branch_base(
+        &applied_statuses[branch_index].0,
+        applied_statuses.iter().map(|(branch, _)| branch),
+        &default_target,
+    )?;
+    let (ref mut target_branch, ref target_status) = applied_statuses[branch_index];
+
     if target_branch.upstream.is_some() && !project_repository.project().ok_with_force_push {
         // amending to a pushed head commit will cause a force push that is not allowed
         return Err(errors::AmendError::ForcePushNotAllowed(
@@ -2824,19 +3123,15 @@ pub fn amend(
     if project_repository
         .l(
             target_branch.head,
-            project_repository::LogUntil::Commit(default_target.sha),
+            project_repository::LogUntil::Commit(base_oid),
         )?
         .is_empty()
     {
         return Err(errors::AmendError::BranchHasNoCommits);
     }
 
-    let diffs_to_consider = calculate_non_commited_diffs(
-        project_repository,
-        target_branch,
-        &default_target,
-        target_status,
-    )?;
+    let diffs_to_consider =
+        calculate_non_commited_diffs(project_repository, target_branch, base_oid, target_status)?;
 
     let head_commit = project_repository
         .git_repository
@@ -2900,8 +3195,22 @@ pub fn amend(
         .context("failed to create commit")?;
 
     let branch_writer = branch::Writer::new(gb_repository).context("failed to create writer")?;
+    let old_head = target_branch.head;
     target_branch.head = commit_oid;
+    let restacked = super::stack::restack_children(
+        gb_repository,
+        project_repository,
+        target_branch,
+        old_head,
+        user,
+        signing_key,
+    )?;
     branch_writer.write(target_branch)?;
+    for mut child in restacked {
+        branch_writer
+            .write(&mut child)
+            .context("failed to write stacked branch")?;
+    }
 
     super::integration::update_gitbutler_integration(gb_repository, project_repository)?;
 
@@ -2913,6 +3222,8 @@ pub fn cherry_pick(
     project_repository: &project_repository::Repository,
     branch_id: &BranchId,
     target_commit_oid: git::Oid,
+    user: Option<&users::User>,
+    signing_key: Option<&keys::PrivateKey>,
 ) -> Result<Option<git::Oid>, errors::CherryPickError> {
     if conflicts::is_conflicting(project_repository, None)? {
         return Err(errors::CherryPickError::Conflict(
@@ -3030,19 +3341,7 @@ pub fn cherry_pick(
             .context("failed to checkout conflicts")?;
 
         // mark conflicts
-        let conflicts = cherrypick_index
-            .conflicts()
-            .context("failed to get conflicts")?;
-        let mut merge_conflicts = Vec::new();
-        for path in conflicts.flatten() {
-            if let Some(ours) = path.our {
-                let path = std::str::from_utf8(&ours.path)
-                    .context("failed to convert path")?
-                    .to_string();
-                merge_conflicts.push(path);
-            }
-        }
-        conflicts::mark(project_repository, &merge_conflicts, Some(branch.head))?;
+        conflicts::mark_index(project_repository, &cherrypick_index, Some(branch.head))?;
 
         None
     } else {
@@ -3082,10 +3381,24 @@ pub fn cherry_pick(
 
         // update branch status
         let writer = branch::Writer::new(gb_repository).context("failed to create writer")?;
+        let old_head = branch.head;
         branch.head = commit_oid;
+        let restacked = super::stack::restack_children(
+            gb_repository,
+            project_repository,
+            &branch,
+            old_head,
+            user,
+            signing_key,
+        )?;
         writer
             .write(&mut branch)
             .context("failed to write branch")?;
+        for mut child in restacked {
+            writer
+                .write(&mut child)
+                .context("failed to write stacked branch")?;
+        }
 
         Some(commit_oid)
     };
@@ -3102,6 +3415,8 @@ pub fn squash(
     project_repository: &project_repository::Repository,
     branch_id: &BranchId,
     commit_oid: git::Oid,
+    user: Option<&users::User>,
+    signing_key: Option<&keys::PrivateKey>,
 ) -> Result<(), errors::SquashError> {
     if conflicts::is_conflicting(project_repository, None)? {
         return Err(errors::SquashError::Conflict(
@@ -3136,10 +3451,19 @@ pub fn squash(
         error => errors::SquashError::Other(error.into()),
     })?;
 
-    let branch_commit_oids = project_repository.l(
-        branch.head,
-        project_repository::LogUntil::Commit(default_target.sha),
-    )?;
+    // commits of stacked branches end at the head of their parent
+    let base_oid = match branch.parent {
+        Some(parent_id) => {
+            branch_reader
+                .read(&parent_id)
+                .context("failed to read parent branch")?
+                .head
+        }
+        None => default_target.sha,
+    };
+
+    let branch_commit_oids =
+        project_repository.l(branch.head, project_repository::LogUntil::Commit(base_oid))?;
 
     if !branch_commit_oids.contains(&commit_oid) {
         return Err(errors::SquashError::CommitNotFound(commit_oid));
@@ -3159,7 +3483,7 @@ pub fn squash(
         |upstream_head| {
             project_repository.l(
                 upstream_head,
-                project_repository::LogUntil::Commit(default_target.sha),
+                project_repository::LogUntil::Commit(base_oid),
             )
         },
     )?;
@@ -3274,10 +3598,24 @@ pub fn squash(
 
     // save new branch head
     let writer = branch::Writer::new(gb_repository).context("failed to create writer")?;
+    let old_head = branch.head;
     branch.head = new_head_id;
+    let restacked = super::stack::restack_children(
+        gb_repository,
+        project_repository,
+        &branch,
+        old_head,
+        user,
+        signing_key,
+    )?;
     writer
         .write(&mut branch)
         .context("failed to write branch")?;
+    for mut child in restacked {
+        writer
+            .write(&mut child)
+            .context("failed to write stacked branch")?;
+    }
 
     super::integration::update_gitbutler_integration(gb_repository, project_repository)?;
 
@@ -3290,6 +3628,8 @@ pub fn update_commit_message(
     branch_id: &BranchId,
     commit_oid: git::Oid,
     message: &str,
+    user: Option<&users::User>,
+    signing_key: Option<&keys::PrivateKey>,
 ) -> Result<(), errors::UpdateCommitMessageError> {
     if message.is_empty() {
         return Err(errors::UpdateCommitMessageError::EmptyMessage);
@@ -3330,10 +3670,19 @@ pub fn update_commit_message(
         error => errors::UpdateCommitMessageError::Other(error.into()),
     })?;
 
-    let branch_commit_oids = project_repository.l(
-        branch.head,
-        project_repository::LogUntil::Commit(default_target.sha),
-    )?;
+    // commits of stacked branches end at the head of their parent
+    let base_oid = match branch.parent {
+        Some(parent_id) => {
+            branch_reader
+                .read(&parent_id)
+                .context("failed to read parent branch")?
+                .head
+        }
+        None => default_target.sha,
+    };
+
+    let branch_commit_oids =
+        project_repository.l(branch.head, project_repository::LogUntil::Commit(base_oid))?;
 
     if !branch_commit_oids.contains(&commit_oid) {
         return Err(errors::UpdateCommitMessageError::CommitNotFound(commit_oid));
@@ -3344,7 +3693,7 @@ pub fn update_commit_message(
         |upstream_head| {
             project_repository.l(
                 upstream_head,
-                project_repository::LogUntil::Commit(default_target.sha),
+                project_repository::LogUntil::Commit(base_oid),
             )
         },
     )?;
@@ -3450,10 +3799,24 @@ pub fn update_commit_message(
 
     // save new branch head
     let writer = branch::Writer::new(gb_repository).context("failed to create writer")?;
+    let old_head = branch.head;
     branch.head = new_head_id;
+    let restacked = super::stack::restack_children(
+        gb_repository,
+        project_repository,
+        &branch,
+        old_head,
+        user,
+        signing_key,
+    )?;
     writer
         .write(&mut branch)
         .context("failed to write branch")?;
+    for mut child in restacked {
+        writer
+            .write(&mut child)
+            .context("failed to write stacked branch")?;
+    }
 
     super::integration::update_gitbutler_integration(gb_repository, project_repository)?;
 
@@ -3597,6 +3960,9 @@ pub fn create_virtual_branch_from_branch(
         ownership,
         order,
         selected_for_changes,
+        parent: None,
+        pr_number: None,
+        pr_status: None,
     };
 
     let writer = branch::Writer::new(gb_repository).context("failed to create writer")?;
@@ -3624,6 +3990,1170 @@ pub fn create_virtual_branch_from_branch(
     }
 }
 
+/// a single entry of a branch rewrite todo list, similar to the todo list of
+/// `git rebase --interactive`. commits are replayed in the order of the list,
+/// so moving a commit is done by moving its entry.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "camelCase")]
+pub enum RewriteStep {
+    /// keep the commit
+    Pick { commit: git::Oid },
+    /// remove the commit together with its changes
+    Drop { commit: git::Oid },
+    /// meld the commit into the previous one, combining their messages
+    Squash { commit: git::Oid },
+    /// meld the commit into the previous one, keeping the previous message
+    Fixup { commit: git::Oid },
+    /// keep the commit, but change its message
+    Reword { commit: git::Oid, message: String },
+    /// split the changes to the given paths off into a commit of their own with the
+    /// given message, which comes right before the commit with the rest of the changes
+    Split {
+        commit: git::Oid,
+        paths: Vec<path::PathBuf>,
+        message: String,
+    },
+}
+
+impl RewriteStep {
+    pub fn commit(&self) -> git::Oid {
+        match self {
+            RewriteStep::Pick { commit }
+            | RewriteStep::Drop { commit }
+            | RewriteStep::Squash { commit }
+            | RewriteStep::Fixup { commit }
+            | RewriteStep::Reword { commit, .. }
+            | RewriteStep::Split { commit, .. } => *commit,
+        }
+    }
+}
+
+// the rest of a rewrite that stopped at a conflict, starting with the conflicting step
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PendingRewrite {
+    branch_id: BranchId,
+    base: git::Oid,
+    steps: Vec<RewriteStep>,
+}
+
+enum Replay<'repo> {
+    Done(git::Commit<'repo>),
+    // the step at the index can not be replayed onto head without conflicts
+    Conflict {
+        head: git::Commit<'repo>,
+        step: usize,
+        index: git::Index,
+    },
+}
+
+/// rewrites the commits of a virtual branch according to the todo list.
+///
+/// every commit of the branch must be listed exactly once. the commits are replayed
+/// on top of the branch base in the order of the list. changes of dropped commits are
+/// removed from the working directory as well.
+///
+/// if a commit can not be replayed cleanly, the rewrite stops there just like cherry
+/// picking does: the commits replayed so far become the branch head, the conflicts
+/// are checked out and `None` is returned. once they are resolved, the rewrite is
+/// finished with [`continue_rewrite`].
+pub fn rewrite_branch(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    branch_id: &BranchId,
+    steps: &[RewriteStep],
+    user: Option<&users::User>,
+    signing_key: Option<&keys::PrivateKey>,
+) -> Result<Option<git::Oid>, errors::RewriteBranchError> {
+    if conflicts::is_conflicting(project_repository, None)?
+        || conflicts::rewrite_todo(project_repository)?.is_some()
+    {
+        return Err(errors::RewriteBranchError::Conflict(
+            errors::ProjectConflictError {
+                project_id: project_repository.project().id,
+            },
+        ));
+    }
+
+    let (base_oid, mut branch) =
+        read_branch_to_rewrite(gb_repository, project_repository, branch_id)?;
+
+    let branch_commit_oids =
+        project_repository.l(branch.head, project_repository::LogUntil::Commit(base_oid))?;
+
+    for step in steps {
+        if !branch_commit_oids.contains(&step.commit()) {
+            return Err(errors::RewriteBranchError::CommitNotFound(step.commit()));
+        }
+        match step {
+            RewriteStep::Reword { message, .. } | RewriteStep::Split { message, .. }
+                if message.is_empty() =>
+            {
+                return Err(errors::RewriteBranchError::EmptyMessage);
+            }
+            RewriteStep::Split { commit, paths, .. } if paths.is_empty() => {
+                return Err(errors::RewriteBranchError::EmptySplit(*commit));
+            }
+            _ => {}
+        }
+    }
+
+    let listed_commit_oids = steps
+        .iter()
+        .map(RewriteStep::commit)
+        .collect::<HashSet<_>>();
+    if listed_commit_oids.len() != steps.len()
+        || listed_commit_oids.len() != branch_commit_oids.len()
+    {
+        return Err(errors::RewriteBranchError::IncompleteTodo);
+    }
+
+    let Some(first_commit_oid) = branch_commit_oids.last() else {
+        // nothing to rewrite
+        return Ok(Some(branch.head));
+    };
+
+    let repo = &project_repository.git_repository;
+    let base_commit = repo
+        .find_commit(*first_commit_oid)
+        .context("failed to find first branch commit")?
+        .parent(0)
+        .context("failed to find branch base commit")?;
+    let base_commit_oid = base_commit.id();
+
+    let old_head_tree = repo
+        .find_commit(branch.head)
+        .context("failed to find branch head commit")?
+        .tree()
+        .context("failed to find branch head tree")?;
+
+    match replay_steps(repo, base_commit, base_commit_oid, steps)? {
+        Replay::Done(head) => finish_rewrite(
+            gb_repository,
+            project_repository,
+            &mut branch,
+            &old_head_tree,
+            &head,
+            user,
+            signing_key,
+        )
+        .map(Some),
+        Replay::Conflict {
+            head,
+            step,
+            mut index,
+        } => {
+            let pending = PendingRewrite {
+                branch_id: branch.id,
+                base: base_commit_oid,
+                steps: steps[step..].to_vec(),
+            };
+            stop_rewrite(
+                gb_repository,
+                project_repository,
+                &branch,
+                &pending,
+                &head,
+                &mut index,
+                user,
+                signing_key,
+            )?;
+            Ok(None)
+        }
+    }
+}
+
+/// continues a branch rewrite that stopped at a conflict, once the conflicts are
+/// resolved.
+///
+/// the resolved working directory is committed in place of the conflicting commit,
+/// and the rest of the todo list is replayed on top of it, which may stop at another
+/// conflict. nothing is written, and the conflicts stay marked, if that fails.
+pub fn continue_rewrite(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    branch_id: &BranchId,
+    user: Option<&users::User>,
+    signing_key: Option<&keys::PrivateKey>,
+) -> Result<Option<git::Oid>, errors::RewriteBranchError> {
+    if conflicts::is_conflicting(project_repository, None)? {
+        return Err(errors::RewriteBranchError::Conflict(
+            errors::ProjectConflictError {
+                project_id: project_repository.project().id,
+            },
+        ));
+    }
+
+    let pending = conflicts::rewrite_todo(project_repository)?
+        .map(|todo| serde_json::from_str::<PendingRewrite>(&todo))
+        .transpose()
+        .context("failed to parse rewrite todo")?
+        .filter(|pending| pending.branch_id == *branch_id)
+        .ok_or(errors::RewriteBranchError::NotRewriting)?;
+    let Some((step, rest)) = pending.steps.split_first() else {
+        return Err(errors::RewriteBranchError::NotRewriting);
+    };
+
+    let (_, mut branch) = read_branch_to_rewrite(gb_repository, project_repository, branch_id)?;
+
+    let repo = &project_repository.git_repository;
+    let resolved_tree = project_repository
+        .get_wd_tree()
+        .context("failed to get working directory tree")?;
+    let head = repo
+        .find_commit(branch.head)
+        .context("failed to find branch head commit")?;
+    let commit = repo
+        .find_commit(step.commit())
+        .context("failed to find commit")?;
+    let head = commit_step(repo, &head, pending.base, step, &commit, resolved_tree.id())?;
+
+    // the resolution is committed, only the rest of the todo list is left. the branch
+    // is written once that is replayed, or stopped at the next conflict.
+    match replay_steps(repo, head, pending.base, rest)? {
+        Replay::Done(head) => {
+            let new_head_oid = finish_rewrite(
+                gb_repository,
+                project_repository,
+                &mut branch,
+                &resolved_tree,
+                &head,
+                user,
+                signing_key,
+            )?;
+            if conflicts::is_resolving(project_repository) {
+                conflicts::clear(project_repository).context("failed to clear conflicts")?;
+            }
+            conflicts::clear_rewrite(project_repository).context("failed to clear rewrite todo")?;
+            Ok(Some(new_head_oid))
+        }
+        Replay::Conflict {
+            head,
+            step,
+            mut index,
+        } => {
+            let pending = PendingRewrite {
+                steps: rest[step..].to_vec(),
+                ..pending
+            };
+            stop_rewrite(
+                gb_repository,
+                project_repository,
+                &branch,
+                &pending,
+                &head,
+                &mut index,
+                user,
+                signing_key,
+            )?;
+            Ok(None)
+        }
+    }
+}
+
+// reads the branch along with the commit its commits start from: the head of its
+// parent for stacked branches, the default target otherwise
+fn read_branch_to_rewrite(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    branch_id: &BranchId,
+) -> Result<(git::Oid, branch::Branch), errors::RewriteBranchError> {
+    let current_session = gb_repository
+        .get_or_create_current_session()
+        .context("failed to get or create current session")?;
+    let current_session_reader = sessions::Reader::open(gb_repository, &current_session)
+        .context("failed to open current session")?;
+    let branch_reader = branch::Reader::new(&current_session_reader);
+
+    let default_target = get_default_target(&current_session_reader)
+        .context("failed to read default target")?
+        .ok_or_else(|| {
+            errors::RewriteBranchError::DefaultTargetNotSet(errors::DefaultTargetNotSetError {
+                project_id: project_repository.project().id,
+            })
+        })?;
+
+    let branch = branch_reader.read(branch_id).map_err(|error| match error {
+        reader::Error::NotFound => {
+            errors::RewriteBranchError::BranchNotFound(errors::BranchNotFoundError {
+                project_id: project_repository.project().id,
+                branch_id: *branch_id,
+            })
+        }
+        error => errors::RewriteBranchError::Other(error.into()),
+    })?;
+
+    if !branch.applied {
+        return Err(errors::RewriteBranchError::NotApplied);
+    }
+
+    let base_oid = match branch.parent {
+        Some(parent_id) => {
+            branch_reader
+                .read(&parent_id)
+                .context("failed to read parent branch")?
+                .head
+        }
+        None => default_target.sha,
+    };
+
+    Ok((base_oid, branch))
+}
+
+// replays the steps on top of head, stopping at the first one that conflicts
+fn replay_steps<'repo>(
+    repo: &'repo git::Repository,
+    mut head: git::Commit<'repo>,
+    base_commit_oid: git::Oid,
+    steps: &[RewriteStep],
+) -> Result<Replay<'repo>, errors::RewriteBranchError> {
+    for (i, step) in steps.iter().enumerate() {
+        if let RewriteStep::Drop { .. } = step {
+            continue;
+        }
+
+        let commit = repo
+            .find_commit(step.commit())
+            .context("failed to find commit")?;
+        let parent_oid = commit
+            .parent(0)
+            .context("failed to find parent commit")?
+            .id();
+
+        if let RewriteStep::Pick { .. } = step {
+            if parent_oid == head.id() {
+                // nothing changed before this commit, so it can be kept as is
+                head = commit;
+                continue;
+            }
+        }
+
+        let tree_oid = if parent_oid == head.id() {
+            commit.tree_id()
+        } else {
+            let mut cherrypick_index = repo
+                .cherry_pick(&head, &commit)
+                .context("failed to cherry pick")?;
+            if cherrypick_index.has_conflicts() {
+                return Ok(Replay::Conflict {
+                    head,
+                    step: i,
+                    index: cherrypick_index,
+                });
+            }
+            cherrypick_index
+                .write_tree_to(repo)
+                .context("failed to write merge tree")?
+        };
+
+        head = commit_step(repo, &head, base_commit_oid, step, &commit, tree_oid)?;
+    }
+
+    Ok(Replay::Done(head))
+}
+
+// commits the replayed tree of a step on top of head, returning the new head
+fn commit_step<'repo>(
+    repo: &'repo git::Repository,
+    head: &git::Commit<'repo>,
+    base_commit_oid: git::Oid,
+    step: &RewriteStep,
+    commit: &git::Commit,
+    tree_oid: git::Oid,
+) -> Result<git::Commit<'repo>, errors::RewriteBranchError> {
+    let tree = repo.find_tree(tree_oid).context("failed to find tree")?;
+
+    let new_commit_oid = match step {
+        RewriteStep::Squash { .. } | RewriteStep::Fixup { .. } => {
+            if head.id() == base_commit_oid {
+                return Err(errors::RewriteBranchError::CantSquashRootCommit);
+            }
+
+            let message = match step {
+                RewriteStep::Squash { .. } => format!(
+                    "{}\n{}",
+                    head.message().unwrap_or_default(),
+                    commit.message().unwrap_or_default(),
+                ),
+                _ => head.message().unwrap_or_default().to_string(),
+            };
+            let parents = head
+                .parents()
+                .context("failed to find head commit parents")?;
+
+            repo.commit(
+                None,
+                &commit.author(),
+                &commit.committer(),
+                &message,
+                &tree,
+                &parents.iter().collect::<Vec<_>>(),
+            )
+        }
+        RewriteStep::Reword { message, .. } => repo.commit(
+            None,
+            &commit.author(),
+            &commit.committer(),
+            message,
+            &tree,
+            &[head],
+        ),
+        RewriteStep::Split { paths, message, .. } => {
+            let head_tree = head.tree().context("failed to find head tree")?;
+            let split_tree_oid = split_tree(repo, &head_tree, &tree, paths)?;
+            if split_tree_oid == head_tree.id() || split_tree_oid == tree_oid {
+                // one of the commits would be empty
+                return Err(errors::RewriteBranchError::EmptySplit(commit.id()));
+            }
+            let split_tree = repo
+                .find_tree(split_tree_oid)
+                .context("failed to find split tree")?;
+            let split_commit_oid = repo
+                .commit(
+                    None,
+                    &commit.author(),
+                    &commit.committer(),
+                    message,
+                    &split_tree,
+                    &[head],
+                )
+                .context("failed to create split commit")?;
+            let split_commit = repo
+                .find_commit(split_commit_oid)
+                .context("failed to find split commit")?;
+
+            repo.commit(
+                None,
+                &commit.author(),
+                &commit.committer(),
+                commit.message().unwrap_or_default(),
+                &tree,
+                &[&split_commit],
+            )
+        }
+        RewriteStep::Pick { .. } | RewriteStep::Drop { .. } => repo.commit(
+            None,
+            &commit.author(),
+            &commit.committer(),
+            commit.message().unwrap_or_default(),
+            &tree,
+            &[head],
+        ),
+    }
+    .context("failed to create commit")?;
+
+    Ok(repo
+        .find_commit(new_commit_oid)
+        .context("failed to find commit")?)
+}
+
+// the tree of head with the given paths as they are in tree
+fn split_tree(
+    repo: &git::Repository,
+    head_tree: &git::Tree,
+    tree: &git::Tree,
+    paths: &[path::PathBuf],
+) -> Result<git::Oid> {
+    let mut builder = repo.treebuilder(Some(head_tree));
+    for path in paths {
+        match tree.get_path(path) {
+            Ok(entry) => {
+                let filemode = match entry.filemode() {
+                    0o100644 => git::FileMode::Blob,
+                    0o100755 => git::FileMode::BlobExecutable,
+                    0o120000 => git::FileMode::Link,
+                    0o040000 => git::FileMode::Tree,
+                    _ => bail!("can not split {}", path.display()),
+                };
+                builder.upsert(path, entry.id(), filemode);
+            }
+            Err(git::Error::NotFound(_)) => builder.remove(path),
+            Err(error) => return Err(error).context("failed to find tree entry"),
+        }
+    }
+    builder.write().context("failed to write split tree")
+}
+
+// writes the rewritten head, removing the changes it dropped from the working directory
+// and restacking the branches stacked on it
+fn finish_rewrite(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    branch: &mut branch::Branch,
+    old_head_tree: &git::Tree,
+    head: &git::Commit,
+    user: Option<&users::User>,
+    signing_key: Option<&keys::PrivateKey>,
+) -> Result<git::Oid, errors::RewriteBranchError> {
+    let new_head_oid = head.id();
+    if new_head_oid == branch.head {
+        return Ok(new_head_oid);
+    }
+
+    if !project_repository.project().ok_with_force_push
+        && !is_requires_force(project_repository, branch)?
+        && is_requires_force(
+            project_repository,
+            &branch::Branch {
+                head: new_head_oid,
+                ..branch.clone()
+            },
+        )?
+    {
+        // rewriting pushed commits will cause a force push that is not allowed
+        return Err(errors::RewriteBranchError::ForcePushNotAllowed(
+            errors::ForcePushNotAllowedError {
+                project_id: project_repository.project().id,
+            },
+        ));
+    }
+
+    let repo = &project_repository.git_repository;
+    let new_head_tree = head.tree().context("failed to find new head tree")?;
+
+    let final_tree = if old_head_tree.id() == new_head_tree.id() {
+        None
+    } else {
+        // some changes were dropped, remove them from the working directory too
+        let wd_tree = project_repository
+            .get_wd_tree()
+            .context("failed to get working directory tree")?;
+        let mut merge_index = repo
+            .merge_trees(old_head_tree, &wd_tree, &new_head_tree)
+            .context("failed to merge trees")?;
+        if merge_index.has_conflicts() {
+            return Err(errors::RewriteBranchError::WorkdirConflict);
+        }
+        let final_tree_oid = merge_index
+            .write_tree_to(repo)
+            .context("failed to write tree")?;
+        Some(
+            repo.find_tree(final_tree_oid)
+                .context("failed to find tree")?,
+        )
+    };
+
+    let old_head = branch.head;
+    branch.head = new_head_oid;
+    let restacked = super::stack::restack_children(
+        gb_repository,
+        project_repository,
+        branch,
+        old_head,
+        user,
+        signing_key,
+    )?;
+
+    if let Some(final_tree) = final_tree {
+        repo.checkout_tree(&final_tree)
+            .force()
+            .remove_untracked()
+            .checkout()
+            .context("failed to checkout tree")?;
+    }
+
+    // save new branch head
+    let writer = branch::Writer::new(gb_repository).context("failed to create writer")?;
+    writer.write(branch).context("failed to write branch")?;
+    for mut child in restacked {
+        writer
+            .write(&mut child)
+            .context("failed to write stacked branch")?;
+    }
+
+    super::integration::update_gitbutler_integration(gb_repository, project_repository)?;
+
+    Ok(new_head_oid)
+}
+
+// stops a rewrite at a conflicting step: the commits replayed so far become the
+// branch head, and the conflicts of the step are checked out to be resolved.
+#[allow(clippy::too_many_arguments)]
+fn stop_rewrite(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    branch: &branch::Branch,
+    pending: &PendingRewrite,
+    head: &git::Commit,
+    index: &mut git::Index,
+    user: Option<&users::User>,
+    signing_key: Option<&keys::PrivateKey>,
+) -> Result<(), errors::RewriteBranchError> {
+    let conflicting_commit_oid = pending.steps[0].commit();
+
+    // every commit from here on is replayed, so what is left out of head now is left
+    // out of the rewritten branch too
+    if !project_repository.project().ok_with_force_push
+        && !is_requires_force(project_repository, branch)?
+        && is_requires_force(
+            project_repository,
+            &branch::Branch {
+                head: head.id(),
+                ..branch.clone()
+            },
+        )?
+    {
+        return Err(errors::RewriteBranchError::ForcePushNotAllowed(
+            errors::ForcePushNotAllowedError {
+                project_id: project_repository.project().id,
+            },
+        ));
+    }
+
+    let current_session = gb_repository
+        .get_or_create_current_session()
+        .context("failed to get or create current session")?;
+    let current_session_reader = sessions::Reader::open(gb_repository, &current_session)
+        .context("failed to open current session")?;
+    let default_target = get_default_target(&current_session_reader)
+        .context("failed to read default target")?
+        .context("no default target set")?;
+
+    let applied_branches = Iterator::new(&current_session_reader)
+        .context("failed to create branch iterator")?
+        .collect::<Result<Vec<branch::Branch>, reader::Error>>()
+        .context("failed to read virtual branches")?
+        .into_iter()
+        .filter(|b| b.applied)
+        .collect::<Vec<_>>();
+    let applied_statuses = get_applied_status(
+        gb_repository,
+        project_repository,
+        &default_target,
+        applied_branches,
+    )?;
+
+    let (branch, branch_files) = applied_statuses
+        .iter()
+        .find(|(b, _)| b.id == branch.id)
+        .context("branch status not found")?;
+    if !branch_files.is_empty() {
+        // uncommitted changes can't be carried over the conflict checkout
+        return Err(errors::RewriteBranchError::RebaseConflict(
+            conflicting_commit_oid,
+        ));
+    }
+
+    // if any other branches are applied, unapply them
+    for other_branch in applied_statuses
+        .iter()
+        .filter(|(b, _)| b.id != branch.id)
+        .map(|(b, _)| b)
+    {
+        unapply_branch(gb_repository, project_repository, &other_branch.id)
+            .context("failed to unapply branch")?;
+    }
+
+    // the branches stacked on this one, unapplied along with the others, follow the
+    // commits replayed so far
+    let old_head = branch.head;
+    let mut branch = branch::Branch {
+        head: head.id(),
+        ..branch.clone()
+    };
+    let restacked = super::stack::restack_children(
+        gb_repository,
+        project_repository,
+        &branch,
+        old_head,
+        user,
+        signing_key,
+    )?;
+
+    // checkout the conflicts
+    project_repository
+        .git_repository
+        .checkout_index(index)
+        .allow_conflicts()
+        .conflict_style_merge()
+        .force()
+        .checkout()
+        .context("failed to checkout conflicts")?;
+
+    // mark conflicts, and remember what is left to do after resolving them
+    conflicts::mark_index(project_repository, index, Some(head.id()))?;
+    conflicts::mark_rewrite(
+        project_repository,
+        &serde_json::to_string(pending).context("failed to serialize rewrite todo")?,
+    )?;
+
+    let writer = branch::Writer::new(gb_repository).context("failed to create writer")?;
+    writer
+        .write(&mut branch)
+        .context("failed to write branch")?;
+    for mut child in restacked {
+        writer
+            .write(&mut child)
+            .context("failed to write stacked branch")?;
+    }
+
+    super::integration::update_gitbutler_integration(gb_repository, project_repository)?;
+
+    Ok(())
+}
+
+/// moves a commit from one applied virtual branch to another.
+///
+/// the commit is removed from the history of the source branch, rebasing its
+/// descendants onto its parent, and replayed on top of the destination branch.
+/// ownership of the files changed by the commit is moved along with it, unless
+/// the remaining commits of the source branch change the same files. branches
+/// stacked on either branch are restacked onto their new heads.
+///
+/// nothing is written if any of the commits can not be replayed cleanly.
+pub fn move_commit(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    commit_oid: git::Oid,
+    from_branch_id: &BranchId,
+    to_branch_id: &BranchId,
+    user: Option<&users::User>,
+    signing_key: Option<&keys::PrivateKey>,
+) -> Result<git::Oid, errors::MoveCommitError> {
+    if conflicts::is_conflicting(project_repository, None)? {
+        return Err(errors::MoveCommitError::Conflict(
+            errors::ProjectConflictError {
+                project_id: project_repository.project().id,
+            },
+        ));
+    }
+
+    let current_session = gb_repository
+        .get_or_create_current_session()
+        .context("failed to get or create current session")?;
+    let current_session_reader = sessions::Reader::open(gb_repository, &current_session)
+        .context("failed to open current session")?;
+    let branch_reader = branch::Reader::new(&current_session_reader);
+
+    let default_target = get_default_target(&current_session_reader)
+        .context("failed to read default target")?
+        .ok_or_else(|| {
+            errors::MoveCommitError::DefaultTargetNotSet(errors::DefaultTargetNotSetError {
+                project_id: project_repository.project().id,
+            })
+        })?;
+
+    let read_branch = |branch_id: &BranchId| {
+        branch_reader.read(branch_id).map_err(|error| match error {
+            reader::Error::NotFound => {
+                errors::MoveCommitError::BranchNotFound(errors::BranchNotFoundError {
+                    project_id: project_repository.project().id,
+                    branch_id: *branch_id,
+                })
+            }
+            error => errors::MoveCommitError::Other(error.into()),
+        })
+    };
+    let mut from_branch = read_branch(from_branch_id)?;
+    let mut to_branch = read_branch(to_branch_id)?;
+
+    if !from_branch.applied || !to_branch.applied {
+        return Err(errors::MoveCommitError::NotApplied);
+    }
+
+    // commits of stacked branches end at the head of their parent
+    let from_base_oid = match from_branch.parent {
+        Some(parent_id) => {
+            branch_reader
+                .read(&parent_id)
+                .context("failed to read parent branch")?
+                .head
+        }
+        None => default_target.sha,
+    };
+    let from_commit_oids = project_repository.l(
+        from_branch.head,
+        project_repository::LogUntil::Commit(from_base_oid),
+    )?;
+    if !from_commit_oids.contains(&commit_oid) {
+        return Err(errors::MoveCommitError::CommitNotFound(commit_oid));
+    }
+
+    if from_branch.id == to_branch.id {
+        return Ok(commit_oid);
+    }
+
+    let repo = &project_repository.git_repository;
+    let commit = repo
+        .find_commit(commit_oid)
+        .context("failed to find commit")?;
+    let parent = commit.parent(0).context("failed to find parent commit")?;
+
+    // rebase commits that come after the moved one onto its parent
+    let mut from_head = repo
+        .find_commit(parent.id())
+        .context("failed to find parent commit")?;
+    let descendant_oids = from_commit_oids
+        .split(|oid| oid == &commit_oid)
+        .next()
+        .unwrap_or_default();
+    for descendant_oid in descendant_oids.iter().rev() {
+        let descendant = repo
+            .find_commit(*descendant_oid)
+            .context("failed to find commit")?;
+        let mut cherrypick_index = repo
+            .cherry_pick(&from_head, &descendant)
+            .context("failed to cherry pick")?;
+        if cherrypick_index.has_conflicts() {
+            return Err(errors::MoveCommitError::SourceBranchConflict(
+                *descendant_oid,
+            ));
+        }
+        let tree_oid = cherrypick_index
+            .write_tree_to(repo)
+            .context("failed to write merge tree")?;
+        let tree = repo.find_tree(tree_oid).context("failed to find tree")?;
+        let new_commit_oid = repo
+            .commit(
+                None,
+                &descendant.author(),
+                &descendant.committer(),
+                descendant.message().unwrap_or_default(),
+                &tree,
+                &[&from_head],
+            )
+            .context("failed to create commit")?;
+        from_head = repo
+            .find_commit(new_commit_oid)
+            .context("failed to find commit")?;
+    }
+
+    if !project_repository.project().ok_with_force_push
+        && !is_requires_force(project_repository, &from_branch)?
+        && is_requires_force(
+            project_repository,
+            &branch::Branch {
+                head: from_head.id(),
+                ..from_branch.clone()
+            },
+        )?
+    {
+        // removing a pushed commit will cause a force push that is not allowed
+        return Err(errors::MoveCommitError::ForcePushNotAllowed(
+            errors::ForcePushNotAllowedError {
+                project_id: project_repository.project().id,
+            },
+        ));
+    }
+
+    // the branches stacked on the source branch follow it, which moves the destination
+    // branch too if it is one of them. nothing is written until both branches moved.
+    let mut all_branches = Iterator::new(&current_session_reader)
+        .context("failed to create branch iterator")?
+        .collect::<Result<Vec<branch::Branch>, reader::Error>>()
+        .context("failed to read virtual branches")?;
+    let mut restacked: HashMap<BranchId, branch::Branch> = HashMap::new();
+    let old_from_head = from_branch.head;
+    from_branch.head = from_head.id();
+    for child in super::stack::restack_children_in(
+        project_repository,
+        &all_branches,
+        &from_branch,
+        old_from_head,
+        user,
+        signing_key,
+    )? {
+        if child.id == to_branch.id {
+            to_branch.head = child.head;
+            to_branch.tree = child.tree;
+        }
+        restacked.insert(child.id, child);
+    }
+
+    // replay the moved commit on top of the destination branch
+    let to_head = repo
+        .find_commit(to_branch.head)
+        .context("failed to find destination branch head")?;
+    let mut cherrypick_index = repo
+        .cherry_pick(&to_head, &commit)
+        .context("failed to cherry pick")?;
+    if cherrypick_index.has_conflicts() {
+        return Err(errors::MoveCommitError::DestinationBranchConflict(
+            commit_oid,
+        ));
+    }
+    let tree_oid = cherrypick_index
+        .write_tree_to(repo)
+        .context("failed to write merge tree")?;
+    let tree = repo.find_tree(tree_oid).context("failed to find tree")?;
+    let new_commit_oid = repo
+        .commit(
+            None,
+            &commit.author(),
+            &commit.committer(),
+            commit.message().unwrap_or_default(),
+            &tree,
+            &[&to_head],
+        )
+        .context("failed to create commit")?;
+
+    // then the branches stacked on the destination branch, which moves the source branch
+    // too if it is one of them
+    for branch in &mut all_branches {
+        if let Some(moved) = [&from_branch, &to_branch]
+            .into_iter()
+            .chain(restacked.values())
+            .find(|moved| moved.id == branch.id)
+        {
+            *branch = moved.clone();
+        }
+    }
+    let old_to_head = to_branch.head;
+    to_branch.head = new_commit_oid;
+    for child in super::stack::restack_children_in(
+        project_repository,
+        &all_branches,
+        &to_branch,
+        old_to_head,
+        user,
+        signing_key,
+    )? {
+        if child.id == from_branch.id {
+            from_branch.head = child.head;
+            from_branch.tree = child.tree;
+        }
+        restacked.insert(child.id, child);
+    }
+
+    // hand over the ownership of the files the commit changed
+    let moved_files = diff::trees(
+        repo,
+        &parent.tree().context("failed to find parent tree")?,
+        &commit.tree().context("failed to find commit tree")?,
+    )
+    .context("failed to diff commit")?;
+    let remaining_files = diff::trees(
+        repo,
+        &repo
+            .find_commit(from_base_oid)
+            .context("failed to find source branch base commit")?
+            .tree()
+            .context("failed to find source branch base tree")?,
+        &from_head
+            .tree()
+            .context("failed to find source branch tree")?,
+    )
+    .context("failed to diff source branch")?;
+    for file_path in moved_files.keys() {
+        if remaining_files.contains_key(file_path) {
+            continue;
+        }
+        let taken = from_branch.ownership.take(&FileOwnership {
+            file_path: file_path.clone(),
+            hunks: vec![],
+        });
+        for file_ownership in &taken {
+            to_branch.ownership.put(file_ownership);
+        }
+    }
+
+    let writer = branch::Writer::new(gb_repository).context("failed to create writer")?;
+    writer
+        .write(&mut from_branch)
+        .context("failed to write source branch")?;
+    writer
+        .write(&mut to_branch)
+        .context("failed to write destination branch")?;
+    for mut branch in restacked
+        .into_values()
+        .filter(|branch| branch.id != from_branch.id && branch.id != to_branch.id)
+    {
+        writer
+            .write(&mut branch)
+            .context("failed to write stacked branch")?;
+    }
+
+    // recalculate branch trees according to the new ownership
+    let applied_branches = Iterator::new(&current_session_reader)
+        .context("failed to create branch iterator")?
+        .collect::<Result<Vec<branch::Branch>, reader::Error>>()
+        .context("failed to read virtual branches")?
+        .into_iter()
+        .filter(|branch| branch.applied)
+        .collect::<Vec<_>>();
+    get_applied_status(
+        gb_repository,
+        project_repository,
+        &default_target,
+        applied_branches,
+    )
+    .context("failed to get status by branch")?;
+
+    super::integration::update_gitbutler_integration(gb_repository, project_repository)?;
+
+    Ok(new_commit_oid)
+}
+
+/// lists three-way content of the files that are still conflicting after a merge.
+pub fn list_conflicts(
+    project_repository: &project_repository::Repository,
+) -> Result<Vec<conflicts::Conflict>, errors::ListConflictsError> {
+    conflicts::list(project_repository).map_err(Into::into)
+}
+
+/// resolves a conflicting file, either with the given content or by choosing a side
+/// for the whole file or for each of its conflicting hunks.
+pub fn resolve_conflict(
+    project_repository: &project_repository::Repository,
+    path: &str,
+    resolution: &conflicts::Resolution,
+) -> Result<(), errors::ResolveConflictError> {
+    if !conflicts::is_conflicting(project_repository, Some(path))? {
+        return Err(errors::ResolveConflictError::NotConflicting(
+            path.to_string(),
+        ));
+    }
+
+    conflicts::resolve_with(project_repository, path, resolution)
+        .context(format!("failed to resolve {}", path))?;
+
+    Ok(())
+}
+
+/// commits the resolved merge to the branch. the commit gets the merged commit as the
+/// second parent.
+pub fn finalize_merge(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    branch_id: &BranchId,
+    message: &str,
+    signing_key: Option<&keys::PrivateKey>,
+    user: Option<&users::User>,
+) -> Result<git::Oid, errors::FinalizeMergeError> {
+    if !conflicts::is_resolving(project_repository) {
+        return Err(errors::FinalizeMergeError::NotResolving);
+    }
+
+    if conflicts::is_conflicting(project_repository, None)? {
+        return Err(errors::FinalizeMergeError::Conflict(
+            errors::ProjectConflictError {
+                project_id: project_repository.project().id,
+            },
+        ));
+    }
+
+    commit(
+        gb_repository,
+        project_repository,
+        branch_id,
+        message,
+        None,
+        signing_key,
+        user,
+        false,
+    )
+    .map_err(Into::into)
+}
+
+// everything that is needed to create or update the pull request of a branch
+pub struct PullRequestParams {
+    pub api_url: String,
+    pub token: String,
+    pub repository: forge::Repository,
+    pub number: Option<u64>,
+    pub title: String,
+    pub body: String,
+    pub head: String,
+    pub base: String,
+}
+
+/// collects what is needed to create a pull request for the pushed branch. the pull
+/// request is titled after the branch name and described with branch notes.
+pub fn pull_request_params(
+    gb_repository: &gb_repository::Repository,
+    project_repository: &project_repository::Repository,
+    branch_id: &BranchId,
+    user: Option<&users::User>,
+) -> Result<PullRequestParams, errors::CreatePullRequestError> {
+    let current_session = gb_repository
+        .get_or_create_current_session()
+        .context("failed to get or create current session")?;
+    let current_session_reader = sessions::Reader::open(gb_repository, &current_session)
+        .context("failed to open current session")?;
+
+    let default_target = get_default_target(&current_session_reader)
+        .context("failed to get default target")?
+        .ok_or_else(|| {
+            errors::CreatePullRequestError::DefaultTargetNotSet(errors::DefaultTargetNotSetError {
+                project_id: project_repository.project().id,
+            })
+        })?;
+
+    let branch = branch::Reader::new(&current_session_reader)
+        .read(branch_id)
+        .map_err(|error| match error {
+            reader::Error::NotFound => {
+                errors::CreatePullRequestError::BranchNotFound(errors::BranchNotFoundError {
+                    project_id: project_repository.project().id,
+                    branch_id: *branch_id,
+                })
+            }
+            error => errors::CreatePullRequestError::Other(error.into()),
+        })?;
+
+    let upstream = branch
+        .upstream
+        .as_ref()
+        .ok_or(errors::CreatePullRequestError::NotPushed)?;
+
+    let token = user
+        .and_then(|user| user.github_access_token.clone())
+        .ok_or(errors::CreatePullRequestError::NotAuthenticated)?;
+
+    // stacked branches are merged into their parent branch
+    let base = match branch.parent {
+        Some(parent_id) => branch::Reader::new(&current_session_reader)
+            .read(&parent_id)
+            .ok()
+            .and_then(|parent| parent.upstream)
+            .map_or_else(
+                || default_target.branch.branch().to_string(),
+                |upstream| upstream.branch().to_string(),
+            ),
+        None => default_target.branch.branch().to_string(),
+    };
+
+    Ok(PullRequestParams {
+        api_url: project_repository
+            .config()
+            .forge_api_url()
+            .context("failed to get forge api url")?,
+        token,
+        repository: forge::Repository::from_remote_url(&default_target.remote_url)?,
+        number: branch.pr_number,
+        title: branch.name.clone(),
+        body: branch.notes.clone(),
+        head: upstream.branch().to_string(),
+        base,
+    })
+}
+
+/// remembers the pull request of the branch, so that it can be updated later on.
+pub fn set_pull_request(
+    gb_repository: &gb_repository::Repository,
+    branch_id: &BranchId,
+    pull_request: &forge::PullRequest,
+) -> Result<(), errors::CreatePullRequestError> {
+    let current_session = gb_repository
+        .get_or_create_current_session()
+        .context("failed to get or create current session")?;
+    let current_session_reader = sessions::Reader::open(gb_repository, &current_session)
+        .context("failed to open current session")?;
+
+    let mut branch = branch::Reader::new(&current_session_reader)
+        .read(branch_id)
+        .context("failed to read branch")?;
+    branch.pr_number = Some(pull_request.number);
+    branch.pr_status = Some(pull_request.status);
+
+    branch::Writer::new(gb_repository)
+        .context("failed to create writer")?
+        .write(&mut branch)
+        .context("failed to write branch")?;
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
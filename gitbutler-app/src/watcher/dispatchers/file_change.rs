@@ -77,6 +77,31 @@ impl Dispatcher {
             path.display()
         ))?;
 
+        // the git directory of a linked worktree is not inside of it, but inside of the
+        // directory shared by all worktrees, which has the refs and the FETCH_HEAD of
+        // fetches made from the main worktree.
+        let git_dir = repo.path().to_path_buf();
+        let common_dir = if repo.is_worktree() {
+            Some(
+                repo.common_dir()
+                    .context("failed to find common git directory")?,
+            )
+        } else {
+            None
+        };
+        let git_dirs = match &common_dir {
+            Some(common_dir) if git_dir.starts_with(common_dir) => vec![common_dir],
+            Some(common_dir) => vec![common_dir, &git_dir],
+            None if git_dir.starts_with(path) => vec![],
+            None => vec![&git_dir],
+        };
+        for dir in git_dirs {
+            debouncer
+                .watcher()
+                .watch(dir, notify::RecursiveMode::Recursive)
+                .context(format!("failed to watch git directory: {}", dir.display()))?;
+        }
+
         self.watcher.lock().unwrap().replace(debouncer);
 
         tracing::debug!(%project_id, "file watcher started");
@@ -89,60 +114,85 @@ impl Dispatcher {
                 let project_id = *project_id;
                 move || {
                     for result in notify_rx {
-                        match result {
+                        let events = match result {
                             Err(errors) => {
                                 tracing::error!(?errors, "file watcher error");
+                                continue;
                             }
-                            Ok(events) => {
-                                let file_paths = events.into_iter().filter(|event| is_interesting_kind(event.kind)).flat_map(|event| event.paths.clone()).filter(|file| is_interesting_file(&repo, file));
-                                for file_path in file_paths {
-                                    match file_path.strip_prefix(&path) {
-                                        Ok(relative_file_path) if relative_file_path.display().to_string().is_empty() => { /* noop */ }
-                                        Ok(relative_file_path) => {
-                                            let event = if relative_file_path.starts_with(".git") {
-                                                tracing::info!(
-                                                    %project_id,
-                                                    file_path = %relative_file_path.display(),
-                                                    "git file change",
-                                                );
-                                                events::Event::GitFileChange(
-                                                    project_id,
-                                                    relative_file_path
-                                                        .strip_prefix(".git")
-                                                        .unwrap()
-                                                        .to_path_buf(),
-                                                )
-                                            } else {
-                                                tracing::info!(
-                                                    %project_id,
-                                                    file_path = %relative_file_path.display(),
-                                                    "project file change",
-                                                );
-                                                events::Event::ProjectFileChange(
-                                                    project_id,
-                                                    relative_file_path.to_path_buf(),
-                                                )
-                                            };
-                                            if let Err(error) = block_on(tx.send(event)) {
-                                                tracing::error!(
-                                                    %project_id,
-                                                    ?error,
-                                                    "failed to send file change event",
-                                                );
-                                            }
-                                        }
-                                        Err(error) => {
-                                            tracing::error!(%project_id, ?error, "failed to strip prefix");
-                                        }
+                            Ok(events) => events,
+                        };
+
+                        // re-read on every batch, the patterns may change at any time
+                        let sparse_checkout = repo.sparse_checkout().unwrap_or_else(|error| {
+                            tracing::error!(%project_id, ?error, "failed to read sparse checkout");
+                            None
+                        });
+
+                        let file_paths = events
+                            .into_iter()
+                            .filter(|event| is_interesting_kind(event.kind))
+                            .flat_map(|event| event.paths.clone())
+                            .filter(|file| {
+                                is_interesting_file(
+                                    &repo,
+                                    common_dir.as_deref(),
+                                    sparse_checkout.as_ref(),
+                                    file,
+                                )
+                            });
+                        for file_path in file_paths {
+                            let git_file_path = file_path.strip_prefix(&git_dir).ok().or_else(|| {
+                                common_dir
+                                    .as_ref()
+                                    .and_then(|common_dir| file_path.strip_prefix(common_dir).ok())
+                            });
+                            let event = if let Some(relative_file_path) = git_file_path {
+                                tracing::info!(
+                                    %project_id,
+                                    file_path = %relative_file_path.display(),
+                                    "git file change",
+                                );
+                                events::Event::GitFileChange(
+                                    project_id,
+                                    relative_file_path.to_path_buf(),
+                                )
+                            } else {
+                                match file_path.strip_prefix(&path) {
+                                    Ok(relative_file_path)
+                                        if relative_file_path.as_os_str().is_empty() =>
+                                    {
+                                        continue;
+                                    }
+                                    Ok(relative_file_path) => {
+                                        tracing::info!(
+                                            %project_id,
+                                            file_path = %relative_file_path.display(),
+                                            "project file change",
+                                        );
+                                        events::Event::ProjectFileChange(
+                                            project_id,
+                                            relative_file_path.to_path_buf(),
+                                        )
+                                    }
+                                    Err(error) => {
+                                        tracing::error!(%project_id, ?error, "failed to strip prefix");
+                                        continue;
+                                    }
                                 }
+                            };
+                            if let Err(error) = block_on(tx.send(event)) {
+                                tracing::error!(
+                                    %project_id,
+                                    ?error,
+                                    "failed to send file change event",
+                                );
                             }
+                        }
                     }
-                }
-                }
                     tracing::debug!(%project_id, "file watcher stopped");
                 }
-
-            }).context(format!("{}: failed to start file watcher thread", project_id))?;
+            })
+            .context(format!("{}: failed to start file watcher thread", project_id))?;
 
         Ok(rx)
     }
@@ -158,7 +208,12 @@ fn is_interesting_kind(kind: notify::EventKind) -> bool {
     )
 }
 
-fn is_interesting_file(git_repo: &git::Repository, file_path: &path::Path) -> bool {
+fn is_interesting_file(
+    git_repo: &git::Repository,
+    common_dir: Option<&path::Path>,
+    sparse_checkout: Option<&git::SparseCheckout>,
+    file_path: &path::Path,
+) -> bool {
     if file_path.starts_with(git_repo.path()) {
         let check_file_path = file_path.strip_prefix(git_repo.path()).unwrap();
         check_file_path.ends_with("FETCH_HEAD")
@@ -166,7 +221,82 @@ fn is_interesting_file(git_repo: &git::Repository, file_path: &path::Path) -> bo
             || check_file_path.eq(path::Path::new("HEAD"))
             || check_file_path.eq(path::Path::new("GB_FLUSH"))
             || check_file_path.eq(path::Path::new("index"))
+    } else if let Some(check_file_path) =
+        common_dir.and_then(|common_dir| file_path.strip_prefix(common_dir).ok())
+    {
+        // shared by all worktrees. the git directories of the other worktrees are in here too.
+        check_file_path.eq(path::Path::new("FETCH_HEAD"))
+            || check_file_path.eq(path::Path::new("packed-refs"))
+            || (check_file_path.starts_with("refs")
+                && check_file_path
+                    .extension()
+                    .map_or(true, |extension| extension != "lock"))
+    } else if file_path.ends_with(".git") {
+        // linked worktrees and submodules have a `.git` file pointing to their git directory
+        false
     } else {
-        !git_repo.is_path_ignored(file_path).unwrap_or(false)
+        let outside_sparse_checkout = sparse_checkout.is_some_and(|sparse_checkout| {
+            git_repo
+                .workdir()
+                .and_then(|workdir| file_path.strip_prefix(workdir).ok())
+                .is_some_and(|file_path| !sparse_checkout.is_included(file_path))
+        });
+        !outside_sparse_checkout && !git_repo.is_path_ignored(file_path).unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::test_utils;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_linked_worktree_shared_files() {
+        let repository = test_utils::test_repository();
+        let main_path = repository.workdir().unwrap();
+        let path = test_utils::temp_dir().join("linked");
+        let main_repository = git2::Repository::open(main_path).unwrap();
+        main_repository.worktree("linked", &path, None).unwrap();
+
+        let dispatcher = Dispatcher::new();
+        let mut rx = dispatcher
+            .clone()
+            .run(&ProjectId::generate(), &path)
+            .unwrap();
+
+        // the main worktree's own files are not interesting
+        let common_dir = main_path.join(".git");
+        std::fs::write(common_dir.join("ORIG_HEAD"), "").unwrap();
+        std::fs::write(common_dir.join("FETCH_HEAD"), "").unwrap();
+        let head = main_repository.head().unwrap().target().unwrap();
+        main_repository
+            .reference("refs/heads/other", head, false, "test")
+            .unwrap();
+
+        let mut changes = HashSet::new();
+        while changes.len() < 2 {
+            match tokio::time::timeout(Duration::from_secs(5), rx.recv())
+                .await
+                .expect("timed out waiting for file changes")
+            {
+                Some(events::Event::GitFileChange(_, file_path)) => {
+                    changes.insert(file_path);
+                }
+                Some(_) => {}
+                None => panic!("file watcher stopped"),
+            }
+        }
+        dispatcher.stop();
+
+        assert_eq!(
+            changes,
+            HashSet::from([
+                path::PathBuf::from("FETCH_HEAD"),
+                path::PathBuf::from("refs/heads/other"),
+            ])
+        );
     }
 }
@@ -266,6 +266,9 @@ mod test {
             ownership: branch::Ownership::default(),
             order: TEST_INDEX.load(Ordering::Relaxed),
             selected_for_changes: None,
+            parent: None,
+            pr_number: None,
+            pr_status: None,
         }
     }
 
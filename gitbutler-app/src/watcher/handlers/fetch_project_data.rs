@@ -57,13 +57,18 @@ impl HandlerInner {
     }
 
     pub async fn handle(&self, project_id: &ProjectId) -> Result<Vec<events::Event>> {
-        match self.vbranches.fetch_from_target(project_id).await {
+        match self
+            .vbranches
+            .fetch_from_target(project_id, gitbutler_git::FetchOptions::default())
+            .await
+        {
             Ok(_)
             | Err(virtual_branches::controller::ControllerError::VerifyError(_))
             | Err(virtual_branches::controller::ControllerError::Action(
                 virtual_branches::errors::FetchFromTargetError::DefaultTargetNotSet(_)
                 | virtual_branches::errors::FetchFromTargetError::Remote(RemoteError::Network)
-                | virtual_branches::errors::FetchFromTargetError::Remote(RemoteError::Auth),
+                | virtual_branches::errors::FetchFromTargetError::Remote(RemoteError::Auth)
+                | virtual_branches::errors::FetchFromTargetError::Remote(RemoteError::Cancelled),
             )) => Ok(vec![events::Event::Emit(app_events::Event::git_fetch(
                 project_id,
             ))]),
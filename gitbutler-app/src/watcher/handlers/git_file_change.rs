@@ -79,7 +79,7 @@ impl Handler {
                 )
                 .context("failed to open repository")?;
 
-                let file_path = project.path.join(".git/GB_FLUSH");
+                let file_path = project_repository.git_repository.path().join("GB_FLUSH");
 
                 if file_path.exists() {
                     if let Err(e) = std::fs::remove_file(&file_path) {
@@ -119,6 +119,12 @@ impl Handler {
             "index" => Ok(vec![events::Event::Emit(app_events::Event::git_index(
                 &project.id,
             ))]),
+            // refs shared with other worktrees, which might have moved them
+            _ if path.as_ref().starts_with("refs") || path.as_ref().ends_with("packed-refs") => {
+                Ok(vec![events::Event::Emit(app_events::Event::git_activity(
+                    &project.id,
+                ))])
+            }
             _ => Ok(vec![]),
         }
     }
@@ -196,6 +202,25 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_shared_ref_change() -> Result<()> {
+        let suite = Suite::default();
+        let Case { project, .. } = suite.new_case();
+
+        let listener = Handler {
+            local_data_dir: suite.local_app_data,
+            projects: suite.projects,
+            users: suite.users,
+        };
+
+        let result = listener.handle("refs/remotes/origin/master", &project.id)?;
+
+        assert_eq!(result.len(), 1);
+        assert!(matches!(result[0], Event::Emit(_)));
+
+        Ok(())
+    }
+
     fn create_new_session_via_new_file(project: &projects::Project, suite: &Suite) {
         fs::write(project.path.join("test.txt"), "test").unwrap();
 
@@ -1,7 +1,7 @@
 use std::{path, sync::Arc, time};
 
 use anyhow::{Context, Result};
-use itertools::Itertools;
+use futures::TryStreamExt;
 use tauri::{AppHandle, Manager};
 use tokio::sync::Mutex;
 
@@ -152,7 +152,8 @@ impl HandlerInner {
             self.batch_size,
             default_target.sha,
             gb_code_last_commit,
-        )?;
+        )
+        .await?;
 
         tracing::info!(
             %project_id,
@@ -257,29 +258,39 @@ fn collect_refs(
         .collect::<Vec<_>>())
 }
 
-fn batch_rev_walk(
+async fn batch_rev_walk(
     repo: &Repository,
     batch_size: usize,
     from: Oid,
     until: Option<Oid>,
 ) -> Result<Vec<Oid>> {
-    let mut revwalk = repo.revwalk().context("failed to create revwalk")?;
-    revwalk
-        .push(from.into())
-        .context(format!("failed to push {}", from))?;
-
-    if let Some(oid) = until {
-        revwalk
-            .hide(oid.into())
-            .context(format!("failed to hide {}", oid))?;
-    }
+    use gitbutler_git::Repository;
+
+    let repository = gitbutler_git::git2::Repository::<
+        gitbutler_git::git2::tokio::TokioThreadedResource,
+    >::open(repo.path())
+    .await
+    .context("failed to open repository")?;
+
+    let range = match until {
+        Some(until) => format!("{}..{}", until, from),
+        None => from.to_string(),
+    };
+    let options = gitbutler_git::LogOptions::default();
+    let mut batches = std::pin::pin!(repository.log(&range, &options).try_chunks(batch_size));
+
     let mut oids = Vec::new();
     oids.push(from);
-    for batch in &revwalk.chunks(batch_size) {
+    while let Some(batch) = batches
+        .try_next()
+        .await
+        .map_err(|error| error.1)
+        .context(format!("failed to walk {}", range))?
+    {
         if let Some(oid) = batch.last() {
-            let oid = oid.context("failed to get oid")?;
-            if oid != from.into() {
-                oids.push(oid.into());
+            let oid = oid.parse::<Oid>().context("failed to parse oid")?;
+            if oid != from {
+                oids.push(oid);
             }
         }
     }
@@ -0,0 +1,156 @@
+mod common;
+
+use std::{fs, path, process};
+
+use gblib::projects;
+
+use self::common::{paths, TestProject};
+
+struct Test {
+    repository: TestProject,
+    data_dir: path::PathBuf,
+}
+
+impl Default for Test {
+    fn default() -> Self {
+        let data_dir = paths::data_dir();
+        let repository = TestProject::default();
+        projects::Controller::try_from(&data_dir)
+            .unwrap()
+            .add(repository.path())
+            .expect("failed to add project");
+
+        let test = Self {
+            repository,
+            data_dir,
+        };
+        test.run(&["set-base", "refs/remotes/origin/master"]);
+        test
+    }
+}
+
+impl Test {
+    fn run(&self, args: &[&str]) -> serde_json::Value {
+        let output = process::Command::new(env!("CARGO_BIN_EXE_gitbutler"))
+            .arg("--data-dir")
+            .arg(&self.data_dir)
+            .args(args)
+            .current_dir(self.repository.path())
+            .output()
+            .expect("failed to run cli");
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        serde_json::from_slice(&output.stdout).expect("output is not json")
+    }
+
+    fn branch(&self, name: &str) -> serde_json::Value {
+        self.run(&["list"])
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|branch| branch["name"] == name)
+            .cloned()
+            .expect("branch not found")
+    }
+}
+
+#[test]
+fn new_and_list() {
+    let test = Test::default();
+
+    let branch_id = test.run(&["new", "feature"]);
+
+    let branch = test.branch("feature");
+    assert_eq!(branch["id"], branch_id);
+    assert_eq!(branch["active"], true);
+}
+
+#[test]
+fn commit() {
+    let test = Test::default();
+
+    test.run(&["new", "feature"]);
+    fs::write(test.repository.path().join("file.txt"), "content").unwrap();
+
+    let commit_id = test.run(&["commit", "-b", "feature", "-m", "first commit"]);
+
+    let branch = test.branch("feature");
+    assert!(branch["files"].as_array().unwrap().is_empty());
+    let commits = branch["commits"].as_array().unwrap();
+    assert_eq!(commits.len(), 1);
+    assert_eq!(commits[0]["id"], commit_id);
+    assert_eq!(commits[0]["description"], "first commit");
+}
+
+#[test]
+fn unapply_and_apply() {
+    let test = Test::default();
+
+    test.run(&["new", "feature"]);
+    fs::write(test.repository.path().join("file.txt"), "content").unwrap();
+
+    test.run(&["unapply", "feature"]);
+    assert_eq!(test.branch("feature")["active"], false);
+    assert!(!test.repository.path().join("file.txt").exists());
+
+    test.run(&["apply", "feature"]);
+    assert_eq!(test.branch("feature")["active"], true);
+    assert_eq!(
+        fs::read_to_string(test.repository.path().join("file.txt")).unwrap(),
+        "content"
+    );
+}
+
+#[test]
+fn move_hunk() {
+    let test = Test::default();
+
+    test.run(&["new", "first"]);
+    fs::write(test.repository.path().join("file.txt"), "content").unwrap();
+    assert_eq!(test.branch("first")["files"].as_array().unwrap().len(), 1);
+
+    test.run(&["new", "second"]);
+    test.run(&["move-hunk", "file.txt:1-2", "-b", "second"]);
+
+    assert!(test.branch("first")["files"].as_array().unwrap().is_empty());
+    assert_eq!(test.branch("second")["files"].as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn push() {
+    let test = Test::default();
+
+    test.run(&["new", "feature"]);
+    fs::write(test.repository.path().join("file.txt"), "content").unwrap();
+    let commit_id = test.run(&["commit", "-b", "feature", "-m", "first commit"]);
+
+    test.run(&["push", "feature"]);
+
+    let branch = test.branch("feature");
+    assert_eq!(branch["upstream"]["name"], "refs/remotes/origin/feature");
+    assert_eq!(branch["upstream"]["sha"], commit_id);
+    assert_eq!(
+        test.repository
+            .remote_branch_head("feature")
+            .map(|oid| oid.to_string()),
+        commit_id.as_str().map(ToString::to_string)
+    );
+}
+
+#[test]
+fn unknown_branch() {
+    let test = Test::default();
+
+    let output = process::Command::new(env!("CARGO_BIN_EXE_gitbutler"))
+        .arg("--data-dir")
+        .arg(&test.data_dir)
+        .args(["apply", "missing"])
+        .current_dir(test.repository.path())
+        .output()
+        .expect("failed to run cli");
+
+    assert!(!output.status.success());
+}
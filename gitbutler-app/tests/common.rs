@@ -302,6 +302,15 @@ impl TestProject {
             .expect("failed to commit")
     }
 
+    /// the commit a branch of the remote repository points to
+    pub fn remote_branch_head(&self, branch: &str) -> Option<git::Oid> {
+        let name: git::Refname = format!("refs/heads/{}", branch).parse().unwrap();
+        self.remote_repository
+            .find_branch(&name)
+            .ok()
+            .map(|branch| branch.peel_to_commit().unwrap().id())
+    }
+
     pub fn references(&self) -> Vec<git::Reference> {
         self.local_repository
             .references()
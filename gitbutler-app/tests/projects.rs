@@ -1,13 +1,139 @@
 mod common;
 
 use self::common::paths;
-use gblib::projects::Controller;
+use gblib::projects::{AuthKey, Controller};
 
 pub fn new() -> Controller {
     let data_dir = paths::data_dir();
     Controller::try_from(&data_dir).unwrap()
 }
 
+mod clone {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn success() {
+        let controller = new();
+        let repository = common::TestProject::default();
+        let url = format!("file://{}", repository.path().display());
+        let path = common::temp_dir().join("clone");
+
+        let progress = Arc::new(Mutex::new(Vec::new()));
+        let on_progress = Arc::clone(&progress);
+        let project = controller
+            .clone(
+                &url,
+                &path,
+                &AuthKey::default(),
+                gitbutler_git::FetchOptions::default()
+                    .with_progress(move |p| on_progress.lock().unwrap().push(p)),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(project.path, path);
+        assert_eq!(project.title, "clone");
+        assert!(path.join(".git").exists());
+        assert!(!progress.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn remembers_preferred_key() {
+        let controller = new();
+        let repository = common::TestProject::default();
+        let url = format!("file://{}", repository.path().display());
+        let path = common::temp_dir().join("clone");
+
+        let project = controller
+            .clone(
+                &url,
+                &path,
+                &AuthKey::GitCredentialsHelper,
+                gitbutler_git::FetchOptions::default(),
+            )
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            controller.get(&project.id).unwrap().preferred_key,
+            AuthKey::GitCredentialsHelper
+        ));
+    }
+
+    mod error {
+        use gblib::projects::CloneError;
+
+        use super::*;
+
+        #[tokio::test]
+        async fn not_empty() {
+            let controller = new();
+            let repository = common::TestProject::default();
+            let url = format!("file://{}", repository.path().display());
+            let path = common::temp_dir();
+            std::fs::write(path.join("file.txt"), "hello world").unwrap();
+
+            assert!(matches!(
+                controller
+                    .clone(
+                        &url,
+                        &path,
+                        &AuthKey::default(),
+                        gitbutler_git::FetchOptions::default()
+                    )
+                    .await,
+                Err(CloneError::PathNotEmpty)
+            ));
+        }
+
+        #[tokio::test]
+        async fn missing_remote() {
+            let controller = new();
+            let path = common::temp_dir().join("clone");
+
+            assert!(matches!(
+                controller
+                    .clone(
+                        "file:///does/not/exist",
+                        &path,
+                        &AuthKey::default(),
+                        gitbutler_git::FetchOptions::default()
+                    )
+                    .await,
+                Err(CloneError::Other(_))
+            ));
+            assert!(!path.exists());
+        }
+
+        #[tokio::test]
+        async fn cancelled() {
+            let controller = new();
+            let repository = common::TestProject::default();
+            let url = format!("file://{}", repository.path().display());
+            let path = common::temp_dir().join("clone");
+
+            let cancellation = gitbutler_git::CancellationToken::new();
+            cancellation.cancel();
+            assert!(matches!(
+                controller
+                    .clone(
+                        &url,
+                        &path,
+                        &AuthKey::default(),
+                        gitbutler_git::FetchOptions::default().with_cancellation(cancellation)
+                    )
+                    .await,
+                Err(CloneError::Cancelled)
+            ));
+            assert!(!path.exists());
+            assert!(controller.list().unwrap().is_empty());
+            assert!(!controller.cancel_clone(&path));
+        }
+    }
+}
+
 mod add {
     use super::*;
 
@@ -21,6 +147,28 @@ mod add {
         assert_eq!(project.title, path.iter().last().unwrap().to_str().unwrap());
     }
 
+    #[test]
+    fn worktree() {
+        let controller = new();
+        let repository = common::TestProject::default();
+        let path = common::temp_dir().join("linked");
+        git2::Repository::open(repository.path())
+            .unwrap()
+            .worktree("linked", &path, None)
+            .unwrap();
+
+        let project = controller.add(&path).unwrap();
+        assert_eq!(project.path, path);
+        assert_eq!(project.title, "linked");
+
+        // gitbutler data is kept in the git directory of the worktree
+        assert!(repository
+            .path()
+            .join(".git/worktrees/linked/gitbutler.json")
+            .exists());
+        assert!(!repository.path().join(".git/gitbutler.json").exists());
+    }
+
     mod error {
         use gblib::projects::AddError;
 
@@ -57,6 +205,24 @@ mod add {
             ));
         }
 
+        #[test]
+        fn worktree_of_project() {
+            let controller = new();
+            let repository = common::TestProject::default();
+            let path = common::temp_dir().join("linked");
+            git2::Repository::open(repository.path())
+                .unwrap()
+                .worktree("linked", &path, None)
+                .unwrap();
+
+            // gitbutler refs would be shared with the existing project
+            controller.add(repository.path()).unwrap();
+            assert!(matches!(
+                controller.add(&path),
+                Err(AddError::SameRepository(existing)) if existing == repository.path()
+            ));
+        }
+
         #[test]
         fn twice() {
             let controller = new();
@@ -23,6 +23,7 @@ struct Test {
     repository: TestProject,
     project_id: ProjectId,
     projects: projects::Controller,
+    users: users::Controller,
     controller: Controller,
 }
 
@@ -42,8 +43,9 @@ impl Default for Test {
         Self {
             repository: test_project,
             project_id: project.id,
-            controller: Controller::new(data_dir, projects.clone(), users, keys, helper),
+            controller: Controller::new(data_dir, projects.clone(), users.clone(), keys, helper),
             projects,
+            users,
         }
     }
 }
@@ -1389,6 +1391,9 @@ async fn resolve_conflict_flow() {
 }
 
 mod fetch_from_target {
+    use gblib::project_repository::RemoteError;
+    use gitbutler_git::{CancellationToken, FetchOptions};
+
     use super::*;
 
     #[tokio::test]
@@ -1407,14 +1412,20 @@ mod fetch_from_target {
         let before_fetch = controller.get_base_branch_data(&project_id).await.unwrap();
         assert!(before_fetch.unwrap().last_fetched_ms.is_none());
 
-        let fetch = controller.fetch_from_target(&project_id).await.unwrap();
+        let fetch = controller
+            .fetch_from_target(&project_id, FetchOptions::default())
+            .await
+            .unwrap();
         assert!(fetch.last_fetched_ms.is_some());
 
         let after_fetch = controller.get_base_branch_data(&project_id).await.unwrap();
         assert!(after_fetch.as_ref().unwrap().last_fetched_ms.is_some());
         assert_eq!(fetch.last_fetched_ms, after_fetch.unwrap().last_fetched_ms);
 
-        let second_fetch = controller.fetch_from_target(&project_id).await.unwrap();
+        let second_fetch = controller
+            .fetch_from_target(&project_id, FetchOptions::default())
+            .await
+            .unwrap();
         assert!(second_fetch.last_fetched_ms.is_some());
         assert_ne!(fetch.last_fetched_ms, second_fetch.last_fetched_ms);
 
@@ -1429,6 +1440,40 @@ mod fetch_from_target {
             after_second_fetch.unwrap().last_fetched_ms
         );
     }
+
+    #[tokio::test]
+    async fn cancelled() {
+        let Test {
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let cancellation = CancellationToken::new();
+        cancellation.cancel();
+
+        assert!(matches!(
+            controller
+                .fetch_from_target(
+                    &project_id,
+                    FetchOptions::default().with_cancellation(cancellation)
+                )
+                .await,
+            Err(ControllerError::Action(
+                errors::FetchFromTargetError::Remote(RemoteError::Cancelled)
+            ))
+        ));
+        assert!(!controller.cancel_fetch(&project_id));
+
+        // a cancelled fetch isn't recorded as a failed one
+        let base_branch = controller.get_base_branch_data(&project_id).await.unwrap();
+        assert!(base_branch.unwrap().last_fetched_ms.is_none());
+    }
 }
 
 mod update_base_branch {
@@ -5441,11 +5486,79 @@ mod update_commit_message {
     }
 }
 
-mod create_virtual_branch_from_branch {
+mod rewrite_branch {
+    use gblib::virtual_branches::RewriteStep;
+
     use super::*;
 
+    struct Commits {
+        one: git::Oid,
+        two: git::Oid,
+        three: git::Oid,
+    }
+
+    async fn setup(
+        repository: &TestProject,
+        project_id: &ProjectId,
+        controller: &Controller,
+    ) -> (branch::BranchId, Commits) {
+        controller
+            .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let branch_id = controller
+            .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+            .await
+            .unwrap();
+
+        let one = {
+            fs::write(repository.path().join("file one.txt"), "one").unwrap();
+            controller
+                .create_commit(project_id, &branch_id, "commit one", None, false)
+                .await
+                .unwrap()
+        };
+
+        let two = {
+            fs::write(repository.path().join("file two.txt"), "two").unwrap();
+            controller
+                .create_commit(project_id, &branch_id, "commit two", None, false)
+                .await
+                .unwrap()
+        };
+
+        let three = {
+            fs::write(repository.path().join("file three.txt"), "three").unwrap();
+            controller
+                .create_commit(project_id, &branch_id, "commit three", None, false)
+                .await
+                .unwrap()
+        };
+
+        (branch_id, Commits { one, two, three })
+    }
+
+    async fn descriptions(
+        project_id: &ProjectId,
+        controller: &Controller,
+        branch_id: &branch::BranchId,
+    ) -> Vec<String> {
+        controller
+            .list_virtual_branches(project_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|b| b.id == *branch_id)
+            .unwrap()
+            .commits
+            .iter()
+            .map(|c| c.description.clone())
+            .collect()
+    }
+
     #[tokio::test]
-    async fn integration() {
+    async fn reorder() {
         let Test {
             repository,
             project_id,
@@ -5453,98 +5566,2893 @@ mod create_virtual_branch_from_branch {
             ..
         } = Test::default();
 
+        let (branch_id, commits) = setup(&repository, &project_id, &controller).await;
+
         controller
-            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .rewrite_branch(
+                &project_id,
+                &branch_id,
+                &[
+                    RewriteStep::Pick {
+                        commit: commits.one,
+                    },
+                    RewriteStep::Pick {
+                        commit: commits.three,
+                    },
+                    RewriteStep::Pick {
+                        commit: commits.two,
+                    },
+                ],
+            )
             .await
             .unwrap();
 
-        let branch_name = {
-            // make a remote branch
+        assert_eq!(
+            descriptions(&project_id, &controller, &branch_id).await,
+            vec!["commit two", "commit three", "commit one"]
+        );
 
-            let branch_id = controller
-                .create_virtual_branch(&project_id, &super::branch::BranchCreateRequest::default())
-                .await
-                .unwrap();
+        let branch = controller
+            .list_virtual_branches(&project_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|b| b.id == branch_id)
+            .unwrap();
+        assert!(branch.files.is_empty());
+        // unchanged prefix is kept as is
+        assert_eq!(branch.commits[2].id, commits.one);
+    }
 
-            std::fs::write(repository.path().join("file.txt"), "first\n").unwrap();
-            controller
-                .create_commit(&project_id, &branch_id, "first", None, false)
-                .await
-                .unwrap();
-            controller
-                .push_virtual_branch(&project_id, &branch_id, false)
-                .await
-                .unwrap();
+    #[tokio::test]
+    async fn drop() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
 
-            let branch = controller
-                .list_virtual_branches(&project_id)
-                .await
-                .unwrap()
-                .into_iter()
-                .find(|branch| branch.id == branch_id)
-                .unwrap();
+        let (branch_id, commits) = setup(&repository, &project_id, &controller).await;
 
-            let name = branch.upstream.unwrap().name;
+        controller
+            .rewrite_branch(
+                &project_id,
+                &branch_id,
+                &[
+                    RewriteStep::Pick {
+                        commit: commits.one,
+                    },
+                    RewriteStep::Drop {
+                        commit: commits.two,
+                    },
+                    RewriteStep::Pick {
+                        commit: commits.three,
+                    },
+                ],
+            )
+            .await
+            .unwrap();
 
-            controller
-                .delete_virtual_branch(&project_id, &branch_id)
-                .await
-                .unwrap();
+        assert_eq!(
+            descriptions(&project_id, &controller, &branch_id).await,
+            vec!["commit three", "commit one"]
+        );
+        assert!(!repository.path().join("file two.txt").exists());
+        assert!(repository.path().join("file three.txt").exists());
+    }
 
-            name
-        };
+    #[tokio::test]
+    async fn squash_fixup_and_reword() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
 
-        // checkout a existing remote branch
-        let branch_id = controller
-            .create_virtual_branch_from_branch(&project_id, &branch_name)
+        let (branch_id, commits) = setup(&repository, &project_id, &controller).await;
+
+        controller
+            .rewrite_branch(
+                &project_id,
+                &branch_id,
+                &[
+                    RewriteStep::Reword {
+                        commit: commits.one,
+                        message: "first".to_string(),
+                    },
+                    RewriteStep::Squash {
+                        commit: commits.two,
+                    },
+                    RewriteStep::Fixup {
+                        commit: commits.three,
+                    },
+                ],
+            )
             .await
             .unwrap();
 
-        {
-            // add a commit
-            std::fs::write(repository.path().join("file.txt"), "first\nsecond").unwrap();
+        assert_eq!(
+            descriptions(&project_id, &controller, &branch_id).await,
+            vec!["first\ncommit two"]
+        );
+        assert!(repository.path().join("file one.txt").exists());
+        assert!(repository.path().join("file two.txt").exists());
+        assert!(repository.path().join("file three.txt").exists());
+    }
 
+    #[tokio::test]
+    async fn incomplete_todo() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        let (branch_id, commits) = setup(&repository, &project_id, &controller).await;
+
+        assert!(matches!(
             controller
-                .create_commit(&project_id, &branch_id, "second", None, false)
+                .rewrite_branch(
+                    &project_id,
+                    &branch_id,
+                    &[
+                        RewriteStep::Pick {
+                            commit: commits.one,
+                        },
+                        RewriteStep::Pick {
+                            commit: commits.two,
+                        },
+                    ],
+                )
                 .await
-                .unwrap();
-        }
+                .unwrap_err(),
+            ControllerError::Action(errors::RewriteBranchError::IncompleteTodo)
+        ));
+    }
 
-        {
-            // meanwhile, there is a new commit on master
-            repository.checkout(&"refs/heads/master".parse().unwrap());
-            std::fs::write(repository.path().join("another.txt"), "").unwrap();
-            repository.commit_all("another");
-            repository.push_branch(&"refs/heads/master".parse().unwrap());
-            repository.checkout(&"refs/heads/gitbutler/integration".parse().unwrap());
-        }
+    #[tokio::test]
+    async fn root_squash() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
 
-        {
-            // merge branch into master
+        let (branch_id, commits) = setup(&repository, &project_id, &controller).await;
+
+        assert!(matches!(
             controller
-                .push_virtual_branch(&project_id, &branch_id, false)
+                .rewrite_branch(
+                    &project_id,
+                    &branch_id,
+                    &[
+                        RewriteStep::Squash {
+                            commit: commits.one,
+                        },
+                        RewriteStep::Pick {
+                            commit: commits.two,
+                        },
+                        RewriteStep::Pick {
+                            commit: commits.three,
+                        },
+                    ],
+                )
                 .await
-                .unwrap();
+                .unwrap_err(),
+            ControllerError::Action(errors::RewriteBranchError::CantSquashRootCommit)
+        ));
+    }
 
-            let branch = controller
-                .list_virtual_branches(&project_id)
+    #[tokio::test]
+    async fn split() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let branch_id = controller
+            .create_virtual_branch(&project_id, &branch::BranchCreateRequest::default())
+            .await
+            .unwrap();
+
+        let commit_oid = {
+            fs::write(repository.path().join("file one.txt"), "one").unwrap();
+            fs::write(repository.path().join("file two.txt"), "two").unwrap();
+            controller
+                .create_commit(&project_id, &branch_id, "commit", None, false)
                 .await
                 .unwrap()
-                .into_iter()
-                .find(|branch| branch.id == branch_id)
-                .unwrap();
+        };
 
-            assert!(branch.commits[0].is_remote);
-            assert!(!branch.commits[0].is_integrated);
-            assert!(branch.commits[1].is_remote);
-            assert!(!branch.commits[1].is_integrated);
+        controller
+            .rewrite_branch(
+                &project_id,
+                &branch_id,
+                &[RewriteStep::Split {
+                    commit: commit_oid,
+                    paths: vec!["file one.txt".into()],
+                    message: "commit one".to_string(),
+                }],
+            )
+            .await
+            .unwrap();
 
-            repository.rebase_and_merge(&branch_name);
-        }
+        assert_eq!(
+            descriptions(&project_id, &controller, &branch_id).await,
+            vec!["commit", "commit one"]
+        );
 
-        {
-            // should mark commits as integrated
-            controller.fetch_from_target(&project_id).await.unwrap();
+        let branch = controller
+            .list_virtual_branches(&project_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|b| b.id == branch_id)
+            .unwrap();
+        assert!(branch.files.is_empty());
+        let split_commit = repository.find_commit(branch.commits[1].id).unwrap();
+        let split_tree = split_commit.tree().unwrap();
+        assert!(split_tree.get_path(path::Path::new("file one.txt")).is_ok());
+        assert!(split_tree
+            .get_path(path::Path::new("file two.txt"))
+            .is_err());
+
+        // splitting off everything leaves an empty commit
+        let commit_oid = branch.commits[0].id;
+        assert!(matches!(
+            controller
+                .rewrite_branch(
+                    &project_id,
+                    &branch_id,
+                    &[
+                        RewriteStep::Pick {
+                            commit: branch.commits[1].id,
+                        },
+                        RewriteStep::Split {
+                            commit: commit_oid,
+                            paths: vec!["file two.txt".into()],
+                            message: "commit two".to_string(),
+                        },
+                    ],
+                )
+                .await
+                .unwrap_err(),
+            ControllerError::Action(errors::RewriteBranchError::EmptySplit(oid)) if oid == commit_oid
+        ));
+    }
+
+    #[tokio::test]
+    async fn conflict() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let branch_id = controller
+            .create_virtual_branch(&project_id, &branch::BranchCreateRequest::default())
+            .await
+            .unwrap();
+
+        let mut commit_oids = vec![];
+        for content in ["one", "two", "three"] {
+            fs::write(repository.path().join("file.txt"), content).unwrap();
+            commit_oids.push(
+                controller
+                    .create_commit(
+                        &project_id,
+                        &branch_id,
+                        &format!("commit {}", content),
+                        None,
+                        false,
+                    )
+                    .await
+                    .unwrap(),
+            );
+        }
+
+        // both of the reordered commits conflict
+        assert_eq!(
+            controller
+                .rewrite_branch(
+                    &project_id,
+                    &branch_id,
+                    &[
+                        RewriteStep::Pick {
+                            commit: commit_oids[0],
+                        },
+                        RewriteStep::Pick {
+                            commit: commit_oids[2],
+                        },
+                        RewriteStep::Pick {
+                            commit: commit_oids[1],
+                        },
+                    ],
+                )
+                .await
+                .unwrap(),
+            None
+        );
+
+        {
+            let branches = controller.list_virtual_branches(&project_id).await.unwrap();
+            assert!(branches[0].conflicted);
+            assert_eq!(
+                descriptions(&project_id, &controller, &branch_id).await,
+                vec!["commit one"]
+            );
+            assert_eq!(
+                fs::read_to_string(repository.path().join("file.txt")).unwrap(),
+                "<<<<<<< ours\none\n=======\nthree\n>>>>>>> theirs\n"
+            );
+        }
+
+        // can't continue before resolving
+        assert!(matches!(
+            controller
+                .continue_rewrite(&project_id, &branch_id)
+                .await
+                .unwrap_err(),
+            ControllerError::Action(errors::RewriteBranchError::Conflict(_))
+        ));
+
+        fs::write(repository.path().join("file.txt"), "three").unwrap();
+        controller.list_virtual_branches(&project_id).await.unwrap();
+        assert_eq!(
+            controller
+                .continue_rewrite(&project_id, &branch_id)
+                .await
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            descriptions(&project_id, &controller, &branch_id).await,
+            vec!["commit three", "commit one"]
+        );
+
+        fs::write(repository.path().join("file.txt"), "two").unwrap();
+        controller.list_virtual_branches(&project_id).await.unwrap();
+        assert!(controller
+            .continue_rewrite(&project_id, &branch_id)
+            .await
+            .unwrap()
+            .is_some());
+
+        let branches = controller.list_virtual_branches(&project_id).await.unwrap();
+        assert!(!branches[0].conflicted);
+        assert!(branches[0].files.is_empty());
+        assert_eq!(
+            descriptions(&project_id, &controller, &branch_id).await,
+            vec!["commit two", "commit three", "commit one"]
+        );
+
+        // nothing left to continue
+        assert!(matches!(
+            controller
+                .continue_rewrite(&project_id, &branch_id)
+                .await
+                .unwrap_err(),
+            ControllerError::Action(errors::RewriteBranchError::NotRewriting)
+        ));
+    }
+
+    #[tokio::test]
+    async fn forcepush_forbidden() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            projects,
+            ..
+        } = Test::default();
+
+        let (branch_id, commits) = setup(&repository, &project_id, &controller).await;
+
+        controller
+            .push_virtual_branch(&project_id, &branch_id, false)
+            .await
+            .unwrap();
+
+        projects
+            .update(&projects::UpdateRequest {
+                id: project_id,
+                ok_with_force_push: Some(false),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            controller
+                .rewrite_branch(
+                    &project_id,
+                    &branch_id,
+                    &[
+                        RewriteStep::Pick {
+                            commit: commits.two,
+                        },
+                        RewriteStep::Pick {
+                            commit: commits.one,
+                        },
+                        RewriteStep::Pick {
+                            commit: commits.three,
+                        },
+                    ],
+                )
+                .await
+                .unwrap_err(),
+            ControllerError::Action(errors::RewriteBranchError::ForcePushNotAllowed(_))
+        ));
+    }
+}
+
+mod move_commit {
+    use super::*;
+
+    async fn commits(
+        project_id: &ProjectId,
+        controller: &Controller,
+        branch_id: &branch::BranchId,
+    ) -> Vec<String> {
+        controller
+            .list_virtual_branches(project_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|b| b.id == *branch_id)
+            .unwrap()
+            .commits
+            .iter()
+            .map(|c| c.description.clone())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn to_other_branch() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let source_branch_id = controller
+            .create_virtual_branch(&project_id, &branch::BranchCreateRequest::default())
+            .await
+            .unwrap();
+
+        let commit_one_oid = {
+            fs::write(repository.path().join("file one.txt"), "one").unwrap();
+            controller
+                .create_commit(&project_id, &source_branch_id, "commit one", None, false)
+                .await
+                .unwrap()
+        };
+
+        {
+            fs::write(repository.path().join("file two.txt"), "two").unwrap();
+            controller
+                .create_commit(&project_id, &source_branch_id, "commit two", None, false)
+                .await
+                .unwrap();
+        };
+
+        let destination_branch_id = controller
+            .create_virtual_branch(&project_id, &branch::BranchCreateRequest::default())
+            .await
+            .unwrap();
+
+        controller
+            .move_commit(
+                &project_id,
+                commit_one_oid,
+                &source_branch_id,
+                &destination_branch_id,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            commits(&project_id, &controller, &source_branch_id).await,
+            vec!["commit two"]
+        );
+        assert_eq!(
+            commits(&project_id, &controller, &destination_branch_id).await,
+            vec!["commit one"]
+        );
+
+        let branches = controller.list_virtual_branches(&project_id).await.unwrap();
+        assert!(branches.iter().all(|b| b.files.is_empty()));
+        assert_eq!(
+            fs::read_to_string(repository.path().join("file one.txt")).unwrap(),
+            "one"
+        );
+        assert_eq!(
+            fs::read_to_string(repository.path().join("file two.txt")).unwrap(),
+            "two"
+        );
+    }
+
+    #[tokio::test]
+    async fn source_conflict() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let source_branch_id = controller
+            .create_virtual_branch(&project_id, &branch::BranchCreateRequest::default())
+            .await
+            .unwrap();
+
+        let commit_one_oid = {
+            fs::write(repository.path().join("file.txt"), "one").unwrap();
+            controller
+                .create_commit(&project_id, &source_branch_id, "commit one", None, false)
+                .await
+                .unwrap()
+        };
+
+        let commit_two_oid = {
+            fs::write(repository.path().join("file.txt"), "two").unwrap();
+            controller
+                .create_commit(&project_id, &source_branch_id, "commit two", None, false)
+                .await
+                .unwrap()
+        };
+
+        let destination_branch_id = controller
+            .create_virtual_branch(&project_id, &branch::BranchCreateRequest::default())
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            controller
+                .move_commit(
+                    &project_id,
+                    commit_one_oid,
+                    &source_branch_id,
+                    &destination_branch_id,
+                )
+                .await,
+            Err(ControllerError::Action(
+                errors::MoveCommitError::SourceBranchConflict(oid)
+            )) if oid == commit_two_oid
+        ));
+
+        // nothing has changed
+        assert_eq!(
+            commits(&project_id, &controller, &source_branch_id).await,
+            vec!["commit two", "commit one"]
+        );
+        assert!(commits(&project_id, &controller, &destination_branch_id)
+            .await
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn commit_not_in_branch() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let branch_id = controller
+            .create_virtual_branch(&project_id, &branch::BranchCreateRequest::default())
+            .await
+            .unwrap();
+
+        let commit_oid = {
+            fs::write(repository.path().join("file.txt"), "content").unwrap();
+            controller
+                .create_commit(&project_id, &branch_id, "commit", None, false)
+                .await
+                .unwrap()
+        };
+
+        let other_branch_id = controller
+            .create_virtual_branch(&project_id, &branch::BranchCreateRequest::default())
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            controller
+                .move_commit(&project_id, commit_oid, &other_branch_id, &branch_id)
+                .await,
+            Err(ControllerError::Action(
+                errors::MoveCommitError::CommitNotFound(oid)
+            )) if oid == commit_oid
+        ));
+    }
+}
+
+mod operations {
+    use super::*;
+
+    #[tokio::test]
+    async fn restore_unapplied_branch() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let branch_id = controller
+            .create_virtual_branch(&project_id, &branch::BranchCreateRequest::default())
+            .await
+            .unwrap();
+
+        fs::write(repository.path().join("file.txt"), "content").unwrap();
+
+        controller
+            .unapply_virtual_branch(&project_id, &branch_id)
+            .await
+            .unwrap();
+        assert!(!repository.path().join("file.txt").exists());
+
+        let operations = controller.list_operations(&project_id).await.unwrap();
+        // only operations that can lose work are recorded
+        assert_eq!(operations.len(), 1);
+        assert_eq!(operations[0].name, "unapply branch");
+
+        controller
+            .restore_operation(&project_id, operations[0].id)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(repository.path().join("file.txt")).unwrap(),
+            "content"
+        );
+        let branches = controller.list_virtual_branches(&project_id).await.unwrap();
+        assert_eq!(branches.len(), 1);
+        assert_eq!(branches[0].id, branch_id);
+        assert!(branches[0].active);
+        assert_eq!(branches[0].files.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn restore_is_undoable() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let branch_id = controller
+            .create_virtual_branch(&project_id, &branch::BranchCreateRequest::default())
+            .await
+            .unwrap();
+
+        fs::write(repository.path().join("file one.txt"), "one").unwrap();
+        let commit_one_oid = controller
+            .create_commit(&project_id, &branch_id, "commit one", None, false)
+            .await
+            .unwrap();
+        fs::write(repository.path().join("file two.txt"), "two").unwrap();
+        controller
+            .create_commit(&project_id, &branch_id, "commit two", None, false)
+            .await
+            .unwrap();
+
+        controller
+            .reset_virtual_branch(&project_id, &branch_id, commit_one_oid)
+            .await
+            .unwrap();
+
+        let operations = controller.list_operations(&project_id).await.unwrap();
+        assert_eq!(operations.len(), 1);
+        assert_eq!(operations[0].name, "reset branch");
+
+        // undo
+        controller
+            .restore_operation(&project_id, operations[0].id)
+            .await
+            .unwrap();
+        let branches = controller.list_virtual_branches(&project_id).await.unwrap();
+        assert_eq!(branches[0].commits.len(), 2);
+        assert!(branches[0].files.is_empty());
+
+        // redo
+        let operations = controller.list_operations(&project_id).await.unwrap();
+        assert!(operations[0].name.starts_with("restore"));
+        controller
+            .restore_operation(&project_id, operations[0].id)
+            .await
+            .unwrap();
+        let branches = controller.list_virtual_branches(&project_id).await.unwrap();
+        assert_eq!(branches[0].commits.len(), 1);
+        assert_eq!(branches[0].files.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn failed_operation_is_not_recorded() {
+        let Test {
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let branch_id = controller
+            .create_virtual_branch(&project_id, &branch::BranchCreateRequest::default())
+            .await
+            .unwrap();
+
+        let commit_oid = git::Oid::from_str("0123456789abcdef0123456789abcdef01234567").unwrap();
+        assert!(matches!(
+            controller
+                .reset_virtual_branch(&project_id, &branch_id, commit_oid)
+                .await,
+            Err(ControllerError::Action(
+                errors::ResetBranchError::CommitNotFoundInBranch(_)
+            ))
+        ));
+
+        assert!(controller
+            .list_operations(&project_id)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn keeps_most_recent_operations() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let branch_id = controller
+            .create_virtual_branch(&project_id, &branch::BranchCreateRequest::default())
+            .await
+            .unwrap();
+
+        fs::write(repository.path().join("file.txt"), "content").unwrap();
+        let commit_oid = controller
+            .create_commit(&project_id, &branch_id, "commit", None, false)
+            .await
+            .unwrap();
+
+        for _ in 0..105 {
+            controller
+                .reset_virtual_branch(&project_id, &branch_id, commit_oid)
+                .await
+                .unwrap();
+        }
+
+        let operations = controller.list_operations(&project_id).await.unwrap();
+        assert_eq!(operations.len(), 100);
+
+        // the oldest kept operation can still be restored
+        controller
+            .restore_operation(&project_id, operations[99].id)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn operation_ids_survive_pruning() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let branch_id = controller
+            .create_virtual_branch(&project_id, &branch::BranchCreateRequest::default())
+            .await
+            .unwrap();
+
+        fs::write(repository.path().join("file.txt"), "content").unwrap();
+        let commit_oid = controller
+            .create_commit(&project_id, &branch_id, "commit", None, false)
+            .await
+            .unwrap();
+
+        for _ in 0..100 {
+            controller
+                .reset_virtual_branch(&project_id, &branch_id, commit_oid)
+                .await
+                .unwrap();
+        }
+        let before = controller.list_operations(&project_id).await.unwrap();
+
+        controller
+            .reset_virtual_branch(&project_id, &branch_id, commit_oid)
+            .await
+            .unwrap();
+        let after = controller.list_operations(&project_id).await.unwrap();
+
+        // the oldest one is dropped, the others keep their ids and names
+        assert_eq!(after.len(), 100);
+        assert_eq!(after[1..], before[..99]);
+        controller
+            .restore_operation(&project_id, before[98].id)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn anchors_working_directory_trees() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let branch_id = controller
+            .create_virtual_branch(&project_id, &branch::BranchCreateRequest::default())
+            .await
+            .unwrap();
+
+        fs::write(repository.path().join("file.txt"), "content").unwrap();
+        controller
+            .unapply_virtual_branch(&project_id, &branch_id)
+            .await
+            .unwrap();
+
+        let operations = controller.list_operations(&project_id).await.unwrap();
+        let reference = repository
+            .references()
+            .into_iter()
+            .find(|reference| {
+                reference.name().map_or(false, |name| {
+                    name.to_string() == "refs/gitbutler-operations"
+                })
+            })
+            .expect("operations are anchored in the project repository");
+        let tree = reference.peel_to_commit().unwrap().tree().unwrap();
+        assert!(tree.get_name(&operations[0].id.to_string()).is_some());
+    }
+
+    #[tokio::test]
+    async fn unknown_operation() {
+        let Test {
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let operation_id = git::Oid::from_str("0123456789abcdef0123456789abcdef01234567").unwrap();
+        assert!(matches!(
+            controller
+                .restore_operation(&project_id, operation_id)
+                .await,
+            Err(ControllerError::Action(
+                errors::RestoreOperationError::OperationNotFound(oid)
+            )) if oid == operation_id
+        ));
+    }
+}
+
+mod line_ownership {
+    use gblib::virtual_branches::branch::Ownership;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn split_hunk_between_branches() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        let lines: Vec<_> = (0_i32..10_i32).map(|i| format!("line {}\n", i)).collect();
+        fs::write(repository.path().join("file.txt"), lines.concat()).unwrap();
+        repository.commit_all("my commit");
+        repository.push();
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let first_branch_id = controller
+            .create_virtual_branch(&project_id, &branch::BranchCreateRequest::default())
+            .await
+            .unwrap();
+
+        // single hunk that adds two lines
+        let mut changed_lines = lines.clone();
+        changed_lines.insert(5, "first\n".to_string());
+        changed_lines.insert(6, "second\n".to_string());
+        fs::write(repository.path().join("file.txt"), changed_lines.concat()).unwrap();
+
+        let second_branch_id = controller
+            .create_virtual_branch(&project_id, &branch::BranchCreateRequest::default())
+            .await
+            .unwrap();
+
+        controller
+            .update_virtual_branch(
+                &project_id,
+                branch::BranchUpdateRequest {
+                    id: second_branch_id,
+                    ownership: Some("file.txt:6-8[+7]".parse::<Ownership>().unwrap()),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let branches = controller.list_virtual_branches(&project_id).await.unwrap();
+        let first_branch = branches.iter().find(|b| b.id == first_branch_id).unwrap();
+        assert_eq!(first_branch.files.len(), 1);
+        assert_eq!(first_branch.files[0].hunks.len(), 1);
+        assert!(first_branch.files[0].hunks[0].diff.contains("+first\n"));
+        assert!(!first_branch.files[0].hunks[0].diff.contains("+second\n"));
+        let second_branch = branches.iter().find(|b| b.id == second_branch_id).unwrap();
+        assert_eq!(second_branch.files.len(), 1);
+        assert_eq!(second_branch.files[0].hunks.len(), 1);
+        assert!(second_branch.files[0].hunks[0].diff.contains("+second\n"));
+        assert!(!second_branch.files[0].hunks[0].diff.contains("+first\n"));
+
+        controller
+            .create_commit(&project_id, &second_branch_id, "second", None, false)
+            .await
+            .unwrap();
+
+        let branches = controller.list_virtual_branches(&project_id).await.unwrap();
+        let second_branch = branches.iter().find(|b| b.id == second_branch_id).unwrap();
+        assert!(second_branch.files.is_empty());
+        assert_eq!(second_branch.commits.len(), 1);
+        let commit_diff = &second_branch.commits[0].files[0].hunks[0].diff;
+        assert!(commit_diff.contains("+second\n"));
+        assert!(!commit_diff.contains("+first\n"));
+
+        // the rest of the hunk is still in the first branch
+        let first_branch = branches.iter().find(|b| b.id == first_branch_id).unwrap();
+        assert_eq!(first_branch.files.len(), 1);
+        assert!(first_branch.files[0].hunks[0].diff.contains("+first\n"));
+
+        assert_eq!(
+            fs::read_to_string(repository.path().join("file.txt")).unwrap(),
+            changed_lines.concat()
+        );
+    }
+
+    #[tokio::test]
+    async fn owned_lines_follow_shifted_hunk() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        let lines: Vec<_> = (0_i32..10_i32).map(|i| format!("line {}\n", i)).collect();
+        fs::write(repository.path().join("file.txt"), lines.concat()).unwrap();
+        repository.commit_all("my commit");
+        repository.push();
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let first_branch_id = controller
+            .create_virtual_branch(&project_id, &branch::BranchCreateRequest::default())
+            .await
+            .unwrap();
+
+        let mut changed_lines = lines.clone();
+        changed_lines.insert(5, "first\n".to_string());
+        changed_lines.insert(6, "second\n".to_string());
+        fs::write(repository.path().join("file.txt"), changed_lines.concat()).unwrap();
+
+        let second_branch_id = controller
+            .create_virtual_branch(&project_id, &branch::BranchCreateRequest::default())
+            .await
+            .unwrap();
+
+        controller
+            .update_virtual_branch(
+                &project_id,
+                branch::BranchUpdateRequest {
+                    id: second_branch_id,
+                    ownership: Some("file.txt:6-8[+7]".parse::<Ownership>().unwrap()),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        controller.list_virtual_branches(&project_id).await.unwrap();
+
+        // lines added above move the hunk down
+        changed_lines.insert(0, "top\n".to_string());
+        fs::write(repository.path().join("file.txt"), changed_lines.concat()).unwrap();
+
+        let branches = controller.list_virtual_branches(&project_id).await.unwrap();
+        let diffs = |branch_id| {
+            branches
+                .iter()
+                .find(|b| b.id == branch_id)
+                .unwrap()
+                .files
+                .iter()
+                .flat_map(|file| file.hunks.iter())
+                .map(|hunk| hunk.diff.as_str())
+                .collect::<Vec<_>>()
+                .concat()
+        };
+        let second_diffs = diffs(second_branch_id);
+        assert!(second_diffs.contains("+second\n"));
+        assert!(!second_diffs.contains("+first\n"));
+        let first_diffs = diffs(first_branch_id);
+        assert!(first_diffs.contains("+first\n"));
+        assert!(!first_diffs.contains("+second\n"));
+
+        let second_branch = branches.iter().find(|b| b.id == second_branch_id).unwrap();
+        assert!(second_branch
+            .ownership
+            .files
+            .iter()
+            .flat_map(|file| file.hunks.iter())
+            .any(|hunk| hunk.lines == vec![branch::Line::Added(8)]));
+    }
+
+    #[tokio::test]
+    async fn commit_hunk_selected_twice() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        let lines: Vec<_> = (0_i32..10_i32).map(|i| format!("line {}\n", i)).collect();
+        fs::write(repository.path().join("file.txt"), lines.concat()).unwrap();
+        repository.commit_all("my commit");
+        repository.push();
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let branch_id = controller
+            .create_virtual_branch(&project_id, &branch::BranchCreateRequest::default())
+            .await
+            .unwrap();
+
+        let mut changed_lines = lines.clone();
+        changed_lines[3] = "updated\n".to_string();
+        fs::write(repository.path().join("file.txt"), changed_lines.concat()).unwrap();
+
+        // both selections of the same hunk are committed together, once
+        controller
+            .create_commit(
+                &project_id,
+                &branch_id,
+                "update",
+                Some(&"file.txt:4-5[-4],4-5[+4]".parse::<Ownership>().unwrap()),
+                false,
+            )
+            .await
+            .unwrap();
+
+        let branch = controller
+            .list_virtual_branches(&project_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|b| b.id == branch_id)
+            .unwrap();
+        assert_eq!(branch.commits.len(), 1);
+        let commit_diff = &branch.commits[0].files[0].hunks[0].diff;
+        assert!(commit_diff.contains("-line 3\n"));
+        assert!(commit_diff.contains("+updated\n"));
+        assert!(branch.files.is_empty());
+    }
+
+    #[tokio::test]
+    async fn commit_selected_lines() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        let lines: Vec<_> = (0_i32..10_i32).map(|i| format!("line {}\n", i)).collect();
+        fs::write(repository.path().join("file.txt"), lines.concat()).unwrap();
+        repository.commit_all("my commit");
+        repository.push();
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let branch_id = controller
+            .create_virtual_branch(&project_id, &branch::BranchCreateRequest::default())
+            .await
+            .unwrap();
+
+        let mut changed_lines = lines.clone();
+        changed_lines[3] = "updated\n".to_string();
+        fs::write(repository.path().join("file.txt"), changed_lines.concat()).unwrap();
+
+        // commit only the removal of the old line
+        controller
+            .create_commit(
+                &project_id,
+                &branch_id,
+                "removal",
+                Some(&"file.txt:4-5[-4]".parse::<Ownership>().unwrap()),
+                false,
+            )
+            .await
+            .unwrap();
+
+        let branch = controller
+            .list_virtual_branches(&project_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|b| b.id == branch_id)
+            .unwrap();
+        assert_eq!(branch.commits.len(), 1);
+        let commit_diff = &branch.commits[0].files[0].hunks[0].diff;
+        assert!(commit_diff.contains("-line 3\n"));
+        assert!(!commit_diff.contains("+updated\n"));
+
+        // the addition is left uncommitted
+        assert_eq!(branch.files.len(), 1);
+        assert!(branch.files[0].hunks[0].diff.contains("+updated\n"));
+    }
+}
+
+mod stacked_branches {
+    use gblib::virtual_branches::RewriteStep;
+
+    use super::*;
+
+    async fn create_stack(
+        repository: &TestProject,
+        project_id: &ProjectId,
+        controller: &Controller,
+    ) -> (branch::BranchId, branch::BranchId) {
+        let parent_id = controller
+            .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+            .await
+            .unwrap();
+        fs::write(repository.path().join("parent.txt"), "parent").unwrap();
+        controller
+            .create_commit(project_id, &parent_id, "parent commit", None, false)
+            .await
+            .unwrap();
+
+        let child_id = controller
+            .create_virtual_branch(
+                project_id,
+                &branch::BranchCreateRequest {
+                    parent: Some(parent_id),
+                    selected_for_changes: Some(true),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        fs::write(repository.path().join("child.txt"), "child").unwrap();
+        controller
+            .create_commit(project_id, &child_id, "child commit", None, false)
+            .await
+            .unwrap();
+
+        (parent_id, child_id)
+    }
+
+    #[tokio::test]
+    async fn based_on_parent() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let (parent_id, child_id) = create_stack(&repository, &project_id, &controller).await;
+
+        let branches = controller.list_virtual_branches(&project_id).await.unwrap();
+        let parent = branches.iter().find(|b| b.id == parent_id).unwrap();
+        let child = branches.iter().find(|b| b.id == child_id).unwrap();
+
+        assert_eq!(child.parent, Some(parent_id));
+        assert!(parent.files.is_empty());
+        assert!(child.files.is_empty());
+        assert_eq!(parent.commits.len(), 1);
+        assert_eq!(parent.commits[0].description, "parent commit");
+        // only commits on top of the parent belong to the child
+        assert_eq!(child.commits.len(), 1);
+        assert_eq!(child.commits[0].description, "child commit");
+        assert_eq!(child.commits[0].parent_ids, vec![parent.head]);
+    }
+
+    #[tokio::test]
+    async fn parent_not_found() {
+        let Test {
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            controller
+                .create_virtual_branch(
+                    &project_id,
+                    &branch::BranchCreateRequest {
+                        parent: Some(branch::BranchId::generate()),
+                        ..Default::default()
+                    },
+                )
+                .await,
+            Err(ControllerError::Action(
+                errors::CreateVirtualBranchError::ParentNotFound(_)
+            ))
+        ));
+    }
+
+    #[tokio::test]
+    async fn push_stack() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let (parent_id, child_id) = create_stack(&repository, &project_id, &controller).await;
+
+        controller
+            .push_virtual_branch(&project_id, &child_id, false)
+            .await
+            .unwrap();
+
+        let branches = controller.list_virtual_branches(&project_id).await.unwrap();
+        let parent = branches.iter().find(|b| b.id == parent_id).unwrap();
+        let child = branches.iter().find(|b| b.id == child_id).unwrap();
+        assert!(parent.upstream.is_some());
+        assert!(child.upstream.is_some());
+
+        let refnames = repository
+            .references()
+            .into_iter()
+            .filter_map(|reference| reference.name().map(|name| name.to_string()))
+            .collect::<Vec<_>>();
+        assert!(refnames.contains(&parent.upstream.clone().unwrap().name.to_string()));
+        assert!(refnames.contains(&child.upstream.clone().unwrap().name.to_string()));
+    }
+
+    #[tokio::test]
+    async fn update_base_branch_rebases_stack() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        // make sure we have an undiscovered commit in the remote branch
+        {
+            fs::write(repository.path().join("file.txt"), "first").unwrap();
+            let first_commit_oid = repository.commit_all("first");
+            fs::write(repository.path().join("file.txt"), "second").unwrap();
+            repository.commit_all("second");
+            repository.push();
+            repository.reset_hard(Some(first_commit_oid));
+        }
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let (parent_id, child_id) = create_stack(&repository, &project_id, &controller).await;
+
+        controller.update_base_branch(&project_id).await.unwrap();
+
+        let branches = controller.list_virtual_branches(&project_id).await.unwrap();
+        let parent = branches.iter().find(|b| b.id == parent_id).unwrap();
+        let child = branches.iter().find(|b| b.id == child_id).unwrap();
+        assert!(parent.active);
+        assert!(child.active);
+        assert_eq!(parent.commits.len(), 1);
+        assert_eq!(child.commits.len(), 1);
+        assert_eq!(child.commits[0].description, "child commit");
+        assert_eq!(child.commits[0].parent_ids, vec![parent.head]);
+
+        assert_eq!(
+            fs::read_to_string(repository.path().join("file.txt")).unwrap(),
+            "second"
+        );
+        assert_eq!(
+            fs::read_to_string(repository.path().join("parent.txt")).unwrap(),
+            "parent"
+        );
+        assert_eq!(
+            fs::read_to_string(repository.path().join("child.txt")).unwrap(),
+            "child"
+        );
+    }
+
+    // new changes go to the parent branch
+    async fn select_for_changes(
+        project_id: &ProjectId,
+        controller: &Controller,
+        branch_id: branch::BranchId,
+    ) {
+        controller
+            .update_virtual_branch(
+                project_id,
+                branch::BranchUpdateRequest {
+                    id: branch_id,
+                    selected_for_changes: Some(true),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+    }
+
+    // asserts that the child is stacked right on the parent head, with only its own changes
+    async fn assert_restacked(
+        project_id: &ProjectId,
+        controller: &Controller,
+        parent_id: branch::BranchId,
+        child_id: branch::BranchId,
+    ) {
+        let branches = controller.list_virtual_branches(project_id).await.unwrap();
+        let parent = branches.iter().find(|b| b.id == parent_id).unwrap();
+        let child = branches.iter().find(|b| b.id == child_id).unwrap();
+        assert!(child.active);
+        assert!(child.files.is_empty());
+        assert_eq!(child.commits.len(), 1);
+        assert_eq!(child.commits[0].description, "child commit");
+        assert_eq!(child.commits[0].parent_ids, vec![parent.head]);
+    }
+
+    #[tokio::test]
+    async fn commit_on_parent_restacks_child() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let (parent_id, child_id) = create_stack(&repository, &project_id, &controller).await;
+
+        select_for_changes(&project_id, &controller, parent_id).await;
+        fs::write(repository.path().join("parent2.txt"), "parent two").unwrap();
+        controller
+            .create_commit(&project_id, &parent_id, "second parent commit", None, false)
+            .await
+            .unwrap();
+
+        let branches = controller.list_virtual_branches(&project_id).await.unwrap();
+        let parent = branches.iter().find(|b| b.id == parent_id).unwrap();
+        assert_eq!(parent.commits.len(), 2);
+        assert!(parent.files.is_empty());
+        assert_restacked(&project_id, &controller, parent_id, child_id).await;
+    }
+
+    #[tokio::test]
+    async fn amend_parent_restacks_child() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let (parent_id, child_id) = create_stack(&repository, &project_id, &controller).await;
+
+        select_for_changes(&project_id, &controller, parent_id).await;
+        fs::write(repository.path().join("parent.txt"), "parent amended").unwrap();
+        controller
+            .amend(&project_id, &parent_id, &"parent.txt:1-2".parse().unwrap())
+            .await
+            .unwrap();
+
+        let branches = controller.list_virtual_branches(&project_id).await.unwrap();
+        let parent = branches.iter().find(|b| b.id == parent_id).unwrap();
+        assert_eq!(parent.commits.len(), 1);
+        assert!(parent.files.is_empty());
+        assert_restacked(&project_id, &controller, parent_id, child_id).await;
+    }
+
+    #[tokio::test]
+    async fn reset_parent_restacks_child() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let (parent_id, child_id) = create_stack(&repository, &project_id, &controller).await;
+
+        select_for_changes(&project_id, &controller, parent_id).await;
+        let first_parent_commit = controller
+            .list_virtual_branches(&project_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|b| b.id == parent_id)
+            .unwrap()
+            .head;
+        fs::write(repository.path().join("parent2.txt"), "parent two").unwrap();
+        controller
+            .create_commit(&project_id, &parent_id, "second parent commit", None, false)
+            .await
+            .unwrap();
+
+        controller
+            .reset_virtual_branch(&project_id, &parent_id, first_parent_commit)
+            .await
+            .unwrap();
+
+        let branches = controller.list_virtual_branches(&project_id).await.unwrap();
+        let parent = branches.iter().find(|b| b.id == parent_id).unwrap();
+        assert_eq!(parent.head, first_parent_commit);
+        assert_eq!(parent.files.len(), 1);
+        assert_eq!(parent.files[0].path.display().to_string(), "parent2.txt");
+        assert_restacked(&project_id, &controller, parent_id, child_id).await;
+    }
+
+    #[tokio::test]
+    async fn squash_parent_restacks_child() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let (parent_id, child_id) = create_stack(&repository, &project_id, &controller).await;
+
+        select_for_changes(&project_id, &controller, parent_id).await;
+        fs::write(repository.path().join("parent2.txt"), "parent two").unwrap();
+        let second_parent_commit = controller
+            .create_commit(&project_id, &parent_id, "second parent commit", None, false)
+            .await
+            .unwrap();
+
+        controller
+            .squash(&project_id, &parent_id, second_parent_commit)
+            .await
+            .unwrap();
+
+        let branches = controller.list_virtual_branches(&project_id).await.unwrap();
+        let parent = branches.iter().find(|b| b.id == parent_id).unwrap();
+        assert_eq!(parent.commits.len(), 1);
+        assert_eq!(
+            parent.commits[0].description,
+            "parent commit\nsecond parent commit"
+        );
+        assert_restacked(&project_id, &controller, parent_id, child_id).await;
+    }
+
+    #[tokio::test]
+    async fn rewrite_parent_restacks_child() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let (parent_id, child_id) = create_stack(&repository, &project_id, &controller).await;
+        let branches = controller.list_virtual_branches(&project_id).await.unwrap();
+        let parent_commit = branches.iter().find(|b| b.id == parent_id).unwrap().head;
+        let child_commit = branches.iter().find(|b| b.id == child_id).unwrap().head;
+
+        // only the commits on top of the parent are rewritten
+        controller
+            .rewrite_branch(
+                &project_id,
+                &child_id,
+                &[RewriteStep::Pick {
+                    commit: child_commit,
+                }],
+            )
+            .await
+            .unwrap();
+
+        controller
+            .rewrite_branch(
+                &project_id,
+                &parent_id,
+                &[RewriteStep::Reword {
+                    commit: parent_commit,
+                    message: "reworded parent commit".to_string(),
+                }],
+            )
+            .await
+            .unwrap();
+
+        let branches = controller.list_virtual_branches(&project_id).await.unwrap();
+        let parent = branches.iter().find(|b| b.id == parent_id).unwrap();
+        assert_eq!(parent.commits.len(), 1);
+        assert_eq!(parent.commits[0].description, "reworded parent commit");
+        assert_restacked(&project_id, &controller, parent_id, child_id).await;
+    }
+
+    #[tokio::test]
+    async fn move_commit_from_parent_restacks_child() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let (parent_id, child_id) = create_stack(&repository, &project_id, &controller).await;
+
+        select_for_changes(&project_id, &controller, parent_id).await;
+        fs::write(repository.path().join("parent2.txt"), "parent two").unwrap();
+        let second_parent_commit = controller
+            .create_commit(&project_id, &parent_id, "second parent commit", None, false)
+            .await
+            .unwrap();
+
+        let other_id = controller
+            .create_virtual_branch(&project_id, &branch::BranchCreateRequest::default())
+            .await
+            .unwrap();
+        controller
+            .move_commit(&project_id, second_parent_commit, &parent_id, &other_id)
+            .await
+            .unwrap();
+
+        let branches = controller.list_virtual_branches(&project_id).await.unwrap();
+        let parent = branches.iter().find(|b| b.id == parent_id).unwrap();
+        let other = branches.iter().find(|b| b.id == other_id).unwrap();
+        assert_eq!(parent.commits.len(), 1);
+        assert_eq!(parent.commits[0].description, "parent commit");
+        assert_eq!(other.commits.len(), 1);
+        assert_eq!(other.commits[0].description, "second parent commit");
+        assert_restacked(&project_id, &controller, parent_id, child_id).await;
+    }
+
+    #[tokio::test]
+    async fn move_commit_from_child_to_parent() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let (parent_id, child_id) = create_stack(&repository, &project_id, &controller).await;
+        let child_commit = controller
+            .list_virtual_branches(&project_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|b| b.id == child_id)
+            .unwrap()
+            .head;
+
+        controller
+            .move_commit(&project_id, child_commit, &child_id, &parent_id)
+            .await
+            .unwrap();
+
+        let branches = controller.list_virtual_branches(&project_id).await.unwrap();
+        let parent = branches.iter().find(|b| b.id == parent_id).unwrap();
+        let child = branches.iter().find(|b| b.id == child_id).unwrap();
+        assert_eq!(parent.commits.len(), 2);
+        assert_eq!(parent.commits[0].description, "child commit");
+        assert!(child.active);
+        assert!(child.commits.is_empty());
+        assert_eq!(child.head, parent.head);
+    }
+
+    #[tokio::test]
+    async fn reset_child_stays_on_parent() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let base_sha = controller
+            .get_base_branch_data(&project_id)
+            .await
+            .unwrap()
+            .unwrap()
+            .base_sha;
+        let (_, child_id) = create_stack(&repository, &project_id, &controller).await;
+
+        // the parent commit is not part of the child
+        assert!(matches!(
+            controller
+                .reset_virtual_branch(&project_id, &child_id, base_sha)
+                .await,
+            Err(ControllerError::Action(
+                errors::ResetBranchError::CommitNotFoundInBranch(oid)
+            )) if oid == base_sha
+        ));
+    }
+
+    #[tokio::test]
+    async fn pushed_child_is_merged_instead_of_rebased() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            projects,
+            ..
+        } = Test::default();
+
+        projects
+            .update(&projects::UpdateRequest {
+                id: project_id,
+                ok_with_force_push: Some(false),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let (parent_id, child_id) = create_stack(&repository, &project_id, &controller).await;
+        let old_child_head = controller
+            .list_virtual_branches(&project_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|b| b.id == child_id)
+            .unwrap()
+            .head;
+
+        // pushes the parent as well
+        controller
+            .push_virtual_branch(&project_id, &child_id, false)
+            .await
+            .unwrap();
+
+        select_for_changes(&project_id, &controller, parent_id).await;
+        fs::write(repository.path().join("parent2.txt"), "parent two").unwrap();
+        controller
+            .create_commit(&project_id, &parent_id, "second parent commit", None, false)
+            .await
+            .unwrap();
+
+        let branches = controller.list_virtual_branches(&project_id).await.unwrap();
+        let parent = branches.iter().find(|b| b.id == parent_id).unwrap();
+        let child = branches.iter().find(|b| b.id == child_id).unwrap();
+        assert!(child.files.is_empty());
+        assert!(!child.requires_force);
+        assert_eq!(child.commits.len(), 2);
+        assert_eq!(
+            child.commits[0].parent_ids,
+            vec![old_child_head, parent.head]
+        );
+        assert_eq!(child.commits[1].id, old_child_head);
+    }
+
+    #[tokio::test]
+    async fn integrated_parent_unstacks_child() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let (parent_id, child_id) = create_stack(&repository, &project_id, &controller).await;
+        controller
+            .push_virtual_branch(&project_id, &parent_id, false)
+            .await
+            .unwrap();
+
+        let parent_upstream = controller
+            .list_virtual_branches(&project_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|b| b.id == parent_id)
+            .unwrap()
+            .upstream
+            .unwrap();
+        repository.rebase_and_merge(&parent_upstream.name);
+        repository.fetch();
+
+        controller.update_base_branch(&project_id).await.unwrap();
+
+        let base_sha = controller
+            .get_base_branch_data(&project_id)
+            .await
+            .unwrap()
+            .unwrap()
+            .base_sha;
+        let branches = controller.list_virtual_branches(&project_id).await.unwrap();
+        assert_eq!(branches.len(), 1);
+        let child = &branches[0];
+        assert_eq!(child.id, child_id);
+        assert!(child.active);
+        assert_eq!(child.parent, None);
+        assert!(child.files.is_empty());
+        assert_eq!(child.commits.len(), 1);
+        assert_eq!(child.commits[0].description, "child commit");
+        assert_eq!(child.commits[0].parent_ids, vec![base_sha]);
+    }
+
+    #[tokio::test]
+    async fn unapply_parent() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let (parent_id, child_id) = create_stack(&repository, &project_id, &controller).await;
+
+        assert!(matches!(
+            controller
+                .unapply_virtual_branch(&project_id, &parent_id)
+                .await,
+            Err(ControllerError::Action(
+                errors::UnapplyBranchError::HasStackedBranches(id)
+            )) if id == child_id
+        ));
+
+        controller
+            .unapply_virtual_branch(&project_id, &child_id)
+            .await
+            .unwrap();
+        controller
+            .unapply_virtual_branch(&project_id, &parent_id)
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            controller.apply_virtual_branch(&project_id, &child_id).await,
+            Err(ControllerError::Action(
+                errors::ApplyBranchError::ParentNotApplied(id)
+            )) if id == parent_id
+        ));
+
+        controller
+            .apply_virtual_branch(&project_id, &parent_id)
+            .await
+            .unwrap();
+        controller
+            .apply_virtual_branch(&project_id, &child_id)
+            .await
+            .unwrap();
+        assert_restacked(&project_id, &controller, parent_id, child_id).await;
+    }
+
+    #[tokio::test]
+    async fn delete_parent() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let (parent_id, child_id) = create_stack(&repository, &project_id, &controller).await;
+
+        assert!(matches!(
+            controller.delete_virtual_branch(&project_id, &parent_id).await,
+            Err(ControllerError::Action(errors::DeleteBranchError::UnapplyBranch(
+                errors::UnapplyBranchError::HasStackedBranches(id)
+            ))) if id == child_id
+        ));
+
+        controller
+            .unapply_virtual_branch(&project_id, &child_id)
+            .await
+            .unwrap();
+        controller
+            .delete_virtual_branch(&project_id, &parent_id)
+            .await
+            .unwrap();
+
+        // the child is explicitly based on the target now, keeping the parent commit
+        let branches = controller.list_virtual_branches(&project_id).await.unwrap();
+        assert_eq!(branches.len(), 1);
+        assert_eq!(branches[0].id, child_id);
+        assert_eq!(branches[0].parent, None);
+
+        controller
+            .apply_virtual_branch(&project_id, &child_id)
+            .await
+            .unwrap();
+        let branches = controller.list_virtual_branches(&project_id).await.unwrap();
+        assert_eq!(branches[0].commits.len(), 2);
+        assert_eq!(branches[0].commits[0].description, "child commit");
+        assert_eq!(branches[0].commits[1].description, "parent commit");
+    }
+}
+
+mod conflicts {
+    use gblib::project_repository::conflicts::{Conflict, Resolution, Side};
+
+    use super::*;
+
+    // cherry picks a commit that conflicts with the working directory, returns the
+    // branch and its head before the conflict
+    async fn conflict(
+        repository: &TestProject,
+        project_id: &ProjectId,
+        controller: &Controller,
+    ) -> (branch::BranchId, git::Oid) {
+        controller
+            .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let branch_id = controller
+            .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+            .await
+            .unwrap();
+
+        let commit_one = {
+            fs::write(repository.path().join("file.txt"), "content").unwrap();
+            controller
+                .create_commit(project_id, &branch_id, "commit one", None, false)
+                .await
+                .unwrap()
+        };
+
+        let commit_two = {
+            fs::write(repository.path().join("file_two.txt"), "content two\n").unwrap();
+            controller
+                .create_commit(project_id, &branch_id, "commit two", None, false)
+                .await
+                .unwrap()
+        };
+
+        controller
+            .reset_virtual_branch(project_id, &branch_id, commit_one)
+            .await
+            .unwrap();
+        repository.reset_hard(None);
+
+        fs::write(repository.path().join("file_two.txt"), "conflict\n").unwrap();
+
+        assert!(controller
+            .cherry_pick(project_id, &branch_id, commit_two)
+            .await
+            .unwrap()
+            .is_none());
+
+        (branch_id, commit_one)
+    }
+
+    #[tokio::test]
+    async fn list() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        conflict(&repository, &project_id, &controller).await;
+
+        assert_eq!(
+            controller.list_conflicts(&project_id).unwrap(),
+            vec![Conflict {
+                path: "file_two.txt".to_string(),
+                base: None,
+                ours: Some("conflict\n".to_string()),
+                theirs: Some("content two\n".to_string()),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn resolve_with_side_and_finalize() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        let (branch_id, commit_one) = conflict(&repository, &project_id, &controller).await;
+
+        assert!(matches!(
+            controller
+                .finalize_merge(&project_id, &branch_id, "merge")
+                .await,
+            Err(ControllerError::Action(
+                errors::FinalizeMergeError::Conflict(_)
+            ))
+        ));
+
+        controller
+            .resolve_conflict(&project_id, "file_two.txt", &Resolution::Side(Side::Theirs))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(repository.path().join("file_two.txt")).unwrap(),
+            "content two\n"
+        );
+        assert!(controller.list_conflicts(&project_id).unwrap().is_empty());
+
+        let merge_oid = controller
+            .finalize_merge(&project_id, &branch_id, "merge")
+            .await
+            .unwrap();
+
+        let commit = repository.find_commit(merge_oid).unwrap();
+        assert_eq!(commit.parent_count(), 2);
+        assert_eq!(commit.parent(0).unwrap().id(), commit_one);
+
+        let branches = controller.list_virtual_branches(&project_id).await.unwrap();
+        assert_eq!(branches.len(), 1);
+        assert!(!branches[0].conflicted);
+        assert_eq!(branches[0].commits[0].id, merge_oid);
+    }
+
+    #[tokio::test]
+    async fn resolve_with_hunks() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        conflict(&repository, &project_id, &controller).await;
+
+        controller
+            .resolve_conflict(
+                &project_id,
+                "file_two.txt",
+                &Resolution::Hunks(vec![Side::Ours]),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(repository.path().join("file_two.txt")).unwrap(),
+            "conflict\n"
+        );
+        assert!(controller.list_conflicts(&project_id).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn resolve_with_content() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        conflict(&repository, &project_id, &controller).await;
+
+        controller
+            .resolve_conflict(
+                &project_id,
+                "file_two.txt",
+                &Resolution::Content("resolved\n".to_string()),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(repository.path().join("file_two.txt")).unwrap(),
+            "resolved\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn resolve_not_conflicting() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        conflict(&repository, &project_id, &controller).await;
+
+        assert!(matches!(
+            controller
+                .resolve_conflict(&project_id, "file.txt", &Resolution::Side(Side::Ours),)
+                .await,
+            Err(ControllerError::Action(
+                errors::ResolveConflictError::NotConflicting(_)
+            ))
+        ));
+    }
+
+    #[tokio::test]
+    async fn finalize_not_resolving() {
+        let Test {
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let branch_id = controller
+            .create_virtual_branch(&project_id, &branch::BranchCreateRequest::default())
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            controller
+                .finalize_merge(&project_id, &branch_id, "merge")
+                .await,
+            Err(ControllerError::Action(
+                errors::FinalizeMergeError::NotResolving
+            ))
+        ));
+    }
+}
+
+mod renames {
+    use gblib::git::diff::ChangeType;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn rename_is_a_single_file() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        let lines: Vec<_> = (0_i32..10_i32).map(|i| format!("line {}\n", i)).collect();
+        fs::write(repository.path().join("file.txt"), lines.concat()).unwrap();
+        repository.commit_all("my commit");
+        repository.push();
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let branch_id = controller
+            .create_virtual_branch(&project_id, &branch::BranchCreateRequest::default())
+            .await
+            .unwrap();
+
+        fs::rename(
+            repository.path().join("file.txt"),
+            repository.path().join("renamed.txt"),
+        )
+        .unwrap();
+
+        let branches = controller.list_virtual_branches(&project_id).await.unwrap();
+        assert_eq!(branches.len(), 1);
+        assert_eq!(branches[0].files.len(), 1);
+        let file = &branches[0].files[0];
+        assert_eq!(file.path, path::PathBuf::from("renamed.txt"));
+        assert_eq!(file.old_path, Some(path::PathBuf::from("file.txt")));
+        assert_eq!(file.hunks[0].change_type, ChangeType::Renamed);
+
+        let commit_oid = controller
+            .create_commit(&project_id, &branch_id, "rename", None, false)
+            .await
+            .unwrap();
+
+        let tree = repository.find_commit(commit_oid).unwrap().tree().unwrap();
+        assert!(tree.get_path(path::Path::new("renamed.txt")).is_ok());
+        assert!(tree.get_path(path::Path::new("file.txt")).is_err());
+
+        let branches = controller.list_virtual_branches(&project_id).await.unwrap();
+        assert!(branches[0].files.is_empty());
+    }
+
+    #[tokio::test]
+    async fn rename_with_changes() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        let lines: Vec<_> = (0_i32..10_i32).map(|i| format!("line {}\n", i)).collect();
+        fs::write(repository.path().join("file.txt"), lines.concat()).unwrap();
+        repository.commit_all("my commit");
+        repository.push();
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let branch_id = controller
+            .create_virtual_branch(&project_id, &branch::BranchCreateRequest::default())
+            .await
+            .unwrap();
+
+        let mut changed_lines = lines.clone();
+        changed_lines[5] = "changed\n".to_string();
+        fs::remove_file(repository.path().join("file.txt")).unwrap();
+        fs::write(
+            repository.path().join("renamed.txt"),
+            changed_lines.concat(),
+        )
+        .unwrap();
+
+        let branches = controller.list_virtual_branches(&project_id).await.unwrap();
+        assert_eq!(branches[0].files.len(), 1);
+        let file = &branches[0].files[0];
+        assert_eq!(file.old_path, Some(path::PathBuf::from("file.txt")));
+        assert!(file.hunks[0].diff.contains("+changed\n"));
+
+        let commit_oid = controller
+            .create_commit(&project_id, &branch_id, "rename", None, false)
+            .await
+            .unwrap();
+
+        let commit = repository.find_commit(commit_oid).unwrap();
+        let tree = commit.tree().unwrap();
+        assert!(tree.get_path(path::Path::new("file.txt")).is_err());
+        let entry = tree.get_path(path::Path::new("renamed.txt")).unwrap();
+        assert_eq!(
+            entry.id(),
+            git2::Oid::hash_object(git2::ObjectType::Blob, changed_lines.concat().as_bytes())
+                .unwrap()
+                .into()
+        );
+    }
+
+    #[tokio::test]
+    async fn rename_stays_with_owner_of_original() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        let lines: Vec<_> = (0_i32..10_i32).map(|i| format!("line {}\n", i)).collect();
+        fs::write(repository.path().join("file.txt"), lines.concat()).unwrap();
+        repository.commit_all("my commit");
+        repository.push();
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let first_branch_id = controller
+            .create_virtual_branch(&project_id, &branch::BranchCreateRequest::default())
+            .await
+            .unwrap();
+
+        let mut changed_lines = lines.clone();
+        changed_lines[5] = "changed\n".to_string();
+        fs::write(repository.path().join("file.txt"), changed_lines.concat()).unwrap();
+
+        let branches = controller.list_virtual_branches(&project_id).await.unwrap();
+        let hunk_id = branches[0].files[0].hunks[0].id.clone();
+
+        let second_branch_id = controller
+            .create_virtual_branch(&project_id, &branch::BranchCreateRequest::default())
+            .await
+            .unwrap();
+        controller
+            .update_virtual_branch(
+                &project_id,
+                branch::BranchUpdateRequest {
+                    id: second_branch_id,
+                    ownership: Some(format!("file.txt:{}", hunk_id).parse().unwrap()),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        fs::rename(
+            repository.path().join("file.txt"),
+            repository.path().join("renamed.txt"),
+        )
+        .unwrap();
+
+        let branches = controller.list_virtual_branches(&project_id).await.unwrap();
+        let first_branch = branches.iter().find(|b| b.id == first_branch_id).unwrap();
+        assert!(first_branch.files.is_empty());
+        let second_branch = branches.iter().find(|b| b.id == second_branch_id).unwrap();
+        assert_eq!(second_branch.files.len(), 1);
+        assert_eq!(
+            second_branch.files[0].path,
+            path::PathBuf::from("renamed.txt")
+        );
+        assert_eq!(
+            second_branch.files[0].old_path,
+            Some(path::PathBuf::from("file.txt"))
+        );
+    }
+
+    #[tokio::test]
+    async fn renamed_file_is_taken_from_other_branches() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        let lines: Vec<_> = (0_i32..10_i32).map(|i| format!("line {}\n", i)).collect();
+        fs::write(repository.path().join("file.txt"), lines.concat()).unwrap();
+        repository.commit_all("my commit");
+        repository.push();
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let first_branch_id = controller
+            .create_virtual_branch(&project_id, &branch::BranchCreateRequest::default())
+            .await
+            .unwrap();
+
+        let mut changed_lines = lines.clone();
+        changed_lines[5] = "changed\n".to_string();
+        fs::write(repository.path().join("file.txt"), changed_lines.concat()).unwrap();
+
+        let branches = controller.list_virtual_branches(&project_id).await.unwrap();
+        let hunk_id = branches[0].files[0].hunks[0].id.clone();
+
+        let second_branch_id = controller
+            .create_virtual_branch(&project_id, &branch::BranchCreateRequest::default())
+            .await
+            .unwrap();
+        controller
+            .update_virtual_branch(
+                &project_id,
+                branch::BranchUpdateRequest {
+                    id: second_branch_id,
+                    ownership: Some(format!("file.txt:{}", hunk_id).parse().unwrap()),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        // the new file shows up in the first branch before the original is removed
+        fs::copy(
+            repository.path().join("file.txt"),
+            repository.path().join("renamed.txt"),
+        )
+        .unwrap();
+        let branches = controller.list_virtual_branches(&project_id).await.unwrap();
+        let copy = branches
+            .iter()
+            .flat_map(|b| b.files.iter())
+            .find(|f| f.path == path::PathBuf::from("renamed.txt"))
+            .unwrap();
+        let copy_ownership = copy
+            .hunks
+            .iter()
+            .map(|hunk| hunk.id.clone())
+            .collect::<Vec<_>>()
+            .join(",");
+        controller
+            .update_virtual_branch(
+                &project_id,
+                branch::BranchUpdateRequest {
+                    id: first_branch_id,
+                    ownership: Some(format!("renamed.txt:{}", copy_ownership).parse().unwrap()),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        fs::remove_file(repository.path().join("file.txt")).unwrap();
+
+        let branches = controller.list_virtual_branches(&project_id).await.unwrap();
+        let first_branch = branches.iter().find(|b| b.id == first_branch_id).unwrap();
+        assert!(first_branch.files.is_empty());
+        assert!(first_branch.ownership.files.is_empty());
+        let second_branch = branches.iter().find(|b| b.id == second_branch_id).unwrap();
+        assert_eq!(second_branch.files.len(), 1);
+        assert_eq!(
+            second_branch.files[0].path,
+            path::PathBuf::from("renamed.txt")
+        );
+    }
+}
+
+mod create_pull_request {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net, thread,
+    };
+
+    use gblib::forge;
+
+    use super::*;
+
+    struct Request {
+        method: String,
+        path: String,
+        body: String,
+    }
+
+    // serves canned github responses, one connection per request
+    fn serve(responses: Vec<&'static str>) -> (String, thread::JoinHandle<Vec<Request>>) {
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let mut requests = vec![];
+            for body in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut parts = request_line.split_whitespace();
+                let method = parts.next().unwrap().to_string();
+                let path = parts.next().unwrap().to_string();
+
+                let mut content_length = 0;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    let header = header.trim_end();
+                    if header.is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = header.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut request_body = vec![0; content_length];
+                reader.read_exact(&mut request_body).unwrap();
+                requests.push(Request {
+                    method,
+                    path,
+                    body: String::from_utf8(request_body).unwrap(),
+                });
+
+                let mut stream = stream;
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+                .unwrap();
+            }
+            requests
+        });
+        (url, handle)
+    }
+
+    const PULL_REQUEST: &str = r#"{"number":12,"html_url":"https://github.com/gitbutlerapp/gitbutler/pull/12","state":"open","draft":false,"merged_at":null}"#;
+
+    fn login(users: &users::Controller) {
+        users
+            .set_user(&users::User {
+                github_access_token: Some("token".to_string()),
+                ..Default::default()
+            })
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn create_and_update() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            users,
+            ..
+        } = Test::default();
+
+        login(&users);
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let branch_id = controller
+            .create_virtual_branch(&project_id, &branch::BranchCreateRequest::default())
+            .await
+            .unwrap();
+        controller
+            .update_virtual_branch(
+                &project_id,
+                branch::BranchUpdateRequest {
+                    id: branch_id,
+                    notes: Some("my notes".to_string()),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        fs::write(repository.path().join("file.txt"), "content").unwrap();
+        controller
+            .create_commit(&project_id, &branch_id, "commit", None, false)
+            .await
+            .unwrap();
+        controller
+            .push_virtual_branch(&project_id, &branch_id, false)
+            .await
+            .unwrap();
+
+        let (url, server) = serve(vec!["[]", PULL_REQUEST]);
+        git::Repository::open(repository.path())
+            .unwrap()
+            .config()
+            .unwrap()
+            .set_str("gitbutler.forgeApiUrl", &url)
+            .unwrap();
+
+        let pull_request = controller
+            .create_pull_request(&project_id, &branch_id)
+            .await
+            .unwrap();
+        assert_eq!(pull_request.number, 12);
+        assert_eq!(pull_request.status, forge::Status::Open);
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests[0].method, "GET");
+        assert_eq!(requests[1].method, "POST");
+        assert!(requests[1].path.ends_with("/pulls"));
+        assert!(requests[1].body.contains(r#""title":"Virtual branch""#));
+        assert!(requests[1].body.contains(r#""body":"my notes""#));
+        assert!(requests[1].body.contains(r#""head":"Virtual-branch""#));
+        assert!(requests[1].body.contains(r#""base":"master""#));
+
+        let branches = controller.list_virtual_branches(&project_id).await.unwrap();
+        assert_eq!(branches[0].pr_number, Some(12));
+        assert_eq!(branches[0].pr_status, Some(forge::Status::Open));
+
+        // the pull request is updated, once it's created
+        let (url, server) = serve(vec![PULL_REQUEST, PULL_REQUEST]);
+        git::Repository::open(repository.path())
+            .unwrap()
+            .config()
+            .unwrap()
+            .set_str("gitbutler.forgeApiUrl", &url)
+            .unwrap();
+
+        controller
+            .create_pull_request(&project_id, &branch_id)
+            .await
+            .unwrap();
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].method, "GET");
+        assert!(requests[0].path.ends_with("/pulls/12"));
+        assert_eq!(requests[1].method, "PATCH");
+        assert!(requests[1].path.ends_with("/pulls/12"));
+    }
+
+    #[tokio::test]
+    async fn refresh() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            users,
+            ..
+        } = Test::default();
+
+        login(&users);
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let branch_id = controller
+            .create_virtual_branch(&project_id, &branch::BranchCreateRequest::default())
+            .await
+            .unwrap();
+        fs::write(repository.path().join("file.txt"), "content").unwrap();
+        controller
+            .create_commit(&project_id, &branch_id, "commit", None, false)
+            .await
+            .unwrap();
+        controller
+            .push_virtual_branch(&project_id, &branch_id, false)
+            .await
+            .unwrap();
+
+        // nothing to refresh before the pull request is created
+        assert_eq!(
+            controller
+                .refresh_pull_request(&project_id, &branch_id)
+                .await
+                .unwrap(),
+            None
+        );
+
+        let (url, server) = serve(vec!["[]", PULL_REQUEST]);
+        git::Repository::open(repository.path())
+            .unwrap()
+            .config()
+            .unwrap()
+            .set_str("gitbutler.forgeApiUrl", &url)
+            .unwrap();
+        controller
+            .create_pull_request(&project_id, &branch_id)
+            .await
+            .unwrap();
+        server.join().unwrap();
+
+        // the pull request is merged on github
+        let (url, server) = serve(vec![
+            r#"{"number":12,"html_url":"https://github.com/gitbutlerapp/gitbutler/pull/12","state":"closed","draft":false,"merged_at":"2024-01-01T00:00:00Z"}"#,
+        ]);
+        git::Repository::open(repository.path())
+            .unwrap()
+            .config()
+            .unwrap()
+            .set_str("gitbutler.forgeApiUrl", &url)
+            .unwrap();
+
+        let pull_request = controller
+            .refresh_pull_request(&project_id, &branch_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(pull_request.number, 12);
+        assert_eq!(pull_request.status, forge::Status::Merged);
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "GET");
+        assert!(requests[0].path.ends_with("/pulls/12"));
+
+        let branches = controller.list_virtual_branches(&project_id).await.unwrap();
+        assert_eq!(branches[0].pr_number, Some(12));
+        assert_eq!(branches[0].pr_status, Some(forge::Status::Merged));
+    }
+
+    #[tokio::test]
+    async fn not_pushed() {
+        let Test {
+            project_id,
+            controller,
+            users,
+            ..
+        } = Test::default();
+
+        login(&users);
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let branch_id = controller
+            .create_virtual_branch(&project_id, &branch::BranchCreateRequest::default())
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            controller
+                .create_pull_request(&project_id, &branch_id)
+                .await
+                .unwrap_err(),
+            ControllerError::Action(errors::CreatePullRequestError::NotPushed)
+        ));
+    }
+
+    #[tokio::test]
+    async fn not_authenticated() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let branch_id = controller
+            .create_virtual_branch(&project_id, &branch::BranchCreateRequest::default())
+            .await
+            .unwrap();
+
+        fs::write(repository.path().join("file.txt"), "content").unwrap();
+        controller
+            .create_commit(&project_id, &branch_id, "commit", None, false)
+            .await
+            .unwrap();
+        controller
+            .push_virtual_branch(&project_id, &branch_id, false)
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            controller
+                .create_pull_request(&project_id, &branch_id)
+                .await
+                .unwrap_err(),
+            ControllerError::Action(errors::CreatePullRequestError::NotAuthenticated)
+        ));
+    }
+}
+
+mod create_virtual_branch_from_branch {
+    use super::*;
+
+    #[tokio::test]
+    async fn integration() {
+        let Test {
+            repository,
+            project_id,
+            controller,
+            ..
+        } = Test::default();
+
+        controller
+            .set_base_branch(&project_id, &"refs/remotes/origin/master".parse().unwrap())
+            .await
+            .unwrap();
+
+        let branch_name = {
+            // make a remote branch
+
+            let branch_id = controller
+                .create_virtual_branch(&project_id, &super::branch::BranchCreateRequest::default())
+                .await
+                .unwrap();
+
+            std::fs::write(repository.path().join("file.txt"), "first\n").unwrap();
+            controller
+                .create_commit(&project_id, &branch_id, "first", None, false)
+                .await
+                .unwrap();
+            controller
+                .push_virtual_branch(&project_id, &branch_id, false)
+                .await
+                .unwrap();
+
+            let branch = controller
+                .list_virtual_branches(&project_id)
+                .await
+                .unwrap()
+                .into_iter()
+                .find(|branch| branch.id == branch_id)
+                .unwrap();
+
+            let name = branch.upstream.unwrap().name;
+
+            controller
+                .delete_virtual_branch(&project_id, &branch_id)
+                .await
+                .unwrap();
+
+            name
+        };
+
+        // checkout a existing remote branch
+        let branch_id = controller
+            .create_virtual_branch_from_branch(&project_id, &branch_name)
+            .await
+            .unwrap();
+
+        {
+            // add a commit
+            std::fs::write(repository.path().join("file.txt"), "first\nsecond").unwrap();
+
+            controller
+                .create_commit(&project_id, &branch_id, "second", None, false)
+                .await
+                .unwrap();
+        }
+
+        {
+            // meanwhile, there is a new commit on master
+            repository.checkout(&"refs/heads/master".parse().unwrap());
+            std::fs::write(repository.path().join("another.txt"), "").unwrap();
+            repository.commit_all("another");
+            repository.push_branch(&"refs/heads/master".parse().unwrap());
+            repository.checkout(&"refs/heads/gitbutler/integration".parse().unwrap());
+        }
+
+        {
+            // merge branch into master
+            controller
+                .push_virtual_branch(&project_id, &branch_id, false)
+                .await
+                .unwrap();
+
+            let branch = controller
+                .list_virtual_branches(&project_id)
+                .await
+                .unwrap()
+                .into_iter()
+                .find(|branch| branch.id == branch_id)
+                .unwrap();
+
+            assert!(branch.commits[0].is_remote);
+            assert!(!branch.commits[0].is_integrated);
+            assert!(branch.commits[1].is_remote);
+            assert!(!branch.commits[1].is_integrated);
+
+            repository.rebase_and_merge(&branch_name);
+        }
+
+        {
+            // should mark commits as integrated
+            controller
+                .fetch_from_target(&project_id, gitbutler_git::FetchOptions::default())
+                .await
+                .unwrap();
 
             let branch = controller
                 .list_virtual_branches(&project_id)
@@ -0,0 +1,582 @@
+use crate::{linefile::all_lines, ChangeSet, Hunk, LineFile, LineSpan};
+use std::{collections::HashMap, ops::Range};
+
+/// The algorithm used by [`diff`] to compute the changes
+/// between two files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Algorithm {
+    /// Eugene W. Myers' O(ND) difference algorithm, in its
+    /// linear space variant. Produces minimal diffs, and is
+    /// what Git uses by default.
+    #[default]
+    Myers,
+    /// Bram Cohen's patience diff, which anchors the diff on
+    /// lines that are unique to both files before falling back
+    /// to Myers in between them. Produces diffs that tend to
+    /// follow the structure of code more closely (e.g. not
+    /// matching up unrelated braces and blank lines).
+    Patience,
+}
+
+/// Computes the changes from the `old` file to the `new` file,
+/// as a set of [`Hunk`]s against the `old` file.
+///
+/// Lines are compared verbatim; whether or not line endings
+/// are significant is up to the [`LineFile`]s.
+pub fn diff<'a, O, N>(old: &'a O, new: &'a N, algorithm: Algorithm) -> ChangeSet
+where
+    O: LineFile<'a>,
+    N: LineFile<'a>,
+{
+    let old_lines = all_lines(old);
+    let new_lines = all_lines(new);
+
+    // Intern the lines so the algorithms can compare them cheaply.
+    let mut ids = HashMap::new();
+    let mut intern = |line: &'a str| {
+        let next = ids.len();
+        *ids.entry(line).or_insert(next)
+    };
+    let old_ids = old_lines
+        .iter()
+        .map(|line| intern(line))
+        .collect::<Vec<_>>();
+    let new_ids = new_lines
+        .iter()
+        .map(|line| intern(line))
+        .collect::<Vec<_>>();
+
+    let mut matches = Vec::new();
+    let diff = Diff {
+        old: &old_ids,
+        new: &new_ids,
+    };
+    match algorithm {
+        Algorithm::Myers => diff.myers(0..old_ids.len(), 0..new_ids.len(), &mut matches),
+        Algorithm::Patience => diff.patience(0..old_ids.len(), 0..new_ids.len(), &mut matches),
+    }
+
+    // Every gap between two matching lines becomes a hunk.
+    let mut hunks = Vec::new();
+    let (mut old_index, mut new_index) = (0, 0);
+    for (old_match, new_match) in matches
+        .into_iter()
+        .chain(std::iter::once((old_lines.len(), new_lines.len())))
+    {
+        if old_match > old_index || new_match > new_index {
+            let inserted = new_lines[new_index..new_match]
+                .iter()
+                .map(ToString::to_string)
+                .collect();
+            hunks.push(if old_match > old_index {
+                Hunk::new(LineSpan::new(old_index, old_match - 1), inserted)
+            } else {
+                Hunk::insertion(old_index, inserted)
+            });
+        }
+        (old_index, new_index) = (old_match + 1, new_match + 1);
+    }
+
+    ChangeSet::from_hunks(hunks).expect("diff produced conflicting hunks")
+}
+
+/// Two interned files being diffed. The algorithms push the
+/// `(old, new)` indices of matching lines, in order, to `matches`.
+struct Diff<'a> {
+    old: &'a [usize],
+    new: &'a [usize],
+}
+
+impl Diff<'_> {
+    fn myers(&self, old: Range<usize>, new: Range<usize>, matches: &mut Vec<(usize, usize)>) {
+        let max_d = max_d(old.len(), new.len());
+        let mut vf = V::new(max_d);
+        let mut vb = V::new(max_d);
+        self.conquer(old, new, &mut vf, &mut vb, matches);
+    }
+
+    /// Recursively splits the ranges at their middle snake
+    /// until they no longer have anything in common.
+    fn conquer(
+        &self,
+        mut old: Range<usize>,
+        mut new: Range<usize>,
+        vf: &mut V,
+        vb: &mut V,
+        matches: &mut Vec<(usize, usize)>,
+    ) {
+        let prefix = self.common_prefix(old.clone(), new.clone());
+        matches.extend((0..prefix).map(|i| (old.start + i, new.start + i)));
+        old.start += prefix;
+        new.start += prefix;
+
+        let suffix = self.common_suffix(old.clone(), new.clone());
+        old.end -= suffix;
+        new.end -= suffix;
+
+        if !old.is_empty() && !new.is_empty() {
+            let (x, y) = self.middle_snake(old.clone(), new.clone(), vf, vb);
+            self.conquer(old.start..x, new.start..y, vf, vb, matches);
+            self.conquer(x..old.end, y..new.end, vf, vb, matches);
+        }
+
+        matches.extend((0..suffix).map(|i| (old.end + i, new.end + i)));
+    }
+
+    /// Finds the start of the middle snake of the optimal edit path,
+    /// searching from both ends at once. Both ranges must be non-empty
+    /// and must not share a common prefix or suffix.
+    fn middle_snake(
+        &self,
+        old: Range<usize>,
+        new: Range<usize>,
+        vf: &mut V,
+        vb: &mut V,
+    ) -> (usize, usize) {
+        let (n, m) = (old.len(), new.len());
+        let delta = isize::try_from(n).unwrap() - isize::try_from(m).unwrap();
+        let odd = delta & 1 == 1;
+
+        vf[1] = 0;
+        vb[1] = 0;
+
+        for d in 0..isize::try_from(max_d(n, m)).unwrap() {
+            for k in (-d..=d).rev().step_by(2) {
+                let x = if k == -d || (k != d && vf[k - 1] < vf[k + 1]) {
+                    vf[k + 1]
+                } else {
+                    vf[k - 1] + 1
+                };
+                let y = x.wrapping_add_signed(-k);
+                let (x0, y0) = (x, y);
+                let x = if x < n && y < m {
+                    x + self.common_prefix(old.start + x..old.end, new.start + y..new.end)
+                } else {
+                    x
+                };
+                vf[k] = x;
+
+                if odd && (k - delta).abs() < d && x + vb[-(k - delta)] >= n {
+                    return (old.start + x0, new.start + y0);
+                }
+            }
+
+            for k in (-d..=d).rev().step_by(2) {
+                let x = if k == -d || (k != d && vb[k - 1] < vb[k + 1]) {
+                    vb[k + 1]
+                } else {
+                    vb[k - 1] + 1
+                };
+                let y = x.wrapping_add_signed(-k);
+                let advance = if x < n && y < m {
+                    self.common_suffix(old.start..old.end - x, new.start..new.end - y)
+                } else {
+                    0
+                };
+                let (x, y) = (x + advance, y + advance);
+                vb[k] = x;
+
+                if !odd && (k - delta).abs() <= d && x + vf[-(k - delta)] >= n {
+                    return (old.end - x, new.end - y);
+                }
+            }
+        }
+
+        unreachable!("no middle snake found")
+    }
+
+    fn patience(
+        &self,
+        mut old: Range<usize>,
+        mut new: Range<usize>,
+        matches: &mut Vec<(usize, usize)>,
+    ) {
+        let prefix = self.common_prefix(old.clone(), new.clone());
+        matches.extend((0..prefix).map(|i| (old.start + i, new.start + i)));
+        old.start += prefix;
+        new.start += prefix;
+
+        let suffix = self.common_suffix(old.clone(), new.clone());
+        old.end -= suffix;
+        new.end -= suffix;
+
+        let anchors = self.unique_anchors(old.clone(), new.clone());
+        if anchors.is_empty() {
+            if !old.is_empty() && !new.is_empty() {
+                self.myers(old.clone(), new.clone(), matches);
+            }
+        } else {
+            let (mut old_start, mut new_start) = (old.start, new.start);
+            for (x, y) in anchors {
+                self.patience(old_start..x, new_start..y, matches);
+                matches.push((x, y));
+                (old_start, new_start) = (x + 1, y + 1);
+            }
+            self.patience(old_start..old.end, new_start..new.end, matches);
+        }
+
+        matches.extend((0..suffix).map(|i| (old.end + i, new.end + i)));
+    }
+
+    /// Returns the longest increasing sequence of `(old, new)` index
+    /// pairs of lines that occur exactly once in both ranges.
+    fn unique_anchors(&self, old: Range<usize>, new: Range<usize>) -> Vec<(usize, usize)> {
+        // line -> (count in old, index in old, count in new, index in new)
+        let mut occurrences = HashMap::<usize, (usize, usize, usize, usize)>::new();
+        for x in old.clone() {
+            let entry = occurrences.entry(self.old[x]).or_default();
+            entry.0 += 1;
+            entry.1 = x;
+        }
+        for y in new {
+            if let Some(entry) = occurrences.get_mut(&self.new[y]) {
+                entry.2 += 1;
+                entry.3 = y;
+            }
+        }
+
+        let mut unique = occurrences
+            .into_values()
+            .filter(|&(old_count, _, new_count, _)| old_count == 1 && new_count == 1)
+            .map(|(_, x, _, y)| (x, y))
+            .collect::<Vec<_>>();
+        unique.sort_unstable();
+
+        // Patience sorting: `tails[i]` is the index into `unique` of the
+        // smallest tail of all increasing sequences of length `i + 1`.
+        let mut tails: Vec<usize> = Vec::new();
+        let mut predecessors = vec![None; unique.len()];
+        for (i, &(_, y)) in unique.iter().enumerate() {
+            let pile = tails.partition_point(|&tail| unique[tail].1 < y);
+            predecessors[i] = pile.checked_sub(1).map(|pile| tails[pile]);
+            if pile == tails.len() {
+                tails.push(i);
+            } else {
+                tails[pile] = i;
+            }
+        }
+
+        let mut anchors = Vec::with_capacity(tails.len());
+        let mut next = tails.last().copied();
+        while let Some(i) = next {
+            anchors.push(unique[i]);
+            next = predecessors[i];
+        }
+        anchors.reverse();
+        anchors
+    }
+
+    fn common_prefix(&self, old: Range<usize>, new: Range<usize>) -> usize {
+        self.old[old]
+            .iter()
+            .zip(&self.new[new])
+            .take_while(|(a, b)| a == b)
+            .count()
+    }
+
+    fn common_suffix(&self, old: Range<usize>, new: Range<usize>) -> usize {
+        self.old[old]
+            .iter()
+            .rev()
+            .zip(self.new[new].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count()
+    }
+}
+
+/// The maximum number of edits the middle snake search
+/// has to explore for ranges of the given lengths.
+fn max_d(n: usize, m: usize) -> usize {
+    (n + m + 1) / 2 + 1
+}
+
+/// The furthest reaching x-coordinates of the diagonals `k` in
+/// `-max_d..=max_d` of the edit graph.
+struct V {
+    offset: isize,
+    v: Vec<usize>,
+}
+
+impl V {
+    fn new(max_d: usize) -> Self {
+        Self {
+            offset: isize::try_from(max_d).unwrap(),
+            v: vec![0; 2 * max_d + 1],
+        }
+    }
+}
+
+impl std::ops::Index<isize> for V {
+    type Output = usize;
+
+    fn index(&self, k: isize) -> &usize {
+        &self.v[(k + self.offset).unsigned_abs()]
+    }
+}
+
+impl std::ops::IndexMut<isize> for V {
+    fn index_mut(&mut self, k: isize) -> &mut usize {
+        &mut self.v[(k + self.offset).unsigned_abs()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CrlfBehavior, MemoryLineFile};
+
+    const ALGORITHMS: [Algorithm; 2] = [Algorithm::Myers, Algorithm::Patience];
+
+    fn file(text: &str) -> MemoryLineFile {
+        if text.is_empty() {
+            MemoryLineFile::new(vec![])
+        } else {
+            MemoryLineFile::new(text.chars().map(String::from).collect())
+        }
+    }
+
+    /// Applies the change set to the old lines; a naive stand-in
+    /// for [`ChangeSet::reflow`], to check diffs for correctness
+    /// independently of it.
+    fn apply(old: &MemoryLineFile, change_set: &ChangeSet) -> Vec<String> {
+        let old = all_lines(old);
+        let mut result = Vec::new();
+        let mut index = 0;
+        for hunk in change_set.hunks() {
+            result.extend(old[index..hunk.position()].iter().map(ToString::to_string));
+            result.extend(hunk.inserted().iter().cloned());
+            index = hunk.position() + hunk.removed_count();
+        }
+        result.extend(old[index..].iter().map(ToString::to_string));
+        result
+    }
+
+    /// The number of lines removed and inserted by the change set.
+    fn edits(change_set: &ChangeSet) -> usize {
+        change_set
+            .hunks()
+            .iter()
+            .map(|hunk| hunk.removed_count() + hunk.inserted().len())
+            .sum()
+    }
+
+    fn assert_diff(old: &str, new: &str) {
+        for algorithm in ALGORITHMS {
+            let (old_file, new_file) = (file(old), file(new));
+            let change_set = diff(&old_file, &new_file, algorithm);
+            assert_eq!(
+                apply(&old_file, &change_set).concat(),
+                new,
+                "{algorithm:?}: {old:?} -> {new:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn diff_roundtrips() {
+        let cases = [
+            ("", ""),
+            ("", "abc"),
+            ("abc", ""),
+            ("abc", "abc"),
+            ("abc", "abd"),
+            ("abc", "xbc"),
+            ("abcabba", "cbabac"),
+            ("abcdefg", "axcyegz"),
+            ("aaaaaa", "aaa"),
+            ("xaxbxcx", "abc"),
+            ("abcdef", "fedcba"),
+            ("ab{}cd{}", "ab{}xy{}cd{}"),
+        ];
+
+        for (old, new) in cases {
+            assert_diff(old, new);
+            assert_diff(new, old);
+        }
+    }
+
+    #[test]
+    fn diff_fixtures() {
+        let fixtures = [
+            ("code1", "code2"),
+            ("code3", "code4"),
+            ("text1", "text2"),
+            ("text1", "text3"),
+            ("large1", "large2"),
+        ];
+
+        for (a, b) in fixtures {
+            let old = include_fixture(a);
+            let new = include_fixture(b);
+            for algorithm in ALGORITHMS {
+                let change_set = diff(&old, &new, algorithm);
+                assert_eq!(apply(&old, &change_set), all_lines(&new), "{a} -> {b}");
+            }
+        }
+    }
+
+    fn include_fixture(name: &str) -> MemoryLineFile {
+        let path = format!("{}/fixture/{name}.txt", env!("CARGO_MANIFEST_DIR"));
+        MemoryLineFile::from_str(&std::fs::read_to_string(path).unwrap(), CrlfBehavior::Trim)
+    }
+
+    #[test]
+    fn myers_is_minimal() {
+        // The classic example from Myers' paper, with an edit distance of 5.
+        let change_set = diff(&file("abcabba"), &file("cbabac"), Algorithm::Myers);
+        assert_eq!(edits(&change_set), 5);
+
+        let change_set = diff(&file("abcdefg"), &file("axcyegz"), Algorithm::Myers);
+        assert_eq!(edits(&change_set), 6);
+    }
+
+    #[test]
+    fn myers_is_minimal_random() {
+        // Compares against the length of the longest common subsequence,
+        // computed the slow way, over a bunch of (deterministic) random inputs.
+        fn lcs(a: &[u8], b: &[u8]) -> usize {
+            let mut table = vec![vec![0; b.len() + 1]; a.len() + 1];
+            for (i, a) in a.iter().enumerate() {
+                for (j, b) in b.iter().enumerate() {
+                    table[i + 1][j + 1] = if a == b {
+                        table[i][j] + 1
+                    } else {
+                        table[i][j + 1].max(table[i + 1][j])
+                    };
+                }
+            }
+            table[a.len()][b.len()]
+        }
+
+        let mut seed = 0x2545_f491_u32;
+        let mut random = |max: u32| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed % max
+        };
+
+        for _ in 0..500 {
+            let mut text = || {
+                (0..random(20))
+                    .map(|_| b"abcd"[random(4) as usize])
+                    .collect::<Vec<_>>()
+            };
+            let (old, new) = (text(), text());
+            let (old, new) = (
+                std::str::from_utf8(&old).unwrap(),
+                std::str::from_utf8(&new).unwrap(),
+            );
+
+            assert_diff(old, new);
+            let change_set = diff(&file(old), &file(new), Algorithm::Myers);
+            assert_eq!(
+                edits(&change_set),
+                old.len() + new.len() - 2 * lcs(old.as_bytes(), new.as_bytes()),
+                "{old:?} -> {new:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn diff_hunks() {
+        let change_set = diff(&file("abcdef"), &file("xabdeyf"), Algorithm::Myers);
+        assert_eq!(
+            change_set.hunks(),
+            &[
+                Hunk::insertion(0, vec!["x".to_owned()]),
+                Hunk::new(LineSpan::new(2, 2), vec![]),
+                Hunk::insertion(5, vec!["y".to_owned()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn patience_anchors_on_unique_lines() {
+        // Bram Cohen's example: `fib` is replaced by `fact`, and moved
+        // below `frobnitz`. Myers interleaves the two functions,
+        // matching up their braces and blank lines.
+        let lines = |text: &str| MemoryLineFile::new(text.lines().map(String::from).collect());
+        let old = lines(concat!(
+            "#include <stdio.h>\n",
+            "\n",
+            "int fib(int n)\n",
+            "{\n",
+            "    if(n > 2)\n",
+            "    {\n",
+            "        return fib(n-1) + fib(n-2);\n",
+            "    }\n",
+            "    return 1;\n",
+            "}\n",
+            "\n",
+            "// Frobs foo heartily\n",
+            "int frobnitz(int foo)\n",
+            "{\n",
+            "    int i;\n",
+            "    for(i = 0; i < 10; i++)\n",
+            "    {\n",
+            "        printf(\"%d\\n\", foo);\n",
+            "    }\n",
+            "}\n",
+            "\n",
+            "int main(int argc, char **argv)\n",
+            "{\n",
+            "    frobnitz(fib(10));\n",
+            "}\n",
+        ));
+        let new = lines(concat!(
+            "#include <stdio.h>\n",
+            "\n",
+            "// Frobs foo heartily\n",
+            "int frobnitz(int foo)\n",
+            "{\n",
+            "    int i;\n",
+            "    for(i = 0; i < 10; i++)\n",
+            "    {\n",
+            "        printf(\"%d\\n\", foo);\n",
+            "    }\n",
+            "}\n",
+            "\n",
+            "int fact(int n)\n",
+            "{\n",
+            "    if(n > 1)\n",
+            "    {\n",
+            "        return fact(n-1) * n;\n",
+            "    }\n",
+            "    return 1;\n",
+            "}\n",
+            "\n",
+            "int main(int argc, char **argv)\n",
+            "{\n",
+            "    frobnitz(fact(10));\n",
+            "}\n",
+        ));
+
+        assert_eq!(diff(&old, &new, Algorithm::Myers).len(), 9);
+        assert_eq!(
+            diff(&old, &new, Algorithm::Patience).hunks(),
+            &[
+                Hunk::new(LineSpan::new(2, 10), vec![]),
+                Hunk::insertion(
+                    21,
+                    all_lines(&new)[12..21]
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect()
+                ),
+                Hunk::new(
+                    LineSpan::new(23, 23),
+                    vec!["    frobnitz(fact(10));".to_owned()]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_unchanged() {
+        for algorithm in ALGORITHMS {
+            let old = include_fixture("code1");
+            assert!(diff(&old, &include_fixture("code1"), algorithm).is_empty());
+        }
+    }
+}
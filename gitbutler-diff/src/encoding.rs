@@ -0,0 +1,478 @@
+use std::fmt;
+
+/// A text encoding of files in the working tree.
+///
+/// [`LineFile`](crate::LineFile)s always deal in UTF-8; files in
+/// other encodings are decoded when reading them (and encoded again
+/// when writing them back) by [`BytesLineFile`](crate::BytesLineFile).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    /// UTF-8, optionally with a byte order mark.
+    Utf8,
+    /// Little-endian UTF-16, optionally with a byte order mark.
+    Utf16Le,
+    /// Big-endian UTF-16, optionally with a byte order mark.
+    Utf16Be,
+    /// UTF-16 as `iconv` understands it: a byte order mark gives the
+    /// byte order (big-endian without one), and one is always written.
+    Utf16,
+    /// Little-endian UTF-16, always written with a byte order mark
+    /// (git's `UTF-16LE-BOM`).
+    Utf16LeBom,
+    /// ISO-8859-1 (Latin-1), which maps each byte to the code point
+    /// of the same value. Any sequence of bytes is valid Latin-1 and
+    /// survives decoding and encoding unchanged, so it doubles as the
+    /// encoding of binary files.
+    Latin1,
+}
+
+/// Returned when a file can't be decoded or encoded.
+#[derive(Debug, thiserror::Error)]
+pub enum EncodingError {
+    /// The name of the encoding isn't known (or not supported).
+    #[error("unknown encoding: {0}")]
+    UnknownEncoding(String),
+    /// The file's bytes aren't valid in its encoding.
+    #[error("invalid {encoding} at byte {offset}")]
+    Decode {
+        /// The encoding the file was decoded with.
+        encoding: Encoding,
+        /// The offset of the first invalid byte.
+        offset: usize,
+    },
+    /// The text contains a character that can't be represented
+    /// in the file's encoding.
+    #[error("{character:?} can't be encoded as {encoding}")]
+    Encode {
+        /// The encoding the file was encoded with.
+        encoding: Encoding,
+        /// The first character that couldn't be encoded.
+        character: char,
+    },
+    /// The gitattributes couldn't be read.
+    #[cfg(feature = "git2")]
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+}
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+const UTF16LE_BOM: &[u8] = &[0xFF, 0xFE];
+const UTF16BE_BOM: &[u8] = &[0xFE, 0xFF];
+
+/// How much of a file git checks for NUL bytes to tell whether
+/// it's binary.
+const BINARY_CHECK_LENGTH: usize = 8000;
+
+impl Encoding {
+    /// Looks up an encoding by one of its names, as given to the
+    /// `working-tree-encoding` gitattribute (which in turn accepts
+    /// anything `iconv` does). Case-insensitive.
+    ///
+    /// Returns `None` for unknown or unsupported encodings.
+    pub fn from_label(label: &str) -> Option<Self> {
+        Some(match label.to_ascii_uppercase().as_str() {
+            "UTF-8" | "UTF8" => Encoding::Utf8,
+            "UTF-16LE" | "UTF16LE" => Encoding::Utf16Le,
+            "UTF-16BE" | "UTF16BE" => Encoding::Utf16Be,
+            "UTF-16" | "UTF16" => Encoding::Utf16,
+            "UTF-16LE-BOM" | "UTF16LE-BOM" => Encoding::Utf16LeBom,
+            "ISO-8859-1" | "ISO8859-1" | "ISO_8859-1" | "LATIN1" | "LATIN-1" | "L1" => {
+                Encoding::Latin1
+            }
+            _ => return None,
+        })
+    }
+
+    /// Guesses the encoding of a file from its contents.
+    ///
+    /// Byte order marks are honored first, if the rest of the file is
+    /// valid in the encoding they indicate; then, files with NUL bytes
+    /// in every other position that are valid UTF-16 are taken to be
+    /// UTF-16. Of the rest, files with a NUL byte among their first
+    /// 8000 bytes are binary (like git decides), for which `None` is
+    /// returned. Valid UTF-8 files are taken to be UTF-8, and everything
+    /// else to be [`Encoding::Latin1`].
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        let bom_encoding = if bytes.starts_with(UTF8_BOM) {
+            Some(Encoding::Utf8)
+        } else if bytes.starts_with(UTF16LE_BOM) {
+            Some(Encoding::Utf16Le)
+        } else if bytes.starts_with(UTF16BE_BOM) {
+            Some(Encoding::Utf16Be)
+        } else {
+            None
+        };
+        if let Some(encoding) = bom_encoding.filter(|encoding| encoding.decode(bytes).is_ok()) {
+            return Some(encoding);
+        }
+
+        // UTF-16 is full of NUL bytes, so this has to be checked first.
+        if let Some(encoding) = detect_utf16(bytes) {
+            return Some(encoding);
+        }
+
+        if bytes[..bytes.len().min(BINARY_CHECK_LENGTH)].contains(&0) {
+            None
+        } else if std::str::from_utf8(bytes).is_ok() {
+            Some(Encoding::Utf8)
+        } else {
+            Some(Encoding::Latin1)
+        }
+    }
+
+    /// Reads the encoding of the given file from the
+    /// `working-tree-encoding` gitattribute, if it's set.
+    ///
+    /// Note that git only applies the attribute to files in the
+    /// working tree; the file's blobs are always UTF-8.
+    #[cfg(feature = "git2")]
+    pub fn from_attributes(
+        repository: &git2::Repository,
+        path: &std::path::Path,
+    ) -> Result<Option<Self>, EncodingError> {
+        let value = repository.get_attr_bytes(
+            path,
+            "working-tree-encoding",
+            git2::AttrCheckFlags::FILE_THEN_INDEX,
+        )?;
+        match git2::AttrValue::from_bytes(value) {
+            git2::AttrValue::String(label) => Self::from_label(label)
+                .map(Some)
+                .ok_or_else(|| EncodingError::UnknownEncoding(label.to_string())),
+            git2::AttrValue::Bytes(label) => {
+                let label = String::from_utf8_lossy(label);
+                Self::from_label(&label)
+                    .map(Some)
+                    .ok_or_else(|| EncodingError::UnknownEncoding(label.into_owned()))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Decodes the bytes, returning the text, the encoding it was
+    /// actually decoded with, and whether it started with a byte order
+    /// mark (which isn't part of the text).
+    ///
+    /// A UTF-16 byte order mark takes precedence over the endianness
+    /// of the encoding (and the encoding it indicates is returned).
+    pub(crate) fn decode(self, bytes: &[u8]) -> Result<(String, Self, bool), EncodingError> {
+        match self {
+            Encoding::Utf8 => {
+                let (bytes, bom) = match bytes.strip_prefix(UTF8_BOM) {
+                    Some(bytes) => (bytes, true),
+                    None => (bytes, false),
+                };
+                let text = std::str::from_utf8(bytes).map_err(|error| EncodingError::Decode {
+                    encoding: self,
+                    offset: error.valid_up_to() + if bom { UTF8_BOM.len() } else { 0 },
+                })?;
+                Ok((text.to_string(), self, bom))
+            }
+            Encoding::Utf16Le | Encoding::Utf16Be | Encoding::Utf16 | Encoding::Utf16LeBom => {
+                let (bytes, encoding, bom) = if let Some(bytes) = bytes.strip_prefix(UTF16LE_BOM) {
+                    (bytes, Encoding::Utf16Le, true)
+                } else if let Some(bytes) = bytes.strip_prefix(UTF16BE_BOM) {
+                    (bytes, Encoding::Utf16Be, true)
+                } else {
+                    (bytes, self, false)
+                };
+                let offset = |index: usize| index * 2 + if bom { 2 } else { 0 };
+
+                let (units, remainder) = bytes.as_chunks::<2>();
+                if !remainder.is_empty() {
+                    return Err(EncodingError::Decode {
+                        encoding,
+                        offset: offset(units.len()),
+                    });
+                }
+
+                let units = units.iter().map(|&unit| match encoding {
+                    Encoding::Utf16Le | Encoding::Utf16LeBom => u16::from_le_bytes(unit),
+                    _ => u16::from_be_bytes(unit),
+                });
+                let mut text = String::with_capacity(bytes.len() / 2);
+                let mut index = 0;
+                for c in char::decode_utf16(units) {
+                    let c = c.map_err(|_| EncodingError::Decode {
+                        encoding,
+                        offset: offset(index),
+                    })?;
+                    index += c.len_utf16();
+                    text.push(c);
+                }
+                Ok((text, encoding, bom))
+            }
+            Encoding::Latin1 => Ok((bytes.iter().map(|&b| char::from(b)).collect(), self, false)),
+        }
+    }
+
+    /// Encodes the text, prefixed with a byte order mark if `bom`
+    /// is set (which is ignored for [`Encoding::Latin1`], and implied
+    /// for [`Encoding::Utf16`] and [`Encoding::Utf16LeBom`]).
+    pub(crate) fn encode(self, text: &str, bom: bool) -> Result<Vec<u8>, EncodingError> {
+        let mut bytes = Vec::with_capacity(text.len() + 3);
+        match self {
+            Encoding::Utf8 => {
+                if bom {
+                    bytes.extend_from_slice(UTF8_BOM);
+                }
+                bytes.extend_from_slice(text.as_bytes());
+            }
+            Encoding::Utf16Le | Encoding::Utf16LeBom => {
+                if bom || self == Encoding::Utf16LeBom {
+                    bytes.extend_from_slice(UTF16LE_BOM);
+                }
+                bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+            }
+            Encoding::Utf16Be | Encoding::Utf16 => {
+                if bom || self == Encoding::Utf16 {
+                    bytes.extend_from_slice(UTF16BE_BOM);
+                }
+                bytes.extend(text.encode_utf16().flat_map(u16::to_be_bytes));
+            }
+            Encoding::Latin1 => {
+                for character in text.chars() {
+                    bytes.push(u8::try_from(character).map_err(|_| EncodingError::Encode {
+                        encoding: self,
+                        character,
+                    })?);
+                }
+            }
+        }
+        Ok(bytes)
+    }
+}
+
+/// Detects BOM-less UTF-16 by its telltale NUL bytes: text that
+/// is mostly ASCII has a NUL in (nearly) every other position.
+fn detect_utf16(bytes: &[u8]) -> Option<Encoding> {
+    if bytes.is_empty() || bytes.len() % 2 != 0 {
+        return None;
+    }
+
+    let (even, odd) = bytes.chunks_exact(2).fold((0, 0), |(even, odd), unit| {
+        (
+            even + usize::from(unit[0] == 0),
+            odd + usize::from(unit[1] == 0),
+        )
+    });
+    let units = bytes.len() / 2;
+
+    let encoding = if even == 0 && odd * 2 >= units {
+        Encoding::Utf16Le
+    } else if odd == 0 && even * 2 >= units {
+        Encoding::Utf16Be
+    } else {
+        return None;
+    };
+
+    encoding.decode(bytes).is_ok().then_some(encoding)
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf16Be => "UTF-16BE",
+            Encoding::Utf16 => "UTF-16",
+            Encoding::Utf16LeBom => "UTF-16LE-BOM",
+            Encoding::Latin1 => "ISO-8859-1",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels() {
+        assert_eq!(Encoding::from_label("utf-8"), Some(Encoding::Utf8));
+        assert_eq!(
+            Encoding::from_label("UTF-16LE-BOM"),
+            Some(Encoding::Utf16LeBom)
+        );
+        assert_eq!(Encoding::from_label("utf-16le"), Some(Encoding::Utf16Le));
+        assert_eq!(Encoding::from_label("UTF-16"), Some(Encoding::Utf16));
+        assert_eq!(Encoding::from_label("latin1"), Some(Encoding::Latin1));
+        assert_eq!(Encoding::from_label("SHIFT-JIS"), None);
+
+        for encoding in [
+            Encoding::Utf8,
+            Encoding::Utf16Le,
+            Encoding::Utf16Be,
+            Encoding::Utf16,
+            Encoding::Utf16LeBom,
+            Encoding::Latin1,
+        ] {
+            assert_eq!(Encoding::from_label(&encoding.to_string()), Some(encoding));
+        }
+    }
+
+    #[test]
+    fn detect() {
+        assert_eq!(Encoding::detect(b""), Some(Encoding::Utf8));
+        assert_eq!(Encoding::detect("héllo\n".as_bytes()), Some(Encoding::Utf8));
+        assert_eq!(Encoding::detect(b"\xEF\xBB\xBFhi\n"), Some(Encoding::Utf8));
+        assert_eq!(Encoding::detect(b"h\xE9llo\n"), Some(Encoding::Latin1));
+
+        assert_eq!(Encoding::detect(b"\xFF\xFEh\0i\0"), Some(Encoding::Utf16Le));
+        assert_eq!(Encoding::detect(b"\xFE\xFF\0h\0i"), Some(Encoding::Utf16Be));
+        assert_eq!(Encoding::detect(b"h\0\xE9\0\n\0"), Some(Encoding::Utf16Le));
+        assert_eq!(Encoding::detect(b"\0h\0\xE9\0\n"), Some(Encoding::Utf16Be));
+
+        // A lone surrogate isn't valid UTF-16, which leaves its NULs.
+        assert_eq!(Encoding::detect(b"h\0i\0\x01\xD8"), None);
+        assert_eq!(Encoding::detect(b"\xFF\xFE\0\xD8a\0"), None);
+
+        // Neither is a byte order mark followed by invalid contents.
+        assert_eq!(
+            Encoding::detect(b"\xEF\xBB\xBF\xFFabc"),
+            Some(Encoding::Latin1)
+        );
+        assert_eq!(Encoding::detect(b"\xFF\xFEa"), Some(Encoding::Latin1));
+    }
+
+    #[test]
+    fn detect_binary() {
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0\x01\0\0\0\x01\0\x08\x06\0\0\0";
+        assert_eq!(Encoding::detect(png), None);
+        assert_eq!(Encoding::detect(b"\0"), None);
+        assert_eq!(Encoding::detect(b"valid UTF-8, but\0binary"), None);
+
+        // Only the start of the file is checked, like git does.
+        let mut text = vec![b'a'; BINARY_CHECK_LENGTH];
+        text.push(0);
+        assert_eq!(Encoding::detect(&text), Some(Encoding::Utf8));
+        text.insert(0, 0);
+        assert_eq!(Encoding::detect(&text), None);
+    }
+
+    #[test]
+    fn decode_and_encode() {
+        for (encoding, bytes, bom) in [
+            (Encoding::Utf8, "héllo".as_bytes(), false),
+            (Encoding::Utf8, b"\xEF\xBB\xBFh\xC3\xA9llo".as_slice(), true),
+            (Encoding::Utf16Le, b"h\0\xE9\0l\0l\0o\0".as_slice(), false),
+            (
+                Encoding::Utf16Be,
+                b"\xFE\xFF\0h\0\xE9\0l\0l\0o".as_slice(),
+                true,
+            ),
+            (Encoding::Latin1, b"h\xE9llo".as_slice(), false),
+        ] {
+            let (text, detected, has_bom) = encoding.decode(bytes).unwrap();
+            assert_eq!(text, "héllo");
+            assert_eq!(detected, encoding);
+            assert_eq!(has_bom, bom);
+            assert_eq!(encoding.encode(&text, bom).unwrap(), bytes);
+        }
+
+        // The byte order mark wins over the given endianness.
+        let (text, encoding, bom) = Encoding::Utf16Be.decode(b"\xFF\xFEh\0").unwrap();
+        assert_eq!(
+            (text.as_str(), encoding, bom),
+            ("h", Encoding::Utf16Le, true)
+        );
+        let (text, encoding, bom) = Encoding::Utf16.decode(b"\xFF\xFEh\0").unwrap();
+        assert_eq!(
+            (text.as_str(), encoding, bom),
+            ("h", Encoding::Utf16Le, true)
+        );
+
+        let (text, _, _) = Encoding::Utf16Le
+            .decode(
+                "😀"
+                    .encode_utf16()
+                    .flat_map(u16::to_le_bytes)
+                    .collect::<Vec<_>>()
+                    .as_slice(),
+            )
+            .unwrap();
+        assert_eq!(text, "😀");
+    }
+
+    #[test]
+    fn encode_with_implied_bom() {
+        // Like `iconv`, BOM-less UTF-16 is big-endian, and gets a BOM.
+        let (text, encoding, bom) = Encoding::Utf16.decode(b"\0h\0i").unwrap();
+        assert_eq!(
+            (text.as_str(), encoding, bom),
+            ("hi", Encoding::Utf16, false)
+        );
+        assert_eq!(encoding.encode(&text, bom).unwrap(), b"\xFE\xFF\0h\0i");
+
+        let (text, encoding, bom) = Encoding::Utf16LeBom.decode(b"h\0i\0").unwrap();
+        assert_eq!(
+            (text.as_str(), encoding, bom),
+            ("hi", Encoding::Utf16LeBom, false)
+        );
+        assert_eq!(encoding.encode(&text, bom).unwrap(), b"\xFF\xFEh\0i\0");
+        assert_eq!(
+            Encoding::Utf16LeBom.decode(b"\xFF\xFEh\0i\0").unwrap(),
+            ("hi".to_string(), Encoding::Utf16Le, true)
+        );
+    }
+
+    #[test]
+    fn decode_errors() {
+        let offset = |encoding: Encoding, bytes: &[u8]| match encoding.decode(bytes) {
+            Err(EncodingError::Decode { offset, .. }) => offset,
+            result => panic!("expected a decode error, got {result:?}"),
+        };
+
+        assert_eq!(offset(Encoding::Utf8, b"ab\xFFc"), 2);
+        assert_eq!(offset(Encoding::Utf8, b"\xEF\xBB\xBFab\xFFc"), 5);
+        assert_eq!(offset(Encoding::Utf16Le, b"a\0b"), 2);
+        assert_eq!(offset(Encoding::Utf16Le, b"\xFF\xFEa\0\0\xDCb\0"), 4);
+        assert_eq!(offset(Encoding::Utf16Be, b"\0a\xD8\0\0b"), 2);
+    }
+
+    #[test]
+    fn latin1_is_lossless() {
+        let bytes = (0..=255).cycle().take(1024).collect::<Vec<u8>>();
+        let (text, _, _) = Encoding::Latin1.decode(&bytes).unwrap();
+        assert_eq!(Encoding::Latin1.encode(&text, false).unwrap(), bytes);
+
+        assert!(matches!(
+            Encoding::Latin1.encode("price: 5€", false),
+            Err(EncodingError::Encode {
+                encoding: Encoding::Latin1,
+                character: '€'
+            })
+        ));
+    }
+
+    #[cfg(feature = "git2")]
+    #[test]
+    fn from_attributes() {
+        let dir = std::env::temp_dir().join(format!(
+            "gitbutler-diff-encoding-{}-{:?}",
+            std::process::id(),
+            std::thread::current().id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        let repository = git2::Repository::init(&dir).unwrap();
+        std::fs::write(
+            dir.join(".gitattributes"),
+            "*.utf16 working-tree-encoding=UTF-16LE-BOM\n\
+             *.latin1 working-tree-encoding=ISO-8859-1\n\
+             *.unknown working-tree-encoding=KLINGON\n\
+             *.set working-tree-encoding\n",
+        )
+        .unwrap();
+
+        let encoding = |path: &str| Encoding::from_attributes(&repository, path.as_ref());
+        assert_eq!(encoding("a.utf16").unwrap(), Some(Encoding::Utf16LeBom));
+        assert_eq!(encoding("dir/b.latin1").unwrap(), Some(Encoding::Latin1));
+        assert_eq!(encoding("c.txt").unwrap(), None);
+        assert_eq!(encoding("d.set").unwrap(), None);
+        assert!(matches!(
+            encoding("e.unknown"),
+            Err(EncodingError::UnknownEncoding(label)) if label == "KLINGON"
+        ));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
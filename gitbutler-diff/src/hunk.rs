@@ -0,0 +1,308 @@
+use crate::LineSpan;
+
+/// A single change to a file, in terms of hunk theory: the removal
+/// of a span of lines from the source file, followed by the insertion
+/// of zero or more lines of replacement text in their place.
+///
+/// Pure insertions don't remove anything from the source file; they
+/// instead insert their text before a given source line.
+///
+/// Line numbers are 0-based, and always refer to the source file.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Hunk {
+    position: usize,
+    removed: Option<LineSpan>,
+    inserted: Vec<String>,
+}
+
+impl Hunk {
+    /// Creates a hunk that removes the given span of source lines
+    /// and inserts the given lines in their place. Pass an empty
+    /// `inserted` vector for a pure removal.
+    pub fn new(removed: LineSpan, inserted: Vec<String>) -> Self {
+        Self {
+            position: removed.start(),
+            removed: Some(removed),
+            inserted,
+        }
+    }
+
+    /// Creates a hunk that inserts the given lines before the given
+    /// source line, without removing anything. Inserting before the
+    /// source file's line count appends to the end of the file.
+    pub fn insertion(before: usize, inserted: Vec<String>) -> Self {
+        Self {
+            position: before,
+            removed: None,
+            inserted,
+        }
+    }
+
+    /// The first source line affected by the hunk; either the first
+    /// removed line, or the line before which text is inserted.
+    #[inline]
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// The span of source lines removed by the hunk, if any.
+    #[inline]
+    pub fn removed(&self) -> Option<LineSpan> {
+        self.removed
+    }
+
+    /// The number of source lines removed by the hunk.
+    pub fn removed_count(&self) -> usize {
+        self.removed.map_or(0, |span| span.line_count())
+    }
+
+    /// The lines inserted by the hunk.
+    #[inline]
+    pub fn inserted(&self) -> &[String] {
+        &self.inserted
+    }
+
+    /// Returns true if the two hunks cannot both be applied
+    /// to the same source file.
+    ///
+    /// Two hunks conflict if their removed spans intersect.
+    /// A pure insertion conflicts with a removal if it would be
+    /// inserted inside of the removed span (inserting right before
+    /// the span is fine), and with another pure insertion at the
+    /// same position, since the order of the inserted text would
+    /// be ambiguous.
+    pub fn conflicts_with(&self, other: &Self) -> bool {
+        match (self.removed, other.removed) {
+            (Some(a), Some(b)) => a.intersects(&b),
+            (Some(span), None) => other.position > span.start() && other.position <= span.end(),
+            (None, Some(span)) => self.position > span.start() && self.position <= span.end(),
+            (None, None) => self.position == other.position,
+        }
+    }
+}
+
+/// Returned when adding a [`Hunk`] to a [`ChangeSet`] that
+/// already holds a hunk it conflicts with.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("hunk at line {} conflicts with the hunk at line {}", .hunk.position(), .existing.position())]
+pub struct ConflictError {
+    /// The hunk that could not be added.
+    pub hunk: Hunk,
+    /// The hunk already in the change set that it conflicts with.
+    pub existing: Hunk,
+}
+
+/// A set of non-conflicting [`Hunk`]s against a single source file,
+/// ordered by their position in the file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct ChangeSet {
+    hunks: Vec<Hunk>,
+}
+
+impl ChangeSet {
+    /// Creates an empty change set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a change set from the given hunks, in any order.
+    ///
+    /// Fails on the first hunk that conflicts with another.
+    pub fn from_hunks<I: IntoIterator<Item = Hunk>>(hunks: I) -> Result<Self, ConflictError> {
+        let mut change_set = Self::new();
+        for hunk in hunks {
+            change_set.insert(hunk)?;
+        }
+        Ok(change_set)
+    }
+
+    /// Adds a hunk to the change set, keeping it ordered.
+    ///
+    /// Fails if the hunk conflicts with a hunk already in the set,
+    /// in which case the set is left unchanged.
+    pub fn insert(&mut self, hunk: Hunk) -> Result<(), ConflictError> {
+        // Since hunks in the set don't overlap, only the neighbours
+        // of the insertion point can possibly conflict with it.
+        let index = self.hunks.partition_point(|existing| {
+            (existing.position, existing.removed.is_some())
+                < (hunk.position, hunk.removed.is_some())
+        });
+
+        let neighbours = index.saturating_sub(1)..(index + 1).min(self.hunks.len());
+        if let Some(existing) = self.hunks[neighbours]
+            .iter()
+            .find(|existing| existing.conflicts_with(&hunk))
+        {
+            return Err(ConflictError {
+                existing: existing.clone(),
+                hunk,
+            });
+        }
+
+        self.hunks.insert(index, hunk);
+        Ok(())
+    }
+
+    /// The hunks of the change set, ordered by position.
+    #[inline]
+    pub fn hunks(&self) -> &[Hunk] {
+        &self.hunks
+    }
+
+    /// The number of hunks in the change set.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.hunks.len()
+    }
+
+    /// Returns true if the change set has no hunks.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.hunks.is_empty()
+    }
+
+    /// Returns all pairs of conflicting hunks between this change set
+    /// and another one against the same source file, e.g. two virtual
+    /// branches changing the same lines.
+    pub fn conflicts<'a>(&'a self, other: &'a Self) -> Vec<(&'a Hunk, &'a Hunk)> {
+        let mut conflicts = Vec::new();
+
+        // Both sets are ordered and free of overlaps, so we can sweep
+        // through them side by side, only ever comparing hunks that are
+        // close to each other.
+        let mut j = 0;
+        for hunk in &self.hunks {
+            while j < other.hunks.len() && hunk_end(&other.hunks[j]) < hunk.position {
+                j += 1;
+            }
+
+            conflicts.extend(
+                other.hunks[j..]
+                    .iter()
+                    .take_while(|theirs| theirs.position <= hunk_end(hunk))
+                    .filter(|theirs| hunk.conflicts_with(theirs))
+                    .map(|theirs| (hunk, theirs)),
+            );
+        }
+
+        conflicts
+    }
+}
+
+impl IntoIterator for ChangeSet {
+    type Item = Hunk;
+    type IntoIter = std::vec::IntoIter<Hunk>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.hunks.into_iter()
+    }
+}
+
+/// The last source line a hunk could conflict on.
+fn hunk_end(hunk: &Hunk) -> usize {
+    hunk.removed.map_or(hunk.position, |span| span.end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn hunk_conflicts() {
+        let replace = Hunk::new(LineSpan::new(5, 10), lines(&["a"]));
+
+        assert!(replace.conflicts_with(&Hunk::new(LineSpan::new(10, 12), vec![])));
+        assert!(replace.conflicts_with(&Hunk::new(LineSpan::new(0, 5), vec![])));
+        assert!(!replace.conflicts_with(&Hunk::new(LineSpan::new(11, 12), vec![])));
+        assert!(!replace.conflicts_with(&Hunk::new(LineSpan::new(0, 4), vec![])));
+
+        assert!(!replace.conflicts_with(&Hunk::insertion(5, lines(&["b"]))));
+        assert!(replace.conflicts_with(&Hunk::insertion(6, lines(&["b"]))));
+        assert!(replace.conflicts_with(&Hunk::insertion(10, lines(&["b"]))));
+        assert!(!replace.conflicts_with(&Hunk::insertion(11, lines(&["b"]))));
+        assert!(Hunk::insertion(10, lines(&["b"])).conflicts_with(&replace));
+
+        assert!(Hunk::insertion(3, vec![]).conflicts_with(&Hunk::insertion(3, vec![])));
+        assert!(!Hunk::insertion(3, vec![]).conflicts_with(&Hunk::insertion(4, vec![])));
+    }
+
+    #[test]
+    fn change_set_orders_hunks() {
+        let change_set = ChangeSet::from_hunks([
+            Hunk::new(LineSpan::new(8, 9), vec![]),
+            Hunk::insertion(0, lines(&["first"])),
+            Hunk::new(LineSpan::new(3, 4), lines(&["x"])),
+            Hunk::insertion(3, lines(&["before"])),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            change_set
+                .hunks()
+                .iter()
+                .map(|hunk| (hunk.position(), hunk.removed_count()))
+                .collect::<Vec<_>>(),
+            vec![(0, 0), (3, 0), (3, 2), (8, 2)]
+        );
+    }
+
+    #[test]
+    fn change_set_rejects_conflicts() {
+        let mut change_set = ChangeSet::from_hunks([
+            Hunk::new(LineSpan::new(3, 4), vec![]),
+            Hunk::new(LineSpan::new(8, 9), vec![]),
+        ])
+        .unwrap();
+
+        let hunk = Hunk::new(LineSpan::new(4, 8), vec![]);
+        let err = change_set.insert(hunk.clone()).unwrap_err();
+        assert_eq!(err.hunk, hunk);
+        assert_eq!(err.existing, Hunk::new(LineSpan::new(3, 4), vec![]));
+        assert_eq!(change_set.len(), 2);
+
+        assert!(change_set.insert(Hunk::insertion(9, vec![])).is_err());
+        assert!(change_set.insert(Hunk::insertion(10, vec![])).is_ok());
+        assert!(change_set.insert(Hunk::insertion(10, vec![])).is_err());
+        assert_eq!(change_set.len(), 3);
+    }
+
+    #[test]
+    fn change_set_conflicts() {
+        let ours = ChangeSet::from_hunks([
+            Hunk::new(LineSpan::new(0, 1), vec![]),
+            Hunk::new(LineSpan::new(5, 6), vec![]),
+            Hunk::insertion(10, vec![]),
+            Hunk::new(LineSpan::new(20, 30), vec![]),
+        ])
+        .unwrap();
+        let theirs = ChangeSet::from_hunks([
+            Hunk::new(LineSpan::new(2, 4), vec![]),
+            Hunk::new(LineSpan::new(6, 8), vec![]),
+            Hunk::insertion(10, vec![]),
+            Hunk::new(LineSpan::new(21, 21), vec![]),
+            Hunk::new(LineSpan::new(25, 26), vec![]),
+        ])
+        .unwrap();
+
+        let positions = |conflicts: Vec<(&Hunk, &Hunk)>| {
+            conflicts
+                .into_iter()
+                .map(|(a, b)| (a.position(), b.position()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            positions(ours.conflicts(&theirs)),
+            vec![(5, 6), (10, 10), (20, 21), (20, 25)]
+        );
+        assert_eq!(
+            positions(theirs.conflicts(&ours)),
+            vec![(6, 5), (10, 10), (21, 20), (25, 20)]
+        );
+        assert!(ours.conflicts(&ChangeSet::new()).is_empty());
+    }
+}
@@ -12,6 +12,9 @@
 //! across virtual branches.
 //!
 //! Note that all text is assumed UTF-8 due to how Git is designed.
+//! Files in the working tree may be in other encodings, though, as
+//! set by the `working-tree-encoding` gitattribute; such files (and
+//! binary files) are decoded by [`BytesLineFile`] (see [`Encoding`]).
 //! For more information about how Git handles encoding, see
 //! <https://git-scm.com/docs/gitattributes/2.19.2#_working_tree_encoding>.
 //!
@@ -45,14 +48,25 @@
 #![deny(missing_docs)]
 #![feature(impl_trait_in_assoc_type, iter_map_windows, slice_as_chunks)]
 
+mod diff;
+mod encoding;
+mod hunk;
 mod linefile;
+mod reflow;
 mod signature;
 mod span;
+mod track;
+pub mod unified;
 
 #[cfg(feature = "mmap")]
 pub use self::linefile::mmap::MmapLineFile;
 pub use self::{
-    linefile::{memory::MemoryLineFile, CrlfBehavior, LineEndings, LineFile},
+    diff::{diff, Algorithm},
+    encoding::{Encoding, EncodingError},
+    hunk::{ChangeSet, ConflictError, Hunk},
+    linefile::{bytes::BytesLineFile, memory::MemoryLineFile, CrlfBehavior, LineEndings, LineFile},
+    reflow::{OutOfBoundsError, ReflowedFile},
     signature::Signature,
     span::LineSpan,
+    track::{relocate_all, TrackedHunk},
 };
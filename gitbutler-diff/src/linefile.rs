@@ -1,6 +1,7 @@
 use crate::LineSpan;
 use std::fmt;
 
+pub mod bytes;
 pub mod memory;
 #[cfg(feature = "mmap")]
 pub mod mmap;
@@ -9,7 +10,8 @@ pub mod mmap;
 /// it is assumed the underlying implementation handles (and omits)
 /// line endings for us.
 ///
-/// All text is assumed to be UTF-8.
+/// All text is assumed to be UTF-8; files in other encodings
+/// (or binary files) can be read with [`BytesLineFile`](crate::BytesLineFile).
 pub trait LineFile<'a> {
     /// The type of iterator returned by [`LineFile::lines`] and [`LineFile::extract`].
     type LineIterator: Iterator<Item = &'a str>;
@@ -49,6 +51,16 @@ pub trait LineFile<'a> {
     }
 }
 
+/// Collects all lines of the file, which (unlike [`LineFile::lines`])
+/// also works for files without any lines.
+pub(crate) fn all_lines<'a, F: LineFile<'a>>(file: &'a F) -> Vec<&'a str> {
+    if file.line_count() == 0 {
+        Vec::new()
+    } else {
+        file.lines().collect()
+    }
+}
+
 /// The behavior of CRLF (carriage return + line feed) characters
 /// when splitting a file into lines.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
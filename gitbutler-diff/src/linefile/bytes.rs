@@ -0,0 +1,239 @@
+use crate::{
+    linefile::all_lines, CrlfBehavior, Encoding, EncodingError, LineEndings, LineFile, LineSpan,
+};
+
+/// A [`LineFile`] read from raw bytes in any supported [`Encoding`],
+/// including binary files (see [`Encoding::Latin1`]).
+///
+/// Lines are decoded to UTF-8 for use with the rest of the crate,
+/// while the encoding, byte order mark and final newline (or lack
+/// thereof) of the file are remembered, so that it (or a changed
+/// version of it) can be turned back into bytes exactly as it would
+/// have been written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BytesLineFile {
+    lines: Vec<String>,
+    encoding: Encoding,
+    bom: bool,
+    final_newline: bool,
+}
+
+impl BytesLineFile {
+    /// Creates a new [`BytesLineFile`] from the given bytes, guessing
+    /// their encoding with [`Encoding::detect`]. Never fails, since
+    /// binary files are read as Latin-1; to treat them differently,
+    /// check [`Encoding::detect`] first.
+    pub fn from_bytes(bytes: &[u8], crlf_behavior: CrlfBehavior) -> Self {
+        let encoding = Encoding::detect(bytes).unwrap_or(Encoding::Latin1);
+        Self::with_encoding(bytes, encoding, crlf_behavior)
+            .expect("detected encodings always decode")
+    }
+
+    /// Creates a new [`BytesLineFile`] from bytes in the given encoding,
+    /// e.g. as read from [`Encoding::from_attributes`].
+    ///
+    /// Fails if the bytes aren't valid in that encoding.
+    pub fn with_encoding(
+        bytes: &[u8],
+        encoding: Encoding,
+        crlf_behavior: CrlfBehavior,
+    ) -> Result<Self, EncodingError> {
+        let (text, encoding, bom) = encoding.decode(bytes)?;
+
+        // An empty file has no lines, rather than a single empty one.
+        let (lines, final_newline) = if text.is_empty() {
+            (Vec::new(), true)
+        } else {
+            let (text, final_newline) = match text.strip_suffix('\n') {
+                Some(text) => (text, true),
+                None => (text.as_str(), false),
+            };
+            let lines = text
+                .split('\n')
+                .map(|line| match crlf_behavior {
+                    CrlfBehavior::Trim => line.strip_suffix('\r').unwrap_or(line).to_owned(),
+                    CrlfBehavior::Keep => line.to_owned(),
+                })
+                .collect();
+            (lines, final_newline)
+        };
+
+        Ok(Self {
+            lines,
+            encoding,
+            bom,
+            final_newline,
+        })
+    }
+
+    /// The encoding the file was decoded with.
+    #[inline]
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// Whether the file started with a byte order mark.
+    #[inline]
+    pub fn has_bom(&self) -> bool {
+        self.bom
+    }
+
+    /// Whether the last line of the file is terminated by a newline.
+    #[inline]
+    pub fn has_final_newline(&self) -> bool {
+        self.final_newline
+    }
+
+    /// Encodes the file back into bytes.
+    ///
+    /// Gives back the original bytes if `line_endings` matches the
+    /// file's (i.e. [`LineEndings::Unix`] for files read with
+    /// [`CrlfBehavior::Keep`]).
+    pub fn to_bytes(&self, line_endings: LineEndings) -> Result<Vec<u8>, EncodingError> {
+        self.encode(self, line_endings)
+    }
+
+    /// Encodes another version of the file (e.g. one with a change
+    /// set reflowed onto it) the same way as this one: in the same
+    /// encoding, with or without a byte order mark, and with or
+    /// without a final newline.
+    ///
+    /// Fails if the text contains characters that can't be represented
+    /// in the encoding.
+    pub fn encode<'a, F: LineFile<'a>>(
+        &self,
+        file: &'a F,
+        line_endings: LineEndings,
+    ) -> Result<Vec<u8>, EncodingError> {
+        let line_ending = match line_endings {
+            LineEndings::Unix => "\n",
+            LineEndings::Windows => "\r\n",
+        };
+
+        let lines = all_lines(file);
+        let mut text = lines.join(line_ending);
+        if self.final_newline && !lines.is_empty() {
+            text.push_str(line_ending);
+        }
+
+        self.encoding.encode(&text, self.bom)
+    }
+}
+
+impl<'a> LineFile<'a> for BytesLineFile {
+    type LineIterator = impl Iterator<Item = &'a str>;
+
+    #[inline]
+    fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    fn extract(&'a self, span: LineSpan) -> Self::LineIterator {
+        self.lines[span.start()..=span.end()]
+            .iter()
+            .map(AsRef::as_ref)
+    }
+
+    fn lines(&'a self) -> Self::LineIterator {
+        // Unlike the default implementation, this supports empty files.
+        self.lines.iter().map(AsRef::as_ref)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{diff, Algorithm};
+
+    fn lines(file: &BytesLineFile) -> Vec<&str> {
+        file.lines().collect()
+    }
+
+    #[test]
+    fn roundtrip() {
+        for (bytes, encoding, line_count) in [
+            (b"".as_slice(), Encoding::Utf8, 0),
+            (b"\n", Encoding::Utf8, 1),
+            (b"a\nb\n", Encoding::Utf8, 2),
+            (b"a\nb", Encoding::Utf8, 2),
+            (b"\xEF\xBB\xBFa\r\nb\r\n", Encoding::Utf8, 2),
+            (b"caf\xE9\n", Encoding::Latin1, 1),
+            (b"\xFF\xFEa\0\n\0b\0", Encoding::Utf16Le, 2),
+            (b"\0\x01\x02\n\xFF\xFE\xFD\n\0", Encoding::Latin1, 3),
+            // Byte order marks followed by invalid contents.
+            (b"\xEF\xBB\xBF\xFFabc", Encoding::Latin1, 1),
+            (b"\xFF\xFE\0\xD8a\0", Encoding::Latin1, 1),
+        ] {
+            let file = BytesLineFile::from_bytes(bytes, CrlfBehavior::Keep);
+            assert_eq!(file.encoding(), encoding);
+            assert_eq!(file.line_count(), line_count);
+            assert_eq!(file.to_bytes(LineEndings::Unix).unwrap(), bytes);
+
+            let file = BytesLineFile::from_bytes(bytes, CrlfBehavior::Trim);
+            let windows = file.to_bytes(LineEndings::Windows).unwrap();
+            let file = BytesLineFile::from_bytes(&windows, CrlfBehavior::Trim);
+            assert_eq!(file.to_bytes(LineEndings::Windows).unwrap(), windows);
+        }
+    }
+
+    #[test]
+    fn decode_lines() {
+        let file = BytesLineFile::from_bytes(b"\xEF\xBB\xBFa\r\nb\r\n", CrlfBehavior::Trim);
+        assert!(file.has_bom());
+        assert!(file.has_final_newline());
+        assert_eq!(lines(&file), ["a", "b"]);
+
+        let file = BytesLineFile::from_bytes(b"h\xE9\nno newline", CrlfBehavior::Keep);
+        assert!(!file.has_bom());
+        assert!(!file.has_final_newline());
+        assert_eq!(lines(&file), ["hé", "no newline"]);
+
+        let file = BytesLineFile::with_encoding(
+            b"\0h\0\xE9\0\n\0!",
+            Encoding::Utf16Be,
+            CrlfBehavior::Keep,
+        )
+        .unwrap();
+        assert_eq!(lines(&file), ["hé", "!"]);
+
+        assert!(matches!(
+            BytesLineFile::with_encoding(b"h\xE9\n", Encoding::Utf8, CrlfBehavior::Keep),
+            Err(EncodingError::Decode {
+                encoding: Encoding::Utf8,
+                offset: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn reflow_and_encode() {
+        // A UTF-16 file with a change applied to it is written back
+        // as UTF-16, byte order mark and all.
+        let encode = |text: &str| {
+            let mut bytes = vec![0xFF, 0xFE];
+            bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+            bytes
+        };
+
+        let old = BytesLineFile::from_bytes(&encode("ä\r\nb\r\nc\r\n"), CrlfBehavior::Trim);
+        let new = BytesLineFile::from_bytes(&encode("ä\r\nß\r\nc\r\n"), CrlfBehavior::Trim);
+        assert_eq!(old.encoding(), Encoding::Utf16Le);
+
+        let change_set = diff(&old, &new, Algorithm::Myers);
+        let reflowed = change_set.reflow(&old).unwrap();
+        assert_eq!(
+            old.encode(&reflowed, LineEndings::Windows).unwrap(),
+            encode("ä\r\nß\r\nc\r\n")
+        );
+
+        // Latin-1 can't represent everything.
+        let latin1 = BytesLineFile::from_bytes(b"caf\xE9\n", CrlfBehavior::Keep);
+        let euro = BytesLineFile::from_bytes("5€\n".as_bytes(), CrlfBehavior::Keep);
+        assert!(matches!(
+            latin1.encode(&euro, LineEndings::Unix),
+            Err(EncodingError::Encode {
+                character: '€', ..
+            })
+        ));
+    }
+}
@@ -39,4 +39,9 @@ impl<'a> LineFile<'a> for MemoryLineFile {
             .iter()
             .map(AsRef::as_ref)
     }
+
+    fn lines(&'a self) -> Self::LineIterator {
+        // Unlike the default implementation, this supports empty files.
+        self.lines.iter().map(AsRef::as_ref)
+    }
 }
@@ -16,6 +16,8 @@ impl MmapLineFile {
     /// # Panics
     ///
     /// Panics if the document's contents are not valid UTF-8.
+    /// Use [`BytesLineFile`](crate::BytesLineFile) for files in
+    /// other encodings.
     pub fn from_mmap(mmap: Mmap, crlf_behavior: CrlfBehavior) -> Result<Self, (Mmap, Error)> {
         let mmap = mmap.make_read_only()?;
         let mut line_slices = Vec::new();
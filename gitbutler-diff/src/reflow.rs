@@ -0,0 +1,300 @@
+use crate::{linefile::all_lines, ChangeSet, Hunk, LineFile, LineSpan};
+
+/// A file with a [`ChangeSet`] applied to it, as returned by
+/// [`ChangeSet::reflow`].
+///
+/// Being a [`LineFile`] itself, it can be rendered with
+/// [`LineFile::render`], diffed against other files, or
+/// have further change sets applied to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReflowedFile<'a> {
+    lines: Vec<&'a str>,
+    spans: Vec<Option<LineSpan>>,
+}
+
+impl<'a> ReflowedFile<'a> {
+    /// The line span that each hunk of the change set occupies in
+    /// the reflowed file, in the same order as [`ChangeSet::hunks`].
+    ///
+    /// Hunks that don't insert any lines (pure removals) don't occupy
+    /// any lines, and have no span.
+    #[inline]
+    pub fn spans(&self) -> &[Option<LineSpan>] {
+        &self.spans
+    }
+}
+
+impl<'a, 'b> LineFile<'b> for ReflowedFile<'a>
+where
+    'a: 'b,
+{
+    type LineIterator = std::iter::Copied<std::slice::Iter<'b, &'b str>>;
+
+    #[inline]
+    fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    fn extract(&'b self, span: LineSpan) -> Self::LineIterator {
+        let lines: &'b [&'b str] = &self.lines[span.start()..=span.end()];
+        lines.iter().copied()
+    }
+
+    fn lines(&'b self) -> Self::LineIterator {
+        // The default implementation can't handle empty files, which
+        // are common here (e.g. after removing every line).
+        let lines: &'b [&'b str] = &self.lines;
+        lines.iter().copied()
+    }
+}
+
+/// The error returned by [`ChangeSet::reflow`] when a hunk
+/// lies outside of the base file.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("hunk at line {} lies outside of the base file ({line_count} lines)", .hunk.position())]
+pub struct OutOfBoundsError {
+    /// The hunk that lies outside of the base file.
+    pub hunk: Hunk,
+    /// The number of lines in the base file.
+    pub line_count: usize,
+}
+
+impl ChangeSet {
+    /// Applies the change set to the given base file, removing the
+    /// source span of each hunk and inserting its text in its place,
+    /// and calculates where each hunk's text ended up in the result.
+    ///
+    /// Since the hunks of a change set never conflict, the result
+    /// doesn't depend on the order they were added in; change sets
+    /// of different virtual branches may be combined (if they don't
+    /// conflict with each other, see [`ChangeSet::conflicts`]) and
+    /// reflowed in one go.
+    ///
+    /// Fails if any hunk lies outside of the base file, e.g. if the
+    /// change set was made against a different version of it.
+    pub fn reflow<'a, F: LineFile<'a>>(
+        &'a self,
+        base: &'a F,
+    ) -> Result<ReflowedFile<'a>, OutOfBoundsError> {
+        let base_lines = all_lines(base);
+
+        let mut lines = Vec::with_capacity(base_lines.len());
+        let mut spans = Vec::with_capacity(self.len());
+        let mut index = 0;
+
+        for hunk in self.hunks() {
+            let end = hunk.position() + hunk.removed_count();
+            if end > base_lines.len() {
+                return Err(OutOfBoundsError {
+                    hunk: hunk.clone(),
+                    line_count: base_lines.len(),
+                });
+            }
+
+            lines.extend_from_slice(&base_lines[index..hunk.position()]);
+
+            let start = lines.len();
+            lines.extend(hunk.inserted().iter().map(String::as_str));
+            spans.push((lines.len() > start).then(|| LineSpan::new(start, lines.len() - 1)));
+
+            index = end;
+        }
+
+        lines.extend_from_slice(&base_lines[index..]);
+
+        Ok(ReflowedFile { lines, spans })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Hunk, LineEndings, MemoryLineFile};
+    use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(ToString::to_string).collect()
+    }
+
+    fn render<'a, F: LineFile<'a>>(file: &'a F) -> String {
+        let mut rendered = String::new();
+        file.render(&mut rendered, LineEndings::Unix).unwrap();
+        rendered
+    }
+
+    #[test]
+    fn reflow_hunks() {
+        let base = MemoryLineFile::new(lines(&["a", "b", "c", "d", "e"]));
+        let change_set = ChangeSet::from_hunks([
+            Hunk::insertion(0, lines(&["start"])),
+            Hunk::new(LineSpan::new(1, 2), lines(&["x", "y", "z"])),
+            Hunk::new(LineSpan::new(3, 3), vec![]),
+            Hunk::insertion(5, lines(&["end"])),
+        ])
+        .unwrap();
+
+        let reflowed = change_set.reflow(&base).unwrap();
+        assert_eq!(render(&reflowed), "start\na\nx\ny\nz\ne\nend\n");
+        assert_eq!(
+            reflowed.spans(),
+            &[
+                Some(LineSpan::new(0, 0)),
+                Some(LineSpan::new(2, 4)),
+                None,
+                Some(LineSpan::new(6, 6)),
+            ]
+        );
+    }
+
+    #[test]
+    fn reflow_empty() {
+        let base = MemoryLineFile::new(lines(&["a", "b"]));
+
+        let change_set = ChangeSet::new();
+        let reflowed = change_set.reflow(&base).unwrap();
+        assert_eq!(render(&reflowed), "a\nb\n");
+        assert!(reflowed.spans().is_empty());
+
+        let change_set = ChangeSet::from_hunks([Hunk::new(LineSpan::new(0, 1), vec![])]).unwrap();
+        let reflowed = change_set.reflow(&base).unwrap();
+        assert_eq!(reflowed.line_count(), 0);
+        assert_eq!(render(&reflowed), "");
+    }
+
+    #[test]
+    fn reflow_out_of_bounds() {
+        let base = MemoryLineFile::new(lines(&["a", "b"]));
+        for hunk in [
+            Hunk::insertion(3, vec![]),
+            Hunk::new(LineSpan::new(1, 2), vec![]),
+        ] {
+            let change_set = ChangeSet::from_hunks([hunk.clone()]).unwrap();
+            assert_eq!(
+                change_set.reflow(&base),
+                Err(OutOfBoundsError {
+                    hunk,
+                    line_count: 2
+                })
+            );
+        }
+    }
+
+    /// Generates a random base file, along with a random set of
+    /// non-conflicting hunks against it.
+    fn random_case(rng: &mut StdRng) -> (MemoryLineFile, Vec<Hunk>) {
+        let line_count = rng.gen_range(0..30);
+        let base = MemoryLineFile::new((0..line_count).map(|i| format!("line {i}")).collect());
+
+        let mut hunks = Vec::new();
+        let mut position = 0;
+        let mut id = 0;
+        let mut text = |rng: &mut StdRng| {
+            (0..rng.gen_range(0..4))
+                .map(|_| {
+                    id += 1;
+                    format!("new {id}")
+                })
+                .collect::<Vec<_>>()
+        };
+
+        while position <= line_count {
+            position += rng.gen_range(0..4);
+            if position > line_count {
+                break;
+            }
+
+            if rng.gen_bool(0.5) {
+                hunks.push(Hunk::insertion(position, text(rng)));
+            }
+
+            let removed = rng.gen_range(0..4).min(line_count - position);
+            if removed > 0 {
+                hunks.push(Hunk::new(
+                    LineSpan::new(position, position + removed - 1),
+                    text(rng),
+                ));
+            }
+
+            position += removed.max(1);
+        }
+
+        (base, hunks)
+    }
+
+    /// Moves a hunk that comes after another one to account for
+    /// the other one having been applied.
+    fn shift(hunk: &Hunk, applied: &Hunk) -> Hunk {
+        let shift = |line: usize| line + applied.inserted().len() - applied.removed_count();
+        match hunk.removed() {
+            Some(span) => Hunk::new(
+                LineSpan::new(shift(span.start()), shift(span.end())),
+                hunk.inserted().to_vec(),
+            ),
+            None => Hunk::insertion(shift(hunk.position()), hunk.inserted().to_vec()),
+        }
+    }
+
+    #[test]
+    fn reflow_one_at_a_time() {
+        // Applying hunks one by one (in any order), each time moving the
+        // ones after it to account for it, gives the same result as
+        // reflowing all of them at once.
+        let mut rng = StdRng::seed_from_u64(0x6869_6e6b);
+
+        for _ in 0..500 {
+            let (base, hunks) = random_case(&mut rng);
+            let change_set = ChangeSet::from_hunks(hunks).unwrap();
+            let expected = render(&change_set.reflow(&base).unwrap());
+
+            // Whether a hunk comes after another can't be told from their
+            // positions alone once a removal collapses the lines between
+            // them, so we keep track of their order in the change set.
+            let mut pending = change_set.into_iter().enumerate().collect::<Vec<_>>();
+            pending.shuffle(&mut rng);
+
+            let mut current = all_lines(&base)
+                .into_iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>();
+            while let Some((order, hunk)) = pending.pop() {
+                let file = MemoryLineFile::new(current);
+                let change_set = ChangeSet::from_hunks([hunk.clone()]).unwrap();
+                current = all_lines(&change_set.reflow(&file).unwrap())
+                    .into_iter()
+                    .map(ToString::to_string)
+                    .collect();
+
+                for (other_order, other) in &mut pending {
+                    if *other_order > order {
+                        *other = shift(other, &hunk);
+                    }
+                }
+            }
+
+            assert_eq!(render(&MemoryLineFile::new(current)), expected);
+        }
+    }
+
+    #[test]
+    fn reflow_spans_hold_inserted_text() {
+        let mut rng = StdRng::seed_from_u64(0x7370_616e);
+
+        for _ in 0..500 {
+            let (base, hunks) = random_case(&mut rng);
+            let change_set = ChangeSet::from_hunks(hunks).unwrap();
+            let reflowed = change_set.reflow(&base).unwrap();
+
+            let mut previous_end = None;
+            for (hunk, span) in change_set.hunks().iter().zip(reflowed.spans()) {
+                match span {
+                    Some(span) => {
+                        assert_eq!(reflowed.extract(*span).collect::<Vec<_>>(), hunk.inserted());
+                        assert!(previous_end < Some(span.start()));
+                        previous_end = Some(span.end());
+                    }
+                    None => assert!(hunk.inserted().is_empty()),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,429 @@
+use std::collections::HashSet;
+
+use crate::{linefile::all_lines, LineFile, LineSpan, Signature};
+
+/// A span of lines in a file (e.g. a hunk owned by a virtual branch),
+/// remembered by a [`Signature`] of its contents so that it can be
+/// found again after the file changes.
+///
+/// Line numbers alone go stale as soon as lines are added or removed
+/// above the hunk, and a content hash stops matching on the smallest
+/// edit inside of it. Relocating by similarity survives both.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TrackedHunk {
+    span: LineSpan,
+    signature: Signature,
+    anchors: Vec<u64>,
+}
+
+impl TrackedHunk {
+    /// Starts tracking the given span of the file.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the span is out of bounds.
+    pub fn new<'a, F: LineFile<'a>>(file: &'a F, span: LineSpan) -> Self {
+        let mut anchors = file
+            .extract(span)
+            .map(line_hash)
+            .filter(|&hash| hash != BLANK_LINE_HASH)
+            .collect::<Vec<_>>();
+        anchors.sort_unstable();
+        anchors.dedup();
+
+        Self {
+            span,
+            signature: Signature::from(span_text(file, span)),
+            anchors,
+        }
+    }
+
+    /// Restores a previously tracked hunk, e.g. one that was
+    /// persisted along with a virtual branch.
+    pub fn from_parts(span: LineSpan, signature: Signature, anchors: Vec<u64>) -> Self {
+        Self {
+            span,
+            signature,
+            anchors,
+        }
+    }
+
+    /// The span the hunk was last known to occupy.
+    #[inline]
+    pub fn span(&self) -> LineSpan {
+        self.span
+    }
+
+    /// The signature of the hunk's contents, as of when it
+    /// was last tracked.
+    #[inline]
+    pub fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    /// Hashes of the hunk's non-blank lines (ignoring whitespace),
+    /// as of when it was last tracked, sorted and deduplicated.
+    ///
+    /// The hashes are stable across versions and platforms, so they
+    /// can be persisted along with the signature.
+    #[inline]
+    pub fn anchors(&self) -> &[u64] {
+        &self.anchors
+    }
+
+    /// Finds the hunk in a new version of the file, returning it
+    /// tracked at its new position (with a signature of its new
+    /// contents), or `None` if nothing in the file scores at least
+    /// `threshold` against it (see [`Signature::score_str`]).
+    ///
+    /// To relocate several hunks of the same file without them
+    /// claiming the same lines, use [`relocate_all`] instead.
+    pub fn relocate<'a, F: LineFile<'a>>(&self, file: &'a F, threshold: f64) -> Option<Self> {
+        let span = self.candidates(file, threshold).first()?.span;
+        Some(Self::new(file, span))
+    }
+
+    /// All spans of the file scoring at least `threshold`, best first.
+    ///
+    /// Spans are allowed to be a few lines shorter or longer than the
+    /// hunk, so that lines added or removed inside of it are picked up.
+    /// Equal scores are ranked by how close the span is to the hunk's
+    /// last known position, and then by how close it is in size.
+    ///
+    /// Only spans sharing at least one of the hunk's [anchors](Self::anchors)
+    /// are scored, which rules out most of a large file without computing
+    /// its bigrams. A span that was edited so heavily that none of its
+    /// lines survived (ignoring whitespace) is not found, but it would
+    /// hardly score high enough anyway. Hunks consisting of blank lines
+    /// only have no anchors, and are compared against every span.
+    fn candidates<'a, F: LineFile<'a>>(&self, file: &'a F, threshold: f64) -> Vec<Candidate> {
+        let line_count = self.span.line_count();
+        let slack = (line_count / 4).max(1);
+        let sizes = line_count.saturating_sub(slack).max(1)..=line_count + slack;
+
+        let lines = all_lines(file);
+
+        let anchors = self.anchors.iter().copied().collect::<HashSet<_>>();
+        let hits = lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| anchors.contains(&line_hash(line)))
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+
+        let mut candidates = Vec::new();
+        for size in sizes {
+            if size > lines.len() {
+                break;
+            }
+
+            let last_start = lines.len() - size;
+            let starts = if self.anchors.is_empty() {
+                (0..=last_start).collect::<Vec<_>>()
+            } else {
+                // The windows around nearby hits overlap, and would
+                // otherwise be scored (and returned) more than once.
+                let mut starts = hits
+                    .iter()
+                    .flat_map(|&hit| hit.saturating_sub(size - 1)..=hit.min(last_start))
+                    .collect::<Vec<_>>();
+                starts.sort_unstable();
+                starts.dedup();
+                starts
+            };
+
+            for start in starts {
+                // Whitespace is ignored by signatures anyway, so joining the
+                // lines without separators is fine (and saves some copying).
+                let score = self
+                    .signature
+                    .score_str(lines[start..start + size].concat());
+                if score >= threshold {
+                    candidates.push(Candidate {
+                        span: LineSpan::new(start, start + size - 1),
+                        score,
+                        distance: start.abs_diff(self.span.start()),
+                        size_difference: size.abs_diff(line_count),
+                    });
+                }
+            }
+        }
+
+        candidates.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then(a.distance.cmp(&b.distance))
+                .then(a.size_difference.cmp(&b.size_difference))
+        });
+        candidates
+    }
+}
+
+/// Relocates several hunks of the same file at once (see
+/// [`TrackedHunk::relocate`]), returning the relocated hunks
+/// in the same order.
+///
+/// Relocated hunks never overlap. The best-scoring hunks get to
+/// claim their spans first (earlier hunks first on equal scores);
+/// the others then take their best candidate that doesn't intersect
+/// an already claimed span, so that e.g. two hunks with identical
+/// contents don't both end up at the same copy.
+pub fn relocate_all<'a, F: LineFile<'a>>(
+    hunks: &[TrackedHunk],
+    file: &'a F,
+    threshold: f64,
+) -> Vec<Option<TrackedHunk>> {
+    let candidates = hunks
+        .iter()
+        .map(|hunk| hunk.candidates(file, threshold))
+        .collect::<Vec<_>>();
+
+    let mut order = (0..hunks.len())
+        .filter(|&i| !candidates[i].is_empty())
+        .collect::<Vec<_>>();
+    order.sort_by(|&a, &b| {
+        candidates[b][0]
+            .score
+            .total_cmp(&candidates[a][0].score)
+            .then(hunks[a].span.start().cmp(&hunks[b].span.start()))
+    });
+
+    let mut claimed: Vec<LineSpan> = Vec::new();
+    let mut relocated = vec![None; hunks.len()];
+    for i in order {
+        if let Some(candidate) = candidates[i]
+            .iter()
+            .find(|candidate| !claimed.iter().any(|span| span.intersects(&candidate.span)))
+        {
+            claimed.push(candidate.span);
+            relocated[i] = Some(TrackedHunk::new(file, candidate.span));
+        }
+    }
+
+    relocated
+}
+
+/// A span of the file that a hunk may have moved to.
+struct Candidate {
+    span: LineSpan,
+    score: f64,
+    distance: usize,
+    size_difference: usize,
+}
+
+/// The [`line_hash`] of lines consisting of whitespace only, which
+/// are too common to be useful as anchors.
+const BLANK_LINE_HASH: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// A 64-bit FNV-1a hash of the line's non-whitespace characters.
+///
+/// Unlike the hashers of the standard library, FNV-1a is stable,
+/// so that anchors can be persisted.
+fn line_hash(line: &str) -> u64 {
+    line.chars()
+        .filter(|&c| !c.is_whitespace())
+        .flat_map(|c| {
+            let mut buf = [0; 4];
+            let len = c.encode_utf8(&mut buf).len();
+            buf.into_iter().take(len)
+        })
+        .fold(BLANK_LINE_HASH, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+        })
+}
+
+fn span_text<'a, F: LineFile<'a>>(file: &'a F, span: LineSpan) -> String {
+    file.extract(span).collect::<Vec<_>>().join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemoryLineFile;
+
+    const THRESHOLD: f64 = 0.95;
+
+    fn file(text: &str) -> MemoryLineFile {
+        MemoryLineFile::new(text.lines().map(ToString::to_string).collect())
+    }
+
+    const BASE: &str = "use std::collections::HashMap;
+
+fn main() {
+    let mut scores = HashMap::new();
+    scores.insert(\"blue\", 10);
+    scores.insert(\"yellow\", 50);
+    println!(\"{scores:?}\");
+}
+
+fn unrelated() -> Result<(), Box<dyn std::error::Error>> {
+    Ok(())
+}";
+
+    #[test]
+    fn relocate_unchanged() {
+        let base = file(BASE);
+        let hunk = TrackedHunk::new(&base, LineSpan::new(3, 5));
+        assert_eq!(hunk.relocate(&base, THRESHOLD), Some(hunk));
+    }
+
+    #[test]
+    fn relocate_after_edits_above() {
+        let base = file(BASE);
+        let hunk = TrackedHunk::new(&base, LineSpan::new(3, 5));
+
+        let edited = file(&format!(
+            "//! A new module comment.\n//! Spanning two lines.\n\n{BASE}"
+        ));
+        let relocated = hunk.relocate(&edited, THRESHOLD).unwrap();
+        assert_eq!(relocated.span(), LineSpan::new(6, 8));
+        assert_eq!(relocated.signature(), hunk.signature());
+
+        let edited = file(&BASE.replacen("use std::collections::HashMap;\n\n", "", 1));
+        let relocated = hunk.relocate(&edited, THRESHOLD).unwrap();
+        assert_eq!(relocated.span(), LineSpan::new(1, 3));
+    }
+
+    #[test]
+    fn relocate_after_edits_inside() {
+        let base = file(BASE);
+        let hunk = TrackedHunk::new(&base, LineSpan::new(3, 5));
+
+        let edited = file(&BASE.replace("\"yellow\", 50", "\"green\", 50"));
+        let relocated = hunk.relocate(&edited, THRESHOLD).unwrap();
+        assert_eq!(relocated.span(), LineSpan::new(3, 5));
+
+        let edited = file(&BASE.replace(
+            "    scores.insert(\"blue\", 10);\n",
+            "    scores.insert(\"blue\", 10);\n    scores.insert(\"red\", 5);\n",
+        ));
+        let relocated = hunk.relocate(&edited, THRESHOLD).unwrap();
+        assert_eq!(relocated.span().start(), 3);
+        assert!(relocated.span().end() <= 6);
+    }
+
+    #[test]
+    fn relocate_removed() {
+        let base = file(BASE);
+        let hunk = TrackedHunk::new(&base, LineSpan::new(3, 5));
+
+        let edited = file(&BASE.replace(
+            "    let mut scores = HashMap::new();\n    scores.insert(\"blue\", 10);\n    scores.insert(\"yellow\", 50);\n",
+            "",
+        ));
+        assert_eq!(hunk.relocate(&edited, THRESHOLD), None);
+        assert_eq!(hunk.relocate(&file(""), THRESHOLD), None);
+    }
+
+    #[test]
+    fn relocate_prefers_nearest_copy() {
+        let text = "a = 1\nb = 2\nlet copied = true;\nc = 3\nd = 4\nlet copied = true;\ne = 5";
+        let base = file(text);
+
+        let first = TrackedHunk::new(&base, LineSpan::new(2, 2));
+        let second = TrackedHunk::new(&base, LineSpan::new(5, 5));
+        assert_eq!(
+            first.relocate(&base, THRESHOLD).unwrap().span(),
+            first.span()
+        );
+        assert_eq!(
+            second.relocate(&base, THRESHOLD).unwrap().span(),
+            second.span()
+        );
+    }
+
+    #[test]
+    fn relocate_all_claims_distinct_spans() {
+        let text = "let copied = true;\nlet copied = true;\nsomething else entirely";
+        let base = file(text);
+        let hunks = [
+            TrackedHunk::new(&base, LineSpan::new(0, 0)),
+            TrackedHunk::new(&base, LineSpan::new(1, 1)),
+        ];
+
+        // Removing the first line moves the second copy up, leaving
+        // only one copy for the two hunks to fight over.
+        let edited = file("let copied = true;\nsomething else entirely");
+        let relocated = relocate_all(&hunks, &edited, THRESHOLD);
+        assert_eq!(
+            relocated
+                .iter()
+                .map(|hunk| hunk.as_ref().map(TrackedHunk::span))
+                .collect::<Vec<_>>(),
+            vec![Some(LineSpan::new(0, 0)), None]
+        );
+
+        let edited = file(&format!("// header\n{text}"));
+        let relocated = relocate_all(&hunks, &edited, THRESHOLD);
+        assert_eq!(
+            relocated
+                .iter()
+                .map(|hunk| hunk.as_ref().map(TrackedHunk::span))
+                .collect::<Vec<_>>(),
+            vec![Some(LineSpan::new(1, 1)), Some(LineSpan::new(2, 2))]
+        );
+    }
+
+    #[test]
+    fn restore_from_parts() {
+        let base = file(BASE);
+        let hunk = TrackedHunk::new(&base, LineSpan::new(3, 5));
+        let restored = TrackedHunk::from_parts(
+            hunk.span(),
+            Signature::new(*hunk.signature().as_bytes()),
+            hunk.anchors().to_vec(),
+        );
+        assert_eq!(restored, hunk);
+    }
+
+    #[test]
+    fn relocate_in_empty_file() {
+        let base = file("fn main() {\n\n\n}");
+        for span in [LineSpan::new(0, 0), LineSpan::new(1, 2)] {
+            let hunk = TrackedHunk::new(&base, span);
+            assert_eq!(hunk.relocate(&file(""), 0.0), None);
+            assert_eq!(relocate_all(&[hunk], &file(""), 0.0), vec![None]);
+        }
+    }
+
+    #[test]
+    fn anchors_ignore_whitespace() {
+        let base = file(BASE);
+        let hunk = TrackedHunk::new(&base, LineSpan::new(3, 5));
+        assert_eq!(hunk.anchors().len(), 3);
+
+        // Reindenting the hunk keeps its anchors, so it is still found.
+        let edited = file(&BASE.replace("\n    scores", "\n\tscores"));
+        let relocated = hunk.relocate(&edited, THRESHOLD).unwrap();
+        assert_eq!(relocated.span(), hunk.span());
+        assert_eq!(relocated.anchors(), hunk.anchors());
+
+        // Blank lines are not anchors; a hunk of them alone scans the file.
+        let blank = TrackedHunk::new(&base, LineSpan::new(1, 1));
+        assert!(blank.anchors().is_empty());
+        assert!(blank.relocate(&base, 0.0).is_some());
+    }
+
+    #[test]
+    fn candidates_are_distinct() {
+        let base = file(BASE);
+        let hunk = TrackedHunk::new(&base, LineSpan::new(3, 5));
+        let candidates = hunk.candidates(&base, 0.0);
+        let spans = candidates
+            .iter()
+            .map(|candidate| candidate.span)
+            .collect::<HashSet<_>>();
+        assert_eq!(spans.len(), candidates.len());
+    }
+
+    #[test]
+    fn relocate_skips_spans_without_anchors() {
+        let base = file("let copied = true;\nx");
+        let hunk = TrackedHunk::new(&base, LineSpan::new(0, 0));
+
+        // Scores well above the threshold, but shares no line with the hunk.
+        let edited = file("x\nlet copied = false;");
+        assert!(hunk.signature().score_str("let copied = false;") >= 0.5);
+        assert_eq!(hunk.relocate(&edited, 0.5), None);
+    }
+}
@@ -0,0 +1,1418 @@
+//! Strict parsing and serialization of unified diffs, as produced
+//! by `git diff` (and, without the git-specific headers, by `diff -u`).
+//!
+//! A [`Patch`] is a list of [`FilePatch`]es, each of which has an
+//! optional `diff --git` header line, any number of extended headers
+//! (mode changes, renames, `index` lines, ...), and either text hunks,
+//! a binary marker, or no content at all (e.g. for pure mode changes).
+//! A single [`Hunk`], starting at its `@@` header, can be parsed on its
+//! own, too.
+//!
+//! Diffs are parsed from and serialized to bytes, as the contents of
+//! the files (and thus the lines and section headings of the hunks)
+//! needn't be UTF-8. Paths and the other headers must be, though. The
+//! [`FromStr`] and [`Display`](fmt::Display) implementations are
+//! conveniences for diffs known to be UTF-8; the latter replaces
+//! invalid sequences with `U+FFFD`.
+//!
+//! # Round-trips
+//!
+//! Serializing a parsed value (with e.g. [`Patch::to_bytes`]) and
+//! parsing the result again always gives back an equal value. Patches
+//! produced by git with its default settings are also reproduced byte
+//! for byte; the few non-canonical spellings the parser accepts (such
+//! as `@@ -3,1 +3,1 @@` for `@@ -3 +3 @@`, or unquoted non-ASCII paths)
+//! are serialized the way git would have written them.
+//!
+//! Anything else the parser doesn't understand is rejected, rather
+//! than skipped; that includes lines outside of file patches (e.g. the
+//! commit message of a `git format-patch` email) and `GIT binary patch`
+//! payloads.
+
+use std::{borrow::Cow, fmt, io, str::FromStr};
+
+use crate::{ChangeSet, ConflictError, LineSpan};
+
+const NO_NEWLINE_MARKER: &[u8] = b"\\ No newline at end of file";
+
+/// A unified diff, possibly spanning several files.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Patch {
+    /// The patches of the individual files, in order.
+    pub files: Vec<FilePatch>,
+}
+
+/// The changes to a single file.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FilePatch {
+    /// The old and new paths of the `diff --git a/<old> b/<new>` line,
+    /// without the `a/` and `b/` prefixes. Absent for plain unified
+    /// diffs, which start right at the `---` line.
+    pub git_paths: Option<(String, String)>,
+    /// The extended header lines following the `diff --git` line,
+    /// in order.
+    pub extended_headers: Vec<ExtendedHeader>,
+    /// The changes to the file's contents.
+    pub content: Content,
+}
+
+/// An extended header line of a git file patch.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ExtendedHeader {
+    /// `old mode <mode>`
+    OldMode(u32),
+    /// `new mode <mode>`
+    NewMode(u32),
+    /// `deleted file mode <mode>`
+    DeletedFileMode(u32),
+    /// `new file mode <mode>`
+    NewFileMode(u32),
+    /// `copy from <path>`
+    CopyFrom(String),
+    /// `copy to <path>`
+    CopyTo(String),
+    /// `rename from <path>`
+    RenameFrom(String),
+    /// `rename to <path>`
+    RenameTo(String),
+    /// `similarity index <percentage>%`
+    Similarity(u8),
+    /// `dissimilarity index <percentage>%`
+    Dissimilarity(u8),
+    /// `index <old>..<new>[ <mode>]`, with abbreviated object ids.
+    Index {
+        /// The (abbreviated) object id of the old blob.
+        old: String,
+        /// The (abbreviated) object id of the new blob.
+        new: String,
+        /// The mode of the file, if it didn't change.
+        mode: Option<u32>,
+    },
+}
+
+/// The changes to a file's contents.
+///
+/// Paths are given without their `a/` and `b/` prefixes, and
+/// are `None` for `/dev/null` (i.e. added or deleted files).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Content {
+    /// The contents didn't change (e.g. a pure rename or mode
+    /// change, or an added or deleted empty file).
+    None,
+    /// `Binary files <old> and <new> differ`
+    Binary {
+        /// The path of the old file.
+        old_path: Option<String>,
+        /// The path of the new file.
+        new_path: Option<String>,
+    },
+    /// `---`/`+++` lines followed by one or more hunks.
+    Text {
+        /// The path of the old file.
+        old_path: Option<String>,
+        /// The path of the new file.
+        new_path: Option<String>,
+        /// The hunks, in order.
+        hunks: Vec<Hunk>,
+    },
+}
+
+/// A single hunk of a unified diff, starting at its `@@` header.
+///
+/// Unlike [`crate::Hunk`], a unified hunk may contain several
+/// changes separated by context lines; see [`Hunk::changes`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Hunk {
+    /// The 1-based line number of the first old line of the hunk,
+    /// or, if the hunk has no old lines, of the line before them
+    /// (0 if there is none).
+    pub old_start: usize,
+    /// The same as `old_start`, but for the new lines.
+    pub new_start: usize,
+    /// The text following the `@@` header (usually the name of the
+    /// enclosing function), without the separating space.
+    pub section: Option<Vec<u8>>,
+    /// The lines of the hunk.
+    pub lines: Vec<Line>,
+}
+
+/// A single line of a [`Hunk`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Line {
+    /// Whether the line was kept, removed or added.
+    pub kind: LineKind,
+    /// The text of the line, without its prefix and line ending.
+    pub text: Vec<u8>,
+    /// Whether the line is the last line of its file and isn't
+    /// terminated by a newline, i.e. followed by a
+    /// `\ No newline at end of file` marker.
+    ///
+    /// Only the last old line and the last new line of a hunk (which
+    /// may be one and the same context line) can lack a newline. The
+    /// parser rejects the marker anywhere else; a hunk constructed with
+    /// it set on any other line serializes to a diff that doesn't parse.
+    pub missing_newline: bool,
+}
+
+/// The kind of a [`Line`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LineKind {
+    /// A context line (` `), present in both the old and new file.
+    Context,
+    /// A removed line (`-`), only present in the old file.
+    Removed,
+    /// An added line (`+`), only present in the new file.
+    Added,
+}
+
+impl LineKind {
+    fn prefix(self) -> u8 {
+        match self {
+            LineKind::Context => b' ',
+            LineKind::Removed => b'-',
+            LineKind::Added => b'+',
+        }
+    }
+
+    fn is_old(self) -> bool {
+        self != LineKind::Added
+    }
+
+    fn is_new(self) -> bool {
+        self != LineKind::Removed
+    }
+}
+
+impl Hunk {
+    /// The number of old lines (context and removed lines) in the hunk.
+    pub fn old_lines(&self) -> usize {
+        self.lines.iter().filter(|line| line.kind.is_old()).count()
+    }
+
+    /// The number of new lines (context and added lines) in the hunk.
+    pub fn new_lines(&self) -> usize {
+        self.lines.iter().filter(|line| line.kind.is_new()).count()
+    }
+
+    /// Returns the hunk that undoes this one, i.e. with its old and
+    /// new sides swapped.
+    pub fn reverse(&self) -> Self {
+        let mut lines = Vec::with_capacity(self.lines.len());
+        let mut added = Vec::new();
+
+        // Within each run of changed lines, removed lines always
+        // come before added ones.
+        for line in &self.lines {
+            let kind = match line.kind {
+                LineKind::Context => {
+                    lines.append(&mut added);
+                    LineKind::Context
+                }
+                LineKind::Removed => {
+                    added.push(Line {
+                        kind: LineKind::Added,
+                        ..line.clone()
+                    });
+                    continue;
+                }
+                LineKind::Added => LineKind::Removed,
+            };
+            lines.push(Line {
+                kind,
+                ..line.clone()
+            });
+        }
+        lines.append(&mut added);
+
+        Self {
+            old_start: self.new_start,
+            new_start: self.old_start,
+            section: self.section.clone(),
+            lines,
+        }
+    }
+
+    /// The hunk's changes in terms of hunk theory, against the old file.
+    ///
+    /// Each run of removed and/or added lines becomes one [`crate::Hunk`];
+    /// context lines are dropped, as is the information about missing
+    /// newlines at the end of the file.
+    ///
+    /// Fails if the hunk has old lines but starts at line 0 (which the
+    /// parser rejects, but a constructed hunk may do), or if one of its
+    /// added lines isn't valid UTF-8.
+    pub fn changes(&self) -> Result<ChangeSet, ChangesError> {
+        let mut change_set = ChangeSet::new();
+        self.add_changes(&mut change_set)?;
+        Ok(change_set)
+    }
+
+    fn add_changes(&self, change_set: &mut ChangeSet) -> Result<(), ChangesError> {
+        // `old_start` is 1-based, except for hunks without old lines,
+        // which give the line *after which* their lines are inserted.
+        let mut position = match (self.old_start, self.old_lines()) {
+            (old_start, 0) => old_start,
+            (0, _) => return Err(ChangesError::ZeroStart),
+            (old_start, _) => old_start - 1,
+        };
+
+        let mut lines = self.lines.iter().peekable();
+        while let Some(line) = lines.next() {
+            if line.kind == LineKind::Context {
+                position += 1;
+                continue;
+            }
+
+            let mut removed = 0;
+            let mut inserted = Vec::new();
+            let mut line = Some(line);
+            while let Some(changed) = line {
+                match changed.kind {
+                    LineKind::Removed => removed += 1,
+                    LineKind::Added => inserted.push(
+                        String::from_utf8(changed.text.clone())
+                            .map_err(|_| ChangesError::NonUtf8Line)?,
+                    ),
+                    LineKind::Context => unreachable!(),
+                }
+                line = lines.next_if(|line| line.kind != LineKind::Context);
+            }
+
+            change_set.insert(if removed == 0 {
+                crate::Hunk::insertion(position, inserted)
+            } else {
+                crate::Hunk::new(LineSpan::new(position, position + removed - 1), inserted)
+            })?;
+            position += removed;
+        }
+
+        Ok(())
+    }
+}
+
+impl FilePatch {
+    /// The changes of all of the file's hunks, in terms of hunk theory
+    /// (see [`Hunk::changes`]). Binary and empty file patches don't
+    /// have any changes.
+    ///
+    /// Fails if the hunks overlap, or for the same reasons as
+    /// [`Hunk::changes`].
+    pub fn changes(&self) -> Result<ChangeSet, ChangesError> {
+        let mut change_set = ChangeSet::new();
+        if let Content::Text { hunks, .. } = &self.content {
+            for hunk in hunks {
+                hunk.add_changes(&mut change_set)?;
+            }
+        }
+        Ok(change_set)
+    }
+}
+
+/// Returned when the changes of a unified diff can't be expressed
+/// in terms of hunk theory.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ChangesError {
+    /// A hunk has old lines, but starts at line 0; only hunks
+    /// without old lines can.
+    #[error("hunk with old lines starts at line 0")]
+    ZeroStart,
+    /// An added line isn't valid UTF-8, which the lines of
+    /// a [`crate::Hunk`] have to be.
+    #[error("added line is not valid UTF-8")]
+    NonUtf8Line,
+    /// Two hunks overlap.
+    #[error(transparent)]
+    Conflict(#[from] ConflictError),
+}
+
+/// Returned when parsing a malformed unified diff.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("line {line}: {kind}")]
+pub struct ParseError {
+    /// The 1-based number of the offending line.
+    pub line: usize,
+    /// What went wrong.
+    pub kind: ParseErrorKind,
+}
+
+/// The kind of a [`ParseError`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ParseErrorKind {
+    /// The input doesn't end with a newline.
+    #[error("line is not terminated by a newline")]
+    UnterminatedLine,
+    /// A line that isn't valid at this point of the diff.
+    #[error("unexpected line")]
+    UnexpectedLine,
+    /// The input ended in the middle of a file patch or hunk.
+    #[error("unexpected end of input")]
+    UnexpectedEnd,
+    /// A malformed `@@` hunk header.
+    #[error("invalid hunk header")]
+    InvalidHunkHeader,
+    /// A `\ No newline at end of file` marker that doesn't follow
+    /// the last old or new line of a hunk.
+    #[error("misplaced \"no newline at end of file\" marker")]
+    MisplacedNoNewline,
+    /// A malformed (e.g. badly quoted, or unprefixed) path.
+    #[error("invalid path")]
+    InvalidPath,
+    /// A malformed extended header line.
+    #[error("invalid extended header")]
+    InvalidExtendedHeader,
+    /// Something that is valid, but not supported by the parser.
+    #[error("unsupported: {0}")]
+    Unsupported(&'static str),
+}
+
+impl Patch {
+    /// Parses a unified diff.
+    pub fn parse(bytes: &[u8]) -> Result<Self, ParseError> {
+        let mut parser = Parser::new(bytes)?;
+        let mut files = Vec::new();
+        while parser.peek().is_some() {
+            files.push(parser.file_patch()?);
+        }
+        Ok(Self { files })
+    }
+
+    /// Serializes the diff.
+    pub fn write_to<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.files.iter().try_for_each(|file| file.write_to(writer))
+    }
+
+    /// Serializes the diff into a new buffer.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        to_bytes(|buffer| self.write_to(buffer))
+    }
+}
+
+impl FilePatch {
+    /// Parses the unified diff of a single file.
+    pub fn parse(bytes: &[u8]) -> Result<Self, ParseError> {
+        let mut parser = Parser::new(bytes)?;
+        let file_patch = parser.file_patch()?;
+        parser.end()?;
+        Ok(file_patch)
+    }
+
+    /// Serializes the file patch.
+    pub fn write_to<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        if let Some((old, new)) = &self.git_paths {
+            writer.write_all(b"diff --git ")?;
+            write_prefixed_path(writer, "a/", old)?;
+            writer.write_all(b" ")?;
+            write_prefixed_path(writer, "b/", new)?;
+            writer.write_all(b"\n")?;
+        }
+
+        for header in &self.extended_headers {
+            writeln!(writer, "{header}")?;
+        }
+
+        match &self.content {
+            Content::None => Ok(()),
+            Content::Binary { old_path, new_path } => {
+                writer.write_all(b"Binary files ")?;
+                write_file_path(writer, "a/", old_path.as_deref())?;
+                writer.write_all(b" and ")?;
+                write_file_path(writer, "b/", new_path.as_deref())?;
+                writer.write_all(b" differ\n")
+            }
+            Content::Text {
+                old_path,
+                new_path,
+                hunks,
+            } => {
+                for (marker, prefix, path) in [("---", "a/", old_path), ("+++", "b/", new_path)] {
+                    write!(writer, "{marker} ")?;
+                    write_file_path(writer, prefix, path.as_deref())?;
+                    // Like git, mark the end of names containing spaces.
+                    if path.as_deref().is_some_and(|path| path.contains(' ')) {
+                        writer.write_all(b"\t")?;
+                    }
+                    writer.write_all(b"\n")?;
+                }
+                hunks.iter().try_for_each(|hunk| hunk.write_to(writer))
+            }
+        }
+    }
+
+    /// Serializes the file patch into a new buffer.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        to_bytes(|buffer| self.write_to(buffer))
+    }
+}
+
+impl Hunk {
+    /// Parses a single hunk, starting at its `@@` header.
+    pub fn parse(bytes: &[u8]) -> Result<Self, ParseError> {
+        let mut parser = Parser::new(bytes)?;
+        let hunk = parser.hunk()?;
+        parser.end()?;
+        Ok(hunk)
+    }
+
+    /// Serializes the hunk.
+    pub fn write_to<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        let range = |start: usize, count: usize| match count {
+            1 => start.to_string(),
+            count => format!("{start},{count}"),
+        };
+
+        write!(
+            writer,
+            "@@ -{} +{} @@",
+            range(self.old_start, self.old_lines()),
+            range(self.new_start, self.new_lines())
+        )?;
+        if let Some(section) = &self.section {
+            writer.write_all(b" ")?;
+            writer.write_all(section)?;
+        }
+        writer.write_all(b"\n")?;
+
+        for line in &self.lines {
+            writer.write_all(&[line.kind.prefix()])?;
+            writer.write_all(&line.text)?;
+            writer.write_all(b"\n")?;
+            if line.missing_newline {
+                writer.write_all(NO_NEWLINE_MARKER)?;
+                writer.write_all(b"\n")?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Serializes the hunk into a new buffer.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        to_bytes(|buffer| self.write_to(buffer))
+    }
+}
+
+fn to_bytes(write: impl FnOnce(&mut Vec<u8>) -> io::Result<()>) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    write(&mut buffer).expect("writing to a vector never fails");
+    buffer
+}
+
+impl FromStr for Patch {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s.as_bytes())
+    }
+}
+
+impl FromStr for FilePatch {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s.as_bytes())
+    }
+}
+
+impl FromStr for Hunk {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s.as_bytes())
+    }
+}
+
+impl fmt::Display for Patch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&String::from_utf8_lossy(&self.to_bytes()))
+    }
+}
+
+impl fmt::Display for FilePatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&String::from_utf8_lossy(&self.to_bytes()))
+    }
+}
+
+impl fmt::Display for Hunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&String::from_utf8_lossy(&self.to_bytes()))
+    }
+}
+
+struct Parser<'a> {
+    lines: Vec<&'a [u8]>,
+    index: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(bytes: &'a [u8]) -> Result<Self, ParseError> {
+        let mut lines = bytes.split(|&b| b == b'\n').collect::<Vec<_>>();
+        // Every line is terminated, so splitting yields a final
+        // empty slice, unless the last line is unterminated.
+        if lines.pop() != Some(b"") {
+            return Err(ParseError {
+                line: lines.len() + 1,
+                kind: ParseErrorKind::UnterminatedLine,
+            });
+        }
+        Ok(Self { lines, index: 0 })
+    }
+
+    fn peek(&self) -> Option<&'a [u8]> {
+        self.lines.get(self.index).copied()
+    }
+
+    fn next(&mut self) -> Result<&'a [u8], ParseError> {
+        let line = self
+            .peek()
+            .ok_or_else(|| self.error(ParseErrorKind::UnexpectedEnd))?;
+        self.index += 1;
+        Ok(line)
+    }
+
+    fn end(&self) -> Result<(), ParseError> {
+        match self.peek() {
+            Some(_) => Err(self.error(ParseErrorKind::UnexpectedLine)),
+            None => Ok(()),
+        }
+    }
+
+    /// An error at the next line (i.e. the one last peeked at).
+    fn error(&self, kind: ParseErrorKind) -> ParseError {
+        ParseError {
+            line: self.index + 1,
+            kind,
+        }
+    }
+
+    /// An error at the line last returned by [`Parser::next`].
+    fn error_before(&self, kind: ParseErrorKind) -> ParseError {
+        ParseError {
+            line: self.index,
+            kind,
+        }
+    }
+
+    fn file_patch(&mut self) -> Result<FilePatch, ParseError> {
+        let line = self.peek().unwrap_or_default();
+
+        let Some(paths) = line.strip_prefix(b"diff --git ") else {
+            if !line.starts_with(b"--- ") {
+                return Err(self.error(ParseErrorKind::UnexpectedLine));
+            }
+            return Ok(FilePatch {
+                git_paths: None,
+                extended_headers: Vec::new(),
+                content: self.text_content()?,
+            });
+        };
+        let git_paths = as_str(paths)
+            .and_then(parse_git_paths)
+            .ok_or_else(|| self.error(ParseErrorKind::InvalidPath))?;
+        self.index += 1;
+
+        let mut extended_headers = Vec::new();
+        while let Some(line) = self.peek() {
+            if line.starts_with(b"diff --git ")
+                || line.starts_with(b"--- ")
+                || line.starts_with(b"Binary files ")
+                || line == b"GIT binary patch"
+            {
+                break;
+            }
+            extended_headers.push(
+                as_str(line)
+                    .and_then(parse_extended_header)
+                    .ok_or_else(|| self.error(ParseErrorKind::InvalidExtendedHeader))?,
+            );
+            self.index += 1;
+        }
+
+        let content = match self.peek() {
+            Some(line) if line.starts_with(b"--- ") => self.text_content()?,
+            Some(line) if line.starts_with(b"Binary files ") => {
+                let (old_path, new_path) = as_str(line)
+                    .and_then(|line| line.strip_prefix("Binary files "))
+                    .and_then(|paths| paths.strip_suffix(" differ"))
+                    .and_then(parse_binary_paths)
+                    .ok_or_else(|| self.error(ParseErrorKind::InvalidPath))?;
+                self.index += 1;
+                Content::Binary { old_path, new_path }
+            }
+            Some(b"GIT binary patch") => {
+                return Err(self.error(ParseErrorKind::Unsupported("binary patch payloads")))
+            }
+            _ => Content::None,
+        };
+
+        Ok(FilePatch {
+            git_paths: Some(git_paths),
+            extended_headers,
+            content,
+        })
+    }
+
+    fn text_content(&mut self) -> Result<Content, ParseError> {
+        let old_path = self.file_line("--- ", "a/")?;
+        let new_path = self.file_line("+++ ", "b/")?;
+
+        let mut hunks = vec![self.hunk()?];
+        while self.peek().is_some_and(|line| line.starts_with(b"@@ ")) {
+            hunks.push(self.hunk()?);
+        }
+
+        Ok(Content::Text {
+            old_path,
+            new_path,
+            hunks,
+        })
+    }
+
+    fn file_line(&mut self, marker: &str, prefix: &str) -> Result<Option<String>, ParseError> {
+        let line = self.next()?;
+        let path = as_str(line)
+            .and_then(|line| line.strip_prefix(marker))
+            .ok_or_else(|| self.error_before(ParseErrorKind::UnexpectedLine))?;
+        // git appends a tab to unquoted names containing spaces.
+        let path = path.strip_suffix('\t').unwrap_or(path);
+        parse_prefixed_path(path, prefix)
+            .ok_or_else(|| self.error_before(ParseErrorKind::InvalidPath))
+    }
+
+    fn hunk(&mut self) -> Result<Hunk, ParseError> {
+        let header = self.next()?;
+        let ((old_start, old_lines), (new_start, new_lines), section) =
+            parse_hunk_header(header)
+                .ok_or_else(|| self.error_before(ParseErrorKind::InvalidHunkHeader))?;
+
+        let mut lines = Vec::with_capacity(old_lines.max(new_lines));
+        let (mut old_seen, mut new_seen) = (0, 0);
+        while old_seen < old_lines || new_seen < new_lines {
+            let line = self.next()?;
+            let (kind, text) = match line.split_first() {
+                Some((b' ', text)) => (LineKind::Context, text),
+                Some((b'-', text)) => (LineKind::Removed, text),
+                Some((b'+', text)) => (LineKind::Added, text),
+                _ => return Err(self.error_before(ParseErrorKind::UnexpectedLine)),
+            };
+
+            if kind.is_old() {
+                old_seen += 1;
+            }
+            if kind.is_new() {
+                new_seen += 1;
+            }
+            if old_seen > old_lines || new_seen > new_lines {
+                return Err(self.error_before(ParseErrorKind::UnexpectedLine));
+            }
+
+            let missing_newline = self.peek() == Some(NO_NEWLINE_MARKER);
+            if missing_newline {
+                self.index += 1;
+                // Only the last line of either file can lack a newline.
+                if (kind.is_old() && old_seen < old_lines)
+                    || (kind.is_new() && new_seen < new_lines)
+                {
+                    return Err(self.error_before(ParseErrorKind::MisplacedNoNewline));
+                }
+            }
+
+            lines.push(Line {
+                kind,
+                text: text.to_vec(),
+                missing_newline,
+            });
+        }
+
+        Ok(Hunk {
+            old_start,
+            new_start,
+            section,
+            lines,
+        })
+    }
+}
+
+/// Parses `@@ -<old> +<new> @@[ <section>]`, returning the old start
+/// and line count, the new start and line count, and the section.
+fn parse_hunk_header(line: &[u8]) -> Option<(Range, Range, Option<Vec<u8>>)> {
+    let rest = line.strip_prefix(b"@@ -")?;
+    let end = rest.windows(3).position(|window| window == b" @@")?;
+    let (old, new) = as_str(&rest[..end])?.split_once(" +")?;
+    let section = match &rest[end + 3..] {
+        [] => None,
+        [b' ', section @ ..] => Some(section.to_vec()),
+        _ => return None,
+    };
+
+    Some((parse_range(old)?, parse_range(new)?, section))
+}
+
+/// The start and line count of either side of a hunk.
+type Range = (usize, usize);
+
+/// Parses `<start>[,<count>]`; the count defaults to 1.
+fn parse_range(range: &str) -> Option<Range> {
+    let (start, count) = match range.split_once(',') {
+        Some((start, count)) => (parse_number(start)?, parse_number(count)?),
+        None => (parse_number(range)?, 1),
+    };
+    // Line numbers are 1-based; 0 is only valid for empty ranges.
+    (start > 0 || count == 0).then_some((start, count))
+}
+
+/// The line as a string, if it's valid UTF-8 (as all header lines,
+/// unlike the lines of hunks, have to be).
+fn as_str(line: &[u8]) -> Option<&str> {
+    std::str::from_utf8(line).ok()
+}
+
+fn parse_number<T: FromStr>(s: &str) -> Option<T> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    s.parse().ok()
+}
+
+fn parse_mode(s: &str) -> Option<u32> {
+    if s.is_empty() || !s.bytes().all(|b| (b'0'..=b'7').contains(&b)) {
+        return None;
+    }
+    u32::from_str_radix(s, 8).ok()
+}
+
+fn parse_percentage(s: &str) -> Option<u8> {
+    parse_number(s.strip_suffix('%')?).filter(|&percentage| percentage <= 100)
+}
+
+fn parse_object_id(s: &str) -> Option<String> {
+    (!s.is_empty() && s.bytes().all(|b| b.is_ascii_hexdigit())).then(|| s.to_string())
+}
+
+fn parse_extended_header(line: &str) -> Option<ExtendedHeader> {
+    let (keyword, value) = [
+        "old mode ",
+        "new mode ",
+        "deleted file mode ",
+        "new file mode ",
+        "copy from ",
+        "copy to ",
+        "rename from ",
+        "rename to ",
+        "similarity index ",
+        "dissimilarity index ",
+        "index ",
+    ]
+    .into_iter()
+    .find_map(|keyword| Some((keyword, line.strip_prefix(keyword)?)))?;
+
+    Some(match keyword {
+        "old mode " => ExtendedHeader::OldMode(parse_mode(value)?),
+        "new mode " => ExtendedHeader::NewMode(parse_mode(value)?),
+        "deleted file mode " => ExtendedHeader::DeletedFileMode(parse_mode(value)?),
+        "new file mode " => ExtendedHeader::NewFileMode(parse_mode(value)?),
+        "copy from " => ExtendedHeader::CopyFrom(parse_whole_path(value)?),
+        "copy to " => ExtendedHeader::CopyTo(parse_whole_path(value)?),
+        "rename from " => ExtendedHeader::RenameFrom(parse_whole_path(value)?),
+        "rename to " => ExtendedHeader::RenameTo(parse_whole_path(value)?),
+        "similarity index " => ExtendedHeader::Similarity(parse_percentage(value)?),
+        "dissimilarity index " => ExtendedHeader::Dissimilarity(parse_percentage(value)?),
+        "index " => {
+            let (ids, mode) = match value.split_once(' ') {
+                Some((ids, mode)) => (ids, Some(parse_mode(mode)?)),
+                None => (value, None),
+            };
+            let (old, new) = ids.split_once("..")?;
+            ExtendedHeader::Index {
+                old: parse_object_id(old)?,
+                new: parse_object_id(new)?,
+                mode,
+            }
+        }
+        _ => unreachable!(),
+    })
+}
+
+/// Parses the paths of a `diff --git` line.
+fn parse_git_paths(paths: &str) -> Option<(String, String)> {
+    let strip = |old: String, new: String| {
+        Some((
+            old.strip_prefix("a/")?.to_string(),
+            new.strip_prefix("b/")?.to_string(),
+        ))
+    };
+
+    if paths.starts_with('"') {
+        let (old, rest) = unquote(paths)?;
+        let new = parse_whole_path(rest.strip_prefix(' ')?)?;
+        return strip(old, new);
+    }
+
+    if paths.ends_with('"') {
+        if let Some(index) = paths.find(" \"b/") {
+            let new = parse_whole_path(&paths[index + 1..])?;
+            return strip(paths[..index].to_string(), new);
+        }
+    }
+
+    // Unquoted paths may contain spaces, making the split ambiguous.
+    // Like git, we prefer the split that gives the same path twice.
+    let splits = paths
+        .match_indices(" b/")
+        .map(|(index, _)| (&paths[..index], &paths[index + 1..]))
+        .collect::<Vec<_>>();
+    let (old, new) = splits
+        .iter()
+        .find(|(old, new)| old.strip_prefix("a/") == new.strip_prefix("b/"))
+        .or_else(|| (splits.len() == 1).then(|| &splits[0]))?;
+    strip(old.to_string(), new.to_string())
+}
+
+/// Parses the paths of a `Binary files <old> and <new> differ` line.
+fn parse_binary_paths(paths: &str) -> Option<(Option<String>, Option<String>)> {
+    paths.match_indices(" and ").find_map(|(index, separator)| {
+        Some((
+            parse_prefixed_path(&paths[..index], "a/")?,
+            parse_prefixed_path(&paths[index + separator.len()..], "b/")?,
+        ))
+    })
+}
+
+/// Parses a (possibly quoted) path with the given prefix, or `/dev/null`.
+fn parse_prefixed_path(path: &str, prefix: &str) -> Option<Option<String>> {
+    if path == "/dev/null" {
+        return Some(None);
+    }
+    Some(Some(
+        parse_whole_path(path)?.strip_prefix(prefix)?.to_string(),
+    ))
+}
+
+/// Parses a (possibly quoted) path spanning the whole string.
+fn parse_whole_path(path: &str) -> Option<String> {
+    if path.starts_with('"') {
+        let (path, rest) = unquote(path)?;
+        rest.is_empty().then_some(path)
+    } else {
+        (!path.is_empty()).then(|| path.to_string())
+    }
+}
+
+/// Parses a C-style quoted string at the start of `s`, as written by
+/// git for paths with special characters, returning the unquoted string
+/// and the rest of `s`.
+fn unquote(s: &str) -> Option<(String, &str)> {
+    let mut bytes = Vec::new();
+    let mut chars = s.strip_prefix('"')?.char_indices();
+    let rest = loop {
+        let (index, c) = chars.next()?;
+        match c {
+            '"' => break &s[index + 2..],
+            '\\' => {
+                let (_, escaped) = chars.next()?;
+                bytes.push(match escaped {
+                    'a' => 0x07,
+                    'b' => 0x08,
+                    't' => b'\t',
+                    'n' => b'\n',
+                    'v' => 0x0b,
+                    'f' => 0x0c,
+                    'r' => b'\r',
+                    '"' => b'"',
+                    '\\' => b'\\',
+                    '0'..='3' => {
+                        let mut value = escaped.to_digit(8)?;
+                        for _ in 0..2 {
+                            value = value * 8 + chars.next()?.1.to_digit(8)?;
+                        }
+                        value as u8
+                    }
+                    _ => return None,
+                });
+            }
+            c => bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    };
+    Some((String::from_utf8(bytes).ok()?, rest))
+}
+
+/// Quotes a path the way git does (with `core.quotePath` enabled),
+/// if it contains any special characters.
+fn quote(path: &str) -> Cow<'_, str> {
+    let needs_quoting = |b: u8| !(0x20..0x7f).contains(&b) || b == b'"' || b == b'\\';
+    if !path.bytes().any(needs_quoting) {
+        return Cow::Borrowed(path);
+    }
+
+    let mut quoted = String::with_capacity(path.len() + 2);
+    quoted.push('"');
+    for b in path.bytes() {
+        match b {
+            0x07 => quoted.push_str("\\a"),
+            0x08 => quoted.push_str("\\b"),
+            b'\t' => quoted.push_str("\\t"),
+            b'\n' => quoted.push_str("\\n"),
+            0x0b => quoted.push_str("\\v"),
+            0x0c => quoted.push_str("\\f"),
+            b'\r' => quoted.push_str("\\r"),
+            b'"' => quoted.push_str("\\\""),
+            b'\\' => quoted.push_str("\\\\"),
+            b if needs_quoting(b) => quoted.push_str(&format!("\\{b:03o}")),
+            b => quoted.push(b as char),
+        }
+    }
+    quoted.push('"');
+    Cow::Owned(quoted)
+}
+
+fn write_prefixed_path<W: io::Write>(writer: &mut W, prefix: &str, path: &str) -> io::Result<()> {
+    writer.write_all(quote(&format!("{prefix}{path}")).as_bytes())
+}
+
+fn write_file_path<W: io::Write>(
+    writer: &mut W,
+    prefix: &str,
+    path: Option<&str>,
+) -> io::Result<()> {
+    match path {
+        None => writer.write_all(b"/dev/null"),
+        Some(path) => write_prefixed_path(writer, prefix, path),
+    }
+}
+
+impl fmt::Display for ExtendedHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExtendedHeader::OldMode(mode) => write!(f, "old mode {mode:06o}"),
+            ExtendedHeader::NewMode(mode) => write!(f, "new mode {mode:06o}"),
+            ExtendedHeader::DeletedFileMode(mode) => write!(f, "deleted file mode {mode:06o}"),
+            ExtendedHeader::NewFileMode(mode) => write!(f, "new file mode {mode:06o}"),
+            ExtendedHeader::CopyFrom(path) => write!(f, "copy from {}", quote(path)),
+            ExtendedHeader::CopyTo(path) => write!(f, "copy to {}", quote(path)),
+            ExtendedHeader::RenameFrom(path) => write!(f, "rename from {}", quote(path)),
+            ExtendedHeader::RenameTo(path) => write!(f, "rename to {}", quote(path)),
+            ExtendedHeader::Similarity(percentage) => write!(f, "similarity index {percentage}%"),
+            ExtendedHeader::Dissimilarity(percentage) => {
+                write!(f, "dissimilarity index {percentage}%")
+            }
+            ExtendedHeader::Index { old, new, mode } => {
+                write!(f, "index {old}..{new}")?;
+                match mode {
+                    Some(mode) => write!(f, " {mode:06o}"),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses the text as `T`, checking that it serializes back to the
+    /// exact same text.
+    fn roundtrip<T>(text: &str) -> T
+    where
+        T: FromStr<Err = ParseError> + fmt::Display,
+    {
+        let parsed = text.parse::<T>().unwrap();
+        assert_eq!(parsed.to_string(), text);
+        parsed
+    }
+
+    fn line(kind: LineKind, text: &str) -> Line {
+        Line {
+            kind,
+            text: text.as_bytes().to_vec(),
+            missing_newline: false,
+        }
+    }
+
+    #[test]
+    fn parse_hunk() {
+        let hunk = roundtrip::<Hunk>(
+            "@@ -1,4 +1,4 @@ fn main() {\n a\n-b\n+B\n c\n-d\n\\ No newline at end of file\n+d\n",
+        );
+        assert_eq!(hunk.old_start, 1);
+        assert_eq!(hunk.new_start, 1);
+        assert_eq!(hunk.old_lines(), 4);
+        assert_eq!(hunk.new_lines(), 4);
+        assert_eq!(hunk.section.as_deref(), Some(b"fn main() {".as_slice()));
+        assert_eq!(
+            hunk.lines,
+            vec![
+                line(LineKind::Context, "a"),
+                line(LineKind::Removed, "b"),
+                line(LineKind::Added, "B"),
+                line(LineKind::Context, "c"),
+                Line {
+                    missing_newline: true,
+                    ..line(LineKind::Removed, "d")
+                },
+                line(LineKind::Added, "d"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_non_utf8_hunk() {
+        // Latin-1, as in e.g. old source files.
+        let text = b"@@ -1 +1 @@ caf\xe9()\n-caf\xe9\n+caf\xc3\xa9\n";
+        let hunk = Hunk::parse(text).unwrap();
+        assert_eq!(hunk.to_bytes(), text);
+        assert_eq!(hunk.section.as_deref(), Some(b"caf\xe9()".as_slice()));
+        assert_eq!(
+            hunk.lines,
+            vec![
+                Line {
+                    text: b"caf\xe9".to_vec(),
+                    ..line(LineKind::Removed, "")
+                },
+                line(LineKind::Added, "café"),
+            ]
+        );
+        assert_eq!(
+            hunk.to_string(),
+            "@@ -1 +1 @@ caf\u{fffd}()\n-caf\u{fffd}\n+café\n"
+        );
+
+        // Headers, on the other hand, have to be UTF-8.
+        assert_eq!(
+            Patch::parse(b"diff --git a/caf\xe9 b/caf\xe9\n").unwrap_err(),
+            ParseError {
+                line: 1,
+                kind: ParseErrorKind::InvalidPath
+            }
+        );
+    }
+
+    #[test]
+    fn parse_hunk_single_lines() {
+        let hunk = roundtrip::<Hunk>("@@ -0,0 +1 @@\n+hello\n\\ No newline at end of file\n");
+        assert_eq!(hunk.old_lines(), 0);
+        assert_eq!(hunk.new_lines(), 1);
+        assert!(hunk.lines[0].missing_newline);
+
+        // Explicit counts of 1 are accepted, but not reproduced.
+        let hunk = "@@ -3,1 +3,1 @@\n-a\n+b\n".parse::<Hunk>().unwrap();
+        assert_eq!(hunk.to_string(), "@@ -3 +3 @@\n-a\n+b\n");
+    }
+
+    #[test]
+    fn parse_hunk_errors() {
+        let error = |text: &str| text.parse::<Hunk>().unwrap_err();
+
+        assert_eq!(
+            error("@@ -1 +1 @@\n-a\n+b"),
+            ParseError {
+                line: 3,
+                kind: ParseErrorKind::UnterminatedLine
+            }
+        );
+        for header in [
+            "@@ -1 +1@@",
+            "@@ -1 +1",
+            "@@ -a +1 @@",
+            "@@ -1,+1 +1 @@",
+            "@@ -0 +1 @@",
+            "@@ -1 +1 @@x",
+            "@@@ -1 +1 @@",
+        ] {
+            assert_eq!(
+                error(&format!("{header}\n-a\n+b\n")).kind,
+                ParseErrorKind::InvalidHunkHeader,
+                "{header}"
+            );
+        }
+        assert_eq!(
+            error("@@ -1,2 +1,2 @@\n-a\n+b\n"),
+            ParseError {
+                line: 4,
+                kind: ParseErrorKind::UnexpectedEnd
+            }
+        );
+        assert_eq!(
+            error("@@ -1 +1 @@\n-a\n-b\n"),
+            ParseError {
+                line: 3,
+                kind: ParseErrorKind::UnexpectedLine
+            }
+        );
+        assert_eq!(
+            error("@@ -1 +1 @@\n-a\n+b\n c\n"),
+            ParseError {
+                line: 4,
+                kind: ParseErrorKind::UnexpectedLine
+            }
+        );
+        assert_eq!(
+            error("@@ -1 +1 @@\n-a\n\n").kind,
+            ParseErrorKind::UnexpectedLine
+        );
+        assert_eq!(
+            error("@@ -1,2 +1,2 @@\n a\n\\ No newline at end of file\n b\n"),
+            ParseError {
+                line: 3,
+                kind: ParseErrorKind::MisplacedNoNewline
+            }
+        );
+    }
+
+    #[test]
+    fn parse_patch() {
+        let patch = roundtrip::<Patch>(
+            "\
+diff --git a/src/main.rs b/src/main.rs
+index 8e6f6a3..b4e4a2a 100644
+--- a/src/main.rs
++++ b/src/main.rs
+@@ -1,3 +1,3 @@
+ fn main() {
+-    println!(\"hello\");
++    println!(\"world\");
+ }
+@@ -10 +10,2 @@ fn other() {
+ x
++y
+diff --git a/old name.txt b/new name.txt
+similarity index 90%
+rename from old name.txt
+rename to new name.txt
+index 1111111..2222222
+--- a/old name.txt\t
++++ b/new name.txt\t
+@@ -1 +1 @@
+-a
++b
+diff --git a/script.sh b/script.sh
+old mode 100644
+new mode 100755
+diff --git a/empty b/empty
+new file mode 100644
+index 0000000..e69de29
+diff --git a/image.png b/image.png
+deleted file mode 100644
+index 3333333..0000000
+Binary files a/image.png and /dev/null differ
+diff --git a/gone.txt b/gone.txt
+deleted file mode 100644
+index 4444444..0000000
+--- a/gone.txt
++++ /dev/null
+@@ -1,2 +0,0 @@
+-one
+-two
+",
+        );
+
+        assert_eq!(patch.files.len(), 6);
+        assert_eq!(
+            patch.files[1].git_paths,
+            Some(("old name.txt".to_string(), "new name.txt".to_string()))
+        );
+        assert_eq!(
+            patch.files[1].extended_headers,
+            vec![
+                ExtendedHeader::Similarity(90),
+                ExtendedHeader::RenameFrom("old name.txt".to_string()),
+                ExtendedHeader::RenameTo("new name.txt".to_string()),
+                ExtendedHeader::Index {
+                    old: "1111111".to_string(),
+                    new: "2222222".to_string(),
+                    mode: None
+                },
+            ]
+        );
+        assert_eq!(
+            patch.files[2].extended_headers,
+            vec![
+                ExtendedHeader::OldMode(0o100644),
+                ExtendedHeader::NewMode(0o100755)
+            ]
+        );
+        assert_eq!(patch.files[2].content, Content::None);
+        assert_eq!(patch.files[3].content, Content::None);
+        assert_eq!(
+            patch.files[4].content,
+            Content::Binary {
+                old_path: Some("image.png".to_string()),
+                new_path: None
+            }
+        );
+        let Content::Text {
+            old_path,
+            new_path,
+            hunks,
+        } = &patch.files[5].content
+        else {
+            panic!("expected text content");
+        };
+        assert_eq!(old_path.as_deref(), Some("gone.txt"));
+        assert_eq!(new_path, &None);
+        assert_eq!(hunks.len(), 1);
+    }
+
+    #[test]
+    fn parse_plain_unified_diff() {
+        let patch = roundtrip::<Patch>("--- a/file\n+++ b/file\n@@ -1 +1 @@\n-a\n+b\n");
+        assert_eq!(patch.files.len(), 1);
+        assert_eq!(patch.files[0].git_paths, None);
+        assert!(roundtrip::<Patch>("").files.is_empty());
+    }
+
+    #[test]
+    fn parse_quoted_paths() {
+        let file_patch = roundtrip::<FilePatch>(
+            "\
+diff --git \"a/caf\\303\\251 \\\"quoted\\\"\" \"b/tab\\there\"
+rename from \"caf\\303\\251 \\\"quoted\\\"\"
+rename to \"tab\\there\"
+",
+        );
+        assert_eq!(
+            file_patch.git_paths,
+            Some(("café \"quoted\"".to_string(), "tab\there".to_string()))
+        );
+
+        // Unquoted non-ASCII paths are accepted, but quoted like git does.
+        let file_patch = "diff --git a/café b/café\nold mode 100644\nnew mode 100755\n"
+            .parse::<FilePatch>()
+            .unwrap();
+        assert_eq!(
+            file_patch.git_paths,
+            Some(("café".to_string(), "café".to_string()))
+        );
+        assert!(file_patch
+            .to_string()
+            .starts_with("diff --git \"a/caf\\303\\251\" \"b/caf\\303\\251\"\n"));
+
+        // Ambiguous splits are resolved in favor of identical paths.
+        let file_patch = roundtrip::<FilePatch>(
+            "diff --git a/x b/y b/x b/y\nold mode 100644\nnew mode 100755\n",
+        );
+        assert_eq!(
+            file_patch.git_paths,
+            Some(("x b/y".to_string(), "x b/y".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_patch_errors() {
+        let error = |text: &str| text.parse::<Patch>().unwrap_err();
+
+        assert_eq!(
+            error("From 1234 Mon Sep 17 00:00:00 2001\n"),
+            ParseError {
+                line: 1,
+                kind: ParseErrorKind::UnexpectedLine
+            }
+        );
+        assert_eq!(error("diff --git x y\n").kind, ParseErrorKind::InvalidPath);
+        assert_eq!(
+            error("diff --git a/x b/x\nold mode 10064a\n"),
+            ParseError {
+                line: 2,
+                kind: ParseErrorKind::InvalidExtendedHeader
+            }
+        );
+        assert_eq!(
+            error("diff --git a/x b/x\nsimilarity index 101%\n").kind,
+            ParseErrorKind::InvalidExtendedHeader
+        );
+        assert_eq!(
+            error("diff --git a/x b/x\nGIT binary patch\nliteral 0\n").kind,
+            ParseErrorKind::Unsupported("binary patch payloads")
+        );
+        assert_eq!(
+            error("--- a/x\n+++ b/x\n"),
+            ParseError {
+                line: 3,
+                kind: ParseErrorKind::UnexpectedEnd
+            }
+        );
+        assert_eq!(
+            error("--- x\n+++ b/x\n@@ -1 +1 @@\n-a\n+b\n"),
+            ParseError {
+                line: 1,
+                kind: ParseErrorKind::InvalidPath
+            }
+        );
+    }
+
+    #[test]
+    fn reverse_hunk() {
+        let hunk = "@@ -1,4 +1,5 @@\n a\n-b\n+c\n+d\n e\n-f\n\\ No newline at end of file\n+f\n"
+            .parse::<Hunk>()
+            .unwrap();
+        let reversed = hunk.reverse();
+        assert_eq!(
+            reversed.to_string(),
+            "@@ -1,5 +1,4 @@\n a\n-c\n-d\n+b\n e\n-f\n+f\n\\ No newline at end of file\n"
+        );
+        assert_eq!(reversed.reverse(), hunk);
+    }
+
+    #[test]
+    fn hunk_changes() {
+        let hunk = "@@ -2,6 +2,6 @@\n a\n-b\n+B\n c\n+new\n d\n-e\n f\n"
+            .parse::<Hunk>()
+            .unwrap();
+        assert_eq!(
+            hunk.changes().unwrap().hunks(),
+            &[
+                crate::Hunk::new(LineSpan::new(2, 2), vec!["B".to_string()]),
+                crate::Hunk::insertion(4, vec!["new".to_string()]),
+                crate::Hunk::new(LineSpan::new(5, 5), vec![]),
+            ]
+        );
+
+        let hunk = "@@ -0,0 +1,2 @@\n+a\n+b\n".parse::<Hunk>().unwrap();
+        assert_eq!(
+            hunk.changes().unwrap().hunks(),
+            &[crate::Hunk::insertion(
+                0,
+                vec!["a".to_string(), "b".to_string()]
+            )]
+        );
+
+        let hunk = "@@ -3,0 +4 @@\n+c\n".parse::<Hunk>().unwrap();
+        assert_eq!(
+            hunk.changes().unwrap().hunks(),
+            &[crate::Hunk::insertion(3, vec!["c".to_string()])]
+        );
+    }
+
+    #[test]
+    fn file_patch_changes() {
+        let file_patch = "--- a/x\n+++ b/x\n@@ -1 +1 @@\n-a\n+b\n@@ -10 +10,0 @@\n-c\n"
+            .parse::<FilePatch>()
+            .unwrap();
+        assert_eq!(file_patch.changes().unwrap().len(), 2);
+
+        let overlapping = "--- a/x\n+++ b/x\n@@ -1,2 +0,0 @@\n-a\n-b\n@@ -2 +1 @@\n-b\n+c\n"
+            .parse::<FilePatch>()
+            .unwrap();
+        assert!(matches!(
+            overlapping.changes(),
+            Err(ChangesError::Conflict(_))
+        ));
+    }
+
+    #[test]
+    fn invalid_changes() {
+        let mut hunk = "@@ -1 +1 @@\n-a\n+b\n".parse::<Hunk>().unwrap();
+        hunk.old_start = 0;
+        assert_eq!(hunk.changes(), Err(ChangesError::ZeroStart));
+
+        let hunk = Hunk::parse(b"@@ -1 +1 @@\n-a\n+\xff\n").unwrap();
+        assert_eq!(hunk.changes(), Err(ChangesError::NonUtf8Line));
+
+        // Removed lines aren't part of the changes.
+        let hunk = Hunk::parse(b"@@ -1 +0,0 @@\n-\xff\n").unwrap();
+        assert_eq!(
+            hunk.changes().unwrap().hunks(),
+            &[crate::Hunk::new(LineSpan::new(0, 0), vec![])]
+        );
+    }
+}
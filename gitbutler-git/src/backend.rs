@@ -4,3 +4,54 @@ pub mod cli;
 // We use the libgit2 backend for tests as well.
 #[cfg(any(test, feature = "git2"))]
 pub mod git2;
+
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
+
+/// What was at the path a repository is cloned into before the clone,
+/// such that a failed clone can be cleaned up without removing anything
+/// that was there before.
+#[cfg(any(test, feature = "cli", feature = "git2"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CloneTarget {
+    Missing,
+    Empty,
+    NotEmpty,
+}
+
+#[cfg(any(test, feature = "cli", feature = "git2"))]
+impl CloneTarget {
+    pub(crate) fn of(path: &std::path::Path) -> Self {
+        match path.read_dir().map(|mut entries| entries.next().is_none()) {
+            Ok(true) => Self::Empty,
+            Ok(false) => Self::NotEmpty,
+            Err(_) if !path.exists() => Self::Missing,
+            // Not a directory; the clone fails without writing anything.
+            Err(_) => Self::NotEmpty,
+        }
+    }
+
+    /// Removes what a failed clone has left at `path`. The directory
+    /// itself is kept if it existed before the clone.
+    ///
+    /// This is best effort; the error of the clone is what matters.
+    pub(crate) fn clean_up(self, path: &std::path::Path) {
+        match self {
+            Self::Missing => {
+                let _ = std::fs::remove_dir_all(path);
+            }
+            Self::Empty => {
+                if let Ok(entries) = path.read_dir() {
+                    for entry in entries.flatten() {
+                        let _ = if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+                            std::fs::remove_dir_all(entry.path())
+                        } else {
+                            std::fs::remove_file(entry.path())
+                        };
+                    }
+                }
+            }
+            Self::NotEmpty => {}
+        }
+    }
+}
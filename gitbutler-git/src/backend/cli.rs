@@ -1,14 +1,21 @@
 //! CLI-based (fork/exec) backend implementation,
 //! executing the `git` command-line tool available
 //! on `$PATH`.
+//!
+//! Git and SSH ask the `gitbutler-git-askpass` utility for
+//! credentials, as a credential helper and for known hosts,
+//! which relays their requests to us over a Unix socket.
+//! Windows is not supported yet: the utility doesn't build
+//! there, as named pipes aren't implemented.
 
 mod executor;
+mod progress;
 mod repository;
 
 #[cfg(unix)]
 pub use self::executor::Uid;
 pub use self::{
-    executor::{AskpassServer, FileStat, GitExecutor, Pid, Socket},
+    executor::{AskpassServer, FileStat, GitExecutor, OutputLine, Pid, Socket},
     repository::Repository,
 };
 
@@ -20,11 +27,7 @@ mod tests {
     use super::*;
 
     async fn make_repo(test_name: String) -> impl crate::Repository {
-        let repo_path = std::env::temp_dir()
-            .join("gitbutler-tests")
-            .join("git")
-            .join("cli")
-            .join(test_name);
+        let repo_path = crate::private::local_repo_path(&test_name);
         let _ = std::fs::remove_dir_all(&repo_path);
         std::fs::create_dir_all(&repo_path).unwrap();
 
@@ -33,5 +36,148 @@ mod tests {
             .unwrap()
     }
 
+    #[test]
+    fn clone_with_progress() {
+        ::tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(async {
+                use crate::Repository as _;
+
+                let test_name = format!("{}::clone_with_progress", module_path!());
+                let (url, head) = crate::private::origin_repo(&test_name);
+
+                let repo_path = crate::private::local_repo_path(&test_name);
+                let _ = std::fs::remove_dir_all(&repo_path);
+
+                let progress = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+                let on_progress = std::sync::Arc::clone(&progress);
+
+                let repo = Repository::clone(
+                    executor::tokio::TokioExecutor,
+                    &url,
+                    repo_path.to_str().unwrap(),
+                    &crate::Authorization::default(),
+                    &crate::FetchOptions::default()
+                        .with_progress(move |progress| on_progress.lock().unwrap().push(progress)),
+                )
+                .await
+                .unwrap();
+
+                assert_eq!(repo.head().await.unwrap(), head);
+                crate::private::assert_transfer_completed(&progress.lock().unwrap());
+            });
+    }
+
+    #[test]
+    fn clone_cancelled() {
+        ::tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let test_name = format!("{}::clone_cancelled", module_path!());
+                let (url, _) = crate::private::origin_repo(&test_name);
+
+                let repo_path = crate::private::local_repo_path(&test_name);
+                let _ = std::fs::remove_dir_all(&repo_path);
+
+                let cancellation = crate::CancellationToken::new();
+                let on_progress = cancellation.clone();
+
+                let result = Repository::clone(
+                    executor::tokio::TokioExecutor,
+                    &url,
+                    repo_path.to_str().unwrap(),
+                    &crate::Authorization::default(),
+                    &crate::FetchOptions::default()
+                        .with_cancellation(cancellation)
+                        .with_progress(move |_| on_progress.cancel()),
+                )
+                .await;
+
+                assert!(matches!(result, Err(crate::Error::Cancelled)));
+                assert!(!repo_path.exists());
+            });
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn fetch_cancelled_mid_flight() {
+        ::tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(async {
+                use crate::Repository as _;
+
+                let test_name = format!("{}::fetch_cancelled_mid_flight", module_path!());
+                let (url, _) = crate::private::origin_repo(&test_name);
+                let repo = make_repo(test_name.clone()).await;
+                repo.create_remote("origin", &url).await.unwrap();
+
+                // The remote never answers; its upload-pack records the PID of
+                // the Git process that is fetching from it, and hangs.
+                let pid_path = crate::private::local_repo_path(&test_name).join("fetch.pid");
+                repo.config_set(
+                    "remote.origin.uploadpack",
+                    &format!("echo $PPID > '{}'; exec sleep 30 #", pid_path.display()),
+                    crate::ConfigScope::Local,
+                )
+                .await
+                .unwrap();
+
+                let cancellation = crate::CancellationToken::new();
+                let authorization = crate::Authorization::default();
+                let options =
+                    crate::FetchOptions::default().with_cancellation(cancellation.clone());
+                let fetch = repo.fetch(
+                    "origin",
+                    crate::RefSpec::parse("+refs/heads/master:refs/remotes/origin/master").unwrap(),
+                    &authorization,
+                    &options,
+                );
+                let cancel = async {
+                    while !pid_path.exists() {
+                        ::tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                    }
+                    cancellation.cancel();
+                };
+
+                let (result, ()) = futures::join!(fetch, cancel);
+                assert!(matches!(result, Err(crate::Error::Cancelled)));
+
+                // The Git process is killed (and possibly not reaped yet).
+                let pid = std::fs::read_to_string(&pid_path).unwrap();
+                let stat_path = format!("/proc/{}/stat", pid.trim());
+                let is_running = || {
+                    std::fs::read_to_string(&stat_path).is_ok_and(|stat| {
+                        stat.rsplit(") ")
+                            .next()
+                            .unwrap_or_default()
+                            .starts_with(|state| state != 'Z')
+                    })
+                };
+                for _ in 0..100 {
+                    if !is_running() {
+                        break;
+                    }
+                    ::tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                }
+                assert!(
+                    !is_running(),
+                    "fetching process {} still running",
+                    pid.trim()
+                );
+            });
+    }
+
+    async fn make_fixture(test_name: String) -> impl crate::private::Fixture {
+        crate::private::DiskFixture::new(make_repo(test_name.clone()).await, test_name)
+    }
+
     crate::gitbutler_git_integration_tests!(make_repo, enable_io);
+    crate::gitbutler_git_host_key_tests!(make_repo);
+    crate::gitbutler_git_conformance_tests!(make_fixture);
 }
@@ -1,7 +1,7 @@
 use std::io::{BufRead, BufReader, BufWriter, Write};
 use std::os::unix::net::UnixStream;
 
-pub fn main(sock_path: &str, secret: &str, prompt: &str) {
+fn connect(sock_path: &str) -> (BufReader<UnixStream>, BufWriter<UnixStream>) {
     let raw_stream = UnixStream::connect(sock_path).expect("connect():");
 
     // Set a timer for 10s.
@@ -9,8 +9,14 @@ pub fn main(sock_path: &str, secret: &str, prompt: &str) {
         .set_read_timeout(Some(std::time::Duration::from_secs(10)))
         .expect("set_read_timeout():");
 
-    let mut reader = BufReader::new(raw_stream.try_clone().unwrap());
-    let mut writer = BufWriter::new(raw_stream);
+    let reader = BufReader::new(raw_stream.try_clone().unwrap());
+    let writer = BufWriter::new(raw_stream);
+
+    (reader, writer)
+}
+
+pub fn main(sock_path: &str, secret: &str, prompt: &str) {
+    let (mut reader, mut writer) = connect(sock_path);
 
     // Write the secret.
     writeln!(writer, "{secret}").expect("write(secret):");
@@ -31,3 +37,67 @@ pub fn main(sock_path: &str, secret: &str, prompt: &str) {
     // `password` already has a newline at the end.
     write!(std::io::stdout(), "{password}").expect("write(password):");
 }
+
+/// Speaks the `git credential` helper protocol on behalf of Git,
+/// relaying the attributes Git gives us on stdin to the socket,
+/// and the attributes we get back from the socket to stdout.
+pub fn credential(sock_path: &str, secret: &str, action: &str) {
+    let (mut reader, mut writer) = connect(sock_path);
+
+    // Write the secret.
+    writeln!(writer, "{secret}").expect("write(secret):");
+
+    // Write the action, followed by the attributes that Git gave us,
+    // terminated by an empty line.
+    writeln!(writer, "credential-helper {action}").expect("write(action):");
+    for line in std::io::stdin().lock().lines() {
+        let line = line.expect("read_line(stdin):");
+        if line.is_empty() {
+            break;
+        }
+        writeln!(writer, "{line}").expect("write(attribute):");
+    }
+    writeln!(writer).expect("write(attribute):");
+
+    writer.flush().expect("flush():");
+
+    relay_response(&mut reader);
+}
+
+/// Acts as SSH's `KnownHostsCommand`, reporting the key the host presented
+/// to the socket, and relaying the `known_hosts` lines we get back to stdout.
+///
+/// SSH also runs the command before the key is known, in which case the
+/// fingerprint, key type and key are empty.
+pub fn known_host(
+    sock_path: &str,
+    secret: &str,
+    host: &str,
+    fingerprint: &str,
+    key_type: &str,
+    key: &str,
+) {
+    let (mut reader, mut writer) = connect(sock_path);
+
+    // Write the secret.
+    writeln!(writer, "{secret}").expect("write(secret):");
+
+    writeln!(writer, "known-host {host} {fingerprint} {key_type} {key}").expect("write(host):");
+
+    writer.flush().expect("flush():");
+
+    relay_response(&mut reader);
+}
+
+/// Relays the response up until (and excluding) the terminating empty line.
+fn relay_response(reader: &mut BufReader<UnixStream>) {
+    let mut stdout = std::io::stdout();
+    loop {
+        let mut line = String::new();
+        let nread = reader.read_line(&mut line).expect("read_line():");
+        if nread == 0 || line.trim_end().is_empty() {
+            break;
+        }
+        write!(stdout, "{line}").expect("write(response):");
+    }
+}
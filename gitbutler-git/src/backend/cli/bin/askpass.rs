@@ -10,6 +10,23 @@ pub fn main() {
     let pipe_secret = std::env::var("GITBUTLER_ASKPASS_SECRET").expect("do not run this binary yourself; it's only meant to be run by GitButler (missing GITBUTLER_ASKPASS_SECRET env var)");
     let prompt = std::env::args().nth(1).expect("do not run this binary yourself; it's only meant to be run by GitButler (missing prompt arg)");
 
-    #[cfg(not(target_os = "windows"))]
-    unix::main(&pipe_name, &pipe_secret, &prompt);
+    // When configured as a credential helper, Git invokes us
+    // as `askpass credential <action>`.
+    if prompt == "credential" {
+        let action = std::env::args().nth(2).expect("do not run this binary yourself; it's only meant to be run by GitButler (missing credential action arg)");
+
+        #[cfg(not(target_os = "windows"))]
+        unix::credential(&pipe_name, &pipe_secret, &action);
+    } else if prompt == "known-host" {
+        // When configured as SSH's `KnownHostsCommand`, SSH invokes us
+        // as `askpass known-host <host> <fingerprint> <key type> <key>`.
+        let args = std::env::args().skip(2).collect::<Vec<_>>();
+        let arg = |i: usize| args.get(i).map(String::as_str).unwrap_or_default();
+
+        #[cfg(not(target_os = "windows"))]
+        unix::known_host(&pipe_name, &pipe_secret, arg(0), arg(1), arg(2), arg(3));
+    } else {
+        #[cfg(not(target_os = "windows"))]
+        unix::main(&pipe_name, &pipe_secret, &prompt);
+    }
 }
@@ -1,3 +1,4 @@
+use futures::Stream;
 use std::{collections::HashMap, time::Duration};
 
 #[cfg(any(test, feature = "tokio"))]
@@ -46,6 +47,9 @@ pub unsafe trait GitExecutor {
     /// The type of the handle returned by [`GitExecutor::create_askpass_server`].
     type ServerHandle: AskpassServer + Send + Sync + 'static;
 
+    /// The type of the stream returned by [`GitExecutor::execute_raw_lines`].
+    type Lines: Stream<Item = Result<OutputLine, Self::Error>> + Send + 'static;
+
     /// Executes the given Git command with the given arguments.
     /// `git` is never passed as the first argument (arg 0).
     ///
@@ -62,6 +66,49 @@ pub unsafe trait GitExecutor {
         envs: Option<HashMap<String, String>>,
     ) -> Result<(usize, String, String), Self::Error>;
 
+    /// Executes the given Git command with the given arguments,
+    /// writing `stdin` to the standard input of the child process.
+    ///
+    /// Otherwise behaves exactly like [`Self::execute_raw`].
+    async fn execute_raw_with_stdin(
+        &self,
+        args: &[&str],
+        envs: Option<HashMap<String, String>>,
+        stdin: &str,
+    ) -> Result<(usize, String, String), Self::Error>;
+
+    /// Executes the given Git command with the given arguments,
+    /// passing each line the child process writes to stderr to
+    /// `on_stderr` as soon as it has been written.
+    ///
+    /// Lines are terminated by either `\n` or `\r`, the latter
+    /// being used by Git to redraw progress output.
+    ///
+    /// Otherwise behaves exactly like [`Self::execute_raw`].
+    async fn execute_raw_streaming(
+        &self,
+        args: &[&str],
+        envs: Option<HashMap<String, String>>,
+        on_stderr: &mut (dyn FnMut(&str) + Send),
+    ) -> Result<(usize, String, String), Self::Error>;
+
+    /// Executes the given Git command with the given arguments,
+    /// yielding each line the child process writes to stdout
+    /// (without its terminator) as soon as it has been written.
+    ///
+    /// Once the child process exited, its exit code and stderr
+    /// are yielded as [`OutputLine::Exit`], ending the stream.
+    /// The child process should be killed if the stream is
+    /// dropped before then.
+    ///
+    /// `Err` is yielded if the command could not be executed,
+    /// **not** if the command returned a non-zero exit code.
+    fn execute_raw_lines(
+        &self,
+        args: &[&str],
+        envs: Option<HashMap<String, String>>,
+    ) -> Self::Lines;
+
     /// Executes the given Git command with sane defaults.
     /// `git` is never passed as the first argument (arg 0).
     ///
@@ -72,21 +119,49 @@ pub unsafe trait GitExecutor {
         args: &[&str],
         envs: Option<HashMap<String, String>>,
     ) -> Result<(usize, String, String), Self::Error> {
-        let mut args = args.as_ref().to_vec();
+        let (args, envs) = with_defaults(args, envs);
+        self.execute_raw(&args, Some(envs)).await
+    }
 
-        args.insert(0, "--no-pager");
-        // TODO(qix-): Test the performance impact of this.
-        args.insert(0, "--no-optional-locks");
-        // '-c' arguments must be inserted in reverse order; Git does not support
-        // shortflags for '-c' arguments, so they must be separated.
-        args.insert(0, "protocol.version=2");
-        args.insert(0, "-c");
+    /// Executes the given Git command with sane defaults,
+    /// writing `stdin` to the standard input of the child process.
+    ///
+    /// Implementers should use this method over [`Self::execute_raw_with_stdin`]
+    /// when possible.
+    async fn execute_with_stdin(
+        &self,
+        args: &[&str],
+        envs: Option<HashMap<String, String>>,
+        stdin: &str,
+    ) -> Result<(usize, String, String), Self::Error> {
+        let (args, envs) = with_defaults(args, envs);
+        self.execute_raw_with_stdin(&args, Some(envs), stdin).await
+    }
 
-        let mut envs = envs.unwrap_or_default();
-        envs.insert("GIT_TERMINAL_PROMPT".into(), "0".into());
-        envs.insert("LC_ALL".into(), "C".into()); // Force English. We need this for parsing output.
+    /// Executes the given Git command with sane defaults,
+    /// passing each line written to stderr to `on_stderr`.
+    ///
+    /// Implementers should use this method over [`Self::execute_raw_streaming`]
+    /// when possible.
+    async fn execute_streaming(
+        &self,
+        args: &[&str],
+        envs: Option<HashMap<String, String>>,
+        on_stderr: &mut (dyn FnMut(&str) + Send),
+    ) -> Result<(usize, String, String), Self::Error> {
+        let (args, envs) = with_defaults(args, envs);
+        self.execute_raw_streaming(&args, Some(envs), on_stderr)
+            .await
+    }
 
-        self.execute_raw(&args, Some(envs)).await
+    /// Executes the given Git command with sane defaults,
+    /// yielding each line it writes to stdout.
+    ///
+    /// Implementers should use this method over [`Self::execute_raw_lines`]
+    /// when possible.
+    fn execute_lines(&self, args: &[&str], envs: Option<HashMap<String, String>>) -> Self::Lines {
+        let (args, envs) = with_defaults(args, envs);
+        self.execute_raw_lines(&args, Some(envs))
     }
 
     /// Creates a named pipe server that is compatible with
@@ -143,6 +218,39 @@ pub unsafe trait GitExecutor {
     async fn stat(&self, path: &str) -> Result<FileStat, Self::Error>;
 }
 
+/// Decorates the given arguments and environment variables
+/// with the defaults used by [`GitExecutor::execute`].
+fn with_defaults<'a>(
+    args: &[&'a str],
+    envs: Option<HashMap<String, String>>,
+) -> (Vec<&'a str>, HashMap<String, String>) {
+    let mut args = args.as_ref().to_vec();
+
+    args.insert(0, "--no-pager");
+    // TODO(qix-): Test the performance impact of this.
+    args.insert(0, "--no-optional-locks");
+    // '-c' arguments must be inserted in reverse order; Git does not support
+    // shortflags for '-c' arguments, so they must be separated.
+    args.insert(0, "protocol.version=2");
+    args.insert(0, "-c");
+
+    let mut envs = envs.unwrap_or_default();
+    envs.insert("GIT_TERMINAL_PROMPT".into(), "0".into());
+    envs.insert("LC_ALL".into(), "C".into()); // Force English. We need this for parsing output.
+
+    (args, envs)
+}
+
+/// An item of the output of [`GitExecutor::execute_raw_lines`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutputLine {
+    /// A line written to stdout, without its terminator.
+    Stdout(String),
+    /// The exit code and the stderr output of the child process,
+    /// once it exited.
+    Exit(usize, String),
+}
+
 /// Stats for a file on the filesystem.
 ///
 /// This is returned by [`GitExecutor::stat`],
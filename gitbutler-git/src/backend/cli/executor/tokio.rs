@@ -1,9 +1,19 @@
 //! A [Tokio](https://tokio.rs)-based [`super::GitExecutor`] implementation.
 
+use futures::{Stream, TryStreamExt};
 #[cfg(unix)]
 use std::os::unix::fs::MetadataExt;
-use std::{collections::HashMap, fs::Permissions, os::unix::fs::PermissionsExt, time::Duration};
-use tokio::process::Command;
+use std::{
+    collections::HashMap,
+    fs::Permissions,
+    os::unix::fs::PermissionsExt,
+    process::{Output, Stdio},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    process::Command,
+};
 
 /// A [`super::GitExecutor`] implementation using the `git` command-line tool
 /// via [`tokio::process::Command`].
@@ -13,42 +23,147 @@ pub struct TokioExecutor;
 unsafe impl super::GitExecutor for TokioExecutor {
     type Error = std::io::Error;
     type ServerHandle = TokioAskpassServer;
+    type Lines = impl Stream<Item = Result<super::OutputLine, Self::Error>> + Send + 'static;
 
     async fn execute_raw(
         &self,
         args: &[&str],
         envs: Option<HashMap<String, String>>,
     ) -> Result<(usize, String, String), Self::Error> {
-        let mut cmd = Command::new("git");
-
-        // Output the command being executed to stderr, for debugging purposes
-        // (only on test configs).
-        #[cfg(test)]
-        {
-            let mut envs_str = String::new();
-            if let Some(envs) = &envs {
-                for (key, value) in envs.iter() {
-                    envs_str.push_str(&format!("{}={} ", key, value));
+        let output = command(args, envs).output().await?;
+
+        Ok(output_tuple(output))
+    }
+
+    async fn execute_raw_with_stdin(
+        &self,
+        args: &[&str],
+        envs: Option<HashMap<String, String>>,
+        stdin: &str,
+    ) -> Result<(usize, String, String), Self::Error> {
+        let mut child = command(args, envs)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        // Dropping the handle closes the pipe, signaling EOF to the child.
+        let mut child_stdin = child.stdin.take().expect("stdin is piped");
+        child_stdin.write_all(stdin.as_bytes()).await?;
+        drop(child_stdin);
+
+        let output = child.wait_with_output().await?;
+
+        Ok(output_tuple(output))
+    }
+
+    async fn execute_raw_streaming(
+        &self,
+        args: &[&str],
+        envs: Option<HashMap<String, String>>,
+        on_stderr: &mut (dyn FnMut(&str) + Send),
+    ) -> Result<(usize, String, String), Self::Error> {
+        let mut child = command(args, envs)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        let mut child_stdout = child.stdout.take().expect("stdout is piped");
+        let mut child_stderr = child.stderr.take().expect("stderr is piped");
+
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+
+        let read_stderr = async {
+            let mut chunk = [0; 4096];
+            let mut line_start = 0;
+
+            loop {
+                let n = child_stderr.read(&mut chunk).await?;
+                if n == 0 {
+                    break;
+                }
+                stderr.extend_from_slice(&chunk[..n]);
+
+                while let Some(len) = stderr[line_start..]
+                    .iter()
+                    .position(|&b| b == b'\n' || b == b'\r')
+                {
+                    let line = String::from_utf8_lossy(&stderr[line_start..line_start + len]);
+                    if !line.is_empty() {
+                        on_stderr(&line);
+                    }
+                    line_start += len + 1;
                 }
             }
-            let args_str = args.join(" ");
-            eprintln!("env {envs_str} git {args_str}");
-        }
 
-        cmd.kill_on_drop(true);
-        cmd.args(args);
+            if line_start < stderr.len() {
+                on_stderr(&String::from_utf8_lossy(&stderr[line_start..]));
+            }
+
+            Ok::<_, std::io::Error>(())
+        };
 
-        if let Some(envs) = envs {
-            cmd.envs(envs);
-        }
+        let (stdout_result, stderr_result) =
+            futures::join!(child_stdout.read_to_end(&mut stdout), read_stderr);
+        stdout_result?;
+        stderr_result?;
 
-        let output = cmd.output().await?;
+        let status = child.wait().await?;
 
-        Ok((
-            output.status.code().unwrap_or(127) as usize,
-            String::from_utf8_lossy(&output.stdout).trim().into(),
-            String::from_utf8_lossy(&output.stderr).trim().into(),
-        ))
+        Ok(output_tuple(Output {
+            status,
+            stdout,
+            stderr,
+        }))
+    }
+
+    fn execute_raw_lines(
+        &self,
+        args: &[&str],
+        envs: Option<HashMap<String, String>>,
+    ) -> Self::Lines {
+        let mut command = command(args, envs);
+        command.stdout(Stdio::piped()).stderr(Stdio::piped());
+
+        let spawn = async move {
+            let mut child = command.spawn()?;
+
+            let stdout = BufReader::new(child.stdout.take().expect("stdout is piped")).lines();
+
+            // stderr is read in the meantime, so that the child never blocks writing to it
+            let mut child_stderr = child.stderr.take().expect("stderr is piped");
+            let stderr = tokio::spawn(async move {
+                let mut stderr = Vec::new();
+                child_stderr.read_to_end(&mut stderr).await.map(|_| stderr)
+            });
+
+            Ok::<_, Self::Error>(Some((child, stdout, stderr)))
+        };
+
+        futures::stream::once(spawn)
+            .map_ok(|running| {
+                futures::stream::try_unfold(running, |running| async move {
+                    let Some((mut child, mut stdout, stderr)) = running else {
+                        return Ok(None);
+                    };
+
+                    if let Some(line) = stdout.next_line().await? {
+                        let running = Some((child, stdout, stderr));
+                        return Ok(Some((super::OutputLine::Stdout(line), running)));
+                    }
+
+                    let stderr = stderr.await.map_err(std::io::Error::other)??;
+                    let (status, _, stderr) = output_tuple(Output {
+                        status: child.wait().await?,
+                        stdout: Vec::new(),
+                        stderr,
+                    });
+
+                    Ok(Some((super::OutputLine::Exit(status, stderr), None)))
+                })
+            })
+            .try_flatten()
     }
 
     #[cfg(unix)]
@@ -111,6 +226,41 @@ impl super::Socket for tokio::io::BufStream<tokio::net::UnixStream> {
     }
 }
 
+fn command(args: &[&str], envs: Option<HashMap<String, String>>) -> Command {
+    let mut cmd = Command::new("git");
+
+    // Output the command being executed to stderr, for debugging purposes
+    // (only on test configs).
+    #[cfg(test)]
+    {
+        let mut envs_str = String::new();
+        if let Some(envs) = &envs {
+            for (key, value) in envs.iter() {
+                envs_str.push_str(&format!("{}={} ", key, value));
+            }
+        }
+        let args_str = args.join(" ");
+        eprintln!("env {envs_str} git {args_str}");
+    }
+
+    cmd.kill_on_drop(true);
+    cmd.args(args);
+
+    if let Some(envs) = envs {
+        cmd.envs(envs);
+    }
+
+    cmd
+}
+
+fn output_tuple(output: Output) -> (usize, String, String) {
+    (
+        output.status.code().unwrap_or(127) as usize,
+        String::from_utf8_lossy(&output.stdout).trim().into(),
+        String::from_utf8_lossy(&output.stderr).trim().into(),
+    )
+}
+
 /// A tokio-based [`super::AskpassServer`] implementation.
 #[cfg(unix)]
 pub struct TokioAskpassServer {
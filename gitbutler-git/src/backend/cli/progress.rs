@@ -0,0 +1,122 @@
+use crate::Progress;
+
+/// Parses a line of progress output written to stderr by
+/// `git clone --progress` or `git fetch --progress`, e.g.
+///
+/// ```text
+/// Receiving objects:  45% (450/1000), 1.20 MiB | 2.00 MiB/s
+/// Resolving deltas: 100% (12/12), done.
+/// ```
+///
+/// Returns `None` for lines that don't report the progress
+/// of a stage we're interested in.
+pub(super) fn parse(line: &str) -> Option<Progress> {
+    let (stage, rest) = line.split_once(": ")?;
+    let (counts, rest) = rest.split_once('(')?.1.split_once(')')?;
+    let (done, total) = counts.split_once('/')?;
+    let (done, total) = (done.parse().ok()?, total.parse().ok()?);
+
+    match stage {
+        // Small fetches are unpacked into loose objects instead of being
+        // kept as a pack, in which case Git reports "unpacking" objects.
+        "Receiving objects" | "Unpacking objects" => Some(Progress::ReceivingObjects {
+            received: done,
+            total,
+            bytes: parse_bytes(rest).unwrap_or_default(),
+        }),
+        "Resolving deltas" => Some(Progress::ResolvingDeltas {
+            resolved: done,
+            total,
+        }),
+        _ => None,
+    }
+}
+
+/// Parses the (human-readable) amount of bytes that follows the counts
+/// of a progress line, e.g. `, 1.20 MiB | 2.00 MiB/s`.
+fn parse_bytes(rest: &str) -> Option<usize> {
+    let size = rest.strip_prefix(", ")?.split(" | ").next()?;
+    let (value, unit) = size.split_once(' ')?;
+    let value = value.parse::<f64>().ok()?;
+
+    let multiplier = match unit {
+        "byte" | "bytes" => 1,
+        "KiB" => 1 << 10,
+        "MiB" => 1 << 20,
+        "GiB" => 1 << 30,
+        _ => return None,
+    };
+
+    Some((value * multiplier as f64) as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_receiving_objects() {
+        assert_eq!(
+            parse("Receiving objects:  45% (450/1000), 1.50 MiB | 2.00 MiB/s"),
+            Some(Progress::ReceivingObjects {
+                received: 450,
+                total: 1000,
+                bytes: 3 << 19,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_receiving_objects_done() {
+        assert_eq!(
+            parse("Receiving objects: 100% (3/3), 250 bytes | 250.00 KiB/s, done."),
+            Some(Progress::ReceivingObjects {
+                received: 3,
+                total: 3,
+                bytes: 250,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_receiving_objects_no_bytes() {
+        assert_eq!(
+            parse("Receiving objects: 100% (3/3), done."),
+            Some(Progress::ReceivingObjects {
+                received: 3,
+                total: 3,
+                bytes: 0,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_unpacking_objects() {
+        assert_eq!(
+            parse("Unpacking objects: 100% (3/3), 1.00 KiB | 1.00 MiB/s, done."),
+            Some(Progress::ReceivingObjects {
+                received: 3,
+                total: 3,
+                bytes: 1024,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_resolving_deltas() {
+        assert_eq!(
+            parse("Resolving deltas: 100% (12/12), done."),
+            Some(Progress::ResolvingDeltas {
+                resolved: 12,
+                total: 12,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_other() {
+        assert_eq!(parse("Cloning into 'repo'..."), None);
+        assert_eq!(parse("remote: Counting objects: 100% (3/3), done."), None);
+        assert_eq!(parse("Updating files: 100% (3/3), done."), None);
+    }
+}
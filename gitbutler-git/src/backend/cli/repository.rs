@@ -1,13 +1,27 @@
-use super::executor::{AskpassServer, GitExecutor, Pid, Socket};
-use crate::{Authorization, ConfigScope, RefSpec};
-use futures::{select, FutureExt};
+use super::executor::{AskpassServer, GitExecutor, OutputLine, Pid, Socket};
+use crate::{Authorization, ConfigScope, FetchOptions, HostKeyPolicy, PushOptions, RefSpec};
+use futures::{select, FutureExt, Stream, TryStreamExt};
 use rand::Rng;
-use std::{collections::HashMap, time::Duration};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+    time::Duration,
+};
 
 /// The number of characters in the secret used for checking
 /// askpass invocations by ssh/git when connecting to our process.
 const ASKPASS_SECRET_LENGTH: usize = 24;
 
+/// The object id Git uses to denote a non-existent ref.
+const ZERO_OID: &str = "0000000000000000000000000000000000000000";
+
+/// The first OpenSSH version supporting the `KnownHostsCommand` option.
+const KNOWN_HOSTS_COMMAND_VERSION: (u32, u32) = (8, 5);
+
+/// Whether or not the SSH commands probed so far support the
+/// `KnownHostsCommand` option (see [`ssh_version`]).
+static KNOWN_HOSTS_COMMAND_SUPPORT: Mutex<BTreeMap<String, bool>> = Mutex::new(BTreeMap::new());
+
 /// Higher level errors that can occur when interacting with the CLI.
 ///
 /// You probably don't want to use this type. Use [`Error`] instead.
@@ -49,6 +63,8 @@ pub enum RepositoryError<
     AskpassDeviceMismatch,
     #[error("failed to perform askpass security check; executable mismatch")]
     AskpassExecutableMismatch,
+    #[error("pinned host keys require OpenSSH 8.5 or later")]
+    PinnedHostKeysUnsupported,
 }
 
 /// Higher level errors that can occur when interacting with the CLI.
@@ -122,13 +138,96 @@ impl<E: GitExecutor> Repository<E> {
         }
     }
 
+    /// Clones the repository at the given URL into the given path
+    /// using the given [`GitExecutor`]. The progress of the transfer
+    /// is reported to, and the clone is aborted by, the given options,
+    /// same as for a fetch.
+    ///
+    /// If the clone fails, whatever was written to the path
+    /// is removed again.
+    ///
+    /// This is an authorized operation; the given authorization
+    /// credentials will be used to authenticate with the remote.
+    #[cold]
+    pub async fn clone<U: AsRef<str>, P: AsRef<str>>(
+        exec: E,
+        url: U,
+        path: P,
+        authorization: &Authorization,
+        options: &FetchOptions,
+    ) -> Result<Self, crate::Error<Error<E>>> {
+        if options.is_cancelled() {
+            return Err(crate::Error::Cancelled);
+        }
+
+        let repo = Self {
+            exec,
+            path: path.as_ref().to_owned(),
+        };
+
+        let target = crate::backend::CloneTarget::of(std::path::Path::new(&repo.path));
+
+        let args = vec!["clone", "--progress", "--", url.as_ref(), &repo.path];
+
+        let mut on_stderr = |line: &str| {
+            if let (Some(progress), Some(on_progress)) =
+                (super::progress::parse(line), &options.progress)
+            {
+                on_progress(progress);
+            }
+        };
+
+        // Dropping the clone future kills the Git process, which
+        // then can't clean up after itself.
+        let result = {
+            let mut clone = core::pin::pin!(repo
+                .execute_with_auth_harness(&args, None, authorization, Some(&mut on_stderr))
+                .fuse());
+
+            match &options.cancellation {
+                Some(cancellation) => select! {
+                    res = clone => res,
+                    _ = cancellation.cancelled().fuse() => Err(crate::Error::Cancelled),
+                },
+                None => clone.await,
+            }
+        };
+
+        let (status, stdout, stderr) = match result {
+            Ok(output) => output,
+            Err(error) => {
+                target.clean_up(std::path::Path::new(&repo.path));
+                return Err(error);
+            }
+        };
+
+        if status == 0 {
+            Ok(repo)
+        } else if is_authorization_failure(&stderr) {
+            Err(crate::Error::AuthorizationFailed(Error::<E>::Failed {
+                status,
+                args: args.into_iter().map(Into::into).collect(),
+                stdout,
+                stderr,
+            }))?
+        } else {
+            Err(Error::<E>::Failed {
+                status,
+                args: args.into_iter().map(Into::into).collect(),
+                stdout,
+                stderr,
+            })?
+        }
+    }
+
     #[cold]
     async fn execute_with_auth_harness(
         &self,
         args: &[&str],
         envs: Option<HashMap<String, String>>,
         authorization: &Authorization,
-    ) -> Result<(usize, String, String), Error<E>> {
+        on_stderr: Option<&mut (dyn FnMut(&str) + Send)>,
+    ) -> Result<(usize, String, String), crate::Error<Error<E>>> {
         let path = std::env::current_exe().map_err(Error::<E>::NoSelfExe)?;
 
         // TODO(qix-): Get parent PID of connecting processes to make sure they're us.
@@ -181,7 +280,44 @@ impl<E: GitExecutor> Repository<E> {
             .map(char::from)
             .collect::<String>();
 
+        // Git asks credential helpers for the credentials of HTTP(S) remotes,
+        // so we register the askpass utility as one. The empty value resets
+        // the list of configured helpers so that ours is asked first; we
+        // consult the user's helpers ourselves (see `credential_helper`).
+        let credential_helper = format!(
+            "credential.helper=!{} credential",
+            shell_quote(&askpath_path)
+        );
+        let args = [
+            &["-c", "credential.helper=", "-c", &credential_helper],
+            args,
+        ]
+        .concat();
+
         let mut envs = envs.unwrap_or_default();
+        let ssh_command = envs
+            .get("GIT_SSH_COMMAND")
+            .cloned()
+            .unwrap_or_else(|| "ssh".into());
+
+        // SSH asks the askpass utility for the known keys of the host, which
+        // tells us the fingerprint of the key it presented, and lets us
+        // serve pinned keys. Older versions of SSH refuse to run with the
+        // option, and only check the known hosts files themselves.
+        let host_key_policy = HostKeyPolicy::of(authorization);
+        let known_hosts_command = if self.supports_known_hosts_command(&ssh_command).await? {
+            format!(
+                " -o {}",
+                shell_quote(&format!(
+                    "KnownHostsCommand=\"{askpath_path}\" known-host %H %f %t %K"
+                ))
+            )
+        } else if let HostKeyPolicy::Pinned(_) = host_key_policy {
+            return Err(Error::<E>::PinnedHostKeysUnsupported)?;
+        } else {
+            String::new()
+        };
+
         envs.insert("GITBUTLER_ASKPASS_PIPE".into(), sock_server.to_string());
         envs.insert("GITBUTLER_ASKPASS_SECRET".into(), secret.clone());
         envs.insert("SSH_ASKPASS".into(), askpath_path);
@@ -198,7 +334,7 @@ impl<E: GitExecutor> Repository<E> {
         envs.insert(
             "GIT_SSH_COMMAND".into(),
             format!(
-                "{}{}{} -o StrictHostKeyChecking=accept-new -o KbdInteractiveAuthentication=no{}",
+                "{}{}{}{}{}{} -o KbdInteractiveAuthentication=no{}",
                 {
                     #[cfg(not(target_os = "windows"))]
                     {
@@ -209,12 +345,26 @@ impl<E: GitExecutor> Repository<E> {
                         ""
                     }
                 },
-                envs.get("GIT_SSH_COMMAND").unwrap_or(&"ssh".into()),
+                ssh_command,
                 match authorization {
                     Authorization::Ssh { .. } => " -o PreferredAuthentications=publickey",
                     Authorization::Basic { .. } => " -o PreferredAuthentications=password",
                     _ => "",
                 },
+                match authorization {
+                    Authorization::Ssh {
+                        private_key: Some(private_key),
+                        ..
+                    } => format!(" -i {} -o IdentitiesOnly=yes", shell_quote(private_key)),
+                    _ => String::new(),
+                },
+                match host_key_policy {
+                    HostKeyPolicy::Strict => " -o StrictHostKeyChecking=yes",
+                    HostKeyPolicy::AcceptNew => " -o StrictHostKeyChecking=accept-new",
+                    // Only the pinned keys (served by the askpass utility) are known.
+                    HostKeyPolicy::Pinned(_) => " -o StrictHostKeyChecking=yes -o UserKnownHostsFile=/dev/null -o GlobalKnownHostsFile=/dev/null",
+                },
+                known_hosts_command,
                 {
                     // In test environments, we don't want to pollute the user's known hosts file.
                     // So, we just use /dev/null instead.
@@ -230,28 +380,34 @@ impl<E: GitExecutor> Repository<E> {
             ),
         );
 
-        if let Authorization::Ssh {
-            private_key: Some(private_key),
-            ..
-        } = authorization
-        {
-            envs.insert("GIT_SSH_VARIANT".into(), "ssh".into());
-            envs.insert("GIT_SSH_KEY".into(), private_key.clone());
-        }
-
         let mut child_process = core::pin::pin! {
             async {
-                self.exec
-                    .execute(args, Some(envs))
-                    .await
-                    .map_err(Error::<E>::Exec)
+                match on_stderr {
+                    Some(on_stderr) => self.exec.execute_streaming(&args, Some(envs), on_stderr).await,
+                    None => self.exec.execute(&args, Some(envs)).await,
+                }
+                .map_err(Error::<E>::Exec)
             }.fuse()
         };
 
+        // the host and the fingerprint of the key it presented, if SSH told us
+        let mut host_key = None::<(String, String)>;
+
+        // where the credentials Git got from us came from, if it asked for any
+        let mut credential_source = None::<CredentialSource>;
+
         loop {
             select! {
                 res = child_process => {
-                    return res;
+                    let (status, stdout, stderr) = res?;
+
+                    if status != 0 && stderr.to_lowercase().contains("host key verification failed") {
+                        if let Some((host, fingerprint)) = host_key.or_else(|| rejected_host_key(&stderr)) {
+                            return Err(crate::Error::HostKeyMismatch(host, fingerprint));
+                        }
+                    }
+
+                    return Ok((status, stdout, stderr));
                 },
                 res = sock_server.accept(Some(Duration::from_secs(60))).fuse() => {
                     let mut sock = res.map_err(Error::<E>::AskpassServer)?;
@@ -294,6 +450,49 @@ impl<E: GitExecutor> Repository<E> {
                     // get the prompt
                     let prompt = sock.read_line().await.map_err(Error::<E>::AskpassIo)?;
 
+                    // Are we being asked as a credential helper?
+                    if let Some(action) = prompt.strip_prefix("credential-helper ") {
+                        let mut attributes = Vec::new();
+                        loop {
+                            let line = sock.read_line().await.map_err(Error::<E>::AskpassIo)?;
+                            if line.is_empty() {
+                                break;
+                            }
+                            attributes.push(line);
+                        }
+
+                        for line in self.credential_helper(action, &attributes, authorization, &mut credential_source).await? {
+                            sock.write_line(&line).await.map_err(Error::<E>::AskpassIo)?;
+                        }
+                        sock.write_line("").await.map_err(Error::<E>::AskpassIo)?;
+
+                        continue;
+                    }
+
+                    // Are we being asked as SSH's `KnownHostsCommand`?
+                    if let Some(request) = prompt.strip_prefix("known-host ") {
+                        // SSH leaves all but the host empty when it
+                        // doesn't know the key yet.
+                        let mut fields = request.split(' ');
+                        let host = fields.next().unwrap_or_default();
+                        let fingerprint = fields.next().unwrap_or_default();
+                        let key_type = fields.next().unwrap_or_default();
+                        let key = fields.next().unwrap_or_default();
+
+                        if !fingerprint.is_empty() {
+                            host_key = Some((host.to_owned(), fingerprint.to_owned()));
+
+                            if let HostKeyPolicy::Pinned(fingerprints) = &host_key_policy {
+                                if fingerprints.iter().any(|pinned| pinned == fingerprint) {
+                                    sock.write_line(&format!("{host} {key_type} {key}")).await.map_err(Error::<E>::AskpassIo)?;
+                                }
+                            }
+                        }
+                        sock.write_line("").await.map_err(Error::<E>::AskpassIo)?;
+
+                        continue;
+                    }
+
                     // TODO(qix-): The prompt matching logic here is fragile as the remote
                     // TODO(qix-): can customize prompts. I need to investigate if there's
                     // TODO(qix-): a better way to do this.
@@ -333,6 +532,191 @@ impl<E: GitExecutor> Repository<E> {
             }
         }
     }
+
+    /// Whether or not the given SSH command supports the `KnownHostsCommand`
+    /// option, i.e. is OpenSSH 8.5 or later. The result is cached per command.
+    async fn supports_known_hosts_command(&self, ssh_command: &str) -> Result<bool, Error<E>> {
+        if let Some(&supported) = KNOWN_HOSTS_COMMAND_SUPPORT.lock().unwrap().get(ssh_command) {
+            return Ok(supported);
+        }
+
+        // Git runs aliases starting with `!` through the shell,
+        // the same way it runs `GIT_SSH_COMMAND`.
+        let alias = format!("alias.gitbutler-ssh-version=!{ssh_command} -V");
+        let (_, stdout, stderr) = self
+            .exec
+            .execute(&["-c", &alias, "gitbutler-ssh-version"], None)
+            .await
+            .map_err(Error::<E>::Exec)?;
+
+        let supported = ssh_version(&stderr)
+            .or_else(|| ssh_version(&stdout))
+            .is_some_and(|version| version >= KNOWN_HOSTS_COMMAND_VERSION);
+
+        KNOWN_HOSTS_COMMAND_SUPPORT
+            .lock()
+            .unwrap()
+            .insert(ssh_command.to_owned(), supported);
+
+        Ok(supported)
+    }
+
+    /// Answers a request of Git to the askpass utility acting as a
+    /// credential helper, as per the `git credential` helper protocol
+    /// (see `gitcredentials(7)`). Returns the attributes to answer with.
+    ///
+    /// Credentials of [`Authorization::Basic`] are served directly.
+    /// Otherwise, the request is forwarded to the credential helpers
+    /// configured by the user, and `source` records which of the two
+    /// answered. Git stores or erases the credentials it got depending
+    /// on whether the remote accepted them, which is only forwarded to
+    /// the user's helpers if the credentials came from them.
+    ///
+    /// Like the askpass utility itself, this is not supported on Windows.
+    async fn credential_helper(
+        &self,
+        action: &str,
+        attributes: &[String],
+        authorization: &Authorization,
+        source: &mut Option<CredentialSource>,
+    ) -> Result<Vec<String>, Error<E>> {
+        match action {
+            "get" => {
+                if let Authorization::Basic { username, password } = authorization {
+                    if username.is_some() || password.is_some() {
+                        *source = Some(CredentialSource::Authorization);
+                        return Ok(username
+                            .iter()
+                            .map(|username| format!("username={username}"))
+                            .chain(
+                                password
+                                    .iter()
+                                    .map(|password| format!("password={password}")),
+                            )
+                            .collect());
+                    }
+                }
+
+                let (status, stdout, _) = self.forward_credential("fill", attributes).await?;
+
+                // Failing to find credentials is not an error; Git
+                // will simply continue without them.
+                if status == 0 {
+                    *source = Some(CredentialSource::Helper);
+                    Ok(stdout.lines().map(Into::into).collect())
+                } else {
+                    *source = None;
+                    Ok(Vec::new())
+                }
+            }
+            "store" if *source == Some(CredentialSource::Helper) => {
+                self.forward_credential("approve", attributes).await?;
+                Ok(Vec::new())
+            }
+            "erase" if *source == Some(CredentialSource::Helper) => {
+                self.forward_credential("reject", attributes).await?;
+                Ok(Vec::new())
+            }
+            // Helpers must ignore actions they don't understand.
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    /// Runs `git credential <command>` with the given attributes,
+    /// which consults the credential helpers configured by the user.
+    async fn forward_credential(
+        &self,
+        command: &str,
+        attributes: &[String],
+    ) -> Result<(usize, String, String), Error<E>> {
+        let mut args = Vec::new();
+
+        // The repository doesn't exist yet when cloning, in which case
+        // only the global and system configuration is used.
+        if self.exec.stat(&self.path).await.is_ok() {
+            args.extend(["-C", &self.path]);
+        }
+
+        args.extend(["credential", command]);
+
+        let mut input = attributes.join("\n");
+        input.push_str("\n\n");
+
+        self.exec
+            .execute_with_stdin(&args, credential_envs(), &input)
+            .await
+            .map_err(Error::<E>::Exec)
+    }
+}
+
+/// Where the credentials served to Git came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CredentialSource {
+    /// The [`Authorization`] the command was run with.
+    Authorization,
+    /// The credential helpers configured by the user.
+    Helper,
+}
+
+/// The environment to consult the user's credential helpers with.
+#[cfg(not(test))]
+fn credential_envs() -> Option<HashMap<String, String>> {
+    None
+}
+
+/// In test environments, we don't want to read from (or worse, write to)
+/// the user's credential helpers, so only the repository's are consulted.
+#[cfg(test)]
+fn credential_envs() -> Option<HashMap<String, String>> {
+    Some(HashMap::from([
+        ("GIT_CONFIG_GLOBAL".into(), "/dev/null".into()),
+        ("GIT_CONFIG_NOSYSTEM".into(), "1".into()),
+    ]))
+}
+
+/// Parses the major and minor version out of the output of `ssh -V`,
+/// e.g. `OpenSSH_9.2p1 Debian-2, OpenSSL 3.0.11 19 Sep 2023`.
+/// Returns `None` if the output isn't OpenSSH's.
+fn ssh_version(output: &str) -> Option<(u32, u32)> {
+    let version = output.trim_start().strip_prefix("OpenSSH_")?;
+    let (major, rest) = version.split_once('.')?;
+    let minor = rest
+        .split(|c: char| !c.is_ascii_digit())
+        .next()
+        .unwrap_or_default();
+    Some((major.parse().ok()?, minor.parse().ok()?))
+}
+
+/// Parses the host and the fingerprint of the key it presented out of the
+/// stderr output of SSH refusing a changed host key, for when SSH doesn't
+/// support asking us about the known keys (see `KnownHostsCommand`).
+///
+/// Returns `None` for unknown hosts, as SSH doesn't print their fingerprint.
+fn rejected_host_key(stderr: &str) -> Option<(String, String)> {
+    let host = stderr.lines().find_map(|line| {
+        line.trim()
+            .strip_prefix("Host key for ")?
+            .split_once(" has changed")
+            .map(|(host, _)| host.to_owned())
+    })?;
+    let fingerprint = stderr
+        .split_whitespace()
+        .find(|word| word.starts_with("SHA256:"))?
+        .trim_end_matches('.')
+        .to_owned();
+    Some((host, fingerprint))
+}
+
+/// Quotes the given string for use as a single word in a POSIX shell.
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// Whether or not the given stderr output of a Git command
+/// indicates that the remote rejected our credentials.
+fn is_authorization_failure(stderr: &str) -> bool {
+    let stderr = stderr.to_lowercase();
+    stderr.contains("permission denied") || stderr.contains("authentication failed")
 }
 
 impl<E: GitExecutor + 'static> crate::Repository for Repository<E> {
@@ -425,23 +809,55 @@ impl<E: GitExecutor + 'static> crate::Repository for Repository<E> {
         remote: &str,
         refspec: RefSpec,
         authorization: &Authorization,
+        options: &FetchOptions,
     ) -> Result<(), crate::Error<Self::Error>> {
-        let mut args = vec![
-            "-C",
-            &self.path,
-            "fetch",
-            "--quiet",
-            "--no-write-fetch-head",
-        ];
+        if options.is_cancelled() {
+            return Err(crate::Error::Cancelled);
+        }
+
+        let mut args = vec!["-C", &self.path, "fetch", "--no-write-fetch-head"];
+
+        // `--quiet` would suppress the progress output as well.
+        if options.progress.is_some() {
+            args.push("--progress");
+        } else {
+            args.push("--quiet");
+        }
 
         let refspec = refspec.to_string();
 
         args.push(remote);
         args.push(&refspec);
 
-        let (status, stdout, stderr) = self
-            .execute_with_auth_harness(&args, None, authorization)
-            .await?;
+        let mut on_stderr = options.progress.as_ref().map(|progress| {
+            |line: &str| {
+                if let Some(p) = super::progress::parse(line) {
+                    progress(p);
+                }
+            }
+        });
+
+        // Dropping the fetch future kills the Git process.
+        let (status, stdout, stderr) = {
+            let mut fetch = core::pin::pin!(self
+                .execute_with_auth_harness(
+                    &args,
+                    None,
+                    authorization,
+                    on_stderr
+                        .as_mut()
+                        .map(|f| f as &mut (dyn FnMut(&str) + Send)),
+                )
+                .fuse());
+
+            match &options.cancellation {
+                Some(cancellation) => select! {
+                    res = fetch => res?,
+                    _ = cancellation.cancelled().fuse() => return Err(crate::Error::Cancelled),
+                },
+                None => fetch.await?,
+            }
+        };
 
         if status == 0 {
             Ok(())
@@ -453,7 +869,7 @@ impl<E: GitExecutor + 'static> crate::Repository for Repository<E> {
                 .map(|line| line.split_whitespace().last().unwrap_or_default())
             {
                 Err(crate::Error::RefNotFound(refname.to_owned()))?
-            } else if stderr.to_lowercase().contains("permission denied") {
+            } else if is_authorization_failure(&stderr) {
                 Err(crate::Error::AuthorizationFailed(Error::<E>::Failed {
                     status,
                     args: args.into_iter().map(Into::into).collect(),
@@ -471,6 +887,84 @@ impl<E: GitExecutor + 'static> crate::Repository for Repository<E> {
         }
     }
 
+    async fn push(
+        &self,
+        remote: &str,
+        refspecs: &[RefSpec],
+        authorization: &Authorization,
+        options: &PushOptions,
+    ) -> Result<(), crate::Error<Self::Error>> {
+        let mut args = vec!["-C", &self.path, "push", "--porcelain"];
+
+        if options.atomic {
+            args.push("--atomic");
+        }
+
+        let leases = options
+            .force_with_lease
+            .iter()
+            .map(|lease| {
+                format!(
+                    "--force-with-lease={}:{}",
+                    lease.refname,
+                    lease.expected.as_deref().unwrap_or_default()
+                )
+            })
+            .collect::<Vec<_>>();
+        args.extend(leases.iter().map(String::as_str));
+
+        let push_options = options
+            .push_options
+            .iter()
+            .map(|push_option| format!("--push-option={push_option}"))
+            .collect::<Vec<_>>();
+        args.extend(push_options.iter().map(String::as_str));
+
+        let refspecs = refspecs.iter().map(ToString::to_string).collect::<Vec<_>>();
+
+        args.push(remote);
+        args.extend(refspecs.iter().map(String::as_str));
+
+        let (status, stdout, stderr) = self
+            .execute_with_auth_harness(&args, None, authorization, None)
+            .await?;
+
+        if status == 0 {
+            return Ok(());
+        }
+
+        // With `--porcelain`, every ref is reported on its own line as
+        // `<flag>\t<from>:<to>\t<summary> (<reason>)`, where `!` flags a rejection.
+        let rejected = stdout.lines().find_map(|line| {
+            let mut fields = line.strip_prefix("!\t")?.split('\t');
+            let refname = fields.next()?.rsplit(':').next()?;
+            let summary = fields.next().unwrap_or_default();
+            let reason = summary
+                .rsplit_once('(')
+                .and_then(|(_, reason)| reason.strip_suffix(')'))
+                .unwrap_or(summary);
+            Some((refname.to_owned(), reason.to_owned()))
+        });
+
+        if is_authorization_failure(&stderr) {
+            Err(crate::Error::AuthorizationFailed(Error::<E>::Failed {
+                status,
+                args: args.into_iter().map(Into::into).collect(),
+                stdout,
+                stderr,
+            }))?
+        } else if let Some((refname, reason)) = rejected {
+            Err(crate::Error::PushRejected(refname, reason))?
+        } else {
+            Err(Error::<E>::Failed {
+                status,
+                args: args.into_iter().map(Into::into).collect(),
+                stdout,
+                stderr,
+            })?
+        }
+    }
+
     async fn create_remote(
         &self,
         remote: &str,
@@ -484,7 +978,17 @@ impl<E: GitExecutor + 'static> crate::Repository for Repository<E> {
             .await
             .map_err(Error::<E>::Exec)?;
 
-        if status != 0 {
+        if status != 0 && stderr.to_lowercase().contains("already exists") {
+            Err(crate::Error::RemoteExists(
+                remote.to_owned(),
+                Error::<E>::Failed {
+                    status,
+                    args: args.into_iter().map(Into::into).collect(),
+                    stdout,
+                    stderr,
+                },
+            ))?
+        } else if status != 0 {
             Err(Error::<E>::Failed {
                 status,
                 args: args.into_iter().map(Into::into).collect(),
@@ -589,7 +1093,7 @@ impl<E: GitExecutor + 'static> crate::Repository for Repository<E> {
     }
 
     async fn symbolic_head(&self) -> Result<String, crate::Error<Self::Error>> {
-        let args = vec!["-C", &self.path, "symbolic-ref", "HEAD"];
+        let args = vec!["-C", &self.path, "symbolic-ref", "--quiet", "HEAD"];
 
         let (status, stdout, stderr) = self
             .exec
@@ -597,6 +1101,13 @@ impl<E: GitExecutor + 'static> crate::Repository for Repository<E> {
             .await
             .map_err(Error::<E>::Exec)?;
 
+        // `--quiet` makes `symbolic-ref` exit with 1 and no
+        // output if HEAD is detached (i.e. not a symbolic ref).
+        if status == 1 && stdout.is_empty() {
+            self.head().await?;
+            return Ok("HEAD".to_owned());
+        }
+
         if status != 0 {
             return Err(Error::<E>::Failed {
                 status,
@@ -629,4 +1140,250 @@ impl<E: GitExecutor + 'static> crate::Repository for Repository<E> {
             })?
         }
     }
+
+    async fn list_refs(&self, prefix: &str) -> Result<Vec<crate::Ref>, crate::Error<Self::Error>> {
+        let mut args = vec![
+            "-C",
+            &self.path,
+            "for-each-ref",
+            "--format=%(objectname) %(refname)",
+        ];
+
+        // Patterns passed to `for-each-ref` only match whole path
+        // components, so we pass the parent "directory" of the prefix
+        // and filter the rest ourselves.
+        if let Some((pattern, _)) = prefix.rsplit_once('/') {
+            args.push(pattern);
+        }
+
+        let (status, stdout, stderr) = self
+            .exec
+            .execute(&args, None)
+            .await
+            .map_err(Error::<E>::Exec)?;
+
+        if status != 0 {
+            return Err(Error::<E>::Failed {
+                status,
+                args: args.into_iter().map(Into::into).collect(),
+                stdout,
+                stderr,
+            })?;
+        }
+
+        Ok(stdout
+            .lines()
+            .filter_map(|line| line.split_once(' '))
+            .filter(|(_, name)| name.starts_with(prefix))
+            .map(|(target, name)| crate::Ref {
+                name: name.to_owned(),
+                target: target.to_owned(),
+            })
+            .collect())
+    }
+
+    async fn resolve_ref(
+        &self,
+        refname: &str,
+    ) -> Result<Option<String>, crate::Error<Self::Error>> {
+        // `rev-parse` would resolve any revision, e.g. `master` or `HEAD~1`.
+        if !crate::repository::is_full_refname(refname) {
+            return Ok(None);
+        }
+
+        let args = vec!["-C", &self.path, "show-ref", "--verify", "--hash", refname];
+
+        let (status, stdout, stderr) = self
+            .exec
+            .execute(&args, None)
+            .await
+            .map_err(Error::<E>::Exec)?;
+
+        if status == 0 {
+            Ok(Some(stdout))
+        } else if status == 128 && stderr.contains("not a valid ref") {
+            // `--quiet` would hide the hash as well, so the ref
+            // not existing is told apart by the message.
+            Ok(None)
+        } else {
+            Err(Error::<E>::Failed {
+                status,
+                args: args.into_iter().map(Into::into).collect(),
+                stdout,
+                stderr,
+            })?
+        }
+    }
+
+    async fn update_refs(
+        &self,
+        transaction: &crate::RefTransaction,
+    ) -> Result<(), crate::Error<Self::Error>> {
+        let mut args = vec!["-C", &self.path, "update-ref"];
+
+        if let Some(message) = transaction.message.as_deref() {
+            args.push("-m");
+            args.push(message);
+        }
+
+        args.push("--stdin");
+
+        // `update-ref --stdin` applies all updates in a single transaction.
+        // A zero new value deletes the ref, and a zero old value requires
+        // the ref not to exist.
+        let mut stdin = String::new();
+        for update in &transaction.updates {
+            stdin.push_str("update ");
+            stdin.push_str(&update.refname);
+            stdin.push(' ');
+            stdin.push_str(update.target.as_deref().unwrap_or(ZERO_OID));
+            match &update.expected {
+                crate::RefExpectation::Any => {}
+                crate::RefExpectation::Absent => {
+                    stdin.push(' ');
+                    stdin.push_str(ZERO_OID);
+                }
+                crate::RefExpectation::Target(target) => {
+                    stdin.push(' ');
+                    stdin.push_str(target);
+                }
+            }
+            stdin.push('\n');
+        }
+
+        let (status, stdout, stderr) = self
+            .exec
+            .execute_with_stdin(&args, None, &stdin)
+            .await
+            .map_err(Error::<E>::Exec)?;
+
+        if status == 0 {
+            return Ok(());
+        }
+
+        // Git reports mismatches as `fatal: cannot lock ref '<refname>': <reason>`.
+        let mismatch = stderr.lines().find_map(|line| {
+            let (refname, reason) = line
+                .strip_prefix("fatal: cannot lock ref '")?
+                .split_once("': ")?;
+            (reason.contains("but expected")
+                || reason.contains("reference already exists")
+                || reason.contains("unable to resolve reference"))
+            .then(|| refname.to_owned())
+        });
+
+        if let Some(refname) = mismatch {
+            Err(crate::Error::RefMismatch(refname))?
+        } else {
+            Err(Error::<E>::Failed {
+                status,
+                args: args.into_iter().map(Into::into).collect(),
+                stdout,
+                stderr,
+            })?
+        }
+    }
+
+    fn log<'a>(
+        &'a self,
+        range: &'a str,
+        options: &'a crate::LogOptions,
+    ) -> impl Stream<Item = Result<String, crate::Error<Self::Error>>> + 'a {
+        let mut args = vec!["-C".to_owned(), self.path.clone(), "rev-list".into()];
+
+        if let Some(max_count) = options.max_count {
+            args.push(format!("--max-count={max_count}"));
+        }
+
+        if options.first_parent {
+            args.push("--first-parent".into());
+        }
+
+        match options.order {
+            crate::LogOrder::Default => {}
+            crate::LogOrder::Topological => args.push("--topo-order".into()),
+            crate::LogOrder::Time => args.push("--date-order".into()),
+        }
+
+        // a range must not be mistaken for an option
+        args.extend(["--end-of-options".into(), range.into(), "--".into()]);
+        args.extend(options.paths.iter().cloned());
+
+        let lines = self
+            .exec
+            .execute_lines(&args.iter().map(String::as_str).collect::<Vec<_>>(), None);
+
+        lines
+            .map_err(|error| Error::<E>::Exec(error).into())
+            .try_filter_map(move |line| {
+                let result = match line {
+                    OutputLine::Stdout(id) => Ok(Some(id)),
+                    OutputLine::Exit(0, _) => Ok(None),
+                    OutputLine::Exit(status, stderr) => Err(Error::<E>::Failed {
+                        status,
+                        args: args.clone(),
+                        stdout: String::new(),
+                        stderr,
+                    }
+                    .into()),
+                };
+                futures::future::ready(result)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ssh_version() {
+        assert_eq!(
+            ssh_version("OpenSSH_9.2p1 Debian-2+deb12u2, OpenSSL 3.0.11 19 Sep 2023\n"),
+            Some((9, 2))
+        );
+        assert_eq!(ssh_version("OpenSSH_8.4p1, LibreSSL 2.8.3"), Some((8, 4)));
+        assert_eq!(ssh_version("OpenSSH_for_Windows_8.1p1"), None);
+        assert_eq!(ssh_version("plink: Release 0.78"), None);
+        assert!(ssh_version("OpenSSH_8.10p1").unwrap() >= KNOWN_HOSTS_COMMAND_VERSION);
+    }
+
+    #[test]
+    fn parses_changed_host_key() {
+        let stderr = "\
+@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@
+@    WARNING: REMOTE HOST IDENTIFICATION HAS CHANGED!     @
+@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@
+IT IS POSSIBLE THAT SOMEONE IS DOING SOMETHING NASTY!
+Someone could be eavesdropping on you right now (man-in-the-middle attack)!
+It is also possible that a host key has just been changed.
+The fingerprint for the ED25519 key sent by the remote host is
+SHA256:pyb0bBMp7qnlnmYuYR2Qm8WQrGyfOWz8Oyn2hN13ggI.
+Please contact your system administrator.
+Add correct host key in /home/user/.ssh/known_hosts to get rid of this message.
+Offending ED25519 key in /home/user/.ssh/known_hosts:1
+  remove with:
+  ssh-keygen -f \"/home/user/.ssh/known_hosts\" -R \"[127.0.0.1]:2222\"
+Host key for [127.0.0.1]:2222 has changed and you have requested strict checking.
+Host key verification failed.
+fatal: Could not read from remote repository.
+";
+        assert_eq!(
+            rejected_host_key(stderr),
+            Some((
+                "[127.0.0.1]:2222".into(),
+                "SHA256:pyb0bBMp7qnlnmYuYR2Qm8WQrGyfOWz8Oyn2hN13ggI".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn ignores_unknown_host() {
+        let stderr = "\
+No ED25519 host key is known for [127.0.0.1]:2222 and you have requested strict checking.
+Host key verification failed.
+fatal: Could not read from remote repository.
+";
+        assert_eq!(rejected_host_key(stderr), None);
+    }
 }
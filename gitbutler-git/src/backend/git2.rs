@@ -3,6 +3,7 @@
 //!
 //! The entry point for this module is the [`Repository`] struct.
 
+mod known_hosts;
 mod repository;
 mod thread_resource;
 
@@ -19,11 +20,7 @@ mod tests {
     use super::*;
 
     async fn make_repo(test_name: String) -> impl crate::Repository {
-        let repo_path = std::env::temp_dir()
-            .join("gitbutler-tests")
-            .join("git")
-            .join("git2")
-            .join(test_name);
+        let repo_path = crate::private::local_repo_path(&test_name);
         let _ = std::fs::remove_dir_all(&repo_path);
         std::fs::create_dir_all(&repo_path).unwrap();
 
@@ -32,5 +29,125 @@ mod tests {
             .unwrap()
     }
 
+    #[test]
+    fn clone_with_progress() {
+        ::tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(async {
+                use crate::Repository as _;
+
+                let test_name = format!("{}::clone_with_progress", module_path!());
+                let (url, head) = crate::private::origin_repo(&test_name);
+
+                let repo_path = crate::private::local_repo_path(&test_name);
+                let _ = std::fs::remove_dir_all(&repo_path);
+
+                let progress = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+                let on_progress = std::sync::Arc::clone(&progress);
+
+                let repo = Repository::<tokio::TokioThreadedResource>::clone(
+                    &url,
+                    &repo_path,
+                    &crate::Authorization::default(),
+                    &crate::FetchOptions::default()
+                        .with_progress(move |progress| on_progress.lock().unwrap().push(progress)),
+                )
+                .await
+                .unwrap();
+
+                assert_eq!(repo.head().await.unwrap(), head);
+                crate::private::assert_transfer_completed(&progress.lock().unwrap());
+            });
+    }
+
+    #[test]
+    fn clone_failure_removes_partial_clone() {
+        ::tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let test_name = format!("{}::clone_failure_removes_partial_clone", module_path!());
+                let repo_path = crate::private::local_repo_path(&test_name);
+                let _ = std::fs::remove_dir_all(&repo_path);
+                let missing = repo_path.with_extension("missing");
+
+                // a directory the clone creates is removed
+                assert!(Repository::<tokio::TokioThreadedResource>::clone(
+                    format!("file://{}", missing.display()),
+                    &repo_path,
+                    &crate::Authorization::default(),
+                    &crate::FetchOptions::default(),
+                )
+                .await
+                .is_err());
+                assert!(!repo_path.exists());
+
+                // an empty directory that was there before is kept, but emptied
+                std::fs::create_dir_all(&repo_path).unwrap();
+                assert!(Repository::<tokio::TokioThreadedResource>::clone(
+                    format!("file://{}", missing.display()),
+                    &repo_path,
+                    &crate::Authorization::default(),
+                    &crate::FetchOptions::default(),
+                )
+                .await
+                .is_err());
+                assert!(repo_path.exists());
+                assert!(repo_path.read_dir().unwrap().next().is_none());
+
+                // a directory that wasn't empty is left alone
+                std::fs::write(repo_path.join("file.txt"), "content").unwrap();
+                assert!(Repository::<tokio::TokioThreadedResource>::clone(
+                    format!("file://{}", missing.display()),
+                    &repo_path,
+                    &crate::Authorization::default(),
+                    &crate::FetchOptions::default(),
+                )
+                .await
+                .is_err());
+                assert!(repo_path.join("file.txt").exists());
+            });
+    }
+
+    #[test]
+    fn clone_cancelled() {
+        ::tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let test_name = format!("{}::clone_cancelled", module_path!());
+                let (url, _) = crate::private::origin_repo(&test_name);
+
+                let repo_path = crate::private::local_repo_path(&test_name);
+                let _ = std::fs::remove_dir_all(&repo_path);
+
+                let cancellation = crate::CancellationToken::new();
+                let on_progress = cancellation.clone();
+
+                let result = Repository::<tokio::TokioThreadedResource>::clone(
+                    &url,
+                    &repo_path,
+                    &crate::Authorization::default(),
+                    &crate::FetchOptions::default()
+                        .with_cancellation(cancellation)
+                        .with_progress(move |_| on_progress.cancel()),
+                )
+                .await;
+
+                assert!(matches!(result, Err(crate::Error::Cancelled)));
+                assert!(!repo_path.exists());
+            });
+    }
+
+    async fn make_fixture(test_name: String) -> impl crate::private::Fixture {
+        crate::private::DiskFixture::new(make_repo(test_name.clone()).await, test_name)
+    }
+
     crate::gitbutler_git_integration_tests!(make_repo, disable_io);
+    crate::gitbutler_git_host_key_tests!(make_repo);
+    crate::gitbutler_git_conformance_tests!(make_fixture);
 }
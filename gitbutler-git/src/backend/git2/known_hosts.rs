@@ -0,0 +1,340 @@
+//! Checks of SSH host keys against OpenSSH `known_hosts` files
+//! (see `sshd(8)`), the way `ssh` checks them.
+
+use hmac::Mac;
+use std::{io::Write, path::PathBuf};
+
+/// The known hosts file of the system, which is only read.
+const GLOBAL_KNOWN_HOSTS: &str = "/etc/ssh/ssh_known_hosts";
+
+/// How a key presented by a host relates to the keys known for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(super) enum Status {
+    /// No key of the same type is known for the host.
+    New,
+    /// Another key of the same type is known for the host.
+    Changed,
+    /// The key is known for the host.
+    Known,
+    /// The key has been revoked.
+    Revoked,
+}
+
+/// Checks the key of the given type (e.g. `ssh-ed25519`) presented by
+/// the host against the user's and the system's known hosts files.
+///
+/// `host` is the name of the host as it is written to known hosts files,
+/// i.e. including the port if it isn't the default one (see [`name`]).
+pub(super) fn check(host: &str, key_type: &str, key: &[u8]) -> Status {
+    [user_known_hosts(), Some(PathBuf::from(GLOBAL_KNOWN_HOSTS))]
+        .into_iter()
+        .flatten()
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .map(|contents| check_contents(&contents, host, key_type, key))
+        .max()
+        .unwrap_or(Status::New)
+}
+
+/// Adds the key to the user's known hosts file, as `ssh` does
+/// for new hosts with `StrictHostKeyChecking=accept-new`.
+pub(super) fn add(host: &str, key_type: &str, key: &[u8]) -> std::io::Result<()> {
+    let Some(path) = user_known_hosts() else {
+        return Ok(());
+    };
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{host} {key_type} {}", base64(key))
+}
+
+/// The name of the host as it is written to known hosts files, which
+/// includes the port (as `[host]:port`) if it isn't the default one.
+pub(super) fn name(host: &str, port: Option<u16>) -> String {
+    match port {
+        Some(port) if port != 22 => format!("[{host}]:{port}"),
+        _ => host.to_owned(),
+    }
+}
+
+/// Returns the port of the given SSH URL, either of the
+/// `ssh://[user@]host[:port]/path` or of the scp-like
+/// `[[user@]host:port]:path` form, if it has one.
+pub(super) fn port(url: &str) -> Option<u16> {
+    let authority = if let Some((scheme, rest)) = url.split_once("://") {
+        if !scheme.contains("ssh") {
+            return None;
+        }
+        rest.split('/').next().unwrap_or_default()
+    } else {
+        url.strip_prefix('[')?.split(']').next().unwrap_or_default()
+    };
+
+    let host = authority.rsplit('@').next().unwrap_or_default();
+
+    // IPv6 addresses are bracketed in URLs.
+    let port = match host.strip_prefix('[') {
+        Some(host) => host.split_once("]:")?.1,
+        None => host.rsplit_once(':')?.1,
+    };
+
+    port.parse().ok()
+}
+
+/// The user's known hosts file, which new keys are added to.
+#[cfg(not(test))]
+fn user_known_hosts() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".ssh").join("known_hosts"))
+}
+
+/// In test environments, we don't want to read from (or worse,
+/// write to) the user's known hosts file.
+#[cfg(test)]
+fn user_known_hosts() -> Option<PathBuf> {
+    None
+}
+
+/// Checks the key against the entries of a single known hosts file.
+fn check_contents(contents: &str, host: &str, key_type: &str, key: &[u8]) -> Status {
+    let key = base64(key);
+
+    let mut status = Status::New;
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut fields = line.split_whitespace();
+        let (revoked, patterns) = match fields.next() {
+            Some("@revoked") => (true, fields.next()),
+            // Certificate authorities sign host certificates, which
+            // libgit2 doesn't tell us about.
+            Some("@cert-authority") => continue,
+            patterns => (false, patterns),
+        };
+        let (Some(patterns), Some(entry_type), Some(entry_key)) =
+            (patterns, fields.next(), fields.next())
+        else {
+            continue;
+        };
+
+        if !matches_host(patterns, host) {
+            continue;
+        }
+
+        if revoked {
+            if entry_key == key {
+                return Status::Revoked;
+            }
+        } else if entry_type == key_type {
+            status = status.max(if entry_key == key {
+                Status::Known
+            } else {
+                Status::Changed
+            });
+        }
+    }
+
+    status
+}
+
+/// Whether or not the host matches the comma-separated patterns of an
+/// entry, or the hash of a hashed entry (`|1|salt|hash`).
+fn matches_host(patterns: &str, host: &str) -> bool {
+    if let Some(hashed) = patterns.strip_prefix("|1|") {
+        let Some((salt, hash)) = hashed.split_once('|') else {
+            return false;
+        };
+        let (Some(salt), Some(hash)) = (unbase64(salt), unbase64(hash)) else {
+            return false;
+        };
+        let Ok(mut mac) = hmac::Hmac::<sha1::Sha1>::new_from_slice(&salt) else {
+            return false;
+        };
+        mac.update(host.as_bytes());
+        return mac.verify_slice(&hash).is_ok();
+    }
+
+    let mut matched = false;
+    for pattern in patterns.split(',') {
+        if let Some(pattern) = pattern.strip_prefix('!') {
+            // A negated pattern that matches overrules all others.
+            if matches_pattern(pattern.as_bytes(), host.as_bytes()) {
+                return false;
+            }
+        } else {
+            matched = matched || matches_pattern(pattern.as_bytes(), host.as_bytes());
+        }
+    }
+    matched
+}
+
+/// Matches a host against a pattern, in which `*` matches any number
+/// of characters and `?` matches exactly one. Host names are case
+/// insensitive.
+fn matches_pattern(pattern: &[u8], host: &[u8]) -> bool {
+    match (pattern.split_first(), host.split_first()) {
+        (None, None) => true,
+        (Some((b'*', rest)), _) => {
+            matches_pattern(rest, host)
+                || host
+                    .split_first()
+                    .is_some_and(|(_, host)| matches_pattern(pattern, host))
+        }
+        (Some((b'?', pattern)), Some((_, host))) => matches_pattern(pattern, host),
+        (Some((p, pattern)), Some((h, host))) => {
+            p.eq_ignore_ascii_case(h) && matches_pattern(pattern, host)
+        }
+        _ => false,
+    }
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes the bytes as (padded) base64, as keys are written in
+/// known hosts files.
+pub(super) fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = u32::from_be_bytes([
+            0,
+            chunk[0],
+            chunk.get(1).copied().unwrap_or_default(),
+            chunk.get(2).copied().unwrap_or_default(),
+        ]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64_ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Decodes (padded or unpadded) base64, returning `None` if it isn't valid.
+fn unbase64(encoded: &str) -> Option<Vec<u8>> {
+    let encoded = encoded.trim_end_matches('=').as_bytes();
+
+    let mut decoded = Vec::with_capacity(encoded.len() * 3 / 4);
+    for chunk in encoded.chunks(4) {
+        if chunk.len() == 1 {
+            return None;
+        }
+
+        let mut n = 0_u32;
+        for (i, c) in chunk.iter().enumerate() {
+            let value = BASE64_ALPHABET.iter().position(|a| a == c)?;
+            n |= u32::try_from(value).ok()? << (18 - 6 * i);
+        }
+        decoded.extend_from_slice(&n.to_be_bytes()[1..chunk.len()]);
+    }
+    Some(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "AAAAC3NzaC1lZDI1NTE5AAAAIGIN1CQQNylZSdnWMVB6lMxY8FqJaUBYc1OUWDSBlcmA";
+    const OTHER_KEY: &str = "AAAAC3NzaC1lZDI1NTE5AAAAIOMqqnkVzrm0SdG6UOoqKLsabgH5C9okWi0dh2l9GKJl";
+
+    fn check(contents: &str, host: &str) -> Status {
+        check_contents(contents, host, "ssh-ed25519", &unbase64(KEY).unwrap())
+    }
+
+    #[test]
+    fn base64_roundtrip() {
+        for bytes in [&b""[..], b"a", b"ab", b"abc", b"abcd", &[0xff, 0x00, 0x7f]] {
+            assert_eq!(unbase64(&base64(bytes)).unwrap(), bytes);
+        }
+        assert_eq!(base64(b"ab"), "YWI=");
+        assert_eq!(base64(&unbase64(KEY).unwrap()), KEY);
+        assert_eq!(unbase64("YWI").unwrap(), b"ab");
+        assert_eq!(unbase64("Y"), None);
+        assert_eq!(unbase64("Y!=="), None);
+    }
+
+    #[test]
+    fn plain_entries() {
+        let contents = format!(
+            "# comment\n\nexample.com,other.com ssh-ed25519 {KEY} comment\n\
+             [example.com]:2222 ssh-ed25519 {OTHER_KEY}\n\
+             rsa.com ssh-rsa {KEY}\n"
+        );
+
+        assert_eq!(check(&contents, "example.com"), Status::Known);
+        assert_eq!(check(&contents, "other.com"), Status::Known);
+        assert_eq!(check(&contents, "EXAMPLE.com"), Status::Known);
+        assert_eq!(check(&contents, "[example.com]:2222"), Status::Changed);
+        assert_eq!(check(&contents, "[example.com]:2223"), Status::New);
+        assert_eq!(check(&contents, "unknown.com"), Status::New);
+        // Keys of other types don't count.
+        assert_eq!(check(&contents, "rsa.com"), Status::New);
+    }
+
+    #[test]
+    fn patterns() {
+        let contents =
+            format!("*.example.com,!evil.example.com ssh-ed25519 {KEY}\nhost? ssh-ed25519 {KEY}\n");
+
+        assert_eq!(check(&contents, "git.example.com"), Status::Known);
+        assert_eq!(check(&contents, "evil.example.com"), Status::New);
+        assert_eq!(check(&contents, "example.com"), Status::New);
+        assert_eq!(check(&contents, "host1"), Status::Known);
+        assert_eq!(check(&contents, "host12"), Status::New);
+    }
+
+    #[test]
+    fn hashed_entries() {
+        // Written with `ssh-keygen -H`.
+        let contents = format!(
+            "|1|/MIAePFGNDc8kX6YmCK+J1VB/rw=|vJ29TtXNnMRfJsKz+J3AWZ0G5zw= ssh-ed25519 {KEY}\n\
+             |1|K8g6Oa3p1Y8B6L+WuBKy8orzf3w=|lHEb46uhtLNtgHoS/RxjkZ53z+U= ssh-ed25519 {OTHER_KEY}\n"
+        );
+
+        assert_eq!(check(&contents, "[localhost]:2222"), Status::Known);
+        assert_eq!(check(&contents, "github.com"), Status::Changed);
+        assert_eq!(check(&contents, "localhost"), Status::New);
+    }
+
+    #[test]
+    fn revoked_keys() {
+        let contents = format!("example.com ssh-ed25519 {KEY}\n@revoked *.com ssh-ed25519 {KEY}\n");
+        assert_eq!(check(&contents, "example.com"), Status::Revoked);
+        assert_eq!(check(&contents, "other.com"), Status::Revoked);
+        assert_eq!(check(&contents, "other.org"), Status::New);
+    }
+
+    #[test]
+    fn known_beats_changed() {
+        let contents =
+            format!("example.com ssh-ed25519 {OTHER_KEY}\nexample.com ssh-ed25519 {KEY}\n");
+        assert_eq!(check(&contents, "example.com"), Status::Known);
+    }
+
+    #[test]
+    fn ports() {
+        assert_eq!(port("ssh://git@example.com/repo.git"), None);
+        assert_eq!(port("ssh://git@example.com:2222/repo.git"), Some(2222));
+        assert_eq!(port("ssh://example.com:22/repo.git"), Some(22));
+        assert_eq!(port("ssh://git@[::1]:2222/repo.git"), Some(2222));
+        assert_eq!(port("git+ssh://example.com:2222/repo.git"), Some(2222));
+        assert_eq!(port("[git@localhost:2222]:repo.git"), Some(2222));
+        assert_eq!(port("[localhost:2222]:repo.git"), Some(2222));
+        assert_eq!(port("git@example.com:repo.git"), None);
+        assert_eq!(port("https://example.com:8443/repo.git"), None);
+
+        assert_eq!(name("localhost", Some(2222)), "[localhost]:2222");
+        assert_eq!(name("localhost", Some(22)), "localhost");
+        assert_eq!(name("localhost", None), "localhost");
+    }
+}
@@ -1,6 +1,15 @@
-use super::{ThreadedResource, ThreadedResourceHandle};
-use crate::{Authorization, ConfigScope, RefSpec};
-use std::path::{Path, PathBuf};
+use super::{known_hosts, ThreadedResource, ThreadedResourceHandle};
+use crate::{Authorization, ConfigScope, FetchOptions, HostKeyPolicy, PushOptions, RefSpec};
+use futures::{SinkExt, Stream, StreamExt, TryStreamExt};
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+/// The number of commits [`crate::Repository::log`] walks ahead
+/// of the ones that have been consumed.
+const LOG_CHANNEL_CAPACITY: usize = 1000;
 
 /// A [`crate::Repository`] implementation using the `git2` crate.
 pub struct Repository<R: ThreadedResource> {
@@ -77,6 +86,90 @@ impl<R: ThreadedResource> Repository<R> {
         })
     }
 
+    /// Clones the repository at the given URL into the given path,
+    /// which must either not exist or be an empty directory. The
+    /// progress of the transfer is reported to, and the clone is
+    /// aborted by, the given options, same as for a fetch.
+    ///
+    /// If the clone fails, whatever was written to the path
+    /// is removed again.
+    ///
+    /// This is an authorized operation; the given authorization
+    /// credentials will be used to authenticate with the remote.
+    #[cold]
+    pub async fn clone<U: AsRef<str>, P: AsRef<Path>>(
+        url: U,
+        path: P,
+        authorization: &Authorization,
+        options: &FetchOptions,
+    ) -> Result<Self, crate::Error<git2::Error>> {
+        let url = url.as_ref().to_owned();
+        let path = path.as_ref().to_path_buf();
+        let authorization = authorization.clone();
+        let options = options.clone();
+
+        let target = crate::backend::CloneTarget::of(&path);
+
+        let repo = R::new({
+            let path = path.clone();
+            move || {
+                if options.is_cancelled() {
+                    return Err(crate::Error::Cancelled);
+                }
+
+                // the host and the fingerprint of its key, if it was not trusted
+                let host_key = RefCell::new(None::<(String, String)>);
+
+                let mut callbacks = git2::RemoteCallbacks::new();
+
+                callbacks
+                    .credentials(|_url, username, _allowed| credentials(&authorization, username));
+
+                callbacks.certificate_check(|cert, host| {
+                    let host = known_hosts::name(host, known_hosts::port(&url));
+                    host_key_check(&authorization, cert, &host, &host_key)
+                });
+
+                // Returning `false` from either callback aborts the clone.
+                callbacks.sideband_progress(|_| !options.is_cancelled());
+                callbacks.transfer_progress(|progress| {
+                    if let Some(on_progress) = &options.progress {
+                        on_progress(crate::Progress::from(&progress));
+                    }
+                    !options.is_cancelled()
+                });
+
+                let mut fetch_options = git2::FetchOptions::new();
+                fetch_options.remote_callbacks(callbacks);
+
+                let r = git2::build::RepoBuilder::new()
+                    .fetch_options(fetch_options)
+                    .clone(&url, &path);
+
+                r.map_err(|e| {
+                    if options.is_cancelled() {
+                        crate::Error::Cancelled
+                    } else if let Some((host, fingerprint)) = host_key.take() {
+                        crate::Error::HostKeyMismatch(host, fingerprint)
+                    } else if e.code() == git2::ErrorCode::Auth {
+                        crate::Error::AuthorizationFailed(e)
+                    } else {
+                        e.into()
+                    }
+                })
+            }
+        })
+        .await;
+
+        match repo {
+            Ok(repo) => Ok(Self { repo }),
+            Err(error) => {
+                target.clean_up(&path);
+                Err(error)
+            }
+        }
+    }
+
     /// Opens a repository at the given path.
     /// Will error if there's no existing repository at the given path.
     #[inline]
@@ -178,66 +271,207 @@ impl<R: ThreadedResource> crate::Repository for Repository<R> {
         remote: &str,
         refspec: RefSpec,
         authorization: &Authorization,
+        options: &FetchOptions,
     ) -> Result<(), crate::Error<Self::Error>> {
         let remote = remote.to_owned();
         let authorization = authorization.clone();
+        let options = options.clone();
 
         self.repo
             .with(move |repo| {
+                if options.is_cancelled() {
+                    return Err(crate::Error::Cancelled);
+                }
+
                 let mut remote = repo.find_remote(&remote)?;
+                let port = remote.url().and_then(known_hosts::port);
+
+                // the host and the fingerprint of its key, if it was not trusted
+                let host_key = RefCell::new(None::<(String, String)>);
 
                 let mut callbacks = git2::RemoteCallbacks::new();
 
-                callbacks.credentials(|_url, username, _allowed| {
-                    let auth = match &authorization {
-                        Authorization::Auto => {
-                            let cred = git2::Cred::default()?;
-                            Ok(cred)
-                        }
-                        Authorization::Basic { username, password } => {
-                            let username = username.as_deref().unwrap_or_default();
-                            let password = password.as_deref().unwrap_or_default();
+                callbacks
+                    .credentials(|_url, username, _allowed| credentials(&authorization, username));
 
-                            git2::Cred::userpass_plaintext(username, password)
-                        }
-                        Authorization::Ssh {
-                            passphrase,
-                            private_key,
-                        } => {
-                            let private_key =
-                                private_key.as_ref().map(PathBuf::from).unwrap_or_else(|| {
-                                    let mut path = dirs::home_dir().unwrap();
-                                    path.push(".ssh");
-                                    path.push("id_rsa");
-                                    path
-                                });
-
-                            let username = username
-                                .map(ToOwned::to_owned)
-                                .unwrap_or_else(|| std::env::var("USER").unwrap_or_default());
-
-                            git2::Cred::ssh_key(
-                                &username,
-                                None,
-                                &private_key,
-                                passphrase.clone().as_deref(),
-                            )
-                        }
-                    };
+                callbacks.certificate_check(|cert, host| {
+                    let host = known_hosts::name(host, port);
+                    host_key_check(&authorization, cert, &host, &host_key)
+                });
 
-                    auth
+                // Returning `false` from either callback aborts the fetch.
+                callbacks.sideband_progress(|_| !options.is_cancelled());
+                callbacks.transfer_progress(|progress| {
+                    if let Some(on_progress) = &options.progress {
+                        on_progress(crate::Progress::from(&progress));
+                    }
+                    !options.is_cancelled()
                 });
 
                 let mut fetch_options = git2::FetchOptions::new();
                 fetch_options.remote_callbacks(callbacks);
 
+                let source = refspec.source.clone().filter(|_| !refspec.is_pattern());
                 let refspec = refspec.to_string();
 
                 let r = remote.fetch(&[&refspec], Some(&mut fetch_options), None);
 
+                // libgit2 silently skips sources the remote doesn't advertise,
+                // whereas `git fetch` fails; check for them ourselves.
+                if let (Ok(()), Some(source)) = (&r, &source) {
+                    let advertised = remote.list()?;
+                    if !advertised.iter().any(|head| head.name() == source) {
+                        return Err(crate::Error::RefNotFound(source.clone()));
+                    }
+                }
+
                 r.map_err(|e| {
-                    if e.code() == git2::ErrorCode::NotFound {
+                    if options.is_cancelled() {
+                        crate::Error::Cancelled
+                    } else if let Some((host, fingerprint)) = host_key.take() {
+                        crate::Error::HostKeyMismatch(host, fingerprint)
+                    } else if e.code() == git2::ErrorCode::NotFound {
                         crate::Error::RefNotFound(refspec)
+                    } else if e.code() == git2::ErrorCode::Auth {
+                        crate::Error::AuthorizationFailed(e)
+                    } else {
+                        e.into()
+                    }
+                })
+            })
+            .await
+            .await
+    }
+
+    async fn push(
+        &self,
+        remote: &str,
+        refspecs: &[RefSpec],
+        authorization: &Authorization,
+        options: &PushOptions,
+    ) -> Result<(), crate::Error<Self::Error>> {
+        let remote = remote.to_owned();
+        let refspecs = refspecs.to_vec();
+        let authorization = authorization.clone();
+        let options = options.clone();
+
+        self.repo
+            .with(move |repo| {
+                // libgit2 supports neither atomic pushes nor push options,
+                // so we'd rather fail loudly than silently push with weaker
+                // guarantees.
+                if options.atomic {
+                    return Err(git2::Error::from_str(
+                        "atomic pushes are not supported by libgit2",
+                    ))?;
+                }
+                if !options.push_options.is_empty() {
+                    return Err(git2::Error::from_str(
+                        "push options are not supported by libgit2",
+                    ))?;
+                }
+
+                let mut remote = repo.find_remote(&remote)?;
+                let port = remote.url().and_then(known_hosts::port);
+
+                // the first rejected ref, along with the reason it was rejected
+                let rejected = RefCell::new(None::<(String, String)>);
+
+                // the host and the fingerprint of its key, if it was not trusted
+                let host_key = RefCell::new(None::<(String, String)>);
+
+                let mut callbacks = git2::RemoteCallbacks::new();
+
+                callbacks
+                    .credentials(|_url, username, _allowed| credentials(&authorization, username));
+
+                callbacks.certificate_check(|cert, host| {
+                    let host = known_hosts::name(host, port);
+                    host_key_check(&authorization, cert, &host, &host_key)
+                });
+
+                // Called with the current (`src`) and the new (`dst`) target of
+                // every remote ref, before anything is sent to the remote, which
+                // makes it the place to check leases. Fast-forwards are checked
+                // by libgit2 as well, but only after this, and without telling
+                // which ref it was.
+                callbacks.push_negotiation(|updates| {
+                    for update in updates {
+                        let refname = String::from_utf8_lossy(update.dst_refname_bytes());
+
+                        let lease = options
+                            .force_with_lease
+                            .iter()
+                            .find(|lease| lease.refname == refname);
+
+                        let reason = if let Some(lease) = lease {
+                            let expected = match &lease.expected {
+                                Some(expected) => git2::Oid::from_str(expected)?,
+                                None => git2::Oid::zero(),
+                            };
+                            (update.src() != expected).then_some("stale info")
+                        } else {
+                            let forced = refspecs.iter().any(|refspec| {
+                                refspec.update_non_fastforward
+                                    && refspec.destination.as_deref() == Some(&*refname)
+                            });
+                            let fast_forward = update.src().is_zero()
+                                || update.dst().is_zero()
+                                || update.src() == update.dst()
+                                || repo
+                                    .graph_descendant_of(update.dst(), update.src())
+                                    .unwrap_or(false);
+                            (!forced && !fast_forward).then_some("non-fast-forward")
+                        };
+
+                        if let Some(reason) = reason {
+                            rejected.replace(Some((refname.into_owned(), reason.to_owned())));
+                            return Err(git2::Error::from_str(reason));
+                        }
+                    }
+                    Ok(())
+                });
+
+                // Called with the status the remote reported for every ref.
+                callbacks.push_update_reference(|refname, status| {
+                    if let Some(status) = status {
+                        rejected
+                            .borrow_mut()
+                            .get_or_insert_with(|| (refname.to_owned(), status.to_owned()));
+                    }
+                    Ok(())
+                });
+
+                let mut push_options = git2::PushOptions::new();
+                push_options.remote_callbacks(callbacks);
+
+                // leased refs are force-pushed, as long as the lease holds
+                let refspecs = refspecs
+                    .iter()
+                    .map(|refspec| {
+                        let leased = options.force_with_lease.iter().any(|lease| {
+                            refspec.destination.as_deref() == Some(lease.refname.as_str())
+                        });
+                        refspec
+                            .clone()
+                            .with_update_non_fastforward(refspec.update_non_fastforward || leased)
+                            .to_string()
+                    })
+                    .collect::<Vec<_>>();
+
+                let r = remote.push(&refspecs, Some(&mut push_options));
+
+                if let Some((refname, reason)) = rejected.take() {
+                    return Err(crate::Error::PushRejected(refname, reason));
+                }
+
+                if let Some((host, fingerprint)) = host_key.take() {
+                    return Err(crate::Error::HostKeyMismatch(host, fingerprint));
+                }
+
+                r.map_err(|e| {
+                    if e.code() == git2::ErrorCode::Auth {
+                        crate::Error::AuthorizationFailed(e)
                     } else {
                         e.into()
                     }
@@ -344,4 +578,415 @@ impl<R: ThreadedResource> crate::Repository for Repository<R> {
             .await
             .await
     }
+
+    async fn list_refs(&self, prefix: &str) -> Result<Vec<crate::Ref>, crate::Error<Self::Error>> {
+        let prefix = prefix.to_owned();
+
+        self.repo
+            .with(move |repo| {
+                let mut refs = Vec::new();
+
+                for reference in repo.references()? {
+                    let reference = reference?;
+                    let name = String::from_utf8_lossy(reference.name_bytes()).to_string();
+                    if !name.starts_with(&prefix) {
+                        continue;
+                    }
+
+                    // Refs whose (symbolic) targets do not exist are skipped,
+                    // just as `git for-each-ref` would do.
+                    if let Some(target) = reference.resolve().ok().and_then(|r| r.target()) {
+                        refs.push(crate::Ref {
+                            name,
+                            target: target.to_string(),
+                        });
+                    }
+                }
+
+                Ok(refs)
+            })
+            .await
+            .await
+    }
+
+    async fn resolve_ref(
+        &self,
+        refname: &str,
+    ) -> Result<Option<String>, crate::Error<Self::Error>> {
+        if !crate::repository::is_full_refname(refname) {
+            return Ok(None);
+        }
+
+        let refname = refname.to_owned();
+
+        self.repo
+            .with(move |repo| match repo.refname_to_id(&refname) {
+                Ok(oid) => Ok(Some(oid.to_string())),
+                Err(e) if e.code() == git2::ErrorCode::NotFound => Ok(None),
+                Err(e) => Err(e)?,
+            })
+            .await
+            .await
+    }
+
+    async fn update_refs(
+        &self,
+        transaction: &crate::RefTransaction,
+    ) -> Result<(), crate::Error<Self::Error>> {
+        let transaction = transaction.clone();
+
+        self.repo
+            .with(move |repo| {
+                let message = transaction.message.as_deref().unwrap_or_default();
+
+                // All refs are locked before any of them is checked, so that
+                // their values cannot change until the transaction is committed.
+                let mut tx = repo.transaction()?;
+                for update in &transaction.updates {
+                    tx.lock_ref(&update.refname)?;
+                }
+
+                for update in &transaction.updates {
+                    let current = match repo.refname_to_id(&update.refname) {
+                        Ok(oid) => Some(oid),
+                        Err(e) if e.code() == git2::ErrorCode::NotFound => None,
+                        Err(e) => Err(e)?,
+                    };
+
+                    let matches = match &update.expected {
+                        crate::RefExpectation::Any => true,
+                        crate::RefExpectation::Absent => current.is_none(),
+                        crate::RefExpectation::Target(target) => {
+                            current == Some(git2::Oid::from_str(target)?)
+                        }
+                    };
+
+                    if !matches {
+                        return Err(crate::Error::RefMismatch(update.refname.clone()))?;
+                    }
+
+                    match &update.target {
+                        Some(target) => {
+                            tx.set_target(
+                                &update.refname,
+                                git2::Oid::from_str(target)?,
+                                None,
+                                message,
+                            )?;
+                        }
+                        None if current.is_some() => {
+                            tx.remove(&update.refname)?;
+                        }
+                        None => {}
+                    }
+                }
+
+                tx.commit()?;
+
+                Ok(())
+            })
+            .await
+            .await
+    }
+
+    fn log<'a>(
+        &'a self,
+        range: &'a str,
+        options: &'a crate::LogOptions,
+    ) -> impl Stream<Item = Result<String, crate::Error<Self::Error>>> + 'a {
+        let (mut sender, receiver) = futures::channel::mpsc::channel(LOG_CHANNEL_CAPACITY);
+        let range = range.to_owned();
+        let options = options.clone();
+
+        // The history is walked on a resource of its own, such
+        // that the repository can be used while the log is consumed.
+        let walk = async move {
+            let path = self.repo.with(|repo| repo.path().to_owned()).await.await;
+            let walker = R::new(move || git2::Repository::open(path)).await?;
+
+            walker
+                .with(move |repo| {
+                    let result = walk(repo, &range, &options, |id| {
+                        futures::executor::block_on(sender.send(Ok(id.to_string()))).is_ok()
+                    });
+                    if let Err(error) = result {
+                        let _ = futures::executor::block_on(sender.send(Err(error)));
+                    }
+                })
+                .await
+                .await;
+
+            Ok(())
+        };
+
+        // Only failing to open the repository ends the walk with an
+        // error; other errors are sent in order with the commits.
+        let walk = futures::stream::once(walk).filter_map(|result: Result<(), git2::Error>| {
+            futures::future::ready(result.err().map(Err))
+        });
+
+        futures::stream::select(receiver, walk).map_err(Into::into)
+    }
+}
+
+impl From<&git2::Progress<'_>> for crate::Progress {
+    fn from(progress: &git2::Progress<'_>) -> Self {
+        // Deltas are only resolved once all objects have been received.
+        if progress.total_deltas() > 0 && progress.received_objects() == progress.total_objects() {
+            Self::ResolvingDeltas {
+                resolved: progress.indexed_deltas(),
+                total: progress.total_deltas(),
+            }
+        } else {
+            Self::ReceivingObjects {
+                received: progress.received_objects(),
+                total: progress.total_objects(),
+                bytes: progress.received_bytes(),
+            }
+        }
+    }
+}
+
+/// Walks the commits of the given range for [`crate::Repository::log`],
+/// passing their ids to `on_commit` until it returns `false`.
+fn walk(
+    repo: &git2::Repository,
+    range: &str,
+    options: &crate::LogOptions,
+    mut on_commit: impl FnMut(git2::Oid) -> bool,
+) -> Result<(), git2::Error> {
+    let mut revwalk = repo.revwalk()?;
+
+    // Git lists commits by time by default. Simplifying the history by
+    // paths relies on children being listed before their parents, which
+    // commit times don't guarantee.
+    revwalk.set_sorting(match options.order {
+        crate::LogOrder::Default if options.paths.is_empty() => git2::Sort::TIME,
+        crate::LogOrder::Topological => git2::Sort::TOPOLOGICAL,
+        crate::LogOrder::Default | crate::LogOrder::Time => {
+            git2::Sort::TOPOLOGICAL | git2::Sort::TIME
+        }
+    })?;
+
+    if options.first_parent {
+        revwalk.simplify_first_parent()?;
+    }
+
+    let revspec = repo.revparse(range)?;
+    let commit = |object: Option<&git2::Object<'_>>| match object {
+        Some(object) => object.peel_to_commit().map(|commit| commit.id()),
+        None => Err(git2::Error::from_str(&format!("invalid range {range}"))),
+    };
+
+    let mut tips = Vec::new();
+    if revspec.mode().contains(git2::RevparseMode::SINGLE) {
+        tips.push(commit(revspec.from())?);
+    } else {
+        let from = commit(revspec.from())?;
+        let to = commit(revspec.to())?;
+        tips.push(to);
+
+        if revspec.mode().contains(git2::RevparseMode::MERGE_BASE) {
+            tips.push(from);
+            match repo.merge_bases(from, to) {
+                Ok(bases) => {
+                    for base in bases.iter() {
+                        revwalk.hide(*base)?;
+                    }
+                }
+                Err(error) if error.code() == git2::ErrorCode::NotFound => {}
+                Err(error) => return Err(error),
+            }
+        } else {
+            revwalk.hide(from)?;
+        }
+    }
+
+    for tip in &tips {
+        revwalk.push(*tip)?;
+    }
+
+    // the commits that are still part of the history simplified by paths
+    let mut reachable = tips.into_iter().collect::<HashSet<_>>();
+    let mut remaining = options.max_count;
+
+    for id in revwalk {
+        if remaining == Some(0) {
+            break;
+        }
+
+        let id = id?;
+
+        if !options.paths.is_empty() {
+            if !reachable.remove(&id) {
+                continue;
+            }
+
+            let commit = repo.find_commit(id)?;
+            let parents = commit
+                .parents()
+                .take(if options.first_parent { 1 } else { usize::MAX })
+                .collect::<Vec<_>>();
+
+            let entries = entries(&commit.tree()?, &options.paths);
+            let mut same = None;
+            for parent in &parents {
+                if entries == self::entries(&parent.tree()?, &options.paths) {
+                    same = Some(parent.id());
+                    break;
+                }
+            }
+
+            // Like Git, only the (first) parent the commit doesn't change
+            // the paths compared to is followed, and the commit is omitted.
+            // Root commits change the paths they have.
+            if let Some(parent) = same {
+                reachable.insert(parent);
+                continue;
+            }
+            reachable.extend(parents.iter().map(git2::Commit::id));
+            if parents.is_empty() && entries.iter().all(Option::is_none) {
+                continue;
+            }
+        }
+
+        if !on_commit(id) {
+            break;
+        }
+        remaining = remaining.map(|remaining| remaining - 1);
+    }
+
+    Ok(())
+}
+
+/// Returns the ids of the entries of the given paths in the tree.
+fn entries(tree: &git2::Tree<'_>, paths: &[String]) -> Vec<Option<git2::Oid>> {
+    paths
+        .iter()
+        .map(|path| tree.get_path(Path::new(path)).ok().map(|entry| entry.id()))
+        .collect()
+}
+
+/// Checks the key presented by an SSH remote against the [`HostKeyPolicy`]
+/// of the authorization, recording the host and the fingerprint of the key
+/// in `host_key` if it is not trusted.
+///
+/// `host` is the name of the remote host as it is written to `known_hosts`
+/// files (see [`known_hosts::name`]). Certificates of HTTPS remotes are
+/// left to libgit2 to check.
+fn host_key_check(
+    authorization: &Authorization,
+    cert: &git2::cert::Cert<'_>,
+    host: &str,
+    host_key: &RefCell<Option<(String, String)>>,
+) -> Result<git2::CertificateCheckStatus, git2::Error> {
+    let Some(hostkey) = cert.as_hostkey() else {
+        return Ok(git2::CertificateCheckStatus::CertificatePassthrough);
+    };
+
+    let Some(hash) = hostkey.hash_sha256() else {
+        return Err(git2::Error::from_str(
+            "remote did not provide a SHA256 hash of its host key",
+        ));
+    };
+
+    let fingerprint = fingerprint(hash);
+
+    let trusted = match HostKeyPolicy::of(authorization) {
+        HostKeyPolicy::Pinned(fingerprints) => fingerprints.contains(&fingerprint),
+        policy @ (HostKeyPolicy::Strict | HostKeyPolicy::AcceptNew) => {
+            let (Some(key), Some(key_type)) = (hostkey.hostkey(), hostkey.hostkey_type()) else {
+                return Err(git2::Error::from_str("remote did not provide its host key"));
+            };
+            let key_type = key_type.name();
+
+            match known_hosts::check(host, key_type, key) {
+                known_hosts::Status::Known => true,
+                known_hosts::Status::New if policy == HostKeyPolicy::AcceptNew => {
+                    known_hosts::add(host, key_type, key).map_err(|error| {
+                        git2::Error::from_str(&format!("failed to add known host: {error}"))
+                    })?;
+                    true
+                }
+                _ => false,
+            }
+        }
+    };
+
+    if trusted {
+        Ok(git2::CertificateCheckStatus::CertificateOk)
+    } else {
+        host_key.replace(Some((host.to_owned(), fingerprint)));
+        Err(git2::Error::from_str("host key verification failed"))
+    }
+}
+
+/// Formats a SHA256 hash of a host key the way OpenSSH prints
+/// it, i.e. `SHA256:` followed by the unpadded base64 of the hash.
+fn fingerprint(hash: &[u8]) -> String {
+    format!("SHA256:{}", known_hosts::base64(hash).trim_end_matches('='))
+}
+
+fn credentials(
+    authorization: &Authorization,
+    username: Option<&str>,
+) -> Result<git2::Cred, git2::Error> {
+    match authorization {
+        Authorization::Auto => {
+            let cred = git2::Cred::default()?;
+            Ok(cred)
+        }
+        Authorization::Basic { username, password } => {
+            let username = username.as_deref().unwrap_or_default();
+            let password = password.as_deref().unwrap_or_default();
+
+            git2::Cred::userpass_plaintext(username, password)
+        }
+        Authorization::Ssh {
+            passphrase,
+            private_key,
+            ..
+        } => {
+            let private_key = private_key.as_ref().map(PathBuf::from).unwrap_or_else(|| {
+                let mut path = dirs::home_dir().unwrap();
+                path.push(".ssh");
+                path.push("id_rsa");
+                path
+            });
+
+            // libssh2 reports a missing key as a generic error.
+            if !private_key.exists() {
+                return Err(git2::Error::new(
+                    git2::ErrorCode::Auth,
+                    git2::ErrorClass::Ssh,
+                    format!("private key {} not found", private_key.display()),
+                ));
+            }
+
+            let username = username
+                .map(ToOwned::to_owned)
+                .unwrap_or_else(|| std::env::var("USER").unwrap_or_default());
+
+            git2::Cred::ssh_key(&username, None, &private_key, passphrase.as_deref())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprint() {
+        // SHA256 of the empty string
+        let hash = [
+            0xe3, 0xb0, 0xc4, 0x42, 0x98, 0xfc, 0x1c, 0x14, 0x9a, 0xfb, 0xf4, 0xc8, 0x99, 0x6f,
+            0xb9, 0x24, 0x27, 0xae, 0x41, 0xe4, 0x64, 0x9b, 0x93, 0x4c, 0xa4, 0x95, 0x99, 0x1b,
+            0x78, 0x52, 0xb8, 0x55,
+        ];
+        assert_eq!(
+            fingerprint(&hash),
+            "SHA256:47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU"
+        );
+    }
 }
@@ -0,0 +1,172 @@
+//! In-memory mock implementation of the core `gitbutler-git`
+//! library traits, for testing code that uses them without
+//! touching the disk (or running Git).
+//!
+//! The entry point for this module is the [`Repository`] struct.
+
+mod repository;
+
+pub use self::repository::{Error, Repository};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Authorization, FetchOptions, LogOptions, LogOrder, PushOptions, RefSpec, Repository as _,
+    };
+
+    async fn make_fixture(_test_name: String) -> Repository {
+        Repository::default()
+    }
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        ::tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn test_fetch_requires_authorization() {
+        block_on(async {
+            let origin = Repository::default();
+            let head = origin.commit("refs/heads/master", &[]);
+            let authorization = Authorization::Basic {
+                username: Some("my_username".to_owned()),
+                password: Some("my_password".to_owned()),
+            };
+            origin.require_authorization(authorization.clone());
+
+            let repo = Repository::default();
+            repo.add_remote_repository("mock://origin", origin);
+            repo.create_remote("origin", "mock://origin").await.unwrap();
+
+            let refspec = RefSpec::parse("refs/heads/master:refs/remotes/origin/master").unwrap();
+
+            match repo
+                .fetch(
+                    "origin",
+                    refspec.clone(),
+                    &Authorization::Auto,
+                    &FetchOptions::default(),
+                )
+                .await
+            {
+                Err(crate::Error::AuthorizationFailed(_)) => {}
+                result => panic!("expected AuthorizationFailed, got {result:?}"),
+            }
+
+            repo.fetch("origin", refspec, &authorization, &FetchOptions::default())
+                .await
+                .unwrap();
+
+            assert_eq!(
+                repo.resolve_ref("refs/remotes/origin/master")
+                    .await
+                    .unwrap(),
+                Some(head)
+            );
+        });
+    }
+
+    #[test]
+    fn test_push_non_fast_forward() {
+        block_on(async {
+            let origin = Repository::default();
+            origin.commit("refs/heads/master", &[("a.txt", "theirs")]);
+
+            let repo = Repository::default();
+            let ours = repo.commit("refs/heads/master", &[("a.txt", "ours")]);
+            repo.add_remote_repository("mock://origin", origin.clone());
+            repo.create_remote("origin", "mock://origin").await.unwrap();
+
+            match repo
+                .push(
+                    "origin",
+                    &[RefSpec::parse("refs/heads/master").unwrap()],
+                    &Authorization::Auto,
+                    &PushOptions::default(),
+                )
+                .await
+            {
+                Err(crate::Error::PushRejected(refname, reason)) => {
+                    assert_eq!(refname, "refs/heads/master");
+                    assert_eq!(reason, "non-fast-forward");
+                }
+                result => panic!("expected PushRejected, got {result:?}"),
+            }
+
+            repo.push(
+                "origin",
+                &[RefSpec::parse("+refs/heads/master").unwrap()],
+                &Authorization::Auto,
+                &PushOptions::default(),
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(
+                origin.resolve_ref("refs/heads/master").await.unwrap(),
+                Some(ours)
+            );
+        });
+    }
+
+    #[test]
+    fn test_log_order() {
+        block_on(async {
+            use futures::TryStreamExt;
+
+            let repo = Repository::default();
+            let base = repo.commit("refs/heads/master", &[]);
+            let f1 = repo.merge("refs/heads/feature", &[&base], &[]);
+            let m1 = repo.commit("refs/heads/master", &[]);
+            let f2 = repo.commit("refs/heads/feature", &[]);
+            let m2 = repo.commit("refs/heads/master", &[]);
+            let merge = repo.merge("refs/heads/master", &[&f2], &[]);
+
+            let log = |range: String, order| {
+                let repo = &repo;
+                async move {
+                    let options = LogOptions::default().with_order(order);
+                    repo.log(&range, &options)
+                        .try_collect::<Vec<_>>()
+                        .await
+                        .unwrap()
+                }
+            };
+
+            let by_time = vec![
+                merge.clone(),
+                m2.clone(),
+                f2.clone(),
+                m1.clone(),
+                f1.clone(),
+                base.clone(),
+            ];
+            assert_eq!(log("HEAD".to_owned(), LogOrder::Default).await, by_time);
+            assert_eq!(log("HEAD".to_owned(), LogOrder::Time).await, by_time);
+
+            // the merged line of history is listed before the one it was merged into
+            assert_eq!(
+                log("HEAD".to_owned(), LogOrder::Topological).await,
+                vec![
+                    merge.clone(),
+                    f2.clone(),
+                    f1.clone(),
+                    m2.clone(),
+                    m1.clone(),
+                    base
+                ]
+            );
+            // ... and the newest tip before the others
+            assert_eq!(
+                log(format!("{m2}...refs/heads/feature"), LogOrder::Topological).await,
+                vec![m2, m1, f2, f1]
+            );
+        });
+    }
+
+    crate::gitbutler_git_conformance_tests!(make_fixture);
+}
@@ -0,0 +1,685 @@
+use crate::{
+    Authorization, ConfigScope, FetchOptions, LogOptions, LogOrder, PushOptions, Ref,
+    RefExpectation, RefSpec, RefTransaction,
+};
+use futures::Stream;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+};
+
+/// The source of the (fake) ids and timestamps of commits, shared
+/// by all mock repositories such that their commits never collide.
+static NEXT_COMMIT: AtomicU64 = AtomicU64::new(1);
+
+/// An error returned by the mock backend.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum Error {
+    /// The remote with the given name does not exist.
+    #[error("no such remote: {0}")]
+    NoSuchRemote(String),
+    /// The remote with the given name already exists.
+    #[error("remote already exists: {0}")]
+    RemoteExists(String),
+    /// No mock repository was added under the given URI.
+    #[error("no repository at {0}")]
+    UnknownUri(String),
+    /// The given revision does not resolve to a commit.
+    #[error("unknown revision: {0}")]
+    UnknownRevision(String),
+    /// HEAD points to the given ref, which does not exist yet.
+    #[error("HEAD points to {0}, which does not exist yet")]
+    UnbornHead(String),
+    /// The remote repository requires a different authorization.
+    #[error("the remote rejected the authorization")]
+    Unauthorized,
+    /// A fetch would have updated the given ref, but the update
+    /// was not a fast-forward and was not forced.
+    #[error("update of {0} is not a fast-forward")]
+    NonFastForward(String),
+}
+
+/// An in-memory [`crate::Repository`], for testing code that uses
+/// the trait without touching the disk.
+///
+/// Clones share the same state. Commits only consist of the contents
+/// of their files, and are created with [`Self::commit`] and [`Self::merge`].
+/// Fetches and pushes operate on other mock repositories, which are added
+/// with [`Self::add_remote_repository`].
+#[derive(Default, Debug, Clone)]
+pub struct Repository {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Default, Debug)]
+struct State {
+    config: HashMap<ConfigScope, HashMap<String, String>>,
+    remotes: BTreeMap<String, String>,
+    network: HashMap<String, Repository>,
+    authorization: Option<Authorization>,
+    refs: BTreeMap<String, String>,
+    head: Head,
+    commits: HashMap<String, Commit>,
+}
+
+#[derive(Debug, Clone)]
+enum Head {
+    Symbolic(String),
+    Detached(String),
+}
+
+impl Default for Head {
+    fn default() -> Self {
+        Self::Symbolic("refs/heads/master".to_owned())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Commit {
+    parents: Vec<String>,
+    time: u64,
+    files: BTreeMap<String, String>,
+}
+
+impl Repository {
+    /// Commits the given files (paths and contents) on top of the
+    /// commit `refname` points to, if any, and points `refname` to
+    /// the new commit. Returns the id of the new commit.
+    pub fn commit(&self, refname: &str, files: &[(&str, &str)]) -> String {
+        self.merge(refname, &[], files)
+    }
+
+    /// Like [`Self::commit`], but the new commit also has the given
+    /// commits as parents, after the one `refname` points to.
+    pub fn merge(&self, refname: &str, merged: &[&str], files: &[(&str, &str)]) -> String {
+        let mut state = self.state();
+
+        let parents = state
+            .refs
+            .get(refname)
+            .cloned()
+            .into_iter()
+            .chain(merged.iter().map(|commit| (*commit).to_owned()))
+            .collect::<Vec<_>>();
+        let mut tree = parents
+            .first()
+            .map(|parent| state.commits[parent].files.clone())
+            .unwrap_or_default();
+        tree.extend(
+            files
+                .iter()
+                .map(|(path, content)| ((*path).to_owned(), (*content).to_owned())),
+        );
+
+        let time = NEXT_COMMIT.fetch_add(1, Ordering::SeqCst);
+        let id = format!("{time:040x}");
+
+        state.commits.insert(
+            id.clone(),
+            Commit {
+                parents,
+                time,
+                files: tree,
+            },
+        );
+        state.refs.insert(refname.to_owned(), id.clone());
+
+        id
+    }
+
+    /// Points HEAD to the given ref, which need not exist yet.
+    pub fn set_head(&self, refname: &str) {
+        self.state().head = Head::Symbolic(refname.to_owned());
+    }
+
+    /// Points HEAD directly to the given commit.
+    pub fn detach_head(&self, commit: &str) {
+        self.state().head = Head::Detached(commit.to_owned());
+    }
+
+    /// Makes fetches from, and pushes to, this repository fail with
+    /// [`crate::Error::AuthorizationFailed`], unless they are given
+    /// the given authorization.
+    pub fn require_authorization(&self, authorization: Authorization) {
+        self.state().authorization = Some(authorization);
+    }
+
+    /// Makes the given repository available to fetch from and push
+    /// to, through remotes with the given URI.
+    pub fn add_remote_repository<S: Into<String>>(&self, uri: S, repository: Repository) {
+        self.state().network.insert(uri.into(), repository);
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    /// Looks up the repository behind the given remote, checking
+    /// that the authorization is the one it requires (if any).
+    fn remote_repository(
+        &self,
+        remote: &str,
+        authorization: &Authorization,
+    ) -> Result<Repository, crate::Error<Error>> {
+        let state = self.state();
+
+        let uri = state.remotes.get(remote).ok_or_else(|| {
+            crate::Error::NoSuchRemote(remote.to_owned(), Error::NoSuchRemote(remote.to_owned()))
+        })?;
+        let repository = state
+            .network
+            .get(uri)
+            .ok_or_else(|| Error::UnknownUri(uri.clone()))?
+            .clone();
+        drop(state);
+
+        let required = repository.state().authorization.clone();
+        match required {
+            Some(required) if &required != authorization => {
+                Err(crate::Error::AuthorizationFailed(Error::Unauthorized))
+            }
+            _ => Ok(repository),
+        }
+    }
+}
+
+impl State {
+    fn head(&self) -> Result<&String, Error> {
+        match &self.head {
+            Head::Symbolic(refname) => self
+                .refs
+                .get(refname)
+                .ok_or_else(|| Error::UnbornHead(refname.clone())),
+            Head::Detached(commit) => Ok(commit),
+        }
+    }
+
+    /// Resolves a commit id, `HEAD`, a full ref name, or
+    /// the short name of a branch, tag or remote-tracking
+    /// branch to a commit id.
+    fn resolve(&self, rev: &str) -> Result<String, Error> {
+        if rev == "HEAD" {
+            return self.head().cloned();
+        }
+        if self.commits.contains_key(rev) {
+            return Ok(rev.to_owned());
+        }
+
+        [
+            rev.to_owned(),
+            format!("refs/heads/{rev}"),
+            format!("refs/tags/{rev}"),
+            format!("refs/remotes/{rev}"),
+        ]
+        .iter()
+        .find_map(|refname| self.refs.get(refname))
+        .cloned()
+        .ok_or_else(|| Error::UnknownRevision(rev.to_owned()))
+    }
+
+    /// Returns the ids of the commit and all of its ancestors
+    /// (only following first parents, if `first_parent` is set).
+    fn ancestors(&self, commit: &str, first_parent: bool) -> HashSet<String> {
+        let mut ancestors = HashSet::new();
+        let mut pending = vec![commit.to_owned()];
+
+        while let Some(id) = pending.pop() {
+            pending.extend(self.parents(&id, first_parent).iter().cloned());
+            ancestors.insert(id);
+        }
+
+        ancestors
+    }
+
+    /// Returns the parents of the given commit (only the first
+    /// one, if `first_parent` is set).
+    fn parents(&self, commit: &str, first_parent: bool) -> &[String] {
+        match self.commits.get(commit) {
+            Some(commit) if first_parent => &commit.parents[..commit.parents.len().min(1)],
+            Some(commit) => &commit.parents,
+            None => &[],
+        }
+    }
+
+    /// Whether or not `ancestor` is `commit` or one of its ancestors.
+    fn is_ancestor(&self, ancestor: &str, commit: &str) -> bool {
+        self.ancestors(commit, false).contains(ancestor)
+    }
+
+    /// Lists the commits of the given range for [`crate::Repository::log`].
+    fn log(&self, range: &str, options: &LogOptions) -> Result<Vec<String>, Error> {
+        let (tips, hidden) = if let Some((from, to)) = range.split_once("...") {
+            let (from, to) = (self.resolve(from)?, self.resolve(to)?);
+            let common = self
+                .ancestors(&from, false)
+                .intersection(&self.ancestors(&to, false))
+                .cloned()
+                .collect();
+            (vec![to, from], common)
+        } else if let Some((from, to)) = range.split_once("..") {
+            let hidden = self.ancestors(&self.resolve(from)?, false);
+            (vec![self.resolve(to)?], hidden)
+        } else {
+            (vec![self.resolve(range)?], HashSet::new())
+        };
+
+        let mut commits = tips
+            .iter()
+            .flat_map(|tip| self.ancestors(tip, options.first_parent))
+            .filter(|id| !hidden.contains(id))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        // Commits are always newer than their parents, so listing them by
+        // time also lists no parents before their children.
+        commits.sort_by_key(|id| std::cmp::Reverse(self.commits[id].time));
+        if options.order == LogOrder::Topological {
+            commits = self.topo_order(&commits, options.first_parent);
+        }
+
+        if !options.paths.is_empty() {
+            // the commits that are still part of the history simplified by paths
+            let mut reachable = tips.into_iter().collect::<HashSet<_>>();
+
+            commits.retain(|id| {
+                if !reachable.remove(id) {
+                    return false;
+                }
+
+                let parents = self.parents(id, options.first_parent);
+                let ours = entries(&self.commits[id].files, &options.paths);
+                match parents
+                    .iter()
+                    .find(|parent| entries(&self.commits[*parent].files, &options.paths) == ours)
+                {
+                    Some(parent) => {
+                        reachable.insert(parent.clone());
+                        false
+                    }
+                    // root commits change the paths they have
+                    None => {
+                        reachable.extend(parents.iter().cloned());
+                        !parents.is_empty() || !ours.is_empty()
+                    }
+                }
+            });
+        }
+
+        if let Some(max_count) = options.max_count {
+            commits.truncate(max_count);
+        }
+
+        Ok(commits)
+    }
+
+    /// Orders the given commits, listed by time, like `git rev-list --topo-order`:
+    /// starting with the newest tip, each line of history is listed up to the
+    /// commit it forked from before continuing with the next one.
+    fn topo_order(&self, commits: &[String], first_parent: bool) -> Vec<String> {
+        // the number of children of each commit that are yet to be listed
+        let mut children = commits
+            .iter()
+            .map(|id| (id.as_str(), 0))
+            .collect::<HashMap<_, _>>();
+        for id in commits {
+            for parent in self.parents(id, first_parent) {
+                if let Some(count) = children.get_mut(parent.as_str()) {
+                    *count += 1;
+                }
+            }
+        }
+
+        // a stack, such that the last parent of a merge, which was merged
+        // into it, is listed first (and the newest tip before the others)
+        let mut pending = commits
+            .iter()
+            .rev()
+            .map(String::as_str)
+            .filter(|id| children[*id] == 0)
+            .collect::<Vec<_>>();
+
+        let mut ordered = Vec::with_capacity(commits.len());
+        while let Some(id) = pending.pop() {
+            for parent in self.parents(id, first_parent) {
+                if let Some(count) = children.get_mut(parent.as_str()) {
+                    *count -= 1;
+                    if *count == 0 {
+                        pending.push(parent.as_str());
+                    }
+                }
+            }
+            ordered.push(id.to_owned());
+        }
+
+        ordered
+    }
+
+    /// Copies the given commit, along with all of its ancestors, from `other`.
+    fn copy_commits(&mut self, other: &State, commit: &str) {
+        for id in other.ancestors(commit, false) {
+            if let Some(commit) = other.commits.get(&id) {
+                self.commits.entry(id).or_insert_with(|| commit.clone());
+            }
+        }
+    }
+}
+
+/// Returns the files (paths and contents) that are, or are in, any of the given paths.
+fn entries<'a>(
+    files: &'a BTreeMap<String, String>,
+    paths: &[String],
+) -> Vec<(&'a String, &'a String)> {
+    files
+        .iter()
+        .filter(|(file, _)| {
+            paths.iter().any(|path| {
+                file.as_str() == path
+                    || file
+                        .strip_prefix(path.as_str())
+                        .is_some_and(|rest| rest.starts_with('/'))
+            })
+        })
+        .collect()
+}
+
+impl crate::Repository for Repository {
+    type Error = Error;
+
+    async fn config_get(
+        &self,
+        key: &str,
+        scope: ConfigScope,
+    ) -> Result<Option<String>, crate::Error<Self::Error>> {
+        let state = self.state();
+        let get = |scope: ConfigScope| state.config.get(&scope).and_then(|c| c.get(key)).cloned();
+
+        // NOTE(qix-): See source comments for ConfigScope to explain
+        // NOTE(qix-): the `#[cfg(not(test))]` attributes.
+        Ok(match scope {
+            #[cfg(not(test))]
+            ConfigScope::Auto => get(ConfigScope::Local)
+                .or_else(|| get(ConfigScope::Global))
+                .or_else(|| get(ConfigScope::System)),
+            scope => get(scope),
+        })
+    }
+
+    async fn config_set(
+        &self,
+        key: &str,
+        value: &str,
+        scope: ConfigScope,
+    ) -> Result<(), crate::Error<Self::Error>> {
+        let scope = match scope {
+            #[cfg(not(test))]
+            ConfigScope::Auto => ConfigScope::Local,
+            scope => scope,
+        };
+
+        self.state()
+            .config
+            .entry(scope)
+            .or_default()
+            .insert(key.to_owned(), value.to_owned());
+
+        Ok(())
+    }
+
+    async fn fetch(
+        &self,
+        remote: &str,
+        refspec: RefSpec,
+        authorization: &Authorization,
+        options: &FetchOptions,
+    ) -> Result<(), crate::Error<Self::Error>> {
+        if options.is_cancelled() {
+            return Err(crate::Error::Cancelled);
+        }
+
+        let remote = self.remote_repository(remote, authorization)?;
+        let remote = remote.state();
+
+        let updates = remote
+            .refs
+            .iter()
+            .filter_map(|(refname, target)| {
+                refspec
+                    .transform(refname)
+                    .map(|destination| (destination, target.clone()))
+            })
+            .collect::<Vec<_>>();
+
+        if !refspec.is_pattern() && !remote.refs.keys().any(|r| refspec.matches_source(r)) {
+            return Err(crate::Error::RefNotFound(
+                refspec.source.clone().unwrap_or_default(),
+            ));
+        }
+
+        let mut state = self.state();
+
+        for (_, target) in &updates {
+            state.copy_commits(&remote, target);
+        }
+
+        for (destination, target) in &updates {
+            if let Some(old) = state.refs.get(destination) {
+                if !refspec.update_non_fastforward && !state.is_ancestor(old, target) {
+                    return Err(Error::NonFastForward(destination.clone()))?;
+                }
+            }
+        }
+
+        for (destination, target) in updates {
+            state.refs.insert(destination, target);
+        }
+
+        if let Some(on_progress) = &options.progress {
+            let total = state.commits.len();
+            on_progress(crate::Progress::ReceivingObjects {
+                received: total,
+                total,
+                bytes: 0,
+            });
+        }
+
+        Ok(())
+    }
+
+    async fn push(
+        &self,
+        remote: &str,
+        refspecs: &[RefSpec],
+        authorization: &Authorization,
+        options: &PushOptions,
+    ) -> Result<(), crate::Error<Self::Error>> {
+        let remote = self.remote_repository(remote, authorization)?;
+        let state = self.state();
+
+        // (destination, new target, forced)
+        let mut updates = Vec::new();
+        for refspec in refspecs {
+            let Some(destination) = refspec.destination.clone() else {
+                continue;
+            };
+            let target = match &refspec.source {
+                Some(source) => Some(state.resolve(source)?),
+                None => None,
+            };
+            updates.push((destination, target, refspec.update_non_fastforward));
+        }
+
+        let mut remote = remote.state();
+
+        // Pushes to the mock are always atomic.
+        for (destination, target, forced) in &updates {
+            let old = remote.refs.get(destination);
+
+            let lease = options
+                .force_with_lease
+                .iter()
+                .find(|lease| &lease.refname == destination);
+
+            let reason = if let Some(lease) = lease {
+                (old != lease.expected.as_ref()).then_some("stale info")
+            } else {
+                let fast_forward = match (old, target) {
+                    (Some(old), Some(target)) => state.is_ancestor(old, target),
+                    _ => true,
+                };
+                (!forced && !fast_forward).then_some("non-fast-forward")
+            };
+
+            if let Some(reason) = reason {
+                return Err(crate::Error::PushRejected(
+                    destination.clone(),
+                    reason.to_owned(),
+                ));
+            }
+        }
+
+        for (destination, target) in updates.into_iter().map(|(d, t, _)| (d, t)) {
+            match target {
+                Some(target) => {
+                    remote.copy_commits(&state, &target);
+                    remote.refs.insert(destination, target);
+                }
+                None => {
+                    remote.refs.remove(&destination);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn create_remote(
+        &self,
+        remote: &str,
+        uri: &str,
+    ) -> Result<(), crate::Error<Self::Error>> {
+        let mut state = self.state();
+
+        if state.remotes.contains_key(remote) {
+            return Err(crate::Error::RemoteExists(
+                remote.to_owned(),
+                Error::RemoteExists(remote.to_owned()),
+            ));
+        }
+
+        state.remotes.insert(remote.to_owned(), uri.to_owned());
+        Ok(())
+    }
+
+    async fn create_or_update_remote(
+        &self,
+        remote: &str,
+        uri: &str,
+    ) -> Result<(), crate::Error<Self::Error>> {
+        self.state()
+            .remotes
+            .insert(remote.to_owned(), uri.to_owned());
+        Ok(())
+    }
+
+    async fn remote(&self, remote: &str) -> Result<String, crate::Error<Self::Error>> {
+        self.state().remotes.get(remote).cloned().ok_or_else(|| {
+            crate::Error::NoSuchRemote(remote.to_owned(), Error::NoSuchRemote(remote.to_owned()))
+        })
+    }
+
+    async fn head(&self) -> Result<String, crate::Error<Self::Error>> {
+        Ok(self.state().head()?.clone())
+    }
+
+    async fn symbolic_head(&self) -> Result<String, crate::Error<Self::Error>> {
+        let state = self.state();
+        state.head()?;
+
+        match &state.head {
+            Head::Symbolic(refname) => Ok(refname.clone()),
+            Head::Detached(_) => Ok("HEAD".to_owned()),
+        }
+    }
+
+    async fn list_refs(&self, prefix: &str) -> Result<Vec<Ref>, crate::Error<Self::Error>> {
+        Ok(self
+            .state()
+            .refs
+            .iter()
+            .filter(|(name, _)| name.starts_with(prefix))
+            .map(|(name, target)| Ref {
+                name: name.clone(),
+                target: target.clone(),
+            })
+            .collect())
+    }
+
+    async fn resolve_ref(
+        &self,
+        refname: &str,
+    ) -> Result<Option<String>, crate::Error<Self::Error>> {
+        if !crate::repository::is_full_refname(refname) {
+            return Ok(None);
+        }
+
+        let state = self.state();
+
+        if refname == "HEAD" {
+            return Ok(state.head().ok().cloned());
+        }
+
+        Ok(state.refs.get(refname).cloned())
+    }
+
+    async fn update_refs(
+        &self,
+        transaction: &RefTransaction,
+    ) -> Result<(), crate::Error<Self::Error>> {
+        let mut state = self.state();
+
+        for update in &transaction.updates {
+            let current = state.refs.get(&update.refname);
+
+            let matches = match &update.expected {
+                RefExpectation::Any => true,
+                RefExpectation::Absent => current.is_none(),
+                RefExpectation::Target(target) => current == Some(target),
+            };
+            if !matches {
+                return Err(crate::Error::RefMismatch(update.refname.clone()));
+            }
+
+            if let Some(target) = &update.target {
+                if !state.commits.contains_key(target) {
+                    return Err(Error::UnknownRevision(target.clone()))?;
+                }
+            }
+        }
+
+        for update in &transaction.updates {
+            match &update.target {
+                Some(target) => state.refs.insert(update.refname.clone(), target.clone()),
+                None => state.refs.remove(&update.refname),
+            };
+        }
+
+        Ok(())
+    }
+
+    fn log<'a>(
+        &'a self,
+        range: &'a str,
+        options: &'a LogOptions,
+    ) -> impl Stream<Item = Result<String, crate::Error<Self::Error>>> + 'a {
+        let commits = match self.state().log(range, options) {
+            Ok(commits) => commits.into_iter().map(Ok).collect(),
+            Err(error) => vec![Err(error.into())],
+        };
+
+        futures::stream::iter(commits)
+    }
+}
@@ -0,0 +1,115 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll, Waker},
+};
+
+/// A token that signals to a running operation that
+/// it should be aborted as soon as possible.
+///
+/// Clones of a token share the same state; cancelling any
+/// of them cancels all of them. Tokens are not tied to any
+/// particular async runtime.
+#[derive(Default, Debug, Clone)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default, Debug)]
+struct Inner {
+    cancelled: AtomicBool,
+    wakers: Mutex<Vec<Waker>>,
+}
+
+impl CancellationToken {
+    /// Creates a new token that has not been cancelled.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels the token, waking up everyone awaiting [`Self::cancelled`].
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        for waker in self.inner.wakers.lock().unwrap().drain(..) {
+            waker.wake();
+        }
+    }
+
+    /// Whether or not the token has been cancelled.
+    #[inline]
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Returns a future that resolves once the token has been cancelled.
+    #[inline]
+    pub fn cancelled(&self) -> Cancelled<'_> {
+        Cancelled { token: self }
+    }
+}
+
+/// The future returned by [`CancellationToken::cancelled`].
+#[derive(Debug)]
+pub struct Cancelled<'a> {
+    token: &'a CancellationToken,
+}
+
+impl Future for Cancelled<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.token.is_cancelled() {
+            return Poll::Ready(());
+        }
+
+        let mut wakers = self.token.inner.wakers.lock().unwrap();
+
+        // The token might have been cancelled before we acquired the lock,
+        // in which case nobody would be left to wake us up.
+        if self.token.is_cancelled() {
+            return Poll::Ready(());
+        }
+
+        if !wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+            wakers.push(cx.waker().clone());
+        }
+
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancel_clone() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        assert!(!token.is_cancelled());
+        clone.cancel();
+        assert!(token.is_cancelled());
+    }
+
+    #[test]
+    fn test_cancelled_wakes() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+
+        ::tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(async move {
+                let handle = ::tokio::spawn(async move { clone.cancelled().await });
+                ::tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                token.cancel();
+                handle.await.unwrap();
+            });
+    }
+}
@@ -7,6 +7,7 @@ use tokio::net::TcpListener;
 pub(crate) struct TestSshServer {
     repo_path: String,
     allowed_auths: Vec<crate::Authorization>,
+    host_key: russh_keys::key::KeyPair,
 }
 
 impl TestSshServer {
@@ -14,9 +15,19 @@ impl TestSshServer {
         Self {
             repo_path,
             allowed_auths: Vec::new(),
+            host_key: russh_keys::key::KeyPair::generate_ed25519().unwrap(),
         }
     }
 
+    /// The fingerprint of the server's host key, as OpenSSH prints it.
+    #[allow(unused)]
+    pub fn host_key_fingerprint(&self) -> String {
+        format!(
+            "SHA256:{}",
+            self.host_key.clone_public_key().unwrap().fingerprint()
+        )
+    }
+
     pub async fn run_with_server<F, FN>(self, cb: FN)
     where
         FN: FnOnce(u16) -> F,
@@ -28,30 +39,492 @@ impl TestSshServer {
         let addr = listener.local_addr().unwrap();
         let port = addr.port();
 
-        let config = Arc::new(russh::server::Config {
+        let socket_future = russh::server::run_on_socket(self.config(), &listener, self);
+
+        futures::select! {
+            _ = cb(port).fuse() => {},
+            _ = socket_future.fuse() => {
+                panic!("server exited prematurely");
+            },
+        }
+    }
+
+    /// Runs the server in the background for the rest of the
+    /// test, and returns the port it listens on.
+    #[allow(unused)]
+    pub async fn spawn(self) -> u16 {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        tokio::spawn(async move {
+            russh::server::run_on_socket(self.config(), &listener, self)
+                .await
+                .unwrap();
+        });
+
+        port
+    }
+
+    fn config(&self) -> Arc<server::Config> {
+        Arc::new(server::Config {
             inactivity_timeout: Some(std::time::Duration::from_secs(10)),
             auth_rejection_time: std::time::Duration::from_secs(3),
             auth_rejection_time_initial: Some(std::time::Duration::from_secs(0)),
-            keys: vec![russh_keys::key::KeyPair::generate_ed25519().unwrap()],
+            keys: vec![self.host_key.clone()],
+            // Only offer the type of our host key; libssh2 would pick
+            // the first type it supports among all the ones russh knows.
+            preferred: russh::Preferred {
+                key: &[russh_keys::key::ED25519],
+                ..Default::default()
+            },
             ..Default::default()
-        });
+        })
+    }
+
+    #[allow(unused)]
+    pub fn allow_authorization(&mut self, auth: crate::Authorization) {
+        self.allowed_auths.push(auth);
+    }
 
-        let socket_future = russh::server::run_on_socket(config, &listener, self);
+    #[allow(unused)]
+    pub fn repo_path(&self) -> &str {
+        &self.repo_path
+    }
+}
+
+/// A minimal smart HTTP Git server that advertises an empty repository,
+/// but only to clients authenticating with the given HTTP Basic credentials.
+#[derive(Debug)]
+pub(crate) struct TestHttpServer {
+    username: String,
+    password: String,
+}
+
+impl TestHttpServer {
+    #[allow(unused)]
+    pub fn new(username: &str, password: &str) -> Self {
+        Self {
+            username: username.to_owned(),
+            password: password.to_owned(),
+        }
+    }
+
+    #[allow(unused)]
+    pub async fn run_with_server<F, FN>(self, cb: FN)
+    where
+        FN: FnOnce(u16) -> F,
+        F: std::future::Future<Output = ()> + 'static,
+    {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let authorization = format!(
+            "Basic {}",
+            base64(format!("{}:{}", self.username, self.password).as_bytes())
+        );
+
+        let server_future = async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+
+                let mut request = Vec::new();
+                while !request.ends_with(b"\r\n\r\n") {
+                    let mut byte = [0];
+                    if stream.read(&mut byte).await.unwrap() == 0 {
+                        break;
+                    }
+                    request.push(byte[0]);
+                }
+
+                let authorized = String::from_utf8_lossy(&request).lines().any(|line| {
+                    line.split_once(':').is_some_and(|(name, value)| {
+                        name.eq_ignore_ascii_case("authorization") && value.trim() == authorization
+                    })
+                });
+
+                let response = if authorized {
+                    let body = format!(
+                        "{}0000{}0000",
+                        pkt_line("# service=git-upload-pack\n"),
+                        pkt_line(&format!(
+                            "{} capabilities^{{}}\0agent=test\n",
+                            "0".repeat(40)
+                        ))
+                    );
+                    format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/x-git-upload-pack-advertisement\r\nCache-Control: no-cache\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                        body.len()
+                    )
+                } else {
+                    "HTTP/1.1 401 Unauthorized\r\nWWW-Authenticate: Basic realm=\"test\"\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_owned()
+                };
+
+                stream.write_all(response.as_bytes()).await.unwrap();
+                stream.shutdown().await.unwrap();
+            }
+        };
 
         futures::select! {
             _ = cb(port).fuse() => {},
-            _ = socket_future.fuse() => {
+            _ = Box::pin(server_future).fuse() => {
                 panic!("server exited prematurely");
             },
         }
     }
+}
+
+fn pkt_line(data: &str) -> String {
+    format!("{:04x}{data}", data.len() + 4)
+}
+
+fn base64(input: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut output = String::new();
+    for chunk in input.chunks(3) {
+        let n = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                output.push(ALPHABET[(n >> (18 - 6 * i)) as usize & 63] as char);
+            } else {
+                output.push('=');
+            }
+        }
+    }
+    output
+}
+
+/// The path of the local repository that backends create
+/// for the test with the given (module-qualified) name.
+pub(crate) fn local_repo_path(test_name: &str) -> std::path::PathBuf {
+    std::env::temp_dir()
+        .join("gitbutler-tests")
+        .join("git")
+        .join("local")
+        .join(test_name)
+}
+
+/// The path of the repository that [`origin_repo`] creates
+/// for the test with the given (module-qualified) name.
+pub(crate) fn origin_repo_path(test_name: &str) -> std::path::PathBuf {
+    std::env::temp_dir()
+        .join("gitbutler-tests")
+        .join("git")
+        .join("origin")
+        .join(test_name)
+}
+
+/// Creates a repository with a single commit on `refs/heads/master`
+/// to clone from, for the test with the given (module-qualified) name.
+///
+/// Returns the `file://` URL of the repository and the id of the commit.
+#[allow(unused)]
+pub(crate) fn origin_repo(test_name: &str) -> (String, String) {
+    let repo_path = origin_repo_path(test_name);
+    let _ = std::fs::remove_dir_all(&repo_path);
+    git2::Repository::init(&repo_path).unwrap();
+
+    let repo_path = repo_path.to_str().unwrap();
+    let head = commit(repo_path, "refs/heads/master", "initial commit");
+
+    (format!("file://{repo_path}"), head)
+}
+
+/// Asserts that the reported progress ends with all
+/// objects having been received.
+#[allow(unused)]
+pub(crate) fn assert_transfer_completed(progress: &[crate::Progress]) {
+    match progress
+        .iter()
+        .rev()
+        .find(|p| matches!(p, crate::Progress::ReceivingObjects { .. }))
+    {
+        Some(crate::Progress::ReceivingObjects {
+            received, total, ..
+        }) => {
+            assert!(*total > 0);
+            assert_eq!(received, total);
+        }
+        _ => panic!("expected objects to be received, got {progress:?}"),
+    }
+}
+
+/// Commits an empty tree on top of `refname` in the repository
+/// at `repo_path`, and returns the id of the new commit.
+#[allow(unused)]
+pub(crate) fn commit(repo_path: &str, refname: &str, message: &str) -> String {
+    let repo = git2::Repository::open(repo_path).unwrap();
+    let signature = git2::Signature::now("test", "test@example.com").unwrap();
+    let tree = repo
+        .find_tree(repo.treebuilder(None).unwrap().write().unwrap())
+        .unwrap();
+    let parent = repo
+        .find_reference(refname)
+        .and_then(|reference| reference.peel_to_commit())
+        .ok();
+
+    repo.commit(
+        Some(refname),
+        &signature,
+        &signature,
+        message,
+        &tree,
+        &parent.iter().collect::<Vec<_>>(),
+    )
+    .unwrap()
+    .to_string()
+}
+
+/// Commits the given files (paths and contents) on top of the tree of
+/// `refname` in the repository at `repo_path`, merging the given commits,
+/// and returns the id of the new commit.
+///
+/// Every commit is made a second after the previous one, such that
+/// listing commits by time is deterministic.
+#[allow(unused)]
+pub(crate) fn commit_files(
+    repo_path: &str,
+    refname: &str,
+    message: &str,
+    files: &[(&str, &str)],
+    merged: &[&str],
+) -> String {
+    static TIME: std::sync::atomic::AtomicI64 = std::sync::atomic::AtomicI64::new(1_700_000_000);
+
+    let repo = git2::Repository::open(repo_path).unwrap();
+    let time = git2::Time::new(TIME.fetch_add(1, std::sync::atomic::Ordering::SeqCst), 0);
+    let signature = git2::Signature::new("test", "test@example.com", &time).unwrap();
+
+    let mut parents = repo
+        .find_reference(refname)
+        .and_then(|reference| reference.peel_to_commit())
+        .into_iter()
+        .collect::<Vec<_>>();
+    for merged in merged {
+        parents.push(
+            repo.find_commit(git2::Oid::from_str(merged).unwrap())
+                .unwrap(),
+        );
+    }
+
+    let mut index = git2::Index::new().unwrap();
+    if let Some(parent) = parents.first() {
+        index.read_tree(&parent.tree().unwrap()).unwrap();
+    }
+    for (path, content) in files {
+        let blob = repo.blob(content.as_bytes()).unwrap();
+        index
+            .add(&git2::IndexEntry {
+                ctime: git2::IndexTime::new(0, 0),
+                mtime: git2::IndexTime::new(0, 0),
+                dev: 0,
+                ino: 0,
+                mode: 0o100644,
+                uid: 0,
+                gid: 0,
+                file_size: content.len() as u32,
+                id: blob,
+                flags: 0,
+                flags_extended: 0,
+                path: path.as_bytes().to_vec(),
+            })
+            .unwrap();
+    }
+    let tree = repo.find_tree(index.write_tree_to(&repo).unwrap()).unwrap();
+
+    repo.commit(
+        Some(refname),
+        &signature,
+        &signature,
+        message,
+        &tree,
+        &parents.iter().collect::<Vec<_>>(),
+    )
+    .unwrap()
+    .to_string()
+}
+
+/// Generates an unencrypted SSH key pair for the test with the given
+/// (module-qualified) name, and returns the path of the private key.
+#[allow(unused)]
+pub(crate) fn ssh_key(test_name: &str) -> String {
+    let key_dir = std::env::temp_dir()
+        .join("gitbutler-tests")
+        .join("git")
+        .join("keys")
+        .join(test_name);
+    let _ = std::fs::remove_dir_all(&key_dir);
+    std::fs::create_dir_all(&key_dir).unwrap();
+
+    let private_key = key_dir.join("id_ed25519");
+    let status = std::process::Command::new("ssh-keygen")
+        .args(["-q", "-t", "ed25519", "-N", "", "-f"])
+        .arg(&private_key)
+        .status()
+        .unwrap();
+    assert!(status.success(), "ssh-keygen failed");
+
+    private_key.to_string_lossy().into_owned()
+}
+
+/// Fetches `refs/heads/master` of an origin that requires the
+/// given authorization, and asserts that it was fetched.
+#[allow(unused)]
+pub(crate) async fn assert_fetches<F: Fixture>(fixture: &F, authorization: &crate::Authorization) {
+    use crate::Repository;
+
+    let repo = fixture.repo();
+    let (uri, head) = fixture.authorized_origin(authorization).await;
+    repo.create_remote("origin", &uri).await.unwrap();
+
+    repo.fetch(
+        "origin",
+        crate::RefSpec::parse("refs/heads/master:refs/remotes/origin/master").unwrap(),
+        authorization,
+        &crate::FetchOptions::default(),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        repo.resolve_ref("refs/remotes/origin/master")
+            .await
+            .unwrap(),
+        Some(head)
+    );
+}
+
+/// Sets up the state that the conformance tests rely on, but
+/// which the [`crate::Repository`] trait has no operations for.
+pub(crate) trait Fixture {
+    /// The repository under test.
+    type Repository: crate::Repository;
+
+    /// Returns the (initially empty) repository under test.
+    fn repo(&self) -> &Self::Repository;
+
+    /// Creates a commit on top of `refname`, and returns its id.
+    fn create_commit(&self, refname: &str) -> String;
+
+    /// Points HEAD directly to the given commit.
+    fn detach_head(&self, commit: &str);
 
+    /// Creates a repository to fetch from, with a single commit on
+    /// `refs/heads/master`, and returns its URI and the commit.
+    fn origin(&self) -> (String, String);
+
+    /// Like [`Fixture::origin`], but the repository can only be fetched
+    /// from with the given authorization (if not [`crate::Authorization::Auto`]).
+    async fn authorized_origin(&self, authorization: &crate::Authorization) -> (String, String);
+}
+
+/// A [`Fixture`] for backends that operate on a repository on disk,
+/// which is set up with libgit2.
+pub(crate) struct DiskFixture<R: crate::Repository> {
+    repo: R,
+    test_name: String,
+}
+
+impl<R: crate::Repository> DiskFixture<R> {
+    /// Wraps a repository created at [`local_repo_path`] for the given test.
     #[allow(unused)]
-    pub fn allow_authorization(&mut self, auth: crate::Authorization) {
-        self.allowed_auths.push(auth);
+    pub fn new(repo: R, test_name: String) -> Self {
+        Self { repo, test_name }
+    }
+
+    fn repo_path(&self) -> String {
+        local_repo_path(&self.test_name)
+            .to_string_lossy()
+            .into_owned()
     }
 }
 
+impl<R: crate::Repository> Fixture for DiskFixture<R> {
+    type Repository = R;
+
+    fn repo(&self) -> &R {
+        &self.repo
+    }
+
+    fn create_commit(&self, refname: &str) -> String {
+        commit(&self.repo_path(), refname, "commit")
+    }
+
+    fn detach_head(&self, commit: &str) {
+        let repo = git2::Repository::open(self.repo_path()).unwrap();
+        repo.set_head_detached(git2::Oid::from_str(commit).unwrap())
+            .unwrap();
+    }
+
+    fn origin(&self) -> (String, String) {
+        origin_repo(&self.test_name)
+    }
+
+    async fn authorized_origin(&self, authorization: &crate::Authorization) -> (String, String) {
+        let username = match authorization {
+            crate::Authorization::Auto => return self.origin(),
+            crate::Authorization::Basic { username, .. } => username.clone().unwrap_or_default(),
+            crate::Authorization::Ssh { .. } => "git".to_owned(),
+        };
+
+        // served over SSH, which takes both passwords and keys
+        let (_, head) = origin_repo(&self.test_name);
+        let mut server = TestSshServer::new(
+            origin_repo_path(&self.test_name)
+                .to_string_lossy()
+                .into_owned(),
+        );
+        server.allow_authorization(authorization.clone());
+        let port = server.spawn().await;
+
+        (format!("[{username}@localhost:{port}]:test.git"), head)
+    }
+}
+
+impl Fixture for crate::mock::Repository {
+    type Repository = Self;
+
+    fn repo(&self) -> &Self {
+        self
+    }
+
+    fn create_commit(&self, refname: &str) -> String {
+        self.commit(refname, &[])
+    }
+
+    fn detach_head(&self, commit: &str) {
+        crate::mock::Repository::detach_head(self, commit)
+    }
+
+    fn origin(&self) -> (String, String) {
+        let origin = crate::mock::Repository::default();
+        let head = origin.commit("refs/heads/master", &[]);
+        self.add_remote_repository("mock://origin", origin);
+        ("mock://origin".to_owned(), head)
+    }
+
+    async fn authorized_origin(&self, authorization: &crate::Authorization) -> (String, String) {
+        let origin = crate::mock::Repository::default();
+        let head = origin.commit("refs/heads/master", &[]);
+        if *authorization != crate::Authorization::Auto {
+            origin.require_authorization(authorization.clone());
+        }
+        self.add_remote_repository("mock://origin", origin);
+        ("mock://origin".to_owned(), head)
+    }
+}
+
+/// Reads the commit `refname` points to in the repository at `repo_path`.
+#[allow(unused)]
+pub(crate) fn resolve(repo_path: &str, refname: &str) -> Option<String> {
+    let repo = git2::Repository::open(repo_path).unwrap();
+    repo.refname_to_id(refname).ok().map(|oid| oid.to_string())
+}
+
 impl server::Server for TestSshServer {
     type Handler = TestSshClient;
     fn new_client(&mut self, _: Option<std::net::SocketAddr>) -> Self::Handler {
@@ -101,6 +574,33 @@ impl server::Handler for TestSshClient {
         ))
     }
 
+    async fn auth_publickey(
+        self,
+        _user: &str,
+        public_key: &russh_keys::key::PublicKey,
+    ) -> Result<(Self, server::Auth), Self::Error> {
+        for auth in &self.allowed_auths {
+            if let crate::Authorization::Ssh {
+                private_key: Some(private_key),
+                passphrase,
+                ..
+            } = auth
+            {
+                let key = russh_keys::load_secret_key(private_key, passphrase.as_deref()).unwrap();
+                if key.clone_public_key().unwrap().fingerprint() == public_key.fingerprint() {
+                    return Ok((self, server::Auth::Accept));
+                }
+            }
+        }
+
+        Ok((
+            self,
+            server::Auth::Reject {
+                proceed_with_methods: None,
+            },
+        ))
+    }
+
     async fn env_request(
         mut self,
         channel: ChannelId,
@@ -152,11 +652,20 @@ impl server::Handler for TestSshClient {
         mut self,
         channel_id: ChannelId,
         command: &[u8],
-        session: server::Session,
+        mut session: server::Session,
     ) -> Result<(Self, server::Session), Self::Error> {
         let req = String::from_utf8_lossy(command);
 
-        if req.starts_with("git-upload-pack") {
+        // the client asks for `git-upload-pack` when fetching and
+        // `git-receive-pack` when pushing; both are served from the test repo.
+        let program = ["git-upload-pack", "git-receive-pack"]
+            .into_iter()
+            .find(|program| req.starts_with(program));
+
+        if let Some(program) = program {
+            // OpenSSH doesn't wait for the reply, but libssh2 does.
+            session.channel_success(channel_id);
+
             let channel = Box::leak(Box::new(self.channels.remove(&channel_id).unwrap()));
             let repo_path = self.repo_path.clone();
             let handle = session.handle();
@@ -166,7 +675,7 @@ impl server::Handler for TestSshClient {
                 let mut writer = channel.channel.make_writer_ext(None);
                 let mut reader = channel.channel.make_reader_ext(None);
 
-                let mut cmd = tokio::process::Command::new("git-upload-pack")
+                let mut cmd = tokio::process::Command::new(program)
                     .kill_on_drop(true)
                     .envs(channel.envs.iter())
                     .arg(&repo_path)
@@ -226,7 +735,11 @@ impl server::Handler for TestSshClient {
 
                 let cmd_future = tokio::spawn(async move { cmd.wait().await.unwrap() });
 
-                let (status, _, _) = futures::try_join!(cmd_future, copy_in, copy_out).unwrap();
+                let (status, _) = futures::try_join!(cmd_future, copy_out).unwrap();
+
+                // Unlike OpenSSH, libssh2 doesn't send EOF before it
+                // is told that the command exited.
+                copy_in.abort();
 
                 let exit_code = status.code().unwrap_or(1) as u32;
 
@@ -328,10 +841,11 @@ macro_rules! test_impl {
                         .join("gitbutler-tests")
                         .join("git")
                         .join("remote")
-                        .join(test_name)
+                        .join(format!("{mod_name}::{test_name}"))
                         .to_string_lossy()
                         .into_owned();
 
+                    let _ = ::std::fs::remove_dir_all(&repo_path);
                     ::std::fs::create_dir_all(&repo_path).unwrap();
 
                     let repo = $crate::backend::git2::Repository::<
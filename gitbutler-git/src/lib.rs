@@ -23,6 +23,11 @@
 //!
 //! This hampers certain use cases, such as implementing
 //! [`cli::GitExecutor`] for e.g. remote connections.
+//!
+//! # Mock Support
+//! This library provides an in-memory implementation via the `mock`
+//! feature, for testing code that uses the library without touching
+//! the disk. See [`mock::Repository`].
 #![deny(missing_docs, unsafe_code)]
 #![allow(async_fn_in_trait)]
 #![cfg_attr(test, feature(async_closure))]
@@ -35,6 +40,7 @@ mod integration_tests;
 pub(crate) use integration_tests::*;
 
 mod backend;
+mod cancellation;
 pub mod ops;
 mod refspec;
 mod repository;
@@ -43,8 +49,15 @@ mod repository;
 pub use backend::cli;
 #[cfg(feature = "git2")]
 pub use backend::git2;
+#[cfg(any(test, feature = "mock"))]
+pub use backend::mock;
 
 pub use self::{
+    cancellation::{CancellationToken, Cancelled},
     refspec::{Error as RefSpecError, RefSpec},
-    repository::{Authorization, ConfigScope, Error, Repository},
+    repository::{
+        Authorization, ConfigScope, Error, FetchOptions, ForceWithLease, HostKeyPolicy, LogOptions,
+        LogOrder, Progress, ProgressSink, PushOptions, Ref, RefExpectation, RefTransaction,
+        RefUpdate, Repository,
+    },
 };
@@ -8,11 +8,31 @@ pub enum Error {
     UnexpectedChar(char, usize),
 }
 
+/// The rules Git expands short ref names with, as prefixes and suffixes,
+/// in order of precedence (see `gitrevisions(7)`).
+const DWIM_RULES: [(&str, &str); 6] = [
+    ("", ""),
+    ("refs/", ""),
+    ("refs/tags/", ""),
+    ("refs/heads/", ""),
+    ("refs/remotes/", ""),
+    ("refs/remotes/", "/HEAD"),
+];
+
 /// A Git [refspec](https://git-scm.com/book/en/v2/Git-Internals-The-Refspec).
+///
+/// Outside of this crate, refspecs are created with [`Self::parse`], from
+/// a `(source, destination)` tuple, or with the `with_*` methods on
+/// [`Self::default`], such that fields can be added without breaking callers.
 #[derive(Debug, Default, Clone, PartialEq)]
+#[non_exhaustive]
 pub struct RefSpec {
     /// If `true`, will update the ref upon a fetch or push even if it is not a fast-forward.
     pub update_non_fastforward: bool,
+    /// If `true`, the refspec is a negative refspec (`^refs/...`), which excludes
+    /// the refs matching its source from the refs matched by other refspecs.
+    /// Negative refspecs have no destination.
+    pub negative: bool,
     /// The source refspec.
     pub source: Option<String>,
     /// The destination refspec.
@@ -27,6 +47,13 @@ impl RefSpec {
         self
     }
 
+    /// Sets the `negative` flag
+    #[inline]
+    pub fn with_negative(mut self, negative: bool) -> Self {
+        self.negative = negative;
+        self
+    }
+
     /// Sets the `source` refspec
     #[inline]
     pub fn with_source(mut self, source: Option<String>) -> Self {
@@ -56,6 +83,27 @@ impl RefSpec {
             s
         };
 
+        if let Some(stripped) = s.strip_prefix('^') {
+            if refspec.update_non_fastforward {
+                return Err(Error::UnexpectedChar('^', offset));
+            }
+
+            // negative refspecs only have a source
+            if let Some(colon) = stripped.find(':') {
+                return Err(Error::UnexpectedChar(':', offset + 1 + colon));
+            }
+
+            // nor can they exclude nothing
+            let source = stripped.trim();
+            if source.is_empty() {
+                return Err(Error::UnexpectedChar('^', offset));
+            }
+
+            refspec.negative = true;
+            refspec.source = Some(source.to_owned());
+            return Ok(refspec);
+        }
+
         let mut split = s.split(':');
 
         if let Some(first) = split.next() {
@@ -87,10 +135,132 @@ impl RefSpec {
 
         Ok(refspec)
     }
+
+    /// Whether or not the source is a pattern (i.e. contains a `*`),
+    /// matching any refs that have the parts around the `*` in common.
+    #[must_use]
+    pub fn is_pattern(&self) -> bool {
+        self.source
+            .as_deref()
+            .is_some_and(|source| source.contains('*'))
+    }
+
+    /// Whether or not the given (full) ref name matches the source.
+    ///
+    /// Like Git, a pattern matches any ref that starts with the part
+    /// before, and ends with the part after, the `*`. Otherwise, the
+    /// source is expanded the way Git expands short names, e.g. `main`
+    /// matches `refs/heads/main`, but also `refs/tags/main`; Git only
+    /// fetches the first of those in the order of `gitrevisions(7)`.
+    ///
+    /// For a negative refspec, this tells whether the ref is excluded.
+    /// Git doesn't expand their sources, so they must be full names.
+    #[must_use]
+    pub fn matches_source(&self, refname: &str) -> bool {
+        self.match_source(refname).is_some()
+    }
+
+    /// Returns the destination of the given (full) ref name, if it
+    /// matches the source, with the part matched by the `*` of a
+    /// pattern substituted for the `*` of the destination, e.g.
+    /// `refs/heads/main` is transformed into `refs/remotes/origin/main`
+    /// by `refs/heads/*:refs/remotes/origin/*`.
+    ///
+    /// Returns `None` if the ref doesn't match, if there is no
+    /// destination, for negative refspecs, and (like Git, which
+    /// rejects them) for refspecs where only one side is a pattern.
+    #[must_use]
+    pub fn transform(&self, refname: &str) -> Option<String> {
+        if self.negative {
+            return None;
+        }
+
+        let destination = self.destination.as_deref()?;
+        let matched = self.match_source(refname)?;
+
+        match (self.is_pattern(), destination.split_once('*')) {
+            (true, Some((prefix, suffix))) => Some(format!("{prefix}{matched}{suffix}")),
+            (false, None) => Some(destination.to_owned()),
+            _ => None,
+        }
+    }
+
+    /// Returns the refspec with its source and destination swapped,
+    /// e.g. to find the remote ref a remote-tracking ref was fetched
+    /// from, using [`Self::transform`] on the reversed refspec.
+    ///
+    /// Negative refspecs, which have no destination, are returned as-is.
+    #[must_use]
+    pub fn reverse(&self) -> Self {
+        if self.negative {
+            return self.clone();
+        }
+
+        Self {
+            source: self.destination.clone(),
+            destination: self.source.clone(),
+            ..self.clone()
+        }
+    }
+
+    /// Matches the given (full) ref name against the source (see
+    /// [`Self::matches_source`]), returning the part of the name matched
+    /// by the `*` of a pattern, or the empty string for other sources.
+    fn match_source<'a>(&self, refname: &'a str) -> Option<&'a str> {
+        let source = self.source.as_deref()?;
+
+        if let Some((prefix, suffix)) = source.split_once('*') {
+            return refname.strip_prefix(prefix)?.strip_suffix(suffix);
+        }
+
+        let matches = if self.negative {
+            source == refname
+        } else {
+            DWIM_RULES.iter().any(|(prefix, suffix)| {
+                refname
+                    .strip_prefix(prefix)
+                    .and_then(|name| name.strip_suffix(suffix))
+                    == Some(source)
+            })
+        };
+        matches.then_some("")
+    }
+
+    /// Returns the destinations of the given (full) ref name under all of the
+    /// given refspecs, as Git computes them for a fetch: if any negative
+    /// refspec matches the ref, it has no destinations at all.
+    #[must_use]
+    pub fn transform_all(refspecs: &[Self], refname: &str) -> Vec<String> {
+        let excluded = refspecs
+            .iter()
+            .any(|refspec| refspec.negative && refspec.matches_source(refname));
+
+        if excluded {
+            return Vec::new();
+        }
+
+        let mut destinations = Vec::new();
+        for destination in refspecs
+            .iter()
+            .filter_map(|refspec| refspec.transform(refname))
+        {
+            if !destinations.contains(&destination) {
+                destinations.push(destination);
+            }
+        }
+        destinations
+    }
 }
 
 impl fmt::Display for RefSpec {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.negative {
+            f.write_str("^")?;
+            if let Some(source) = &self.source {
+                f.write_str(source)?;
+            }
+            return Ok(());
+        }
         if self.update_non_fastforward {
             f.write_str("+")?;
         }
@@ -125,6 +295,7 @@ mod tests {
             RefSpec::parse("refs/heads/*:refs/remotes/origin/*").unwrap(),
             RefSpec {
                 update_non_fastforward: false,
+                negative: false,
                 source: Some("refs/heads/*".to_owned()),
                 destination: Some("refs/remotes/origin/*".to_owned()),
             }
@@ -137,6 +308,7 @@ mod tests {
             RefSpec::parse("+refs/heads/*:refs/remotes/origin/*").unwrap(),
             RefSpec {
                 update_non_fastforward: true,
+                negative: false,
                 source: Some("refs/heads/*".to_owned()),
                 destination: Some("refs/remotes/origin/*".to_owned()),
             }
@@ -158,6 +330,7 @@ mod tests {
             RefSpec::parse(":").unwrap(),
             RefSpec {
                 update_non_fastforward: false,
+                negative: false,
                 source: None,
                 destination: None,
             }
@@ -170,6 +343,7 @@ mod tests {
             RefSpec::parse("+:").unwrap(),
             RefSpec {
                 update_non_fastforward: true,
+                negative: false,
                 source: None,
                 destination: None,
             }
@@ -182,6 +356,7 @@ mod tests {
             RefSpec::parse("").unwrap(),
             RefSpec {
                 update_non_fastforward: false,
+                negative: false,
                 source: None,
                 destination: None,
             }
@@ -194,6 +369,7 @@ mod tests {
             RefSpec::parse("+").unwrap(),
             RefSpec {
                 update_non_fastforward: true,
+                negative: false,
                 source: None,
                 destination: None,
             }
@@ -206,6 +382,7 @@ mod tests {
             RefSpec::parse("refs/heads/*").unwrap(),
             RefSpec {
                 update_non_fastforward: false,
+                negative: false,
                 source: Some("refs/heads/*".to_owned()),
                 destination: Some("refs/heads/*".to_owned()),
             }
@@ -218,6 +395,7 @@ mod tests {
             RefSpec::parse(":refs/heads/experimental").unwrap(),
             RefSpec {
                 update_non_fastforward: false,
+                negative: false,
                 source: None,
                 destination: Some("refs/heads/experimental".to_owned()),
             }
@@ -230,6 +408,7 @@ mod tests {
             RefSpec::parse("+refs/heads/*").unwrap(),
             RefSpec {
                 update_non_fastforward: true,
+                negative: false,
                 source: Some("refs/heads/*".to_owned()),
                 destination: Some("refs/heads/*".to_owned()),
             }
@@ -242,6 +421,7 @@ mod tests {
             RefSpec::parse("+:refs/heads/experimental").unwrap(),
             RefSpec {
                 update_non_fastforward: true,
+                negative: false,
                 source: None,
                 destination: Some("refs/heads/experimental".to_owned()),
             }
@@ -254,6 +434,7 @@ mod tests {
             RefSpec::parse("master").unwrap(),
             RefSpec {
                 update_non_fastforward: false,
+                negative: false,
                 source: Some("master".to_owned()),
                 destination: Some("master".to_owned()),
             }
@@ -266,6 +447,7 @@ mod tests {
             RefSpec::parse("+master").unwrap(),
             RefSpec {
                 update_non_fastforward: true,
+                negative: false,
                 source: Some("master".to_owned()),
                 destination: Some("master".to_owned()),
             }
@@ -278,6 +460,7 @@ mod tests {
             RefSpec::parse("refs/heads/*:").unwrap(),
             RefSpec {
                 update_non_fastforward: false,
+                negative: false,
                 source: Some("refs/heads/*".to_owned()),
                 destination: None,
             }
@@ -289,6 +472,7 @@ mod tests {
         assert_eq!(
             RefSpec {
                 update_non_fastforward: false,
+                negative: false,
                 source: None,
                 destination: None,
             }
@@ -302,6 +486,7 @@ mod tests {
         assert_eq!(
             RefSpec {
                 update_non_fastforward: true,
+                negative: false,
                 source: None,
                 destination: None,
             }
@@ -315,6 +500,7 @@ mod tests {
         assert_eq!(
             RefSpec {
                 update_non_fastforward: false,
+                negative: false,
                 source: Some("refs/heads/*".to_owned()),
                 destination: None,
             }
@@ -328,6 +514,7 @@ mod tests {
         assert_eq!(
             RefSpec {
                 update_non_fastforward: true,
+                negative: false,
                 source: Some("refs/heads/*".to_owned()),
                 destination: None,
             }
@@ -341,6 +528,7 @@ mod tests {
         assert_eq!(
             RefSpec {
                 update_non_fastforward: false,
+                negative: false,
                 source: Some("refs/heads/*".to_owned()),
                 destination: Some("refs/remotes/origin/*".to_owned()),
             }
@@ -354,6 +542,7 @@ mod tests {
         assert_eq!(
             RefSpec {
                 update_non_fastforward: true,
+                negative: false,
                 source: Some("refs/heads/*".to_owned()),
                 destination: Some("refs/remotes/origin/*".to_owned()),
             }
@@ -367,6 +556,7 @@ mod tests {
         assert_eq!(
             RefSpec {
                 update_non_fastforward: false,
+                negative: false,
                 source: None,
                 destination: Some("refs/heads/*".to_owned()),
             }
@@ -380,6 +570,7 @@ mod tests {
         assert_eq!(
             RefSpec {
                 update_non_fastforward: true,
+                negative: false,
                 source: None,
                 destination: Some("refs/heads/*".to_owned()),
             }
@@ -394,9 +585,376 @@ mod tests {
             RefSpec::from(("refs/heads/*", "refs/remotes/origin/*")),
             RefSpec {
                 update_non_fastforward: false,
+                negative: false,
                 source: Some("refs/heads/*".to_owned()),
                 destination: Some("refs/remotes/origin/*".to_owned()),
             }
         );
     }
+
+    #[test]
+    fn test_parse_negative() {
+        assert_eq!(
+            RefSpec::parse("^refs/heads/wip").unwrap(),
+            RefSpec {
+                update_non_fastforward: false,
+                negative: true,
+                source: Some("refs/heads/wip".to_owned()),
+                destination: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_negative_destination() {
+        assert_eq!(
+            RefSpec::parse("^refs/heads/wip:refs/heads/foo").unwrap_err(),
+            Error::UnexpectedChar(':', 15)
+        );
+    }
+
+    #[test]
+    fn test_parse_negative_empty() {
+        assert_eq!(
+            RefSpec::parse("^").unwrap_err(),
+            Error::UnexpectedChar('^', 0)
+        );
+        assert_eq!(
+            RefSpec::parse("^ ").unwrap_err(),
+            Error::UnexpectedChar('^', 0)
+        );
+    }
+
+    #[test]
+    fn test_parse_negative_force() {
+        assert_eq!(
+            RefSpec::parse("+^refs/heads/wip").unwrap_err(),
+            Error::UnexpectedChar('^', 1)
+        );
+    }
+
+    #[test]
+    fn format_negative() {
+        assert_eq!(
+            RefSpec::parse("^refs/heads/feature/*").unwrap().to_string(),
+            "^refs/heads/feature/*".to_owned()
+        );
+    }
+
+    #[test]
+    fn test_is_pattern() {
+        assert!(RefSpec::parse("refs/heads/*:refs/remotes/origin/*")
+            .unwrap()
+            .is_pattern());
+        assert!(!RefSpec::parse("refs/heads/main").unwrap().is_pattern());
+        assert!(!RefSpec::parse(":refs/heads/*").unwrap().is_pattern());
+    }
+
+    #[test]
+    fn test_matches_source_exact() {
+        let refspec = RefSpec::parse("refs/heads/main:refs/remotes/origin/main").unwrap();
+        assert!(refspec.matches_source("refs/heads/main"));
+        assert!(!refspec.matches_source("refs/heads/main2"));
+        assert!(!refspec.matches_source("refs/heads/mai"));
+    }
+
+    #[test]
+    fn test_matches_source_short() {
+        let refspec = RefSpec::parse("main").unwrap();
+        assert!(refspec.matches_source("main"));
+        assert!(refspec.matches_source("refs/main"));
+        assert!(refspec.matches_source("refs/heads/main"));
+        assert!(refspec.matches_source("refs/tags/main"));
+        assert!(refspec.matches_source("refs/remotes/main"));
+        assert!(refspec.matches_source("refs/remotes/main/HEAD"));
+        assert!(!refspec.matches_source("refs/remotes/origin/main"));
+        assert!(!refspec.matches_source("refs/heads/feature/main"));
+
+        let refspec = RefSpec::parse("heads/main:refs/remotes/origin/main").unwrap();
+        assert!(refspec.matches_source("refs/heads/main"));
+        assert!(!refspec.matches_source("refs/tags/main"));
+        assert_eq!(
+            refspec.transform("refs/heads/main"),
+            Some("refs/remotes/origin/main".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_matches_source_negative_short() {
+        let refspec = RefSpec::parse("^main").unwrap();
+        assert!(refspec.matches_source("main"));
+        assert!(!refspec.matches_source("refs/heads/main"));
+    }
+
+    #[test]
+    fn test_matches_source_pattern() {
+        let refspec = RefSpec::parse("refs/heads/*:refs/remotes/origin/*").unwrap();
+        assert!(refspec.matches_source("refs/heads/main"));
+        assert!(refspec.matches_source("refs/heads/feature/x"));
+        assert!(!refspec.matches_source("refs/tags/v1"));
+        assert!(!refspec.matches_source("refs/heads"));
+    }
+
+    #[test]
+    fn test_matches_source_pattern_infix() {
+        let refspec = RefSpec::parse("refs/heads/*/x:refs/x/*-y").unwrap();
+        assert!(refspec.matches_source("refs/heads/feature/x"));
+        assert!(refspec.matches_source("refs/heads/a/b/x"));
+        assert!(!refspec.matches_source("refs/heads/feature/y"));
+        // the prefix and suffix must not overlap
+        assert!(!RefSpec::parse("refs/x*x/x")
+            .unwrap()
+            .matches_source("refs/x/x"));
+    }
+
+    #[test]
+    fn test_matches_source_none() {
+        let refspec = RefSpec::parse(":refs/heads/main").unwrap();
+        assert!(!refspec.matches_source("refs/heads/main"));
+        assert!(!refspec.matches_source(""));
+    }
+
+    #[test]
+    fn test_transform_pattern() {
+        let refspec = RefSpec::parse("+refs/heads/*:refs/remotes/origin/*").unwrap();
+        assert_eq!(
+            refspec.transform("refs/heads/main"),
+            Some("refs/remotes/origin/main".to_owned())
+        );
+        assert_eq!(
+            refspec.transform("refs/heads/feature/x"),
+            Some("refs/remotes/origin/feature/x".to_owned())
+        );
+        assert_eq!(refspec.transform("refs/tags/v1"), None);
+    }
+
+    #[test]
+    fn test_transform_pattern_infix() {
+        let refspec = RefSpec::parse("refs/heads/*/x:refs/x/*-y").unwrap();
+        assert_eq!(
+            refspec.transform("refs/heads/feature/x"),
+            Some("refs/x/feature-y".to_owned())
+        );
+        assert_eq!(
+            refspec.transform("refs/heads/a/b/x"),
+            Some("refs/x/a/b-y".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_transform_exact() {
+        let refspec = RefSpec::parse("refs/heads/feature/x:refs/dup").unwrap();
+        assert_eq!(
+            refspec.transform("refs/heads/feature/x"),
+            Some("refs/dup".to_owned())
+        );
+        assert_eq!(refspec.transform("refs/heads/feature/y"), None);
+    }
+
+    #[test]
+    fn test_transform_no_destination() {
+        let refspec = RefSpec::parse("refs/heads/*:").unwrap();
+        assert!(refspec.matches_source("refs/heads/main"));
+        assert_eq!(refspec.transform("refs/heads/main"), None);
+    }
+
+    #[test]
+    fn test_transform_one_sided_pattern() {
+        assert_eq!(
+            RefSpec::parse("refs/heads/*:refs/one")
+                .unwrap()
+                .transform("refs/heads/main"),
+            None
+        );
+        assert_eq!(
+            RefSpec::parse("refs/heads/main:refs/remotes/origin/*")
+                .unwrap()
+                .transform("refs/heads/main"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_transform_negative() {
+        let refspec = RefSpec::parse("^refs/heads/*").unwrap();
+        assert!(refspec.matches_source("refs/heads/main"));
+        assert_eq!(refspec.transform("refs/heads/main"), None);
+    }
+
+    #[test]
+    fn test_reverse() {
+        let refspec = RefSpec::parse("+refs/heads/*:refs/remotes/origin/*")
+            .unwrap()
+            .reverse();
+        assert_eq!(
+            refspec,
+            RefSpec {
+                update_non_fastforward: true,
+                negative: false,
+                source: Some("refs/remotes/origin/*".to_owned()),
+                destination: Some("refs/heads/*".to_owned()),
+            }
+        );
+        assert_eq!(
+            refspec.transform("refs/remotes/origin/feature/x"),
+            Some("refs/heads/feature/x".to_owned())
+        );
+        assert_eq!(refspec.transform("refs/heads/main"), None);
+    }
+
+    #[test]
+    fn test_reverse_delete() {
+        assert_eq!(
+            RefSpec::parse(":refs/heads/main").unwrap().reverse(),
+            RefSpec {
+                update_non_fastforward: false,
+                negative: false,
+                source: Some("refs/heads/main".to_owned()),
+                destination: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_reverse_negative() {
+        let refspec = RefSpec::parse("^refs/heads/wip").unwrap();
+        assert_eq!(refspec.reverse(), refspec);
+    }
+
+    #[test]
+    fn test_transform_all() {
+        let refspecs = [
+            "refs/heads/feature/*:refs/remotes/o/f/*",
+            "^refs/heads/feature/wip",
+            "refs/heads/*/x:refs/x/*-y",
+            "refs/heads/feature/x:refs/dup",
+        ]
+        .map(|refspec| RefSpec::parse(refspec).unwrap());
+
+        assert_eq!(
+            RefSpec::transform_all(&refspecs, "refs/heads/feature/x"),
+            vec![
+                "refs/remotes/o/f/x".to_owned(),
+                "refs/x/feature-y".to_owned(),
+                "refs/dup".to_owned(),
+            ]
+        );
+        assert_eq!(
+            RefSpec::transform_all(&refspecs, "refs/heads/a/x"),
+            vec!["refs/x/a-y".to_owned()]
+        );
+        assert!(RefSpec::transform_all(&refspecs, "refs/heads/feature/wip").is_empty());
+        assert!(RefSpec::transform_all(&refspecs, "refs/heads/main2").is_empty());
+    }
+
+    #[test]
+    fn test_transform_all_duplicates() {
+        let refspecs = [
+            "refs/heads/*:refs/remotes/origin/*",
+            "refs/heads/main:refs/remotes/origin/main",
+        ]
+        .map(|refspec| RefSpec::parse(refspec).unwrap());
+
+        assert_eq!(
+            RefSpec::transform_all(&refspecs, "refs/heads/main"),
+            vec!["refs/remotes/origin/main".to_owned()]
+        );
+    }
+
+    /// Fetches from a repository with the given refs using each of the
+    /// given sets of refspecs, and checks that Git creates exactly the
+    /// refs [`RefSpec::transform_all`] computes for them.
+    #[test]
+    fn transform_all_matches_git() {
+        let root = std::env::temp_dir()
+            .join("gitbutler-tests")
+            .join("git")
+            .join("refspec");
+        let _ = std::fs::remove_dir_all(&root);
+
+        let git = |dir: &std::path::Path, args: &[&str]| {
+            let output = std::process::Command::new("git")
+                .arg("-C")
+                .arg(dir)
+                .args(args)
+                .output()
+                .unwrap();
+            assert!(
+                output.status.success(),
+                "git {args:?}: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+            String::from_utf8(output.stdout).unwrap()
+        };
+
+        // short names must be unambiguous, as Git only fetches one of the refs
+        let refnames = [
+            "refs/heads/main",
+            "refs/heads/feature/x",
+            "refs/heads/feature/wip",
+            "refs/heads/a/x",
+            "refs/heads/a/b/x",
+            "refs/tags/v1",
+            "refs/remotes/upstream/HEAD",
+            "refs/notes/commits",
+        ];
+
+        let origin = root.join("origin");
+        std::fs::create_dir_all(&origin).unwrap();
+        git(&origin, &["init", "--bare", "--quiet"]);
+        let commit = crate::private::commit(origin.to_str().unwrap(), "refs/heads/main", "main");
+        for refname in refnames {
+            git(&origin, &["update-ref", refname, &commit]);
+        }
+
+        let cases: &[&[&str]] = &[
+            &["refs/heads/*:refs/remotes/origin/*"],
+            &[
+                "+refs/heads/*:refs/remotes/origin/*",
+                "^refs/heads/feature/*",
+            ],
+            &["refs/heads/*:refs/r/*", "^refs/heads/a/x", "^main"],
+            &["refs/heads/*/x:refs/x/*-y"],
+            &[
+                "refs/heads/feature/*:refs/f/*",
+                "refs/heads/feature/x:refs/dup",
+            ],
+            &["refs/*:refs/all/*", "^refs/notes/*"],
+            &["refs/tags/*:refs/tags/*"],
+            &["refs/heads/main:refs/m"],
+            &["main:refs/m", "v1:refs/t"],
+            &["heads/feature/x:refs/fx", "tags/v1:refs/t"],
+            &["upstream:refs/u"],
+            &["feature/wip:refs/wip", "a/b/x:refs/abx"],
+        ];
+
+        for (i, refspecs) in cases.iter().enumerate() {
+            let local = root.join(format!("local-{i}"));
+            std::fs::create_dir_all(&local).unwrap();
+            git(&local, &["init", "--bare", "--quiet"]);
+
+            let mut args = vec!["fetch", "--quiet", "--no-tags", origin.to_str().unwrap()];
+            args.extend_from_slice(refspecs);
+            git(&local, &args);
+
+            let mut actual = git(&local, &["for-each-ref", "--format=%(refname)"])
+                .lines()
+                .map(ToOwned::to_owned)
+                .collect::<Vec<_>>();
+            actual.sort();
+
+            let parsed = refspecs
+                .iter()
+                .map(|refspec| RefSpec::parse(refspec).unwrap())
+                .collect::<Vec<_>>();
+            let mut expected = refnames
+                .iter()
+                .flat_map(|refname| RefSpec::transform_all(&parsed, refname))
+                .collect::<Vec<_>>();
+            expected.sort();
+            expected.dedup();
+
+            assert_eq!(actual, expected, "{refspecs:?}");
+        }
+    }
 }
@@ -1,4 +1,6 @@
-use crate::RefSpec;
+use crate::{CancellationToken, RefSpec};
+use futures::Stream;
+use std::sync::Arc;
 
 /// A backend-agnostic operation error.
 #[derive(Debug, thiserror::Error)]
@@ -29,6 +31,31 @@ pub enum Error<BE: std::error::Error + core::fmt::Debug + Send + Sync + 'static>
     /// the remote already existed.
     #[error("remote already exists: {0}")]
     RemoteExists(String, #[source] BE),
+    /// The update of a ref was rejected by a push, either by the remote
+    /// or before anything was sent (e.g. the update was not a fast-forward,
+    /// or the lease of a force-with-lease push did not hold).
+    ///
+    /// Holds the name of the rejected (remote) ref and the reason it was rejected.
+    #[error("push of {0} was rejected: {1}")]
+    PushRejected(String, String),
+    /// A ref update was not applied because the ref did not
+    /// have the value it was expected to have.
+    ///
+    /// Holds the name of the ref that did not match.
+    #[error("ref {0} does not have the expected value")]
+    RefMismatch(String),
+    /// The operation was aborted because its [`CancellationToken`]
+    /// was cancelled.
+    #[error("the operation was cancelled")]
+    Cancelled,
+    /// The key presented by an SSH remote was not trusted under the
+    /// [`HostKeyPolicy`] of the authorization (e.g. the host was unknown,
+    /// its key changed, or the key was not among the pinned fingerprints).
+    ///
+    /// Holds the host and the fingerprint of the key it presented
+    /// (e.g. `SHA256:...`), such that the user can be asked to trust it.
+    #[error("host key of {0} is not trusted: {1}")]
+    HostKeyMismatch(String, String),
 }
 
 /// The scope from/to which a configuration value is read/written.
@@ -93,11 +120,30 @@ pub trait Repository {
     ///
     /// This is an authorized operation; the given authorization
     /// credentials will be used to authenticate with the remote.
+    ///
+    /// If the cancellation token of the options is cancelled,
+    /// the fetch is aborted and [`Error::Cancelled`] is returned.
     async fn fetch(
         &self,
         remote: &str,
         refspec: RefSpec,
         authorization: &Authorization,
+        options: &FetchOptions,
+    ) -> Result<(), Error<Self::Error>>;
+
+    /// Pushes the given refspecs to the given remote.
+    ///
+    /// This is an authorized operation; the given authorization
+    /// credentials will be used to authenticate with the remote.
+    ///
+    /// If the update of any ref is rejected, [`Error::PushRejected`]
+    /// is returned.
+    async fn push(
+        &self,
+        remote: &str,
+        refspecs: &[RefSpec],
+        authorization: &Authorization,
+        options: &PushOptions,
     ) -> Result<(), Error<Self::Error>>;
 
     /// Sets the URI for a remote.
@@ -129,6 +175,39 @@ pub trait Repository {
     ///
     /// Errors if the repository is empty.
     async fn symbolic_head(&self) -> Result<String, Error<Self::Error>>;
+
+    /// Lists all refs whose full names start with `prefix`
+    /// (e.g. `refs/heads/`), along with the objects they point to.
+    ///
+    /// Symbolic refs are resolved to the objects they ultimately point to.
+    async fn list_refs(&self, prefix: &str) -> Result<Vec<Ref>, Error<Self::Error>>;
+
+    /// Resolves the ref with the given full name (e.g. `refs/heads/master`
+    /// or `HEAD`) to the object it points to.
+    ///
+    /// Returns `None` if the ref does not exist. Names that are not
+    /// full ref names (e.g. `master` or `HEAD~1`) never resolve.
+    async fn resolve_ref(&self, refname: &str) -> Result<Option<String>, Error<Self::Error>>;
+
+    /// Atomically applies all updates of the given transaction;
+    /// either all refs are updated, or none of them are.
+    ///
+    /// If any ref does not have its expected value,
+    /// [`Error::RefMismatch`] is returned and no ref is updated.
+    async fn update_refs(&self, transaction: &RefTransaction) -> Result<(), Error<Self::Error>>;
+
+    /// Lists the ids of the commits in the given range, as `git rev-list`
+    /// would. The range is either a single revision (e.g. `HEAD`), listing
+    /// all of its ancestors, `<from>..<to>`, listing the ancestors of `<to>`
+    /// that are not ancestors of `<from>`, or `<from>...<to>`, listing the
+    /// ancestors of either that are not ancestors of both.
+    ///
+    /// The commits are listed lazily, as the history is walked.
+    fn log<'a>(
+        &'a self,
+        range: &'a str,
+        options: &'a LogOptions,
+    ) -> impl Stream<Item = Result<String, Error<Self::Error>>> + 'a;
 }
 
 /// Provides authentication credentials when performing
@@ -169,5 +248,372 @@ pub enum Authorization {
         /// If `None`, the key is assumed to be unencrypted.
         /// A prompt for a passphrase will result in an error.
         passphrase: Option<String>,
+        /// Which keys of the remote host are trusted.
+        host_key_policy: HostKeyPolicy,
+    },
+}
+
+/// Determines which keys presented by an SSH remote are trusted.
+///
+/// A key that isn't trusted fails the operation with
+/// [`Error::HostKeyMismatch`].
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum HostKeyPolicy {
+    /// Only trusts keys that are already in the user's `known_hosts` file.
+    Strict,
+    /// Trusts keys in the user's `known_hosts` file, and adds the keys of
+    /// hosts that aren't in it yet. A changed key is not trusted.
+    #[default]
+    AcceptNew,
+    /// Only trusts keys with one of the given SHA256 fingerprints, in the
+    /// format OpenSSH prints them in (e.g. `SHA256:...`), regardless of
+    /// the user's `known_hosts` file.
+    Pinned(Vec<String>),
+}
+
+impl HostKeyPolicy {
+    /// Returns the policy of the given authorization; authorizations
+    /// other than [`Authorization::Ssh`] use the default policy.
+    #[must_use]
+    pub fn of(authorization: &Authorization) -> Self {
+        match authorization {
+            Authorization::Ssh {
+                host_key_policy, ..
+            } => host_key_policy.clone(),
+            _ => Self::default(),
+        }
+    }
+}
+
+/// The order in which [`Repository::log`] lists commits.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LogOrder {
+    /// Reverse chronological order, as `git rev-list` lists them by default.
+    #[default]
+    Default,
+    /// Lists no parents before all of their children, and avoids
+    /// intermixing multiple lines of history (`--topo-order`).
+    Topological,
+    /// Lists no parents before all of their children, and otherwise
+    /// in commit timestamp order (`--date-order`).
+    Time,
+}
+
+/// Additional options for listing commits with [`Repository::log`].
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct LogOptions {
+    /// Only follows the first parent of merge commits.
+    pub first_parent: bool,
+    /// If not empty, only lists commits that change any of the given
+    /// paths (relative to the root of the repository), compared to
+    /// all of their parents. Like Git, merges that don't change them
+    /// compared to one of their parents are only followed to that parent.
+    pub paths: Vec<String>,
+    /// Lists at most this many commits, if set.
+    pub max_count: Option<usize>,
+    /// The order in which commits are listed.
+    pub order: LogOrder,
+}
+
+impl LogOptions {
+    /// Sets whether to only follow the first parent of merge commits
+    #[inline]
+    pub fn with_first_parent(mut self, first_parent: bool) -> Self {
+        self.first_parent = first_parent;
+        self
+    }
+
+    /// Adds a path that listed commits must change
+    #[inline]
+    pub fn with_path<S: Into<String>>(mut self, path: S) -> Self {
+        self.paths.push(path.into());
+        self
+    }
+
+    /// Sets the maximum number of commits to list
+    #[inline]
+    pub fn with_max_count(mut self, max_count: usize) -> Self {
+        self.max_count = Some(max_count);
+        self
+    }
+
+    /// Sets the order in which commits are listed
+    #[inline]
+    pub fn with_order(mut self, order: LogOrder) -> Self {
+        self.order = order;
+        self
+    }
+}
+
+/// A sink for the [`Progress`] reported by an operation.
+pub type ProgressSink = Arc<dyn Fn(Progress) + Send + Sync>;
+
+/// Additional options for a fetch operation.
+#[derive(Default, Clone)]
+pub struct FetchOptions {
+    /// Receives the progress of the transfer, if set.
+    pub progress: Option<ProgressSink>,
+    /// Aborts the fetch once cancelled, if set.
+    pub cancellation: Option<CancellationToken>,
+}
+
+impl FetchOptions {
+    /// Sets the progress sink
+    #[inline]
+    pub fn with_progress<F: Fn(Progress) + Send + Sync + 'static>(mut self, progress: F) -> Self {
+        self.progress = Some(Arc::new(progress));
+        self
+    }
+
+    /// Sets the cancellation token
+    #[inline]
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    /// Whether or not the fetch has been cancelled.
+    #[inline]
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancellation
+            .as_ref()
+            .map(CancellationToken::is_cancelled)
+            .unwrap_or(false)
+    }
+}
+
+impl core::fmt::Debug for FetchOptions {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("FetchOptions")
+            .field("progress", &self.progress.as_ref().map(|_| ".."))
+            .field("cancellation", &self.cancellation)
+            .finish()
+    }
+}
+
+/// Additional options for a push operation.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PushOptions {
+    /// Remote refs that may be force-updated, but only if they
+    /// still point to the expected object (`--force-with-lease`).
+    pub force_with_lease: Vec<ForceWithLease>,
+    /// Either all refs are updated, or none of them are (`--atomic`).
+    ///
+    /// Not supported by the libgit2 backend.
+    pub atomic: bool,
+    /// Strings passed to the hooks of the remote (`--push-option`).
+    ///
+    /// Not supported by the libgit2 backend.
+    pub push_options: Vec<String>,
+}
+
+impl PushOptions {
+    /// Adds a lease for the given remote ref.
+    #[inline]
+    pub fn with_force_with_lease(mut self, lease: ForceWithLease) -> Self {
+        self.force_with_lease.push(lease);
+        self
+    }
+
+    /// Sets the `atomic` flag
+    #[inline]
+    pub fn with_atomic(mut self, atomic: bool) -> Self {
+        self.atomic = atomic;
+        self
+    }
+
+    /// Adds a push option
+    #[inline]
+    pub fn with_push_option<S: Into<String>>(mut self, push_option: S) -> Self {
+        self.push_options.push(push_option.into());
+        self
+    }
+}
+
+/// Lease of a remote ref for a force push; the ref is only
+/// updated if it points to the `expected` object.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ForceWithLease {
+    /// The full name of the remote ref, e.g. `refs/heads/master`.
+    pub refname: String,
+    /// The object id the remote ref is expected to point to.
+    /// If `None`, the ref is expected not to exist.
+    pub expected: Option<String>,
+}
+
+/// A ref, along with the object it points to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Ref {
+    /// The full name of the ref, e.g. `refs/heads/master`.
+    pub name: String,
+    /// The object id the ref points to.
+    pub target: String,
+}
+
+/// Whether `refname` is the full name of a ref, i.e. `HEAD` or a name
+/// under `refs/` that is well-formed as per `git check-ref-format`,
+/// as opposed to a revision that merely resolves to a ref or an
+/// object (e.g. `master`, `HEAD~1` or an object id).
+pub(crate) fn is_full_refname(refname: &str) -> bool {
+    if refname == "HEAD" {
+        return true;
+    }
+
+    refname.starts_with("refs/")
+        && !refname.ends_with('.')
+        && !refname.contains("..")
+        && !refname.contains("@{")
+        && !refname.chars().any(|c| {
+            c.is_ascii_control() || matches!(c, ' ' | '~' | '^' | ':' | '?' | '*' | '[' | '\\')
+        })
+        && refname.split('/').all(|component| {
+            !component.is_empty() && !component.starts_with('.') && !component.ends_with(".lock")
+        })
+}
+
+/// The value a ref must have for a [`RefUpdate`] to be applied.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RefExpectation {
+    /// The ref may have any value, or not exist at all.
+    #[default]
+    Any,
+    /// The ref must not exist.
+    Absent,
+    /// The ref must point to the given object id.
+    Target(String),
+}
+
+/// A single update of a ref, as part of a [`RefTransaction`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RefUpdate {
+    /// The full name of the ref, e.g. `refs/heads/master`.
+    pub refname: String,
+    /// The object id the ref will point to.
+    /// If `None`, the ref is deleted.
+    pub target: Option<String>,
+    /// The value the ref must have before the update.
+    pub expected: RefExpectation,
+}
+
+/// A set of ref updates that are applied atomically
+/// by [`Repository::update_refs`].
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RefTransaction {
+    /// The updates to apply.
+    pub updates: Vec<RefUpdate>,
+    /// The message written to the reflogs of the updated refs.
+    pub message: Option<String>,
+}
+
+impl RefTransaction {
+    /// Points `refname` to `target`, if the ref matches `expected`.
+    #[inline]
+    pub fn with_update<R: Into<String>, T: Into<String>>(
+        mut self,
+        refname: R,
+        target: T,
+        expected: RefExpectation,
+    ) -> Self {
+        self.updates.push(RefUpdate {
+            refname: refname.into(),
+            target: Some(target.into()),
+            expected,
+        });
+        self
+    }
+
+    /// Deletes `refname`, if the ref matches `expected`.
+    #[inline]
+    pub fn with_delete<R: Into<String>>(mut self, refname: R, expected: RefExpectation) -> Self {
+        self.updates.push(RefUpdate {
+            refname: refname.into(),
+            target: None,
+            expected,
+        });
+        self
+    }
+
+    /// Sets the reflog message
+    #[inline]
+    pub fn with_message<S: Into<String>>(mut self, message: S) -> Self {
+        self.message = Some(message.into());
+        self
+    }
+}
+
+/// Progress of a transfer of objects from a remote,
+/// e.g. when cloning a repository.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "stage", rename_all = "camelCase")
+)]
+pub enum Progress {
+    /// Objects are being received from the remote.
+    ReceivingObjects {
+        /// The number of objects received so far.
+        received: usize,
+        /// The total number of objects to receive.
+        total: usize,
+        /// The number of bytes received so far.
+        bytes: usize,
     },
+    /// The deltas of the received objects are being resolved.
+    ResolvingDeltas {
+        /// The number of deltas resolved so far.
+        resolved: usize,
+        /// The total number of deltas to resolve.
+        total: usize,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_refname_matches_git() {
+        assert!(is_full_refname("HEAD"));
+
+        for refname in [
+            "refs/heads/master",
+            "refs/heads/feature/a",
+            "refs/tags/v1.0",
+            "refs/heads/@",
+            "refs/heads/a@b",
+            "refs/heads/caf\u{e9}",
+            "refs/",
+            "refs/heads/a..b",
+            "refs/heads/.hidden",
+            "refs/heads/a.lock",
+            "refs/heads/a.lock/b",
+            "refs/heads/a/",
+            "refs/heads//a",
+            "refs/heads/a.",
+            "refs/heads/a@{1}",
+            "refs/heads/a b",
+            "refs/heads/a~1",
+            "refs/heads/a^",
+            "refs/heads/a:b",
+            "refs/heads/a?",
+            "refs/heads/a*",
+            "refs/heads/a[",
+            "refs/heads/a\\b",
+            "refs/heads/a\u{7}",
+            "heads/master",
+            "master",
+        ] {
+            let valid = std::process::Command::new("git")
+                .args(["check-ref-format", refname])
+                .status()
+                .unwrap()
+                .success();
+            assert_eq!(
+                is_full_refname(refname),
+                valid && refname.starts_with("refs/"),
+                "{refname}"
+            );
+        }
+    }
 }